@@ -1,22 +1,76 @@
 use std::mem;
+use std::sync::Arc;
 
 use ironrdp_connector::{
-    legacy, reason_err, ConnectorError, ConnectorErrorExt, ConnectorResult, DesktopSize, Sequence, State, Written,
+    general_err, legacy, reason_err, ConnectorError, ConnectorErrorExt, ConnectorResult, DesktopSize, Sequence, State,
+    Written,
 };
 use ironrdp_pdu as pdu;
 use ironrdp_svc::{StaticChannelSet, SvcServerProcessor};
 use pdu::rdp::capability_sets::CapabilitySet;
 use pdu::rdp::headers::ShareControlPdu;
+use pdu::rdp::server_error_info::ErrorInfo;
+use pdu::rdp::server_redirection::ServerRedirectionPdu;
+use pdu::rdstls::{RdstlsAuthenticationRequest, RdstlsAuthenticationResponse, RdstlsCapabilities, RdstlsResultCode};
 use pdu::write_buf::WriteBuf;
-use pdu::{gcc, mcs, nego, rdp, PduParsing};
+use pdu::{gcc, mcs, nego, pcb, rdp, PduErrorExt as _, PduParsing};
 
 use super::channel_connection::ChannelConnectionSequence;
 use super::finalization::FinalizationSequence;
 use crate::util::{self, wrap_share_data};
+use crate::{ConnectionRedirector, CredentialValidator, Credentials, RdstlsAuthenticator, UserIdentity};
 
 const IO_CHANNEL_ID: u16 = 1003;
 const USER_CHANNEL_ID: u16 = 1002;
 
+const INITIATION_HINT: InitiationHint = InitiationHint;
+
+/// Matches the X.224 Connection Request, and the Preconnection PDU which may precede it ([MS-RDPEPS] 3.2.5.1)
+#[derive(Clone, Copy, Debug)]
+struct InitiationHint;
+
+impl pdu::PduHint for InitiationHint {
+    fn find_size(&self, bytes: &[u8]) -> pdu::PduResult<Option<usize>> {
+        if bytes.len() < 4 {
+            return Ok(None);
+        }
+
+        if is_tpkt(bytes) {
+            pdu::X224_HINT.find_size(bytes)
+        } else {
+            // Largest V2 blob: fixed part, then cchPCB and a string of up to 65535 UTF-16 characters
+            const MAX_SIZE: u32 = 16 + 2 + 65535 * 2;
+
+            let size = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]); // cbSize
+
+            if size < pcb::PreconnectionBlob::FIXED_PART_SIZE as u32 {
+                return Err(pdu::PduError::invalid_message(
+                    "InitiationHint",
+                    "cbSize",
+                    "advertised size too small for Preconnection PDU",
+                ));
+            }
+
+            if size > MAX_SIZE {
+                return Err(pdu::PduError::invalid_message(
+                    "InitiationHint",
+                    "cbSize",
+                    "Preconnection PDU too big",
+                ));
+            }
+
+            Ok(Some(size as usize))
+        }
+    }
+}
+
+/// Tells a TPKT header apart from the size field of a Preconnection PDU
+///
+/// A Preconnection PDU can only start like a TPKT header (version 3, reserved byte 0) if it is over 64 KiB.
+fn is_tpkt(bytes: &[u8]) -> bool {
+    matches!(bytes, [0x03, 0x00, ..])
+}
+
 pub struct Acceptor {
     state: AcceptorState,
     security: nego::SecurityProtocol,
@@ -25,6 +79,27 @@ pub struct Acceptor {
     desktop_size: DesktopSize,
     server_capabilities: Vec<CapabilitySet>,
     static_channels: StaticChannelSet,
+    credential_validator: Option<Arc<dyn CredentialValidator>>,
+    redirector: Option<Arc<dyn ConnectionRedirector>>,
+    rdstls_authenticator: Option<Arc<dyn RdstlsAuthenticator>>,
+    identity: Option<UserIdentity>,
+    /// Set when the client was already authenticated by CredSSP or RDSTLS, the credentials of the Client Info PDU
+    /// then being left unchecked
+    authenticated: bool,
+    nego_data: Option<nego::NegoRequestData>,
+    preconnection_blob: Option<pcb::PreconnectionBlob>,
+    client_gcc_blocks: Option<gcc::ClientGccBlocks>,
+    client_info: Option<rdp::client_info::ClientInfo>,
+    saved_for_reactivation: Option<ReactivationContext>,
+    /// Redirection sent to the client instead of activating the session
+    redirection: Option<ServerRedirectionPdu>,
+}
+
+/// Connection parameters reused by the deactivation-reactivation sequence
+#[derive(Debug, Clone)]
+struct ReactivationContext {
+    early_capability: Option<gcc::ClientEarlyCapabilityFlags>,
+    channels: Vec<(u16, gcc::ChannelDef)>,
 }
 
 #[derive(Debug)]
@@ -34,6 +109,29 @@ pub struct AcceptorResult {
     pub input_events: Vec<Vec<u8>>,
     pub user_channel_id: u16,
     pub io_channel_id: u16,
+    /// Desktop size announced to the client in the Demand Active PDU
+    pub desktop_size: DesktopSize,
+    /// Identity of the user authenticated by CredSSP or by the credential validator
+    pub identity: Option<UserIdentity>,
+    /// Data sent by the client during the connection sequence
+    pub client_data: Option<ClientConnectionData>,
+}
+
+/// Settings sent by the client during the connection sequence
+#[derive(Debug, Clone)]
+pub struct ClientConnectionData {
+    /// Routing token or cookie of the X.224 Connection Request PDU
+    ///
+    /// A redirected client sends back the load balancing info of the Server Redirection PDU as its routing token.
+    pub nego_data: Option<nego::NegoRequestData>,
+    /// Preconnection PDU sent before the X.224 Connection Request, identifying the requested RDP source
+    pub preconnection_blob: Option<pcb::PreconnectionBlob>,
+    /// Client GCC blocks of the MCS Connect Initial PDU: name, build, keyboard, color depth, channels, monitors…
+    pub gcc_blocks: gcc::ClientGccBlocks,
+    /// Client Info PDU: time zone, performance flags, auto-reconnect cookie…
+    ///
+    /// The password is cleared, it is only given to the [`CredentialValidator`].
+    pub client_info: rdp::client_info::ClientInfo,
 }
 
 impl Acceptor {
@@ -46,9 +144,89 @@ impl Acceptor {
             desktop_size,
             server_capabilities: capabilities,
             static_channels: StaticChannelSet::new(),
+            credential_validator: None,
+            redirector: None,
+            rdstls_authenticator: None,
+            identity: None,
+            authenticated: false,
+            nego_data: None,
+            preconnection_blob: None,
+            client_gcc_blocks: None,
+            client_info: None,
+            saved_for_reactivation: None,
+            redirection: None,
         }
     }
 
+    /// Creates an acceptor running the deactivation-reactivation sequence on an accepted connection
+    ///
+    /// `consumed` is the acceptor which accepted the connection, and `static_channels` the channels
+    /// returned in its [`AcceptorResult`]. The sequence starts by sending a Server Deactivate All PDU,
+    /// then exchanges capabilities again using the new desktop size, and ends with a new [`AcceptorResult`].
+    pub fn new_deactivation_reactivation(
+        mut consumed: Acceptor,
+        static_channels: StaticChannelSet,
+        desktop_size: DesktopSize,
+    ) -> ConnectorResult<Self> {
+        let Some(context) = consumed.saved_for_reactivation.clone() else {
+            return Err(general_err!("connection capabilities were never exchanged"));
+        };
+
+        for capability in consumed.server_capabilities.iter_mut() {
+            if let CapabilitySet::Bitmap(bitmap) = capability {
+                bitmap.desktop_width = desktop_size.width;
+                bitmap.desktop_height = desktop_size.height;
+            }
+        }
+
+        Ok(Self {
+            state: AcceptorState::DeactivateAll {
+                early_capability: context.early_capability,
+                channels: context.channels,
+            },
+            desktop_size,
+            static_channels,
+            identity: None,
+            ..consumed
+        })
+    }
+
+    /// Validates the credentials sent by the client in the Client Info PDU
+    ///
+    /// The connection is ended with a Set Error Info PDU when the validator rejects the logon.
+    /// Clients already authenticated by CredSSP or RDSTLS are not validated again.
+    pub fn attach_credential_validator(&mut self, validator: Arc<dyn CredentialValidator>) {
+        self.credential_validator = Some(validator);
+    }
+
+    /// Decides whether the client is redirected to another server once its logon is accepted
+    ///
+    /// The Server Redirection PDU is sent instead of the Demand Active PDU, and the connection
+    /// is not finalized, see [`Acceptor::redirection`].
+    pub fn attach_redirector(&mut self, redirector: Arc<dyn ConnectionRedirector>) {
+        self.redirector = Some(redirector);
+    }
+
+    /// Authenticates the clients connecting with the RDSTLS security protocol
+    ///
+    /// RDSTLS is only selected when enabled in the supported security protocols. Without an authenticator,
+    /// every RDSTLS client is denied access.
+    pub fn attach_rdstls_authenticator(&mut self, authenticator: Arc<dyn RdstlsAuthenticator>) {
+        self.rdstls_authenticator = Some(authenticator);
+    }
+
+    /// Returns the Preconnection PDU sent by the client, if any
+    ///
+    /// Available once the X.224 Connection Request is received, for the server to pick the RDP source.
+    pub fn preconnection_blob(&self) -> Option<&pcb::PreconnectionBlob> {
+        self.preconnection_blob.as_ref()
+    }
+
+    /// Returns the redirection sent to the client, which is then expected to disconnect.
+    pub fn redirection(&self) -> Option<&ServerRedirectionPdu> {
+        self.redirection.as_ref()
+    }
+
     pub fn attach_static_channel<T>(&mut self, channel: T)
     where
         T: SvcServerProcessor + 'static,
@@ -58,15 +236,86 @@ impl Acceptor {
 
     pub fn reached_security_upgrade(&self) -> Option<nego::SecurityProtocol> {
         match self.state {
-            AcceptorState::SecurityUpgrade { .. } => Some(self.security),
+            AcceptorState::SecurityUpgrade { protocol, .. } => Some(protocol),
             _ => None,
         }
     }
 
+    pub fn mark_security_upgrade_as_done(&mut self) -> ConnectorResult<()> {
+        if self.reached_security_upgrade().is_none() {
+            return Err(general_err!(
+                "security upgrade is not expected in the current acceptor state"
+            ));
+        }
+
+        self.step(&[], &mut WriteBuf::new())?;
+        debug_assert!(self.reached_security_upgrade().is_none());
+
+        Ok(())
+    }
+
+    /// Returns the protocol selected for CredSSP when Network Level Authentication must be performed.
+    pub fn should_perform_credssp(&self) -> Option<nego::SecurityProtocol> {
+        match self.state {
+            AcceptorState::Credssp { protocol, .. } => Some(protocol),
+            _ => None,
+        }
+    }
+
+    pub fn mark_credssp_as_done(&mut self) -> ConnectorResult<()> {
+        if self.should_perform_credssp().is_none() {
+            return Err(general_err!("CredSSP is not expected in the current acceptor state"));
+        }
+
+        let written = self.step(&[], &mut WriteBuf::new())?;
+        debug_assert!(self.should_perform_credssp().is_none());
+        debug_assert_eq!(written, Written::Nothing);
+        self.authenticated = true;
+
+        Ok(())
+    }
+
+    /// Same as [`Acceptor::mark_credssp_as_done`], recording the user authenticated by CredSSP.
+    pub fn mark_credssp_as_done_for(&mut self, identity: UserIdentity) -> ConnectorResult<()> {
+        self.mark_credssp_as_done()?;
+        self.identity = Some(identity);
+
+        Ok(())
+    }
+
+    /// Picks the strongest security protocol supported by both sides.
+    fn select_protocol(&self, requested: nego::SecurityProtocol) -> Result<nego::SecurityProtocol, nego::FailureCode> {
+        const PREFERENCE: [nego::SecurityProtocol; 4] = [
+            // Only requested by redirected clients, which can't authenticate otherwise
+            nego::SecurityProtocol::RDSTLS,
+            nego::SecurityProtocol::HYBRID_EX,
+            nego::SecurityProtocol::HYBRID,
+            nego::SecurityProtocol::SSL,
+        ];
+
+        if let Some(protocol) = PREFERENCE
+            .into_iter()
+            .find(|&protocol| self.security.contains(protocol) && requested.contains(protocol))
+        {
+            return Ok(protocol);
+        }
+
+        if self.security.is_empty() {
+            Ok(nego::SecurityProtocol::empty())
+        } else if self
+            .security
+            .intersects(nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX)
+        {
+            Err(nego::FailureCode::HYBRID_REQUIRED_BY_SERVER)
+        } else {
+            Err(nego::FailureCode::SSL_REQUIRED_BY_SERVER)
+        }
+    }
+
     pub fn get_result(&mut self) -> Option<AcceptorResult> {
         match std::mem::take(&mut self.state) {
             AcceptorState::Accepted {
-                channels: _channels, // TODO: what about ChannelDef?
+                channels: _channels, // the client channel definitions are part of the client data
                 client_capabilities,
                 input_events,
             } => Some(AcceptorResult {
@@ -75,6 +324,9 @@ impl Acceptor {
                 input_events,
                 user_channel_id: self.user_channel_id,
                 io_channel_id: self.io_channel_id,
+                desktop_size: self.desktop_size,
+                identity: self.identity.take(),
+                client_data: self.client_data(),
             }),
             previous_state => {
                 self.state = previous_state;
@@ -82,6 +334,15 @@ impl Acceptor {
             }
         }
     }
+
+    fn client_data(&self) -> Option<ClientConnectionData> {
+        Some(ClientConnectionData {
+            nego_data: self.nego_data.clone(),
+            preconnection_blob: self.preconnection_blob.clone(),
+            gcc_blocks: self.client_gcc_blocks.clone()?,
+            client_info: self.client_info.clone()?,
+        })
+    }
 }
 
 #[derive(Default, Debug)]
@@ -93,8 +354,29 @@ pub enum AcceptorState {
     InitiationSendConfirm {
         requested_protocol: nego::SecurityProtocol,
     },
+    InitiationRejected {
+        code: nego::FailureCode,
+    },
     SecurityUpgrade {
         requested_protocol: nego::SecurityProtocol,
+        protocol: nego::SecurityProtocol,
+    },
+    Credssp {
+        requested_protocol: nego::SecurityProtocol,
+        protocol: nego::SecurityProtocol,
+    },
+    RdstlsSendCapabilities {
+        requested_protocol: nego::SecurityProtocol,
+    },
+    RdstlsWaitAuthenticationRequest {
+        requested_protocol: nego::SecurityProtocol,
+    },
+    RdstlsSendAuthenticationResponse {
+        requested_protocol: nego::SecurityProtocol,
+        result_code: RdstlsResultCode,
+    },
+    RdstlsRejected {
+        result_code: RdstlsResultCode,
     },
     BasicSettingsWaitInitial {
         requested_protocol: nego::SecurityProtocol,
@@ -117,6 +399,14 @@ pub enum AcceptorState {
         early_capability: Option<gcc::ClientEarlyCapabilityFlags>,
         channels: Vec<(u16, gcc::ChannelDef)>,
     },
+    LogonRejected {
+        reason: ErrorInfo,
+    },
+    Redirected,
+    DeactivateAll {
+        early_capability: Option<gcc::ClientEarlyCapabilityFlags>,
+        channels: Vec<(u16, gcc::ChannelDef)>,
+    },
     LicensingExchange {
         early_capability: Option<gcc::ClientEarlyCapabilityFlags>,
         channels: Vec<(u16, gcc::ChannelDef)>,
@@ -130,6 +420,7 @@ pub enum AcceptorState {
     },
     CapabilitiesWaitConfirm {
         channels: Vec<(u16, gcc::ChannelDef)>,
+        input_events: Vec<Vec<u8>>,
     },
     ConnectionFinalization {
         finalization: FinalizationSequence,
@@ -149,12 +440,21 @@ impl State for AcceptorState {
             Self::Consumed => "Consumed",
             Self::InitiationWaitRequest => "InitiationWaitRequest",
             Self::InitiationSendConfirm { .. } => "InitiationSendConfirm",
+            Self::InitiationRejected { .. } => "InitiationRejected",
             Self::SecurityUpgrade { .. } => "SecurityUpgrade",
+            Self::Credssp { .. } => "Credssp",
+            Self::RdstlsSendCapabilities { .. } => "RdstlsSendCapabilities",
+            Self::RdstlsWaitAuthenticationRequest { .. } => "RdstlsWaitAuthenticationRequest",
+            Self::RdstlsSendAuthenticationResponse { .. } => "RdstlsSendAuthenticationResponse",
+            Self::RdstlsRejected { .. } => "RdstlsRejected",
             Self::BasicSettingsWaitInitial { .. } => "BasicSettingsWaitInitial",
             Self::BasicSettingsSendResponse { .. } => "BasicSettingsSendResponse",
             Self::ChannelConnection { .. } => "ChannelConnection",
             Self::RdpSecurityCommencement { .. } => "RdpSecurityCommencement",
             Self::SecureSettingsExchange { .. } => "SecureSettingsExchange",
+            Self::LogonRejected { .. } => "LogonRejected",
+            Self::Redirected => "Redirected",
+            Self::DeactivateAll { .. } => "DeactivateAll",
             Self::LicensingExchange { .. } => "LicensingExchange",
             Self::CapabilitiesSendServer { .. } => "CapabilitiesSendServer",
             Self::MonitorLayoutSend { .. } => "MonitorLayoutSend",
@@ -177,18 +477,28 @@ impl Sequence for Acceptor {
     fn next_pdu_hint(&self) -> Option<&dyn pdu::PduHint> {
         match &self.state {
             AcceptorState::Consumed => None,
-            AcceptorState::InitiationWaitRequest => Some(&pdu::X224_HINT),
+            AcceptorState::InitiationWaitRequest => Some(&INITIATION_HINT),
             AcceptorState::InitiationSendConfirm { .. } => None,
+            AcceptorState::InitiationRejected { .. } => None,
             AcceptorState::SecurityUpgrade { .. } => None,
+            AcceptorState::Credssp { .. } => None,
+            AcceptorState::RdstlsSendCapabilities { .. } => None,
+            AcceptorState::RdstlsWaitAuthenticationRequest { .. } => Some(&pdu::rdstls::RDSTLS_HINT),
+            AcceptorState::RdstlsSendAuthenticationResponse { .. } => None,
+            AcceptorState::RdstlsRejected { .. } => None,
             AcceptorState::BasicSettingsWaitInitial { .. } => Some(&pdu::X224_HINT),
             AcceptorState::BasicSettingsSendResponse { .. } => None,
             AcceptorState::ChannelConnection { connection, .. } => connection.next_pdu_hint(),
             AcceptorState::RdpSecurityCommencement { .. } => None,
             AcceptorState::SecureSettingsExchange { .. } => Some(&pdu::X224_HINT),
+            AcceptorState::LogonRejected { .. } => None,
+            AcceptorState::Redirected => None,
+            AcceptorState::DeactivateAll { .. } => None,
             AcceptorState::LicensingExchange { .. } => None,
             AcceptorState::CapabilitiesSendServer { .. } => None,
             AcceptorState::MonitorLayoutSend { .. } => None,
-            AcceptorState::CapabilitiesWaitConfirm { .. } => Some(&pdu::X224_HINT),
+            // Input events may still be received during a deactivation-reactivation sequence
+            AcceptorState::CapabilitiesWaitConfirm { .. } => Some(&pdu::RDP_HINT),
             AcceptorState::ConnectionFinalization { finalization, .. } => finalization.next_pdu_hint(),
             AcceptorState::Accepted { .. } => None,
         }
@@ -200,12 +510,29 @@ impl Sequence for Acceptor {
 
     fn step(&mut self, input: &[u8], output: &mut WriteBuf) -> ConnectorResult<Written> {
         let (written, next_state) = match std::mem::take(&mut self.state) {
+            AcceptorState::InitiationWaitRequest if !is_tpkt(input) => {
+                if self.preconnection_blob.is_some() {
+                    return Err(general_err!("unexpected second Preconnection PDU"));
+                }
+
+                let preconnection_blob =
+                    ironrdp_pdu::decode::<pcb::PreconnectionBlob>(input).map_err(ConnectorError::pdu)?;
+
+                debug!(message = ?preconnection_blob, "Received");
+
+                self.preconnection_blob = Some(preconnection_blob);
+
+                (Written::Nothing, AcceptorState::InitiationWaitRequest)
+            }
+
             AcceptorState::InitiationWaitRequest => {
                 let connection_request =
                     ironrdp_pdu::decode::<nego::ConnectionRequest>(input).map_err(ConnectorError::pdu)?;
 
                 debug!(message = ?connection_request, "Received");
 
+                self.nego_data = connection_request.nego_data;
+
                 (
                     Written::Nothing,
                     AcceptorState::InitiationSendConfirm {
@@ -215,26 +542,128 @@ impl Sequence for Acceptor {
             }
 
             AcceptorState::InitiationSendConfirm { requested_protocol } => {
-                let connection_confirm = nego::ConnectionConfirm::Response {
-                    flags: nego::ResponseFlags::empty(),
-                    protocol: self.security,
+                let (connection_confirm, next_state) = match self.select_protocol(requested_protocol) {
+                    Ok(protocol) => (
+                        nego::ConnectionConfirm::Response {
+                            flags: nego::ResponseFlags::empty(),
+                            protocol,
+                        },
+                        AcceptorState::SecurityUpgrade {
+                            requested_protocol,
+                            protocol,
+                        },
+                    ),
+                    Err(code) => (
+                        nego::ConnectionConfirm::Failure { code },
+                        AcceptorState::InitiationRejected { code },
+                    ),
                 };
 
                 debug!(message = ?connection_confirm, "Send");
 
                 let written = ironrdp_pdu::encode_buf(&connection_confirm, output).map_err(ConnectorError::pdu)?;
 
-                (
-                    Written::from_size(written)?,
-                    AcceptorState::SecurityUpgrade { requested_protocol },
-                )
+                (Written::from_size(written)?, next_state)
             }
 
-            AcceptorState::SecurityUpgrade { requested_protocol } => (
+            AcceptorState::InitiationRejected { code } => {
+                return Err(reason_err!("Initiation", "{code}"));
+            }
+
+            AcceptorState::SecurityUpgrade {
+                requested_protocol,
+                protocol,
+            } => {
+                let next_state =
+                    if protocol.intersects(nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX) {
+                        debug!("Begin NLA using CredSSP");
+                        AcceptorState::Credssp {
+                            requested_protocol,
+                            protocol,
+                        }
+                    } else if protocol.contains(nego::SecurityProtocol::RDSTLS) {
+                        debug!("Begin RDSTLS authentication");
+                        AcceptorState::RdstlsSendCapabilities { requested_protocol }
+                    } else {
+                        AcceptorState::BasicSettingsWaitInitial { requested_protocol }
+                    };
+
+                (Written::Nothing, next_state)
+            }
+
+            AcceptorState::Credssp { requested_protocol, .. } => (
                 Written::Nothing,
                 AcceptorState::BasicSettingsWaitInitial { requested_protocol },
             ),
 
+            AcceptorState::RdstlsSendCapabilities { requested_protocol } => {
+                let capabilities = RdstlsCapabilities::default();
+
+                debug!(message = ?capabilities, "Send");
+
+                let written = ironrdp_pdu::encode_buf(&capabilities, output).map_err(ConnectorError::pdu)?;
+
+                (
+                    Written::from_size(written)?,
+                    AcceptorState::RdstlsWaitAuthenticationRequest { requested_protocol },
+                )
+            }
+
+            AcceptorState::RdstlsWaitAuthenticationRequest { requested_protocol } => {
+                let request = ironrdp_pdu::decode::<RdstlsAuthenticationRequest>(input).map_err(ConnectorError::pdu)?;
+
+                debug!(message = ?request, "Received");
+
+                let result = match &self.rdstls_authenticator {
+                    Some(authenticator) => authenticator.authenticate(&request),
+                    None => Err(RdstlsResultCode::ACCESS_DENIED),
+                };
+
+                let result_code = match result {
+                    Ok(identity) => {
+                        info!(username = %identity.username, "RDSTLS authentication succeeded");
+                        self.identity = Some(identity);
+                        self.authenticated = true;
+                        RdstlsResultCode::SUCCESS
+                    }
+                    Err(result_code) => {
+                        warn!(%result_code, "RDSTLS authentication failed");
+                        result_code
+                    }
+                };
+
+                (
+                    Written::Nothing,
+                    AcceptorState::RdstlsSendAuthenticationResponse {
+                        requested_protocol,
+                        result_code,
+                    },
+                )
+            }
+
+            AcceptorState::RdstlsSendAuthenticationResponse {
+                requested_protocol,
+                result_code,
+            } => {
+                let response = RdstlsAuthenticationResponse { result_code };
+
+                debug!(message = ?response, "Send");
+
+                let written = ironrdp_pdu::encode_buf(&response, output).map_err(ConnectorError::pdu)?;
+
+                let next_state = if result_code == RdstlsResultCode::SUCCESS {
+                    AcceptorState::BasicSettingsWaitInitial { requested_protocol }
+                } else {
+                    AcceptorState::RdstlsRejected { result_code }
+                };
+
+                (Written::from_size(written)?, next_state)
+            }
+
+            AcceptorState::RdstlsRejected { result_code } => {
+                return Err(reason_err!("RDSTLS", "{result_code}"));
+            }
+
             AcceptorState::BasicSettingsWaitInitial { requested_protocol } => {
                 let settings_initial = legacy::decode_x224_packet::<mcs::ConnectInitial>(input)?;
 
@@ -247,6 +676,8 @@ impl Sequence for Acceptor {
                     .optional_data
                     .early_capability_flags;
 
+                self.client_gcc_blocks = Some(settings_initial.conference_create_request.gcc_blocks.clone());
+
                 let joined: Vec<_> = settings_initial
                     .conference_create_request
                     .gcc_blocks
@@ -378,9 +809,86 @@ impl Sequence for Acceptor {
 
                 debug!(message = ?client_info, "Received");
 
+                // The user was already authenticated by CredSSP or RDSTLS, whose identity is kept.
+                let validator = self.credential_validator.as_ref().filter(|_| !self.authenticated);
+
+                let validation = validator.map(|validator| {
+                    let credentials = &client_info.client_info.credentials;
+
+                    let credentials = Credentials {
+                        username: credentials.username.clone(),
+                        password: credentials.password.clone(),
+                        domain: credentials.domain.clone(),
+                    };
+
+                    validator
+                        .validate(&credentials, &client_info.client_info)
+                        .map(|()| UserIdentity {
+                            username: credentials.username,
+                            domain: credentials.domain,
+                        })
+                });
+
+                match validation {
+                    Some(Err(reason)) => {
+                        warn!(reason = reason.description(), "Logon rejected");
+
+                        let written =
+                            util::encode_disconnect(reason, self.user_channel_id, self.io_channel_id, output)?;
+
+                        (Written::from_size(written)?, AcceptorState::LogonRejected { reason })
+                    }
+                    validation => {
+                        if let Some(Ok(identity)) = validation {
+                            info!(username = %identity.username, "Logon accepted");
+                            self.identity = Some(identity);
+                        }
+
+                        let mut client_info = client_info.client_info;
+                        client_info.credentials.password.clear();
+                        self.client_info = Some(client_info);
+
+                        (
+                            Written::Nothing,
+                            AcceptorState::LicensingExchange {
+                                early_capability,
+                                channels,
+                            },
+                        )
+                    }
+                }
+            }
+
+            AcceptorState::LogonRejected { reason } => {
+                return Err(reason_err!("Logon", "{}", reason.description()));
+            }
+
+            AcceptorState::Redirected => {
+                return Err(reason_err!("Redirection", "client redirected to another server"));
+            }
+
+            AcceptorState::DeactivateAll {
+                early_capability,
+                channels,
+            } => {
+                let deactivate_all = rdp::headers::ShareControlHeader {
+                    share_id: 0,
+                    pdu_source: self.io_channel_id,
+                    share_control_pdu: ShareControlPdu::ServerDeactivateAll(rdp::headers::ServerDeactivateAll),
+                };
+
+                debug!(message = ?deactivate_all, "Send");
+
+                let written = util::encode_send_data_indication(
+                    self.user_channel_id,
+                    self.io_channel_id,
+                    &deactivate_all,
+                    output,
+                )?;
+
                 (
-                    Written::Nothing,
-                    AcceptorState::LicensingExchange {
+                    Written::from_size(written)?,
+                    AcceptorState::CapabilitiesSendServer {
                         early_capability,
                         channels,
                     },
@@ -395,16 +903,43 @@ impl Sequence for Acceptor {
 
                 debug!(message = ?license, "Send");
 
-                let written =
+                let mut written =
                     util::encode_send_data_indication(self.user_channel_id, self.io_channel_id, &license, output)?;
 
-                (
-                    Written::from_size(written)?,
-                    AcceptorState::CapabilitiesSendServer {
-                        early_capability,
-                        channels,
-                    },
-                )
+                let redirection = self.redirector.as_ref().and_then(|redirector| {
+                    let client = self.client_data()?;
+                    redirector.redirect(&client, self.identity.as_ref())
+                });
+
+                if let Some(redirection) = redirection {
+                    let redirection_pdu = rdp::headers::ShareControlHeader {
+                        share_id: 0,
+                        pdu_source: self.io_channel_id,
+                        share_control_pdu: ShareControlPdu::ServerRedirect(redirection.clone()),
+                    };
+
+                    debug!(message = ?redirection_pdu, "Send");
+
+                    written += util::encode_send_data_indication(
+                        self.user_channel_id,
+                        self.io_channel_id,
+                        &redirection_pdu,
+                        output,
+                    )?;
+
+                    info!(target = ?redirection.target_net_address, "Client redirected");
+                    self.redirection = Some(redirection);
+
+                    (Written::from_size(written)?, AcceptorState::Redirected)
+                } else {
+                    (
+                        Written::from_size(written)?,
+                        AcceptorState::CapabilitiesSendServer {
+                            early_capability,
+                            channels,
+                        },
+                    )
+                }
             }
 
             AcceptorState::CapabilitiesSendServer {
@@ -426,6 +961,11 @@ impl Sequence for Acceptor {
 
                 debug!(message = ?demand_active, "Send");
 
+                self.saved_for_reactivation = Some(ReactivationContext {
+                    early_capability,
+                    channels: channels.clone(),
+                });
+
                 let written = util::encode_send_data_indication(
                     self.user_channel_id,
                     self.io_channel_id,
@@ -437,7 +977,10 @@ impl Sequence for Acceptor {
                 let next_state = if early_capability.is_some_and(|c| c.contains(layout_flag)) {
                     AcceptorState::MonitorLayoutSend { channels }
                 } else {
-                    AcceptorState::CapabilitiesWaitConfirm { channels }
+                    AcceptorState::CapabilitiesWaitConfirm {
+                        channels,
+                        input_events: Vec::new(),
+                    }
                 };
 
                 (Written::from_size(written)?, next_state)
@@ -449,8 +992,9 @@ impl Sequence for Acceptor {
                         monitors: vec![gcc::Monitor {
                             left: 0,
                             top: 0,
-                            right: i32::from(self.desktop_size.width),
-                            bottom: i32::from(self.desktop_size.height),
+                            // The right and bottom bounds are inclusive
+                            right: i32::from(self.desktop_size.width) - 1,
+                            bottom: i32::from(self.desktop_size.height) - 1,
                             flags: gcc::MonitorFlags::PRIMARY,
                         }],
                     });
@@ -464,43 +1008,94 @@ impl Sequence for Acceptor {
 
                 (
                     Written::from_size(written)?,
-                    AcceptorState::CapabilitiesWaitConfirm { channels },
+                    AcceptorState::CapabilitiesWaitConfirm {
+                        channels,
+                        input_events: Vec::new(),
+                    },
                 )
             }
 
-            AcceptorState::CapabilitiesWaitConfirm { channels } => {
-                let message = ironrdp_pdu::decode::<mcs::McsMessage<'_>>(input).map_err(ConnectorError::pdu)?;
-
-                match message {
-                    mcs::McsMessage::SendDataRequest(data) => {
-                        let capabilities_confirm =
-                            rdp::headers::ShareControlHeader::from_buffer(data.user_data.as_ref())?;
+            AcceptorState::CapabilitiesWaitConfirm {
+                channels,
+                mut input_events,
+            } => {
+                let is_fast_path = input
+                    .first()
+                    .is_some_and(|&header| pdu::Action::from_fp_output_header(header) == Ok(pdu::Action::FastPath));
 
-                        debug!(message = ?capabilities_confirm, "Received");
+                if is_fast_path {
+                    input_events.push(input.to_vec());
 
-                        let ShareControlPdu::ClientConfirmActive(confirm) = capabilities_confirm.share_control_pdu
-                        else {
-                            return Err(ConnectorError::general("expected client confirm active"));
-                        };
+                    (
+                        Written::Nothing,
+                        AcceptorState::CapabilitiesWaitConfirm { channels, input_events },
+                    )
+                } else {
+                    let message = ironrdp_pdu::decode::<mcs::McsMessage<'_>>(input).map_err(ConnectorError::pdu)?;
+
+                    match message {
+                        mcs::McsMessage::SendDataRequest(data) if data.channel_id != self.io_channel_id => {
+                            // Virtual channel data sent before the client processed the Deactivate All PDU
+                            debug!(
+                                channel_id = data.channel_id,
+                                "Virtual channel data kept for later processing"
+                            );
+                            input_events.push(input.to_vec());
+
+                            (
+                                Written::Nothing,
+                                AcceptorState::CapabilitiesWaitConfirm { channels, input_events },
+                            )
+                        }
 
-                        (
-                            Written::Nothing,
-                            AcceptorState::ConnectionFinalization {
-                                channels,
-                                finalization: FinalizationSequence::new(self.user_channel_id, self.io_channel_id),
-                                client_capabilities: confirm.pdu.capability_sets,
-                            },
-                        )
-                    }
+                        mcs::McsMessage::SendDataRequest(data) => {
+                            let capabilities_confirm =
+                                rdp::headers::ShareControlHeader::from_buffer(data.user_data.as_ref())?;
+
+                            debug!(message = ?capabilities_confirm, "Received");
+
+                            match capabilities_confirm.share_control_pdu {
+                                ShareControlPdu::ClientConfirmActive(confirm) => {
+                                    let mut finalization =
+                                        FinalizationSequence::new(self.user_channel_id, self.io_channel_id);
+                                    finalization.input_events = input_events;
+
+                                    (
+                                        Written::Nothing,
+                                        AcceptorState::ConnectionFinalization {
+                                            channels,
+                                            finalization,
+                                            client_capabilities: confirm.pdu.capability_sets,
+                                        },
+                                    )
+                                }
+
+                                // Such as slow-path input sent before the client processed the Deactivate All PDU
+                                ShareControlPdu::Data(_) => {
+                                    input_events.push(input.to_vec());
+
+                                    (
+                                        Written::Nothing,
+                                        AcceptorState::CapabilitiesWaitConfirm { channels, input_events },
+                                    )
+                                }
+
+                                _ => return Err(ConnectorError::general("expected client confirm active")),
+                            }
+                        }
 
-                    mcs::McsMessage::DisconnectProviderUltimatum(ultimatum) => {
-                        return Err(reason_err!("received disconnect ultimatum", "{:?}", ultimatum.reason))
-                    }
+                        mcs::McsMessage::DisconnectProviderUltimatum(ultimatum) => {
+                            return Err(reason_err!("received disconnect ultimatum", "{:?}", ultimatum.reason))
+                        }
 
-                    _ => {
-                        warn!(?message, "Unexpected MCS message received");
+                        _ => {
+                            warn!(?message, "Unexpected MCS message received");
 
-                        (Written::Nothing, AcceptorState::CapabilitiesWaitConfirm { channels })
+                            (
+                                Written::Nothing,
+                                AcceptorState::CapabilitiesWaitConfirm { channels, input_events },
+                            )
+                        }
                     }
                 }
             }
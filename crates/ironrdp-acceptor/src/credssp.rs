@@ -0,0 +1,229 @@
+use std::io;
+use std::sync::Arc;
+
+pub use ironrdp_connector::credssp::KerberosConfig;
+use ironrdp_connector::sspi::credssp::{self, ClientMode, CredSspServer, CredentialsProxy, ServerError, ServerState};
+use ironrdp_connector::sspi::{self, AuthIdentity, Username};
+use ironrdp_connector::{custom_err, general_err, ConnectorError, ConnectorErrorKind, ConnectorResult, Written};
+use ironrdp_pdu::write_buf::WriteBuf;
+use ironrdp_pdu::{nego, PduHint};
+
+/// User credentials known by the server
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    pub domain: Option<String>,
+}
+
+/// Credential store used to authenticate clients during CredSSP
+///
+/// NTLM needs the secret of the user in order to verify the client response, so the store is
+/// looked up by user name once the client has sent its authentication message.
+pub trait CredentialStore: Send + Sync {
+    /// Returns the credentials of the user, or `None` if the user is unknown.
+    fn credentials(&self, username: &str, domain: Option<&str>) -> Option<Credentials>;
+}
+
+/// A store holding the credentials of a single user
+impl CredentialStore for Credentials {
+    fn credentials(&self, username: &str, domain: Option<&str>) -> Option<Credentials> {
+        let same_user = self.username.eq_ignore_ascii_case(username);
+
+        let same_domain = match (self.domain.as_deref(), domain) {
+            (Some(expected), Some(domain)) => expected.eq_ignore_ascii_case(domain),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        (same_user && same_domain).then(|| self.clone())
+    }
+}
+
+struct CredentialStoreProxy {
+    store: Arc<dyn CredentialStore>,
+}
+
+impl CredentialsProxy for CredentialStoreProxy {
+    type AuthenticationData = AuthIdentity;
+
+    fn auth_data_by_user(&mut self, username: &Username) -> io::Result<Self::AuthenticationData> {
+        let credentials = self
+            .store
+            .credentials(username.account_name(), username.domain_name())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "unknown user"))?;
+
+        let username = Username::new(&credentials.username, credentials.domain.as_deref())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(AuthIdentity {
+            username,
+            password: credentials.password.into(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CredsspTsRequestHint;
+
+const CREDSSP_TS_REQUEST_HINT: CredsspTsRequestHint = CredsspTsRequestHint;
+
+impl PduHint for CredsspTsRequestHint {
+    fn find_size(&self, bytes: &[u8]) -> ironrdp_pdu::PduResult<Option<usize>> {
+        match credssp::TsRequest::read_length(bytes) {
+            Ok(length) => Ok(Some(length)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(ironrdp_pdu::custom_err!("CredsspTsRequestHint", e)),
+        }
+    }
+}
+
+pub struct CredsspSequence {
+    server: CredSspServer<CredentialStoreProxy>,
+    state: CredsspState,
+    selected_protocol: nego::SecurityProtocol,
+}
+
+#[derive(Debug)]
+enum CredsspState {
+    Ongoing,
+    Finished(AuthIdentity),
+    Failed(sspi::Error),
+}
+
+impl CredsspSequence {
+    pub fn next_pdu_hint(&self) -> Option<&dyn PduHint> {
+        match self.state {
+            CredsspState::Ongoing => Some(&CREDSSP_TS_REQUEST_HINT),
+            CredsspState::Finished(_) | CredsspState::Failed(_) => None,
+        }
+    }
+
+    /// `public_key` must be the public key of the certificate used for the TLS upgrade
+    ///
+    /// NTLM is used unless a Kerberos configuration is provided.
+    pub fn init(
+        credentials: Arc<dyn CredentialStore>,
+        public_key: Vec<u8>,
+        selected_protocol: nego::SecurityProtocol,
+        kerberos_config: Option<KerberosConfig>,
+    ) -> ConnectorResult<Self> {
+        let client_mode = match kerberos_config {
+            Some(krb_config) => ClientMode::Kerberos(krb_config.into()),
+            None => ClientMode::Ntlm(sspi::ntlm::NtlmConfig::default()),
+        };
+
+        let server = CredSspServer::new(public_key, CredentialStoreProxy { store: credentials }, client_mode)
+            .map_err(|e| ConnectorError::new("CredSSP", ConnectorErrorKind::Credssp(e)))?;
+
+        Ok(Self {
+            server,
+            state: CredsspState::Ongoing,
+            selected_protocol,
+        })
+    }
+
+    pub fn decode_client_message(&self, input: &[u8]) -> ConnectorResult<credssp::TsRequest> {
+        match self.state {
+            CredsspState::Ongoing => {
+                let message = credssp::TsRequest::from_buffer(input).map_err(|e| custom_err!("TsRequest", e))?;
+                debug!(?message, "Received");
+                Ok(message)
+            }
+            _ => Err(general_err!(
+                "attempted to feed client request to CredSSP sequence in an unexpected state"
+            )),
+        }
+    }
+
+    #[allow(clippy::result_large_err)] // the error type is defined by sspi
+    pub fn process_ts_request(&mut self, request: credssp::TsRequest) -> Result<ServerState, ServerError> {
+        self.server.process(request)
+    }
+
+    pub fn handle_process_result(
+        &mut self,
+        result: Result<ServerState, ServerError>,
+        output: &mut WriteBuf,
+    ) -> ConnectorResult<Written> {
+        if !matches!(self.state, CredsspState::Ongoing) {
+            return Err(general_err!("CredSSP sequence is already done"));
+        }
+
+        let (written, next_state) = match result {
+            Ok(ServerState::ReplyNeeded(ts_request)) => {
+                debug!(message = ?ts_request, "Send");
+
+                let written = write_credssp_request(ts_request, output)?;
+
+                (Written::from_size(written)?, CredsspState::Ongoing)
+            }
+            Ok(ServerState::Finished(identity)) => {
+                info!(username = %identity.username.account_name(), "Client authenticated");
+
+                // The Early User Authorization Result PDU is only sent when HYBRID_EX is selected.
+                let written = if self.selected_protocol.contains(nego::SecurityProtocol::HYBRID_EX) {
+                    let result = credssp::EarlyUserAuthResult::Success;
+
+                    debug!(message = ?result, "Send");
+
+                    Written::from_size(write_early_user_auth_result(result, output)?)?
+                } else {
+                    Written::Nothing
+                };
+
+                (written, CredsspState::Finished(identity))
+            }
+            Err(ServerError { ts_request, error }) => {
+                warn!(%error, "CredSSP authentication failed");
+
+                debug!(message = ?ts_request, "Send");
+
+                let written = write_credssp_request(ts_request, output)?;
+
+                (Written::from_size(written)?, CredsspState::Failed(error))
+            }
+        };
+
+        self.state = next_state;
+
+        Ok(written)
+    }
+
+    /// Returns the identity of the authenticated user once the sequence is done.
+    pub fn into_result(self) -> ConnectorResult<AuthIdentity> {
+        match self.state {
+            CredsspState::Finished(identity) => Ok(identity),
+            CredsspState::Failed(error) => Err(ConnectorError::new("CredSSP", ConnectorErrorKind::Credssp(error))),
+            CredsspState::Ongoing => Err(general_err!("CredSSP sequence is not done")),
+        }
+    }
+}
+
+fn write_credssp_request(ts_request: credssp::TsRequest, output: &mut WriteBuf) -> ConnectorResult<usize> {
+    let length = usize::from(ts_request.buffer_len());
+
+    let unfilled_buffer = output.unfilled_to(length);
+
+    ts_request
+        .encode_ts_request(unfilled_buffer)
+        .map_err(|e| custom_err!("TsRequest", e))?;
+
+    output.advance(length);
+
+    Ok(length)
+}
+
+fn write_early_user_auth_result(result: credssp::EarlyUserAuthResult, output: &mut WriteBuf) -> ConnectorResult<usize> {
+    let length = result.buffer_len();
+
+    let unfilled_buffer = output.unfilled_to(length);
+
+    result
+        .to_buffer(unfilled_buffer)
+        .map_err(|e| custom_err!("EarlyUserAuthResult", e))?;
+
+    output.advance(length);
+
+    Ok(length)
+}
@@ -1,20 +1,33 @@
 #[macro_use]
 extern crate tracing;
 
+use std::sync::Arc;
+
 use ironrdp_async::{Framed, FramedRead, FramedWrite, StreamWrapper};
-use ironrdp_connector::{custom_err, ConnectorResult, Sequence, Written};
+use ironrdp_connector::credssp::KerberosConfig;
+use ironrdp_connector::sspi::AuthIdentity;
+use ironrdp_connector::{custom_err, general_err, ConnectorResult, Sequence, Written};
 use ironrdp_pdu::write_buf::WriteBuf;
 
 mod channel_connection;
 mod connection;
+pub mod credssp;
 mod finalization;
+mod logon;
+mod rdstls;
+mod redirection;
 mod util;
 
 pub use ironrdp_connector::DesktopSize;
 
 pub use self::channel_connection::{ChannelConnectionSequence, ChannelConnectionState};
-pub use self::connection::{Acceptor, AcceptorResult, AcceptorState};
+pub use self::connection::{Acceptor, AcceptorResult, AcceptorState, ClientConnectionData};
+pub use self::credssp::{CredentialStore, Credentials};
 pub use self::finalization::{FinalizationSequence, FinalizationState};
+pub use self::logon::{CredentialValidator, UserIdentity};
+pub use self::rdstls::RdstlsAuthenticator;
+pub use self::redirection::ConnectionRedirector;
+pub use self::util::encode_disconnect;
 
 pub enum BeginResult<S>
 where
@@ -59,10 +72,75 @@ where
             return Ok((framed, result));
         }
 
+        if acceptor.should_perform_credssp().is_some() {
+            return Err(general_err!(
+                "CredSSP must be performed before finalizing the connection"
+            ));
+        }
+
         single_accept_state(&mut framed, acceptor, &mut buf).await?;
     }
 }
 
+/// Performs Network Level Authentication using CredSSP
+///
+/// Must be called on the upgraded stream, after [`Acceptor::mark_security_upgrade_as_done`],
+/// whenever [`Acceptor::should_perform_credssp`] returns a protocol.
+/// `public_key` must be the public key of the TLS certificate presented to the client.
+pub async fn accept_credssp<S>(
+    framed: &mut Framed<S>,
+    acceptor: &mut Acceptor,
+    credentials: Arc<dyn CredentialStore>,
+    public_key: Vec<u8>,
+    kerberos_config: Option<KerberosConfig>,
+) -> ConnectorResult<AuthIdentity>
+where
+    S: FramedRead + FramedWrite,
+{
+    let Some(selected_protocol) = acceptor.should_perform_credssp() else {
+        return Err(general_err!("CredSSP is not expected in the current acceptor state"));
+    };
+
+    let mut sequence = credssp::CredsspSequence::init(credentials, public_key, selected_protocol, kerberos_config)?;
+    let mut buf = WriteBuf::new();
+
+    while let Some(next_pdu_hint) = sequence.next_pdu_hint() {
+        debug!(
+            acceptor.state = acceptor.state().name(),
+            hint = ?next_pdu_hint,
+            "Wait for PDU"
+        );
+
+        let pdu = framed
+            .read_by_hint(next_pdu_hint)
+            .await
+            .map_err(|e| custom_err!("read frame by hint", e))?;
+
+        trace!(length = pdu.len(), "PDU received");
+
+        let ts_request = sequence.decode_client_message(&pdu)?;
+        let result = sequence.process_ts_request(ts_request);
+
+        buf.clear();
+        let written = sequence.handle_process_result(result, &mut buf)?;
+
+        if let Some(response_len) = written.size() {
+            let response = &buf[..response_len];
+            trace!(response_len, "Send response");
+            framed
+                .write_all(response)
+                .await
+                .map_err(|e| custom_err!("write all", e))?;
+        }
+    }
+
+    let identity = sequence.into_result()?;
+
+    acceptor.mark_credssp_as_done_for(UserIdentity::from(&identity))?;
+
+    Ok(identity)
+}
+
 async fn single_accept_state<S>(
     framed: &mut Framed<S>,
     acceptor: &mut Acceptor,
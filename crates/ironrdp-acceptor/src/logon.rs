@@ -0,0 +1,49 @@
+use ironrdp_connector::sspi::AuthIdentity;
+use ironrdp_pdu::rdp::client_info::ClientInfo;
+use ironrdp_pdu::rdp::server_error_info::{ErrorInfo, ProtocolIndependentCode};
+
+use crate::Credentials;
+
+/// Identity of the user logged on the session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserIdentity {
+    pub username: String,
+    pub domain: Option<String>,
+}
+
+impl From<&AuthIdentity> for UserIdentity {
+    fn from(identity: &AuthIdentity) -> Self {
+        Self {
+            username: identity.username.account_name().to_owned(),
+            domain: identity.username.domain_name().map(str::to_owned),
+        }
+    }
+}
+
+/// Decides whether a client may log on with the credentials sent in the Client Info PDU
+pub trait CredentialValidator: Send + Sync {
+    /// Returns the reason reported to the client in the Set Error Info PDU if the logon is rejected.
+    fn validate(&self, credentials: &Credentials, client_info: &ClientInfo) -> Result<(), ErrorInfo>;
+}
+
+/// Only accepts the logon of a single user
+impl CredentialValidator for Credentials {
+    fn validate(&self, credentials: &Credentials, _: &ClientInfo) -> Result<(), ErrorInfo> {
+        let same_domain = match (self.domain.as_deref(), credentials.domain.as_deref()) {
+            (Some(expected), Some(domain)) => expected.eq_ignore_ascii_case(domain),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        if self.username.eq_ignore_ascii_case(&credentials.username)
+            && same_domain
+            && self.password == credentials.password
+        {
+            Ok(())
+        } else {
+            Err(ErrorInfo::ProtocolIndependentCode(
+                ProtocolIndependentCode::ServerDeniedConnection,
+            ))
+        }
+    }
+}
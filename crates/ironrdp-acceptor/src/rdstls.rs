@@ -0,0 +1,12 @@
+use ironrdp_pdu::rdstls::{RdstlsAuthenticationRequest, RdstlsResultCode};
+
+use crate::UserIdentity;
+
+/// Authenticates clients connecting with the RDSTLS security protocol
+///
+/// Redirected clients send back the redirection GUID and the password cookie handed out in the
+/// Server Redirection PDU, or the auto-reconnect cookie of their previous session.
+pub trait RdstlsAuthenticator: Send + Sync {
+    /// Returns the result code reported to the client in the Authentication Response PDU if the client is rejected.
+    fn authenticate(&self, request: &RdstlsAuthenticationRequest) -> Result<UserIdentity, RdstlsResultCode>;
+}
@@ -0,0 +1,12 @@
+use ironrdp_pdu::rdp::server_redirection::ServerRedirectionPdu;
+
+use crate::{ClientConnectionData, UserIdentity};
+
+/// Decides whether a client is redirected to another server, for load balancing
+pub trait ConnectionRedirector: Send + Sync {
+    /// Returns the redirection sent to the client instead of activating the session, if any.
+    ///
+    /// Called once the logon is accepted, `identity` being the user authenticated by CredSSP or by
+    /// the credential validator.
+    fn redirect(&self, client: &ClientConnectionData, identity: Option<&UserIdentity>) -> Option<ServerRedirectionPdu>;
+}
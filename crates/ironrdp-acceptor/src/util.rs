@@ -1,8 +1,9 @@
 use std::borrow::Cow;
 
 use ironrdp_connector::{ConnectorError, ConnectorErrorExt, ConnectorResult};
+use ironrdp_pdu::rdp::server_error_info::{ErrorInfo, ServerSetErrorInfoPdu};
 use ironrdp_pdu::write_buf::WriteBuf;
-use ironrdp_pdu::{rdp, PduParsing};
+use ironrdp_pdu::{mcs, rdp, PduParsing};
 
 pub(crate) fn encode_send_data_indication<T>(
     initiator_id: u16,
@@ -42,3 +43,32 @@ pub(crate) fn wrap_share_data(pdu: rdp::headers::ShareDataPdu, io_channel_id: u1
         }),
     }
 }
+
+/// Encodes a Set Error Info PDU with `reason`, followed by a Disconnect Provider Ultimatum
+///
+/// This tells the client why the server is disconnecting it, and returns the number of bytes written.
+pub fn encode_disconnect(
+    reason: ErrorInfo,
+    user_channel_id: u16,
+    io_channel_id: u16,
+    output: &mut WriteBuf,
+) -> ConnectorResult<usize> {
+    let error_info = wrap_share_data(
+        rdp::headers::ShareDataPdu::ServerSetErrorInfo(ServerSetErrorInfoPdu(reason)),
+        io_channel_id,
+    );
+
+    debug!(message = ?error_info, "Send");
+
+    let mut written = encode_send_data_indication(user_channel_id, io_channel_id, &error_info, output)?;
+
+    let ultimatum = mcs::McsMessage::DisconnectProviderUltimatum(mcs::DisconnectProviderUltimatum::from_reason(
+        mcs::DisconnectReason::ProviderInitiated,
+    ));
+
+    debug!(message = ?ultimatum, "Send");
+
+    written += ironrdp_pdu::encode_buf(&ultimatum, output).map_err(ConnectorError::pdu)?;
+
+    Ok(written)
+}
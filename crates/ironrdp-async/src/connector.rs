@@ -40,6 +40,18 @@ pub struct Upgraded;
 #[instrument(skip_all)]
 pub fn mark_as_upgraded(_: ShouldUpgrade, connector: &mut ClientConnector) -> Upgraded {
     trace!("Marked as upgraded");
+    debug_assert!(!connector.is_standard_security_selected());
+    connector.mark_security_upgrade_as_done();
+    Upgraded
+}
+
+/// Moves on without upgrading the transport, as required when the server selected Standard RDP Security
+///
+/// The traffic is then encrypted by the connector, and by the session once connected.
+#[instrument(skip_all)]
+pub fn skip_security_upgrade(_: ShouldUpgrade, connector: &mut ClientConnector) -> Upgraded {
+    assert!(connector.is_standard_security_selected());
+    trace!("Security upgrade skipped");
     connector.mark_security_upgrade_as_done();
     Upgraded
 }
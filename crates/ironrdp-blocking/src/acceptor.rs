@@ -0,0 +1,159 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use ironrdp_acceptor::credssp::{CredentialStore, CredsspSequence};
+use ironrdp_acceptor::{Acceptor, AcceptorResult, UserIdentity};
+use ironrdp_connector::credssp::KerberosConfig;
+use ironrdp_connector::sspi::AuthIdentity;
+use ironrdp_connector::{custom_err, general_err, ConnectorResult, Sequence as _, Written};
+use ironrdp_pdu::write_buf::WriteBuf;
+
+use crate::framed::Framed;
+
+pub enum BeginResult<S> {
+    ShouldUpgrade(S),
+    Continue(Framed<S>),
+}
+
+#[instrument(skip_all)]
+pub fn accept_begin<S>(mut framed: Framed<S>, acceptor: &mut Acceptor) -> ConnectorResult<BeginResult<S>>
+where
+    S: Read + Write,
+{
+    let mut buf = WriteBuf::new();
+
+    info!("Begin connection acceptance");
+
+    loop {
+        if let Some(security) = acceptor.reached_security_upgrade() {
+            let result = if security.is_empty() {
+                BeginResult::Continue(framed)
+            } else {
+                BeginResult::ShouldUpgrade(framed.into_inner_no_leftover())
+            };
+
+            return Ok(result);
+        }
+
+        single_accept_state(&mut framed, acceptor, &mut buf)?;
+    }
+}
+
+#[instrument(skip_all)]
+pub fn accept_finalize<S>(
+    mut framed: Framed<S>,
+    acceptor: &mut Acceptor,
+) -> ConnectorResult<(Framed<S>, AcceptorResult)>
+where
+    S: Read + Write,
+{
+    let mut buf = WriteBuf::new();
+
+    loop {
+        if let Some(result) = acceptor.get_result() {
+            info!("Connection accepted");
+            return Ok((framed, result));
+        }
+
+        if acceptor.should_perform_credssp().is_some() {
+            return Err(general_err!(
+                "CredSSP must be performed before finalizing the connection"
+            ));
+        }
+
+        single_accept_state(&mut framed, acceptor, &mut buf)?;
+    }
+}
+
+/// Performs Network Level Authentication using CredSSP
+///
+/// Must be called on the upgraded stream, after [`Acceptor::mark_security_upgrade_as_done`],
+/// whenever [`Acceptor::should_perform_credssp`] returns a protocol.
+/// `public_key` must be the public key of the TLS certificate presented to the client.
+#[instrument(level = "trace", skip_all)]
+pub fn accept_credssp<S>(
+    framed: &mut Framed<S>,
+    acceptor: &mut Acceptor,
+    credentials: Arc<dyn CredentialStore>,
+    public_key: Vec<u8>,
+    kerberos_config: Option<KerberosConfig>,
+) -> ConnectorResult<AuthIdentity>
+where
+    S: Read + Write,
+{
+    let Some(selected_protocol) = acceptor.should_perform_credssp() else {
+        return Err(general_err!("CredSSP is not expected in the current acceptor state"));
+    };
+
+    let mut sequence = CredsspSequence::init(credentials, public_key, selected_protocol, kerberos_config)?;
+    let mut buf = WriteBuf::new();
+
+    while let Some(next_pdu_hint) = sequence.next_pdu_hint() {
+        debug!(
+            acceptor.state = acceptor.state().name(),
+            hint = ?next_pdu_hint,
+            "Wait for PDU"
+        );
+
+        let pdu = framed
+            .read_by_hint(next_pdu_hint)
+            .map_err(|e| custom_err!("read frame by hint", e))?;
+
+        trace!(length = pdu.len(), "PDU received");
+
+        let ts_request = sequence.decode_client_message(&pdu)?;
+        let result = sequence.process_ts_request(ts_request);
+
+        buf.clear();
+        let written = sequence.handle_process_result(result, &mut buf)?;
+
+        if let Some(response_len) = written.size() {
+            let response = &buf[..response_len];
+            trace!(response_len, "Send response");
+            framed.write_all(response).map_err(|e| custom_err!("write all", e))?;
+        }
+    }
+
+    let identity = sequence.into_result()?;
+
+    acceptor.mark_credssp_as_done_for(UserIdentity::from(&identity))?;
+
+    Ok(identity)
+}
+
+pub fn single_accept_state<S>(
+    framed: &mut Framed<S>,
+    acceptor: &mut Acceptor,
+    buf: &mut WriteBuf,
+) -> ConnectorResult<Written>
+where
+    S: Read + Write,
+{
+    buf.clear();
+
+    let written = if let Some(next_pdu_hint) = acceptor.next_pdu_hint() {
+        debug!(
+            acceptor.state = acceptor.state().name(),
+            hint = ?next_pdu_hint,
+            "Wait for PDU"
+        );
+
+        let pdu = framed
+            .read_by_hint(next_pdu_hint)
+            .map_err(|e| custom_err!("read frame by hint", e))?;
+
+        trace!(length = pdu.len(), "PDU received");
+
+        acceptor.step(&pdu, buf)?
+    } else {
+        acceptor.step_no_input(buf)?
+    };
+
+    if let Some(response_len) = written.size() {
+        let response = &buf[..response_len];
+        trace!(response_len, "Send response");
+        framed.write_all(response).map_err(|e| custom_err!("write all", e))?;
+    }
+
+    Ok(written)
+}
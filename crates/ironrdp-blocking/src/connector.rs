@@ -1,12 +1,13 @@
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs as _};
 
 use ironrdp_connector::credssp::{CredsspProcessGenerator, CredsspSequence, KerberosConfig};
 use ironrdp_connector::sspi::credssp::ClientState;
 use ironrdp_connector::sspi::generator::GeneratorState;
 use ironrdp_connector::sspi::network_client::NetworkClient;
 use ironrdp_connector::{
-    ClientConnector, ClientConnectorState, ConnectionResult, ConnectorError, ConnectorResult, Sequence as _,
-    ServerName, State as _,
+    ClientConnector, ClientConnectorState, ConnectionResult, ConnectorError, ConnectorResult, Redirection,
+    Sequence as _, ServerName, State as _,
 };
 use ironrdp_pdu::write_buf::WriteBuf;
 
@@ -42,6 +43,18 @@ pub struct Upgraded;
 #[instrument(skip_all)]
 pub fn mark_as_upgraded(_: ShouldUpgrade, connector: &mut ClientConnector) -> Upgraded {
     trace!("Marked as upgraded");
+    debug_assert!(!connector.is_standard_security_selected());
+    connector.mark_security_upgrade_as_done();
+    Upgraded
+}
+
+/// Moves on without upgrading the transport, as required when the server selected Standard RDP Security
+///
+/// The traffic is then encrypted by the connector, and by the session once connected.
+#[instrument(skip_all)]
+pub fn skip_security_upgrade(_: ShouldUpgrade, connector: &mut ClientConnector) -> Upgraded {
+    assert!(connector.is_standard_security_selected());
+    trace!("Security upgrade skipped");
     connector.mark_security_upgrade_as_done();
     Upgraded
 }
@@ -90,6 +103,50 @@ where
     Ok(result)
 }
 
+/// Opens a TCP connection to the target of a server redirection
+///
+/// The target addresses are tried in order, using the port of `server_addr`. When the redirection carries
+/// no address, `server_addr` is connected to again. Returns the stream along with the address connected to.
+///
+/// The connection sequence is then performed with a connector set up using [`ClientConnector::with_redirection`].
+pub fn connect_redirected(
+    redirection: &Redirection,
+    server_addr: SocketAddr,
+) -> ConnectorResult<(TcpStream, SocketAddr)> {
+    if redirection.target_addresses.is_empty() {
+        let stream = TcpStream::connect(server_addr).map_err(|e| ironrdp_connector::custom_err!("TCP connect", e))?;
+        return Ok((stream, server_addr));
+    }
+
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no target address could be resolved");
+
+    for address in &redirection.target_addresses {
+        let resolved = match (address.as_str(), server_addr.port()).to_socket_addrs() {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                debug!(address, error = %e, "Failed to resolve redirection target");
+                last_error = e;
+                continue;
+            }
+        };
+
+        for addr in resolved {
+            match TcpStream::connect(addr) {
+                Ok(stream) => return Ok((stream, addr)),
+                Err(e) => {
+                    debug!(%addr, error = %e, "Failed to connect to redirection target");
+                    last_error = e;
+                }
+            }
+        }
+    }
+
+    Err(ironrdp_connector::custom_err!(
+        "connect to redirection target",
+        last_error
+    ))
+}
+
 fn resolve_generator(
     generator: &mut CredsspProcessGenerator<'_>,
     network_client: &mut impl NetworkClient,
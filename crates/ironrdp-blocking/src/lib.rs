@@ -1,9 +1,11 @@
 #[macro_use]
 extern crate tracing;
 
+mod acceptor;
 mod connector;
 mod framed;
 mod session;
 
+pub use self::acceptor::*;
 pub use self::connector::*;
 pub use self::framed::*;
@@ -6,6 +6,7 @@ use anyhow::Context as _;
 use clap::clap_derive::ValueEnum;
 use clap::Parser;
 use ironrdp::connector::{self, Credentials};
+use ironrdp::pdu::pcb::{PcbVersion, PreconnectionBlob};
 use ironrdp::pdu::rdp::capability_sets::MajorPlatformType;
 use tap::prelude::*;
 
@@ -230,6 +231,28 @@ struct Args {
     #[clap(long, alias = "no-nla")]
     no_credssp: bool,
 
+    /// Allow Standard RDP Security (RC4 encryption and RSA key exchange) for legacy servers
+    ///
+    /// The server is not authenticated and the encryption is weak: only use it when TLS is not supported.
+    #[clap(long)]
+    standard_security: bool,
+
+    /// Use Restricted Admin mode: the credentials are not sent to the server
+    ///
+    /// Requires CredSSP, and the server to have Restricted Admin mode enabled.
+    #[clap(long)]
+    restricted_admin: bool,
+
+    /// Preconnection blob (PCB) string, sent before the connection request
+    ///
+    /// Hyper-V virtual machine consoles (port 2179) are reached by passing the VM ID.
+    #[clap(long)]
+    pcb: Option<String>,
+
+    /// Preconnection blob (PCB) ID, sent before the connection request
+    #[clap(long)]
+    pcb_id: Option<u32>,
+
     /// The clipboard type
     #[clap(long, value_enum, value_parser, default_value_t = ClipboardType::Default)]
     clipboard_type: ClipboardType,
@@ -288,6 +311,20 @@ impl Config {
             None
         };
 
+        let pcb = match (args.pcb, args.pcb_id) {
+            (None, None) => None,
+            (None, Some(id)) => Some(PreconnectionBlob {
+                version: PcbVersion::V1,
+                id,
+                v2_payload: None,
+            }),
+            (Some(payload), id) => Some(PreconnectionBlob {
+                version: PcbVersion::V2,
+                id: id.unwrap_or(0),
+                v2_payload: Some(payload),
+            }),
+        };
+
         let clipboard_type = if args.clipboard_type == ClipboardType::Default {
             #[cfg(windows)]
             {
@@ -306,6 +343,12 @@ impl Config {
             domain: args.domain,
             enable_tls: !args.no_tls,
             enable_credssp: !args.no_credssp,
+            enable_standard_security: args.standard_security,
+            credential_delegation: if args.restricted_admin {
+                connector::CredentialDelegation::RestrictedAdmin
+            } else {
+                connector::CredentialDelegation::Full
+            },
             keyboard_type: KeyboardType::parse(args.keyboard_type),
             keyboard_subtype: args.keyboard_subtype,
             keyboard_functional_keys_count: args.keyboard_functional_keys_count,
@@ -315,6 +358,7 @@ impl Config {
                 width: DEFAULT_WIDTH,
                 height: DEFAULT_HEIGHT,
             },
+            monitors: Vec::new(),
             graphics,
             bitmap,
             client_build: semver::Version::parse(env!("CARGO_PKG_VERSION"))
@@ -336,6 +380,8 @@ impl Config {
             },
             no_server_pointer: args.no_server_pointer,
             autologon: args.autologon,
+            auto_reconnect_cookie: None,
+            pcb,
             pointer_software_rendering: true,
         };
 
@@ -1,12 +1,17 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
 use ironrdp::cliprdr::backend::{ClipboardMessage, CliprdrBackendFactory};
 use ironrdp::connector::{ConnectionResult, ConnectorResult};
 use ironrdp::graphics::image_processing::PixelFormat;
 use ironrdp::pdu::input::fast_path::FastPathInputEvent;
+use ironrdp::pdu::rdp::session_info::ServerAutoReconnect;
 use ironrdp::session::image::DecodedImage;
-use ironrdp::session::{ActiveStage, ActiveStageOutput, GracefulDisconnectReason, SessionResult};
+use ironrdp::session::{ActiveStage, ActiveStageOutput, GracefulDisconnectReason, SessionError, SessionResult};
 use ironrdp::{cliprdr, connector, rdpdr, rdpsnd, session};
 use rdpdr::NoopRdpdrBackend;
 use smallvec::SmallVec;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
 use winit::event_loop::EventLoopProxy;
@@ -46,15 +51,26 @@ pub struct RdpClient {
 
 impl RdpClient {
     pub async fn run(mut self) {
+        let mut reconnection: Option<Reconnection> = None;
+
         loop {
             let (connection_result, framed) = match connect(&self.config, self.cliprdr_factory.as_deref()).await {
                 Ok(result) => result,
                 Err(e) => {
+                    // The server may not be reachable yet after the connection was lost
+                    if let Some(delay) = reconnection.as_mut().and_then(Reconnection::next_attempt) {
+                        warn!(error = %e, ?delay, "Reconnection failed, trying again");
+                        tokio::time::sleep(delay).await;
+                        continue;
+                    }
+
                     let _ = self.event_loop_proxy.send_event(RdpOutputEvent::ConnectionFailure(e));
                     break;
                 }
             };
 
+            reconnection = None;
+
             match active_session(
                 framed,
                 connection_result,
@@ -63,9 +79,35 @@ impl RdpClient {
             )
             .await
             {
-                Ok(RdpControlFlow::ReconnectWithNewSize { width, height }) => {
+                Ok(RdpControlFlow::ReconnectWithNewSize {
+                    width,
+                    height,
+                    auto_reconnect_cookie,
+                }) => {
                     self.config.connector.desktop_size.width = width;
                     self.config.connector.desktop_size.height = height;
+                    self.config.connector.auto_reconnect_cookie = auto_reconnect_cookie;
+                }
+                Ok(RdpControlFlow::ConnectionLost {
+                    error,
+                    auto_reconnect_cookie: Some(auto_reconnect_cookie),
+                }) => {
+                    let mut attempts = Reconnection::default();
+                    let delay = attempts.next_attempt().unwrap_or_default();
+
+                    warn!(%error, ?delay, "Connection lost, reconnecting");
+
+                    // The auto-reconnect cookie lets the server resume the session which was interrupted.
+                    self.config.connector.auto_reconnect_cookie = Some(auto_reconnect_cookie);
+                    reconnection = Some(attempts);
+                    tokio::time::sleep(delay).await;
+                }
+                Ok(RdpControlFlow::ConnectionLost {
+                    error,
+                    auto_reconnect_cookie: None,
+                }) => {
+                    let _ = self.event_loop_proxy.send_event(RdpOutputEvent::Terminated(Err(error)));
+                    break;
                 }
                 Ok(RdpControlFlow::TerminatedGracefully(reason)) => {
                     let _ = self.event_loop_proxy.send_event(RdpOutputEvent::Terminated(Ok(reason)));
@@ -81,17 +123,60 @@ impl RdpClient {
 }
 
 enum RdpControlFlow {
-    ReconnectWithNewSize { width: u16, height: u16 },
+    ReconnectWithNewSize {
+        width: u16,
+        height: u16,
+        auto_reconnect_cookie: Option<ServerAutoReconnect>,
+    },
+    /// The connection dropped, the session may be resumed with the auto-reconnect cookie
+    ConnectionLost {
+        error: SessionError,
+        auto_reconnect_cookie: Option<ServerAutoReconnect>,
+    },
     TerminatedGracefully(GracefulDisconnectReason),
 }
 
-type UpgradedFramed = ironrdp_tokio::TokioFramed<ironrdp_tls::TlsStream<TcpStream>>;
+/// Attempts to connect again after the connection was lost
+#[derive(Debug, Default)]
+struct Reconnection {
+    attempts: u32,
+}
+
+impl Reconnection {
+    /// Maximum number of attempts made in a row, before giving up
+    const MAX_ATTEMPTS: u32 = 5;
+
+    /// Delay before the first attempt, increased with each further attempt
+    const DELAY: Duration = Duration::from_secs(1);
+
+    /// Returns the delay to wait before the next attempt, or `None` once all attempts were made
+    fn next_attempt(&mut self) -> Option<Duration> {
+        if self.attempts >= Self::MAX_ATTEMPTS {
+            return None;
+        }
+
+        let delay = Self::DELAY * self.attempts;
+        self.attempts += 1;
+
+        Some(delay)
+    }
+}
+
+trait AsyncReadWrite: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+
+impl<T> AsyncReadWrite for T where T: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+
+/// TLS stream, or TCP stream when Standard RDP Security is used
+type UpgradedFramed = ironrdp_tokio::TokioFramed<Box<dyn AsyncReadWrite>>;
+
+/// Maximum number of server redirections followed in a row, protecting against redirection loops
+const MAX_REDIRECTIONS: usize = 4;
 
 async fn connect(
     config: &Config,
     cliprdr_factory: Option<&(dyn CliprdrBackendFactory + Send)>,
 ) -> ConnectorResult<(ConnectionResult, UpgradedFramed)> {
-    let server_addr = config
+    let mut server_addr = config
         .destination
         .lookup_addr()
         .map_err(|e| connector::custom_err!("lookup addr", e))?;
@@ -100,6 +185,51 @@ async fn connect(
         .await
         .map_err(|e| connector::custom_err!("TCP connect", e))?;
 
+    let mut server_name = config.destination.name().to_owned();
+
+    let mut result = connect_to(config, cliprdr_factory, stream, server_addr, &server_name, None).await;
+
+    for _ in 0..MAX_REDIRECTIONS {
+        let redirection = match &result {
+            Err(e) => match e.kind() {
+                connector::ConnectorErrorKind::Redirection(redirection) => redirection.as_ref().clone(),
+                _ => break,
+            },
+            Ok(_) => break,
+        };
+
+        let (stream, target_addr) = ironrdp_tokio::connect_redirected(&redirection, server_addr).await?;
+
+        info!(%target_addr, "Following server redirection");
+
+        server_addr = target_addr;
+
+        if let Some(target_fqdn) = &redirection.target_fqdn {
+            server_name.clone_from(target_fqdn);
+        }
+
+        result = connect_to(
+            config,
+            cliprdr_factory,
+            stream,
+            server_addr,
+            &server_name,
+            Some(redirection),
+        )
+        .await;
+    }
+
+    result
+}
+
+async fn connect_to(
+    config: &Config,
+    cliprdr_factory: Option<&(dyn CliprdrBackendFactory + Send)>,
+    stream: TcpStream,
+    server_addr: SocketAddr,
+    server_name: &str,
+    redirection: Option<connector::Redirection>,
+) -> ConnectorResult<(ConnectionResult, UpgradedFramed)> {
     let mut framed = ironrdp_tokio::TokioFramed::new(stream);
 
     let mut connector = connector::ClientConnector::new(config.connector.clone())
@@ -108,6 +238,10 @@ async fn connect(
         .with_static_channel(rdpsnd::Rdpsnd::new())
         .with_static_channel(rdpdr::Rdpdr::new(Box::new(NoopRdpdrBackend {}), "IronRDP".to_owned()).with_smartcard(0));
 
+    if let Some(redirection) = redirection {
+        connector.attach_redirection(redirection);
+    }
+
     if let Some(builder) = cliprdr_factory {
         let backend = builder.build_cliprdr_backend();
 
@@ -118,16 +252,34 @@ async fn connect(
 
     let should_upgrade = ironrdp_tokio::connect_begin(&mut framed, &mut connector).await?;
 
-    debug!("TLS upgrade");
-
     // Ensure there is no leftover
     let initial_stream = framed.into_inner_no_leftover();
 
-    let (upgraded_stream, server_public_key) = ironrdp_tls::upgrade(initial_stream, config.destination.name())
-        .await
-        .map_err(|e| connector::custom_err!("TLS upgrade", e))?;
+    let (upgraded, upgraded_stream, server_public_key) = if connector.is_standard_security_selected() {
+        debug!("Standard RDP Security, skipping TLS upgrade");
 
-    let upgraded = ironrdp_tokio::mark_as_upgraded(should_upgrade, &mut connector);
+        let upgraded = ironrdp_tokio::skip_security_upgrade(should_upgrade, &mut connector);
+
+        (
+            upgraded,
+            Box::new(initial_stream) as Box<dyn AsyncReadWrite>,
+            Vec::new(),
+        )
+    } else {
+        debug!("TLS upgrade");
+
+        let (upgraded_stream, server_public_key) = ironrdp_tls::upgrade(initial_stream, server_name)
+            .await
+            .map_err(|e| connector::custom_err!("TLS upgrade", e))?;
+
+        let upgraded = ironrdp_tokio::mark_as_upgraded(should_upgrade, &mut connector);
+
+        (
+            upgraded,
+            Box::new(upgraded_stream) as Box<dyn AsyncReadWrite>,
+            server_public_key,
+        )
+    };
 
     let mut upgraded_framed = ironrdp_tokio::TokioFramed::new(upgraded_stream);
 
@@ -136,7 +288,7 @@ async fn connect(
         upgraded,
         &mut upgraded_framed,
         connector,
-        (&config.destination).into(),
+        connector::ServerName::new(server_name),
         server_public_key,
         Some(&mut network_client),
         None,
@@ -165,7 +317,10 @@ async fn active_session(
     let disconnect_reason = 'outer: loop {
         let outputs = tokio::select! {
             frame = framed.read_pdu() => {
-                let (action, payload) = frame.map_err(|e| session::custom_err!("read frame", e))?;
+                let (action, payload) = match frame {
+                    Ok(frame) => frame,
+                    Err(e) => return Ok(connection_lost(&active_stage, session::custom_err!("read frame", e))),
+                };
                 trace!(?action, frame_length = payload.len(), "Frame received");
 
                 active_stage.process(&mut image, action, &payload)?
@@ -187,11 +342,12 @@ async fn active_session(
                             }
                         }
 
-                        // TODO(#271): use the "auto-reconnect cookie": https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpbcgr/15b0d1c9-2891-4adb-a45e-deb4aeeeab7c
+                        // The auto-reconnect cookie lets the server resume the current session instead of creating a new one.
+                        let auto_reconnect_cookie = active_stage.auto_reconnect_cookie().cloned();
 
-                        info!(width, height, "resize event");
+                        info!(width, height, auto_reconnect = auto_reconnect_cookie.is_some(), "resize event");
 
-                        return Ok(RdpControlFlow::ReconnectWithNewSize { width, height })
+                        return Ok(RdpControlFlow::ReconnectWithNewSize { width, height, auto_reconnect_cookie })
                     },
                     RdpInputEvent::FastPath(events) => {
                         trace!(?events);
@@ -238,10 +394,14 @@ async fn active_session(
 
         for out in outputs {
             match out {
-                ActiveStageOutput::ResponseFrame(frame) => framed
-                    .write_all(&frame)
-                    .await
-                    .map_err(|e| session::custom_err!("write response", e))?,
+                ActiveStageOutput::ResponseFrame(frame) => {
+                    if let Err(e) = framed.write_all(&frame).await {
+                        return Ok(connection_lost(
+                            &active_stage,
+                            session::custom_err!("write response", e),
+                        ));
+                    }
+                }
                 ActiveStageOutput::GraphicsUpdate(_region) => {
                     let buffer: Vec<u32> = image
                         .data()
@@ -287,3 +447,10 @@ async fn active_session(
 
     Ok(RdpControlFlow::TerminatedGracefully(disconnect_reason))
 }
+
+fn connection_lost(active_stage: &ActiveStage, error: SessionError) -> RdpControlFlow {
+    RdpControlFlow::ConnectionLost {
+        error,
+        auto_reconnect_cookie: active_stage.auto_reconnect_cookie().cloned(),
+    }
+}
@@ -1,17 +1,27 @@
+use core::fmt;
+use std::borrow::Cow;
 use std::mem;
 use std::net::SocketAddr;
 
+use ironrdp_pdu::crypto::standard_security::{self, SessionKeys, StandardSecurity};
 use ironrdp_pdu::rdp::capability_sets::CapabilitySet;
 use ironrdp_pdu::rdp::client_info::{PerformanceFlags, TimezoneInfo};
+use ironrdp_pdu::rdp::headers::BasicSecurityHeaderFlags;
+use ironrdp_pdu::rdp::server_redirection::ServerRedirectionFlags;
+use ironrdp_pdu::rdstls::{
+    RdstlsAuthenticationRequest, RdstlsAuthenticationResponse, RdstlsCapabilities, RdstlsResultCode,
+};
 use ironrdp_pdu::write_buf::WriteBuf;
-use ironrdp_pdu::{gcc, mcs, nego, rdp, PduHint};
+use ironrdp_pdu::{gcc, mcs, nego, rdp, PduHint, PduParsing as _};
 use ironrdp_svc::{StaticChannelSet, StaticVirtualChannel, SvcClientProcessor};
+use rand_core::{OsRng, RngCore as _};
 
 use crate::channel_connection::{ChannelConnectionSequence, ChannelConnectionState};
 use crate::connection_finalization::ConnectionFinalizationSequence;
 use crate::license_exchange::LicenseExchangeSequence;
 use crate::{
-    legacy, Config, ConnectorError, ConnectorErrorExt as _, ConnectorResult, DesktopSize, Sequence, State, Written,
+    legacy, Config, ConnectorError, ConnectorErrorExt as _, ConnectorErrorKind, ConnectorResult, CredentialDelegation,
+    DesktopSize, MonitorConfig, Redirection, Sequence, State, Written,
 };
 
 const DEFAULT_POINTER_CACHE_SIZE: u16 = 32;
@@ -26,6 +36,13 @@ pub struct ConnectionResult {
     pub graphics_config: Option<crate::GraphicsConfig>,
     pub no_server_pointer: bool,
     pub pointer_software_rendering: bool,
+    /// Encryption state to carry on with when Standard RDP Security is used
+    pub standard_security: Option<StandardSecurity>,
+    /// Monitor layout of the virtual desktop sent by the server, if any
+    ///
+    /// Coordinates are relative to the primary monitor, whereas the top-left corner of the desktop
+    /// bitmap is the top-left corner of the bounding box of the monitors.
+    pub monitors: Vec<gcc::Monitor>,
 }
 
 #[derive(Default, Debug)]
@@ -35,6 +52,7 @@ pub enum ClientConnectorState {
     #[default]
     Consumed,
 
+    PreconnectionSendBlob,
     ConnectionInitiationSendRequest,
     ConnectionInitiationWaitConfirm {
         requested_protocol: nego::SecurityProtocol,
@@ -45,6 +63,12 @@ pub enum ClientConnectorState {
     Credssp {
         selected_protocol: nego::SecurityProtocol,
     },
+    RdstlsWaitCapabilities {
+        selected_protocol: nego::SecurityProtocol,
+    },
+    RdstlsWaitAuthenticationResponse {
+        selected_protocol: nego::SecurityProtocol,
+    },
     BasicSettingsExchangeSendInitial {
         selected_protocol: nego::SecurityProtocol,
     },
@@ -54,6 +78,12 @@ pub enum ClientConnectorState {
     ChannelConnection {
         io_channel_id: u16,
         channel_connection: ChannelConnectionSequence,
+        encrypted_client_random: Option<Vec<u8>>,
+    },
+    SecurityExchange {
+        io_channel_id: u16,
+        user_channel_id: u16,
+        encrypted_client_random: Vec<u8>,
     },
     SecureSettingsExchange {
         io_channel_id: u16,
@@ -91,13 +121,17 @@ impl State for ClientConnectorState {
     fn name(&self) -> &'static str {
         match self {
             Self::Consumed => "Consumed",
+            Self::PreconnectionSendBlob => "PreconnectionSendBlob",
             Self::ConnectionInitiationSendRequest => "ConnectionInitiationSendRequest",
             Self::ConnectionInitiationWaitConfirm { .. } => "ConnectionInitiationWaitResponse",
             Self::EnhancedSecurityUpgrade { .. } => "EnhancedSecurityUpgrade",
             Self::Credssp { .. } => "Credssp",
+            Self::RdstlsWaitCapabilities { .. } => "RdstlsWaitCapabilities",
+            Self::RdstlsWaitAuthenticationResponse { .. } => "RdstlsWaitAuthenticationResponse",
             Self::BasicSettingsExchangeSendInitial { .. } => "BasicSettingsExchangeSendInitial",
             Self::BasicSettingsExchangeWaitResponse { .. } => "BasicSettingsExchangeWaitResponse",
             Self::ChannelConnection { .. } => "ChannelConnection",
+            Self::SecurityExchange { .. } => "SecurityExchange",
             Self::SecureSettingsExchange { .. } => "SecureSettingsExchange",
             Self::ConnectTimeAutoDetection { .. } => "ConnectTimeAutoDetection",
             Self::LicensingExchange { .. } => "LicensingExchange",
@@ -117,22 +151,56 @@ impl State for ClientConnectorState {
     }
 }
 
-#[derive(Debug)]
 #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ClientConnector {
     pub config: Config,
     pub state: ClientConnectorState,
     pub server_addr: Option<SocketAddr>,
     pub static_channels: StaticChannelSet,
+    /// Redirection being followed by this connection
+    pub redirection: Option<Redirection>,
+    /// Encryption state, when Standard RDP Security is used
+    pub standard_security: Option<StandardSecurity>,
+    /// Client random, when Standard RDP Security is used
+    ///
+    /// Also used to compute the security verifier of the auto-reconnect cookie.
+    pub client_random: Option<[u8; standard_security::CLIENT_RANDOM_SIZE]>,
+    /// Flags sent by the server in the Connection Confirm
+    pub server_nego_flags: nego::ResponseFlags,
+}
+
+impl fmt::Debug for ClientConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The client random is not printed, the session keys are derived from it
+        f.debug_struct("ClientConnector")
+            .field("config", &self.config)
+            .field("state", &self.state)
+            .field("server_addr", &self.server_addr)
+            .field("static_channels", &self.static_channels)
+            .field("redirection", &self.redirection)
+            .field("standard_security", &self.standard_security)
+            .field("server_nego_flags", &self.server_nego_flags)
+            .finish_non_exhaustive()
+    }
 }
 
 impl ClientConnector {
     pub fn new(config: Config) -> Self {
+        let state = if config.pcb.is_some() {
+            ClientConnectorState::PreconnectionSendBlob
+        } else {
+            ClientConnectorState::ConnectionInitiationSendRequest
+        };
+
         Self {
             config,
-            state: ClientConnectorState::ConnectionInitiationSendRequest,
+            state,
             server_addr: None,
             static_channels: StaticChannelSet::new(),
+            redirection: None,
+            standard_security: None,
+            client_random: None,
+            server_nego_flags: nego::ResponseFlags::empty(),
         }
     }
 
@@ -148,6 +216,51 @@ impl ClientConnector {
         self.server_addr = Some(addr);
     }
 
+    /// Follows a server redirection received by a previous connection
+    ///
+    /// The routing token and the session to reconnect to are sent to the target server,
+    /// and the username and domain are replaced by the redirected ones.
+    #[must_use]
+    pub fn with_redirection(mut self, redirection: Redirection) -> Self {
+        self.attach_redirection(redirection);
+        self
+    }
+
+    /// Follows a server redirection received by a previous connection
+    ///
+    /// When the server handed out an encrypted password cookie, the credentials are replaced by
+    /// [`Credentials::RedirectionCookie`](crate::Credentials::RedirectionCookie) and RDSTLS is used.
+    pub fn attach_redirection(&mut self, redirection: Redirection) {
+        let password_cookie = redirection.password_cookie.as_ref().filter(|_| {
+            redirection
+                .flags
+                .contains(ServerRedirectionFlags::PASSWORD_IS_PK_ENCRYPTED)
+        });
+
+        if let Some(password_cookie) = password_cookie {
+            self.config.credentials = crate::Credentials::RedirectionCookie {
+                username: redirection
+                    .username
+                    .clone()
+                    .unwrap_or_else(|| self.config.credentials.username().to_owned()),
+                redirection_guid: redirection.redirection_guid.clone().unwrap_or_default(),
+                password_cookie: password_cookie.clone(),
+            };
+        } else if let Some(username) = &redirection.username {
+            match &mut self.config.credentials {
+                crate::Credentials::UsernamePassword { username: current, .. }
+                | crate::Credentials::RedirectionCookie { username: current, .. } => current.clone_from(username),
+                crate::Credentials::SmartCard { .. } => warn!("Redirected username ignored for smart card logon"),
+            }
+        }
+
+        if let Some(domain) = &redirection.domain {
+            self.config.domain = Some(domain.clone());
+        }
+
+        self.redirection = Some(redirection);
+    }
+
     #[must_use]
     pub fn with_static_channel<T>(mut self, channel: T) -> Self
     where
@@ -174,6 +287,17 @@ impl ClientConnector {
         debug_assert!(!self.should_perform_security_upgrade());
     }
 
+    /// Returns `true` when the server selected Standard RDP Security
+    ///
+    /// In this case, the transport must not be upgraded and [`Self::mark_security_upgrade_as_done`] should be
+    /// called right away.
+    pub fn is_standard_security_selected(&self) -> bool {
+        matches!(
+            self.state,
+            ClientConnectorState::EnhancedSecurityUpgrade { selected_protocol } if selected_protocol.is_standard_rdp_security()
+        )
+    }
+
     pub fn should_perform_credssp(&self) -> bool {
         matches!(self.state, ClientConnectorState::Credssp { .. })
     }
@@ -190,13 +314,17 @@ impl Sequence for ClientConnector {
     fn next_pdu_hint(&self) -> Option<&dyn PduHint> {
         match &self.state {
             ClientConnectorState::Consumed => None,
+            ClientConnectorState::PreconnectionSendBlob => None,
             ClientConnectorState::ConnectionInitiationSendRequest => None,
             ClientConnectorState::ConnectionInitiationWaitConfirm { .. } => Some(&ironrdp_pdu::X224_HINT),
             ClientConnectorState::EnhancedSecurityUpgrade { .. } => None,
             ClientConnectorState::Credssp { .. } => None,
+            ClientConnectorState::RdstlsWaitCapabilities { .. } => Some(&ironrdp_pdu::rdstls::RDSTLS_HINT),
+            ClientConnectorState::RdstlsWaitAuthenticationResponse { .. } => Some(&ironrdp_pdu::rdstls::RDSTLS_HINT),
             ClientConnectorState::BasicSettingsExchangeSendInitial { .. } => None,
             ClientConnectorState::BasicSettingsExchangeWaitResponse { .. } => Some(&ironrdp_pdu::X224_HINT),
             ClientConnectorState::ChannelConnection { channel_connection, .. } => channel_connection.next_pdu_hint(),
+            ClientConnectorState::SecurityExchange { .. } => None,
             ClientConnectorState::SecureSettingsExchange { .. } => None,
             ClientConnectorState::ConnectTimeAutoDetection { .. } => None,
             ClientConnectorState::LicensingExchange { license_exchange, .. } => license_exchange.next_pdu_hint(),
@@ -215,12 +343,76 @@ impl Sequence for ClientConnector {
     }
 
     fn step(&mut self, input: &[u8], output: &mut WriteBuf) -> ConnectorResult<Written> {
+        let Some(standard_security) = self.standard_security.as_mut() else {
+            return self.step_unencrypted(input, output);
+        };
+
+        // Once the Security Exchange PDU is sent, the MCS traffic is encrypted.
+        let (decrypt_input, keep_security_header, encrypt_output) = match self.state {
+            ClientConnectorState::LicensingExchange { .. } => (true, true, false),
+            ClientConnectorState::CapabilitiesExchange { .. } | ClientConnectorState::ConnectionFinalization { .. } => {
+                (true, false, true)
+            }
+            _ => (false, false, false),
+        };
+
+        let decrypted_input = if decrypt_input && !input.is_empty() {
+            Some(decrypt_send_data_indication(
+                standard_security,
+                input,
+                keep_security_header,
+            )?)
+        } else {
+            None
+        };
+        let input = decrypted_input.as_deref().unwrap_or(input);
+
+        if !encrypt_output {
+            return self.step_unencrypted(input, output);
+        }
+
+        let mut unencrypted_output = WriteBuf::new();
+
+        match self.step_unencrypted(input, &mut unencrypted_output)? {
+            Written::Nothing => Ok(Written::Nothing),
+            Written::Size(_) => {
+                let standard_security = self.standard_security.as_mut().expect("standard security state is set");
+
+                let written = encrypt_send_data_request(standard_security, unencrypted_output.filled(), output)?;
+
+                Written::from_size(written)
+            }
+        }
+    }
+}
+
+impl ClientConnector {
+    fn step_unencrypted(&mut self, input: &[u8], output: &mut WriteBuf) -> ConnectorResult<Written> {
         let (written, next_state) = match mem::take(&mut self.state) {
             // Invalid state
             ClientConnectorState::Consumed => {
                 return Err(general_err!("connector sequence state is consumed (this is a bug)",))
             }
 
+            //== Preconnection ==//
+            // The blob is sent before anything else, for the listener to pick the RDP source ([MS-RDPEPS] 3.1.5.1).
+            ClientConnectorState::PreconnectionSendBlob => {
+                let pcb = self
+                    .config
+                    .pcb
+                    .as_ref()
+                    .ok_or_else(|| general_err!("preconnection blob is missing"))?;
+
+                debug!(message = ?pcb, "Send");
+
+                let written = ironrdp_pdu::encode_buf(pcb, output).map_err(ConnectorError::pdu)?;
+
+                (
+                    Written::from_size(written)?,
+                    ClientConnectorState::ConnectionInitiationSendRequest,
+                )
+            }
+
             //== Connection Initiation ==//
             // Exchange supported security protocols and a few other connection flags.
             ClientConnectorState::ConnectionInitiationSendRequest => {
@@ -245,15 +437,40 @@ impl Sequence for ClientConnector {
                     security_protocol.insert(nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX);
                 }
 
-                if security_protocol.is_standard_rdp_security() {
-                    return Err(reason_err!("Initiation", "standard RDP security is not supported",));
+                // The password cookie of a redirected client can't be used with any other security protocol.
+                if let crate::Credentials::RedirectionCookie { .. } = self.config.credentials {
+                    security_protocol = nego::SecurityProtocol::RDSTLS;
+                }
+
+                if security_protocol.is_standard_rdp_security() && !self.config.enable_standard_security {
+                    return Err(reason_err!("Initiation", "standard RDP security is not enabled",));
+                }
+
+                let flags = match self.config.credential_delegation {
+                    CredentialDelegation::Full => nego::RequestFlags::empty(),
+                    CredentialDelegation::RestrictedAdmin => nego::RequestFlags::RESTRICTED_ADMIN_MODE_REQUIRED,
+                    CredentialDelegation::RemoteCredentialGuard => {
+                        nego::RequestFlags::REDIRECTED_AUTHENTICATION_MODE_REQUIRED
+                    }
+                };
+
+                if !flags.is_empty() && !self.config.enable_credssp {
+                    return Err(reason_err!(
+                        "Initiation",
+                        "{} requires CredSSP",
+                        self.config.credential_delegation
+                    ));
                 }
 
+                let nego_data = self
+                    .redirection
+                    .as_ref()
+                    .and_then(Redirection::routing_token)
+                    .unwrap_or_else(|| nego::NegoRequestData::cookie(self.config.credentials.username().to_owned()));
+
                 let connection_request = nego::ConnectionRequest {
-                    nego_data: Some(nego::NegoRequestData::cookie(
-                        self.config.credentials.username().to_owned(),
-                    )),
-                    flags: nego::RequestFlags::empty(),
+                    nego_data: Some(nego_data),
+                    flags,
                     protocol: security_protocol,
                 };
 
@@ -284,13 +501,20 @@ impl Sequence for ClientConnector {
 
                 info!(?selected_protocol, ?flags, "Server confirmed connection");
 
-                if !selected_protocol.intersects(requested_protocol) {
+                let standard_security_selected =
+                    selected_protocol.is_standard_rdp_security() && self.config.enable_standard_security;
+
+                if !selected_protocol.intersects(requested_protocol) && !standard_security_selected {
                     return Err(reason_err!(
                         "Initiation",
                         "client advertised {requested_protocol}, but server selected {selected_protocol}",
                     ));
                 }
 
+                check_credential_delegation(self.config.credential_delegation, selected_protocol, flags)?;
+
+                self.server_nego_flags = flags;
+
                 (
                     Written::Nothing,
                     ClientConnectorState::EnhancedSecurityUpgrade { selected_protocol },
@@ -298,14 +522,17 @@ impl Sequence for ClientConnector {
             }
 
             //== Upgrade to Enhanced RDP Security ==//
-            // NOTE: we assume the selected protocol is never the standard RDP security (RC4).
             // User code should match this variant and perform the appropriate upgrade (TLS handshake, etc).
+            // When standard RDP security (RC4) is selected, there is nothing to upgrade.
             ClientConnectorState::EnhancedSecurityUpgrade { selected_protocol } => {
                 let next_state = if selected_protocol
                     .intersects(nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX)
                 {
                     debug!("Begin NLA using CredSSP");
                     ClientConnectorState::Credssp { selected_protocol }
+                } else if selected_protocol.contains(nego::SecurityProtocol::RDSTLS) {
+                    debug!("Begin RDSTLS authentication");
+                    ClientConnectorState::RdstlsWaitCapabilities { selected_protocol }
                 } else {
                     debug!("CredSSP is disabled, skipping NLA");
                     ClientConnectorState::BasicSettingsExchangeSendInitial { selected_protocol }
@@ -320,13 +547,79 @@ impl Sequence for ClientConnector {
                 ClientConnectorState::BasicSettingsExchangeSendInitial { selected_protocol },
             ),
 
+            //== RDSTLS ==//
+            // The server advertises the RDSTLS versions it supports, and the client answers with the
+            // credentials it was handed out by the connection broker ([MS-RDPBCGR] 5.4.5.3).
+            ClientConnectorState::RdstlsWaitCapabilities { selected_protocol } => {
+                let capabilities = ironrdp_pdu::decode::<RdstlsCapabilities>(input).map_err(ConnectorError::pdu)?;
+
+                debug!(message = ?capabilities, "Received");
+
+                if capabilities.supported_versions & ironrdp_pdu::rdstls::RDSTLS_VERSION_1 == 0 {
+                    return Err(reason_err!(
+                        "RDSTLS",
+                        "unsupported RDSTLS versions: {:#06X}",
+                        capabilities.supported_versions
+                    ));
+                }
+
+                let crate::Credentials::RedirectionCookie {
+                    username,
+                    redirection_guid,
+                    password_cookie,
+                } = &self.config.credentials
+                else {
+                    return Err(general_err!("RDSTLS requires redirection cookie credentials"));
+                };
+
+                let authentication_request = RdstlsAuthenticationRequest::Password {
+                    redirection_guid: redirection_guid.clone(),
+                    username: username.clone(),
+                    domain: self.config.domain.clone().unwrap_or_default(),
+                    password: password_cookie.clone(),
+                };
+
+                debug!(message = ?authentication_request, "Send");
+
+                let written = ironrdp_pdu::encode_buf(&authentication_request, output).map_err(ConnectorError::pdu)?;
+
+                (
+                    Written::from_size(written)?,
+                    ClientConnectorState::RdstlsWaitAuthenticationResponse { selected_protocol },
+                )
+            }
+            ClientConnectorState::RdstlsWaitAuthenticationResponse { selected_protocol } => {
+                let authentication_response =
+                    ironrdp_pdu::decode::<RdstlsAuthenticationResponse>(input).map_err(ConnectorError::pdu)?;
+
+                debug!(message = ?authentication_response, "Received");
+
+                if authentication_response.result_code != RdstlsResultCode::SUCCESS {
+                    return Err(reason_err!(
+                        "RDSTLS",
+                        "authentication failed: {}",
+                        authentication_response.result_code
+                    ));
+                }
+
+                (
+                    Written::Nothing,
+                    ClientConnectorState::BasicSettingsExchangeSendInitial { selected_protocol },
+                )
+            }
+
             //== Basic Settings Exchange ==//
             // Exchange basic settings including Core Data, Security Data and Network Data.
             ClientConnectorState::BasicSettingsExchangeSendInitial { selected_protocol } => {
                 debug!("Basic Settings Exchange");
 
-                let client_gcc_blocks =
-                    create_gcc_blocks(&self.config, selected_protocol, self.static_channels.values());
+                let client_gcc_blocks = create_gcc_blocks(
+                    &self.config,
+                    selected_protocol,
+                    self.server_nego_flags,
+                    self.static_channels.values(),
+                    self.redirection.as_ref(),
+                )?;
 
                 let connect_initial = mcs::ConnectInitial::with_gcc_blocks(client_gcc_blocks);
 
@@ -354,6 +647,20 @@ impl Sequence for ClientConnector {
                     return Err(general_err!("can’t satisfy server security settings"));
                 }
 
+                let encrypted_client_random = if server_gcc_blocks.security.encryption_method.is_empty() {
+                    None
+                } else {
+                    let (standard_security, client_random, encrypted_client_random) =
+                        start_standard_security(&server_gcc_blocks.security)?;
+
+                    info!(method = ?standard_security.method(), "Using standard RDP security");
+
+                    self.standard_security = Some(standard_security);
+                    self.client_random = Some(client_random);
+
+                    Some(encrypted_client_random)
+                };
+
                 if server_gcc_blocks.message_channel.is_some() {
                     warn!("Unexpected ServerMessageChannelData GCC block (not supported)");
                 }
@@ -392,6 +699,7 @@ impl Sequence for ClientConnector {
                         } else {
                             ChannelConnectionSequence::new(io_channel_id, static_channel_ids)
                         },
+                        encrypted_client_random,
                     },
                 )
             }
@@ -401,6 +709,7 @@ impl Sequence for ClientConnector {
             ClientConnectorState::ChannelConnection {
                 io_channel_id,
                 mut channel_connection,
+                encrypted_client_random,
             } => {
                 debug!("Channel Connection");
                 let written = channel_connection.step(input, output)?;
@@ -409,14 +718,22 @@ impl Sequence for ClientConnector {
                 {
                     debug_assert!(channel_connection.state.is_terminal());
 
-                    ClientConnectorState::SecureSettingsExchange {
-                        io_channel_id,
-                        user_channel_id,
+                    match encrypted_client_random {
+                        Some(encrypted_client_random) => ClientConnectorState::SecurityExchange {
+                            io_channel_id,
+                            user_channel_id,
+                            encrypted_client_random,
+                        },
+                        None => ClientConnectorState::SecureSettingsExchange {
+                            io_channel_id,
+                            user_channel_id,
+                        },
                     }
                 } else {
                     ClientConnectorState::ChannelConnection {
                         io_channel_id,
                         channel_connection,
+                        encrypted_client_random,
                     }
                 };
 
@@ -424,10 +741,32 @@ impl Sequence for ClientConnector {
             }
 
             //== RDP Security Commencement ==//
-            // When using standard RDP security (RC4), a Security Exchange PDU is sent at this point.
-            // However, IronRDP does not support this unsecure security protocol (purposefully) and
-            // this part of the sequence is not implemented.
-            //==============================//
+            // When using standard RDP security (RC4), the client random encrypted with the server public key
+            // is sent in the Security Exchange PDU. All the following MCS traffic is encrypted.
+            ClientConnectorState::SecurityExchange {
+                io_channel_id,
+                user_channel_id,
+                encrypted_client_random,
+            } => {
+                debug!("RDP Security Commencement");
+
+                let security_exchange = rdp::SecurityExchangePdu {
+                    encrypted_client_random,
+                };
+
+                debug!(message = ?security_exchange, "Send");
+
+                let written =
+                    legacy::encode_send_data_request(user_channel_id, io_channel_id, &security_exchange, output)?;
+
+                (
+                    Written::from_size(written)?,
+                    ClientConnectorState::SecureSettingsExchange {
+                        io_channel_id,
+                        user_channel_id,
+                    },
+                )
+            }
 
             //== Secure Settings Exchange ==//
             // Send Client Info PDU (information about supported types of compression, username, password, etc).
@@ -442,11 +781,31 @@ impl Sequence for ClientConnector {
                     .as_ref()
                     .ok_or_else(|| general_err!("server address is missing"))?;
 
-                let client_info = create_client_info_pdu(&self.config, routing_addr);
+                let client_info = create_client_info_pdu(&self.config, routing_addr, self.client_random.as_ref());
 
                 debug!(message = ?client_info, "Send");
 
-                let written = legacy::encode_send_data_request(user_channel_id, io_channel_id, &client_info, output)?;
+                let written = match self.standard_security.as_mut() {
+                    Some(standard_security) => {
+                        let mut client_info_data = Vec::with_capacity(client_info.client_info.buffer_length());
+                        client_info
+                            .client_info
+                            .to_buffer(&mut client_info_data)
+                            .map_err(rdp::RdpError::from)?;
+
+                        let send_data_request = mcs::SendDataRequest {
+                            initiator_id: user_channel_id,
+                            channel_id: io_channel_id,
+                            user_data: Cow::Owned(
+                                standard_security
+                                    .encrypt_user_data(client_info.security_header.flags, &client_info_data),
+                            ),
+                        };
+
+                        ironrdp_pdu::encode_buf(&send_data_request, output).map_err(ConnectorError::pdu)?
+                    }
+                    None => legacy::encode_send_data_request(user_channel_id, io_channel_id, &client_info, output)?,
+                };
 
                 (
                     Written::from_size(written)?,
@@ -536,14 +895,29 @@ impl Sequence for ClientConnector {
                     );
                 }
 
-                let capability_sets = if let rdp::headers::ShareControlPdu::ServerDemandActive(server_demand_active) =
-                    share_control_ctx.pdu
-                {
-                    server_demand_active.pdu.capability_sets
-                } else {
-                    return Err(general_err!(
-                        "unexpected Share Control Pdu (expected ServerDemandActive)",
-                    ));
+                let capability_sets = match share_control_ctx.pdu {
+                    rdp::headers::ShareControlPdu::ServerDemandActive(server_demand_active) => {
+                        server_demand_active.pdu.capability_sets
+                    }
+                    rdp::headers::ShareControlPdu::ServerRedirect(redirection) => {
+                        let redirection = Redirection::from(redirection);
+
+                        info!(
+                            target_addresses = ?redirection.target_addresses,
+                            session_id = redirection.session_id,
+                            "Redirected by server"
+                        );
+
+                        return Err(ConnectorError::new(
+                            "Server Redirection",
+                            ConnectorErrorKind::Redirection(Box::new(redirection)),
+                        ));
+                    }
+                    _ => {
+                        return Err(general_err!(
+                            "unexpected Share Control Pdu (expected ServerDemandActive)",
+                        ))
+                    }
                 };
 
                 for c in &capability_sets {
@@ -564,10 +938,7 @@ impl Sequence for ClientConnector {
                         }),
                         _ => None,
                     })
-                    .unwrap_or(DesktopSize {
-                        width: self.config.desktop_size.width,
-                        height: self.config.desktop_size.height,
-                    });
+                    .unwrap_or_else(|| requested_desktop_size(&self.config));
 
                 let client_confirm_active = rdp::headers::ShareControlPdu::ClientConfirmActive(
                     create_client_confirm_active(&self.config, capability_sets),
@@ -617,6 +988,8 @@ impl Sequence for ClientConnector {
                             graphics_config: self.config.graphics.clone(),
                             no_server_pointer: self.config.no_server_pointer,
                             pointer_software_rendering: self.config.pointer_software_rendering,
+                            standard_security: self.standard_security.take(),
+                            monitors: mem::take(&mut connection_finalization.monitors),
                         },
                     }
                 } else {
@@ -642,12 +1015,131 @@ impl Sequence for ClientConnector {
     }
 }
 
+/// Ensures the server accepted the credential delegation mode requested by the client
+fn check_credential_delegation(
+    delegation: CredentialDelegation,
+    selected_protocol: nego::SecurityProtocol,
+    flags: nego::ResponseFlags,
+) -> ConnectorResult<()> {
+    let required_flag = match delegation {
+        CredentialDelegation::Full => return Ok(()),
+        CredentialDelegation::RestrictedAdmin => nego::ResponseFlags::RESTRICTED_ADMIN_MODE_SUPPORTED,
+        CredentialDelegation::RemoteCredentialGuard => nego::ResponseFlags::REDIRECTED_AUTHENTICATION_MODE_SUPPORTED,
+    };
+
+    if !selected_protocol.intersects(nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX) {
+        return Err(reason_err!(
+            "Initiation",
+            "{delegation} requires CredSSP, but server selected {selected_protocol}",
+        ));
+    }
+
+    if !flags.contains(required_flag) {
+        return Err(reason_err!("Initiation", "server does not support {delegation}",));
+    }
+
+    Ok(())
+}
+
+/// Size of the virtual desktop spanned by the configured monitors
+fn requested_desktop_size(config: &Config) -> DesktopSize {
+    if config.monitors.is_empty() {
+        return config.desktop_size;
+    }
+
+    let left = config.monitors.iter().map(|m| i64::from(m.left)).min().unwrap_or(0);
+    let top = config.monitors.iter().map(|m| i64::from(m.top)).min().unwrap_or(0);
+    let right = config
+        .monitors
+        .iter()
+        .map(|m| i64::from(m.left) + i64::from(m.width))
+        .max()
+        .unwrap_or(0);
+    let bottom = config
+        .monitors
+        .iter()
+        .map(|m| i64::from(m.top) + i64::from(m.height))
+        .max()
+        .unwrap_or(0);
+
+    DesktopSize {
+        width: u16::try_from(right - left).unwrap_or(u16::MAX),
+        height: u16::try_from(bottom - top).unwrap_or(u16::MAX),
+    }
+}
+
+/// Builds the Client Monitor Data and Client Monitor Extended Data ([MS-RDPBCGR] 2.2.1.3.6 and 2.2.1.3.9)
+fn create_monitor_blocks(
+    monitors: &[MonitorConfig],
+) -> ConnectorResult<(gcc::ClientMonitorData, gcc::ClientMonitorExtendedData)> {
+    const MONITOR_COUNT_MAX: usize = 16;
+
+    if monitors.len() > MONITOR_COUNT_MAX {
+        return Err(general_err!("too many monitors (at most 16 are supported)"));
+    }
+
+    let mut primary_monitors = monitors.iter().filter(|m| m.is_primary);
+
+    match (primary_monitors.next(), primary_monitors.next()) {
+        (Some(primary), None) if primary.left == 0 && primary.top == 0 => {}
+        (Some(_), None) => return Err(general_err!("primary monitor is not at the origin")),
+        _ => return Err(general_err!("exactly one monitor must be the primary monitor")),
+    }
+
+    let monitor_data = gcc::ClientMonitorData {
+        monitors: monitors
+            .iter()
+            .map(|m| {
+                if m.width == 0 || m.height == 0 {
+                    return Err(general_err!("monitor size can’t be zero"));
+                }
+
+                Ok(gcc::Monitor {
+                    left: m.left,
+                    top: m.top,
+                    // The right and bottom bounds are inclusive
+                    right: m
+                        .left
+                        .checked_add(i32::from(m.width) - 1)
+                        .ok_or_else(|| general_err!("monitor out of bounds"))?,
+                    bottom: m
+                        .top
+                        .checked_add(i32::from(m.height) - 1)
+                        .ok_or_else(|| general_err!("monitor out of bounds"))?,
+                    flags: if m.is_primary {
+                        gcc::MonitorFlags::PRIMARY
+                    } else {
+                        gcc::MonitorFlags::empty()
+                    },
+                })
+            })
+            .collect::<ConnectorResult<_>>()?,
+    };
+
+    let monitor_extended_data = gcc::ClientMonitorExtendedData {
+        extended_monitors_info: monitors
+            .iter()
+            .map(|m| gcc::ExtendedMonitorInfo {
+                physical_width: m.physical_width,
+                physical_height: m.physical_height,
+                orientation: m.orientation,
+                desktop_scale_factor: m.desktop_scale_factor,
+                device_scale_factor: m.device_scale_factor,
+            })
+            .collect(),
+    };
+
+    Ok((monitor_data, monitor_extended_data))
+}
+
 #[allow(single_use_lifetimes)] // anonymous lifetimes in `impl Trait` are unstable
 fn create_gcc_blocks<'a>(
     config: &Config,
     selected_protocol: nego::SecurityProtocol,
+    server_nego_flags: nego::ResponseFlags,
     static_channels: impl Iterator<Item = &'a StaticVirtualChannel>,
-) -> gcc::ClientGccBlocks {
+    redirection: Option<&Redirection>,
+) -> ConnectorResult<gcc::ClientGccBlocks> {
     use ironrdp_pdu::gcc::*;
 
     let max_color_depth = config.bitmap.as_ref().map(|bitmap| bitmap.color_depth).unwrap_or(32);
@@ -664,11 +1156,26 @@ fn create_gcc_blocks<'a>(
         .map(ironrdp_svc::make_channel_definition)
         .collect::<Vec<_>>();
 
-    ClientGccBlocks {
+    let (monitor, monitor_extended) = if config.monitors.is_empty() {
+        (None, None)
+    } else {
+        let (monitor, monitor_extended) = create_monitor_blocks(&config.monitors)?;
+
+        // The extended data must only be sent to servers advertising support for it
+        let monitor_extended = server_nego_flags
+            .contains(nego::ResponseFlags::EXTENDED_CLIENT_DATA_SUPPORTED)
+            .then_some(monitor_extended);
+
+        (Some(monitor), monitor_extended)
+    };
+
+    let desktop_size = requested_desktop_size(config);
+
+    Ok(ClientGccBlocks {
         core: ClientCoreData {
             version: RdpVersion::V5_PLUS,
-            desktop_width: config.desktop_size.width,
-            desktop_height: config.desktop_size.height,
+            desktop_width: desktop_size.width,
+            desktop_height: desktop_size.height,
             color_depth: ColorDepth::Bpp8, // ignored because we use the optional core data below
             sec_access_sequence: SecureAccessSequence::Del,
             keyboard_layout: 0, // the server SHOULD use the default active input locale identifier
@@ -700,6 +1207,10 @@ fn create_gcc_blocks<'a>(
                         early_capability_flags |= ClientEarlyCapabilityFlags::WANT_32_BPP_SESSION;
                     }
 
+                    if monitor.is_some() {
+                        early_capability_flags |= ClientEarlyCapabilityFlags::SUPPORT_MONITOR_LAYOUT_PDU;
+                    }
+
                     Some(early_capability_flags)
                 },
                 dig_product_id: Some(config.dig_product_id.clone()),
@@ -713,7 +1224,11 @@ fn create_gcc_blocks<'a>(
             },
         },
         security: ClientSecurityData {
-            encryption_methods: EncryptionMethod::empty(),
+            encryption_methods: if selected_protocol.is_standard_rdp_security() {
+                EncryptionMethod::BIT_40 | EncryptionMethod::BIT_56 | EncryptionMethod::BIT_128
+            } else {
+                EncryptionMethod::empty()
+            },
             ext_encryption_methods: 0,
         },
         network: if channels.is_empty() {
@@ -721,23 +1236,120 @@ fn create_gcc_blocks<'a>(
         } else {
             Some(ClientNetworkData { channels })
         },
-        // TODO(#139): support for Some(ClientClusterData { flags: RedirectionFlags::REDIRECTION_SUPPORTED, redirection_version: RedirectionVersion::V4, redirected_session_id: 0, }),
-        cluster: None,
-        monitor: None,
+        cluster: Some(ClientClusterData {
+            flags: match redirection {
+                Some(_) => RedirectionFlags::REDIRECTION_SUPPORTED | RedirectionFlags::REDIRECTED_SESSION_FIELD_VALID,
+                None => RedirectionFlags::REDIRECTION_SUPPORTED,
+            },
+            redirection_version: RedirectionVersion::V4,
+            redirected_session_id: redirection.map(|redirection| redirection.session_id).unwrap_or(0),
+        }),
+        monitor,
         // TODO(#140): support for Client Message Channel Data (https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpbcgr/f50e791c-de03-4b25-b17e-e914c9020bc3)
         message_channel: None,
         // TODO(#140): support for Some(MultiTransportChannelData { flags: MultiTransportFlags::empty(), })
         multi_transport_channel: None,
-        monitor_extended: None,
-    }
+        monitor_extended,
+    })
+}
+
+/// Generates the client random and derives the session keys from the Server Security Data
+///
+/// Returns the encryption state, the client random and the client random encrypted for the server.
+fn start_standard_security(
+    server_security: &gcc::ServerSecurityData,
+) -> ConnectorResult<(StandardSecurity, [u8; standard_security::CLIENT_RANDOM_SIZE], Vec<u8>)> {
+    let server_random = server_security
+        .server_random
+        .as_ref()
+        .ok_or_else(|| general_err!("server random is missing"))?;
+
+    let mut client_random = [0; standard_security::CLIENT_RANDOM_SIZE];
+    OsRng.fill_bytes(&mut client_random);
+
+    let encrypted_client_random =
+        standard_security::encrypt_client_random(&client_random, &server_security.server_cert)
+            .map_err(|e| custom_err!("encrypt client random", e))?;
+
+    let session_keys = SessionKeys::derive(&client_random, server_random, server_security.encryption_method)
+        .map_err(|e| custom_err!("derive session keys", e))?;
+
+    Ok((
+        StandardSecurity::new(session_keys),
+        client_random,
+        encrypted_client_random,
+    ))
 }
 
-fn create_client_info_pdu(config: &Config, routing_addr: &SocketAddr) -> rdp::ClientInfoPdu {
+/// Decrypts the user data of a Send Data Indication, re-encoding it without encryption
+///
+/// The basic security header is removed unless `keep_security_header` is set, as licensing PDUs always include one.
+fn decrypt_send_data_indication(
+    standard_security: &mut StandardSecurity,
+    input: &[u8],
+    keep_security_header: bool,
+) -> ConnectorResult<Vec<u8>> {
+    let mcs::McsMessage::SendDataIndication(send_data_indication) =
+        ironrdp_pdu::decode::<mcs::McsMessage<'_>>(input).map_err(ConnectorError::pdu)?
+    else {
+        return Ok(input.to_vec());
+    };
+
+    let (flags, user_data) = standard_security
+        .decrypt_user_data(&send_data_indication.user_data)
+        .map_err(|e| custom_err!("decrypt user data", e))?;
+
+    let user_data = if keep_security_header {
+        let security_header = rdp::headers::BasicSecurityHeader {
+            flags: flags - BasicSecurityHeaderFlags::ENCRYPT - BasicSecurityHeaderFlags::SECURE_CHECKSUM,
+        };
+
+        let mut buf = Vec::with_capacity(security_header.buffer_length() + user_data.len());
+        security_header.to_buffer(&mut buf)?;
+        buf.extend_from_slice(&user_data);
+        buf
+    } else {
+        user_data
+    };
+
+    let send_data_indication = mcs::SendDataIndication {
+        user_data: Cow::Owned(user_data),
+        ..send_data_indication
+    };
+
+    ironrdp_pdu::encode_vec(&send_data_indication).map_err(ConnectorError::pdu)
+}
+
+/// Encrypts the user data of a Send Data Request
+fn encrypt_send_data_request(
+    standard_security: &mut StandardSecurity,
+    frame: &[u8],
+    output: &mut WriteBuf,
+) -> ConnectorResult<usize> {
+    let send_data_request = ironrdp_pdu::decode::<mcs::SendDataRequest<'_>>(frame).map_err(ConnectorError::pdu)?;
+
+    let send_data_request = mcs::SendDataRequest {
+        user_data: Cow::Owned(
+            standard_security.encrypt_user_data(BasicSecurityHeaderFlags::empty(), &send_data_request.user_data),
+        ),
+        ..send_data_request
+    };
+
+    ironrdp_pdu::encode_buf(&send_data_request, output).map_err(ConnectorError::pdu)
+}
+
+/// `client_random` is the client random of Standard RDP Security, if selected.
+fn create_client_info_pdu(
+    config: &Config,
+    routing_addr: &SocketAddr,
+    client_random: Option<&[u8; standard_security::CLIENT_RANDOM_SIZE]>,
+) -> rdp::ClientInfoPdu {
     use ironrdp_pdu::rdp::client_info::{
         AddressFamily, ClientInfo, ClientInfoFlags, CompressionType, Credentials, ExtendedClientInfo,
         ExtendedClientOptionalInfo,
     };
-    use ironrdp_pdu::rdp::headers::{BasicSecurityHeader, BasicSecurityHeaderFlags};
+    use ironrdp_pdu::rdp::headers::BasicSecurityHeader;
+    use ironrdp_pdu::rdp::session_info::{ClientAutoReconnect, ENHANCED_SECURITY_CLIENT_RANDOM};
     use ironrdp_pdu::rdp::ClientInfoPdu;
 
     let security_header = BasicSecurityHeader {
@@ -761,6 +1373,32 @@ fn create_client_info_pdu(config: &Config, routing_addr: &SocketAddr) -> rdp::Cl
         flags |= ClientInfoFlags::PASSWORD_IS_SC_PIN;
     }
 
+    let optional_data = ExtendedClientOptionalInfo::builder()
+        .timezone(TimezoneInfo {
+            bias: 0,
+            standard_name: String::new(),
+            standard_date: None,
+            standard_bias: 0,
+            daylight_name: String::new(),
+            daylight_date: None,
+            daylight_bias: 0,
+        })
+        .session_id(0)
+        .performance_flags(
+            PerformanceFlags::DISABLE_FULLWINDOWDRAG
+                | PerformanceFlags::DISABLE_MENUANIMATIONS
+                | PerformanceFlags::ENABLE_FONT_SMOOTHING,
+        );
+
+    let optional_data = match &config.auto_reconnect_cookie {
+        Some(server_cookie) => {
+            let client_random = client_random.unwrap_or(&ENHANCED_SECURITY_CLIENT_RANDOM);
+            let cookie = ClientAutoReconnect::new(server_cookie, client_random);
+            optional_data.reconnect_cookie(cookie.to_cookie()).build()
+        }
+        None => optional_data.build(),
+    };
+
     let client_info = ClientInfo {
         credentials: Credentials {
             username: config.credentials.username().to_owned(),
@@ -779,23 +1417,7 @@ fn create_client_info_pdu(config: &Config, routing_addr: &SocketAddr) -> rdp::Cl
             },
             address: routing_addr.ip().to_string(),
             dir: config.client_dir.clone(),
-            optional_data: ExtendedClientOptionalInfo::builder()
-                .timezone(TimezoneInfo {
-                    bias: 0,
-                    standard_name: String::new(),
-                    standard_date: None,
-                    standard_bias: 0,
-                    daylight_name: String::new(),
-                    daylight_date: None,
-                    daylight_bias: 0,
-                })
-                .session_id(0)
-                .performance_flags(
-                    PerformanceFlags::DISABLE_FULLWINDOWDRAG
-                        | PerformanceFlags::DISABLE_MENUANIMATIONS
-                        | PerformanceFlags::ENABLE_FONT_SMOOTHING,
-                )
-                .build(),
+            optional_data,
         },
     };
 
@@ -4,7 +4,7 @@ use ironrdp_pdu::rdp::capability_sets::SERVER_CHANNEL_ID;
 use ironrdp_pdu::rdp::headers::ShareDataPdu;
 use ironrdp_pdu::rdp::{finalization_messages, server_error_info};
 use ironrdp_pdu::write_buf::WriteBuf;
-use ironrdp_pdu::PduHint;
+use ironrdp_pdu::{gcc, PduHint};
 
 use crate::{legacy, ConnectorResult, Sequence, State, Written};
 
@@ -53,6 +53,8 @@ pub struct ConnectionFinalizationSequence {
     pub state: ConnectionFinalizationState,
     pub io_channel_id: u16,
     pub user_channel_id: u16,
+    /// Monitor layout received from the server, if any
+    pub monitors: Vec<gcc::Monitor>,
 }
 
 impl ConnectionFinalizationSequence {
@@ -61,6 +63,7 @@ impl ConnectionFinalizationSequence {
             state: ConnectionFinalizationState::SendSynchronize,
             io_channel_id,
             user_channel_id,
+            monitors: Vec::new(),
         }
     }
 }
@@ -202,6 +205,12 @@ impl Sequence for ConnectionFinalizationSequence {
                             }
                         }
                     }
+                    ShareDataPdu::MonitorLayout(monitor_layout) => {
+                        // Sent when the client advertised SUPPORT_MONITOR_LAYOUT_PDU ([MS-RDPBCGR] 2.2.12.1)
+                        debug!(monitors = ?monitor_layout.monitors, "Server Monitor Layout");
+                        self.monitors = monitor_layout.monitors;
+                        ConnectionFinalizationState::WaitForResponse
+                    }
                     ShareDataPdu::FontMap(_) => {
                         // https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpbcgr/023f1e69-cfe8-4ee6-9ee0-7e759fb4e4ee
                         //
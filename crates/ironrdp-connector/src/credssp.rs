@@ -6,7 +6,8 @@ use sspi::negotiate::ProtocolConfig;
 use sspi::Username;
 
 use crate::{
-    ClientConnector, ClientConnectorState, ConnectorError, ConnectorErrorKind, ConnectorResult, ServerName, Written,
+    ClientConnector, ClientConnectorState, ConnectorError, ConnectorErrorKind, ConnectorResult, CredentialDelegation,
+    ServerName, Written,
 };
 
 #[derive(Debug, Clone, Default)]
@@ -102,6 +103,18 @@ impl CredsspSequence {
             ));
         }
 
+        let credssp_mode = match config.credential_delegation {
+            CredentialDelegation::Full => credssp::CredSspMode::WithCredentials,
+            // Empty TSCredentials are sent
+            CredentialDelegation::RestrictedAdmin => credssp::CredSspMode::CredentialLess,
+            // sspi only sends TSPasswordCreds and TSSmartCardCreds
+            CredentialDelegation::RemoteCredentialGuard => {
+                return Err(general_err!(
+                    "Remote Credential Guard credentials (TSRemoteGuardCreds) are not currently supported"
+                ));
+            }
+        };
+
         let username = Username::new(config.credentials.username(), config.domain.as_deref())
             .map_err(|e| custom_err!("invalid username", e))?;
 
@@ -125,7 +138,7 @@ impl CredsspSequence {
         let client = credssp::CredSspClient::new(
             server_public_key,
             credentials.into(),
-            credssp::CredSspMode::WithCredentials,
+            credssp_mode,
             credssp::ClientMode::Negotiate(sspi::NegotiateConfig {
                 protocol_config: credssp_config,
                 package_list: None,
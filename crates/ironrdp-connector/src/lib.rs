@@ -14,6 +14,7 @@ mod connection;
 mod connection_finalization;
 pub mod credssp;
 mod license_exchange;
+mod redirection;
 mod server_name;
 
 use core::any::Any;
@@ -22,10 +23,13 @@ use core::fmt;
 pub use channel_connection::{ChannelConnectionSequence, ChannelConnectionState};
 pub use connection::{ClientConnector, ClientConnectorState, ConnectionResult};
 pub use connection_finalization::{ConnectionFinalizationSequence, ConnectionFinalizationState};
+use ironrdp_pdu::pcb::PreconnectionBlob;
 use ironrdp_pdu::rdp::capability_sets;
+use ironrdp_pdu::rdp::session_info::ServerAutoReconnect;
 use ironrdp_pdu::write_buf::WriteBuf;
 use ironrdp_pdu::{gcc, PduHint};
 pub use license_exchange::{LicenseExchangeSequence, LicenseExchangeState};
+pub use redirection::Redirection;
 pub use server_name::ServerName;
 pub use sspi;
 
@@ -53,10 +57,65 @@ pub struct BitmapConfig {
     pub color_depth: u32,
 }
 
+/// A client monitor, positioned in the virtual desktop
+///
+/// See [`Config::monitors`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorConfig {
+    /// Position of the left edge, the primary monitor being at the origin
+    pub left: i32,
+    /// Position of the top edge, the primary monitor being at the origin
+    pub top: i32,
+    pub width: u16,
+    pub height: u16,
+    pub is_primary: bool,
+    /// Physical width, in millimeters (0 if unknown)
+    pub physical_width: u32,
+    /// Physical height, in millimeters (0 if unknown)
+    pub physical_height: u32,
+    pub orientation: gcc::MonitorOrientation,
+    /// Desktop scale factor, in percent (100 to 500)
+    pub desktop_scale_factor: u32,
+    /// Device scale factor, in percent (100, 140 or 180)
+    pub device_scale_factor: u32,
+}
+
+impl MonitorConfig {
+    /// Creates an unscaled landscape monitor
+    pub fn new(left: i32, top: i32, width: u16, height: u16, is_primary: bool) -> Self {
+        Self {
+            left,
+            top,
+            width,
+            height,
+            is_primary,
+            physical_width: 0,
+            physical_height: 0,
+            orientation: gcc::MonitorOrientation::Landscape,
+            desktop_scale_factor: 100,
+            device_scale_factor: 100,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Credentials {
-    UsernamePassword { username: String, password: String },
-    SmartCard { pin: String },
+    UsernamePassword {
+        username: String,
+        password: String,
+    },
+    SmartCard {
+        pin: String,
+    },
+    /// Credentials handed out by a connection broker in a Server Redirection PDU
+    ///
+    /// Only usable with the RDSTLS security protocol, which is then the only protocol requested.
+    RedirectionCookie {
+        username: String,
+        redirection_guid: Vec<u8>,
+        /// Encrypted password cookie, opaque to the client
+        password_cookie: Vec<u8>,
+    },
 }
 
 impl Credentials {
@@ -64,6 +123,7 @@ impl Credentials {
         match self {
             Self::UsernamePassword { username, .. } => username,
             Self::SmartCard { .. } => "", // Username is ultimately provided by the smart card certificate.
+            Self::RedirectionCookie { username, .. } => username,
         }
     }
 
@@ -71,6 +131,33 @@ impl Credentials {
         match self {
             Self::UsernamePassword { password, .. } => password,
             Self::SmartCard { pin, .. } => pin,
+            Self::RedirectionCookie { .. } => "", // The password cookie is only sent over RDSTLS.
+        }
+    }
+}
+
+/// Credentials delegated to the server at the end of CredSSP
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum CredentialDelegation {
+    /// The username and password are sent to the server, which may reuse them
+    #[default]
+    Full,
+    /// Restricted Admin mode: no credentials are sent, and the session can't authenticate to other hosts
+    RestrictedAdmin,
+    /// Remote Credential Guard: authentication requests of the session are redirected back to the client
+    ///
+    /// The mode is requested and the support of the server is checked, but the connection then fails during CredSSP:
+    /// sending TSRemoteGuardCreds ([MS-CSSP] 2.2.1.2.1) is not supported by sspi yet.
+    RemoteCredentialGuard,
+}
+
+impl fmt::Display for CredentialDelegation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "full credential delegation"),
+            Self::RestrictedAdmin => write!(f, "restricted admin mode"),
+            Self::RemoteCredentialGuard => write!(f, "Remote Credential Guard"),
         }
     }
 }
@@ -80,6 +167,12 @@ impl Credentials {
 pub struct Config {
     /// The initial desktop size to request
     pub desktop_size: DesktopSize,
+    /// The monitor layout to request, spanning a virtual desktop
+    ///
+    /// When empty, a single monitor of `desktop_size` is used. Otherwise, `desktop_size` is ignored and the size
+    /// of the bounding box of the monitors is requested instead. Up to 16 monitors are supported, and exactly
+    /// one of them must be the primary monitor.
+    pub monitors: Vec<MonitorConfig>,
     /// TLS + Graphical login (legacy)
     ///
     /// Also called SSL or TLS security protocol.
@@ -126,6 +219,16 @@ pub struct Config {
     /// computers.
     #[doc(alias("enable_nla", "nla"))]
     pub enable_credssp: bool,
+    /// Standard RDP Security, using RC4 encryption and an RSA key exchange
+    ///
+    /// Allows the server to select PROTOCOL_RDP. The MCS traffic is then encrypted with weak
+    /// algorithms and the server is not authenticated: only enable it for legacy servers.
+    pub enable_standard_security: bool,
+    /// Credentials left on the server once logged on
+    ///
+    /// Restricted Admin mode and Remote Credential Guard require CredSSP, and the connection fails when the
+    /// server does not support the requested mode.
+    pub credential_delegation: CredentialDelegation,
     pub credentials: Credentials,
     pub domain: Option<String>,
     /// The build number of the client.
@@ -145,6 +248,15 @@ pub struct Config {
     pub platform: capability_sets::MajorPlatformType,
     /// If true, the INFO_AUTOLOGON flag is set in the [`ClientInfoPdu`](ironrdp_pdu::rdp::ClientInfoPdu)
     pub autologon: bool,
+    /// Auto-reconnect cookie received from the server in a previous session
+    ///
+    /// When set, the client asks the server to reconnect it to this session.
+    pub auto_reconnect_cookie: Option<ServerAutoReconnect>,
+    /// Preconnection PDU sent before the X.224 Connection Request ([MS-RDPEPS] 2.2.1)
+    ///
+    /// Tells the listener which RDP source to connect to, such as the ID of a Hyper-V virtual machine
+    /// whose console is reached on port 2179. A V2 blob carries a string in addition to the numeric ID.
+    pub pcb: Option<PreconnectionBlob>,
 
     // FIXME(@CBenoit): these are client-only options, not part of the connector.
     pub no_server_pointer: bool,
@@ -235,6 +347,8 @@ pub enum ConnectorErrorKind {
     Credssp(sspi::Error),
     Reason(String),
     AccessDenied,
+    /// The server redirected the client to another server
+    Redirection(Box<Redirection>),
     General,
     Custom,
 }
@@ -246,6 +360,7 @@ impl fmt::Display for ConnectorErrorKind {
             ConnectorErrorKind::Credssp(_) => write!(f, "CredSSP"),
             ConnectorErrorKind::Reason(description) => write!(f, "reason: {description}"),
             ConnectorErrorKind::AccessDenied => write!(f, "access denied"),
+            ConnectorErrorKind::Redirection(_) => write!(f, "redirected to another server"),
             ConnectorErrorKind::General => write!(f, "general error"),
             ConnectorErrorKind::Custom => write!(f, "custom error"),
         }
@@ -259,6 +374,7 @@ impl std::error::Error for ConnectorErrorKind {
             ConnectorErrorKind::Credssp(e) => Some(e),
             ConnectorErrorKind::Reason(_) => None,
             ConnectorErrorKind::AccessDenied => None,
+            ConnectorErrorKind::Redirection(_) => None,
             ConnectorErrorKind::Custom => None,
             ConnectorErrorKind::General => None,
         }
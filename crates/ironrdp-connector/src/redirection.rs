@@ -0,0 +1,76 @@
+use ironrdp_pdu::nego;
+use ironrdp_pdu::rdp::server_redirection::{ServerRedirectionFlags, ServerRedirectionPdu};
+
+/// Server redirection received during the connection sequence
+///
+/// Returned in [`ConnectorErrorKind::Redirection`](crate::ConnectorErrorKind::Redirection) when the server,
+/// typically a connection broker, asks the client to connect again to another server. The new connection is
+/// set up with [`ClientConnector::with_redirection`](crate::ClientConnector::with_redirection).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Redirection {
+    /// Session to reconnect to on the target server
+    pub session_id: u32,
+    pub flags: ServerRedirectionFlags,
+    /// Addresses of the target server, to be tried in order
+    ///
+    /// Empty when the client must connect again to the same server, sending the load balance info only.
+    pub target_addresses: Vec<String>,
+    pub target_fqdn: Option<String>,
+    pub target_netbios_name: Option<String>,
+    /// Sent back as the routing token of the X.224 Connection Request
+    pub load_balance_info: Option<Vec<u8>>,
+    pub username: Option<String>,
+    pub domain: Option<String>,
+    /// Opaque cookie standing for the password, encrypted when [`ServerRedirectionFlags::PASSWORD_IS_PK_ENCRYPTED`] is set
+    pub password_cookie: Option<Vec<u8>>,
+    pub redirection_guid: Option<Vec<u8>>,
+    pub tsv_url: Option<Vec<u8>>,
+    pub target_certificate: Option<Vec<u8>>,
+}
+
+impl Redirection {
+    /// Returns the routing token to send in the X.224 Connection Request, if any
+    ///
+    /// The load balance info is sent back verbatim, whatever its format ([MS-RDPBCGR] 2.2.1.1).
+    pub fn routing_token(&self) -> Option<nego::NegoRequestData> {
+        self.load_balance_info
+            .clone()
+            .map(nego::NegoRequestData::load_balance_info)
+    }
+}
+
+impl From<ServerRedirectionPdu> for Redirection {
+    fn from(pdu: ServerRedirectionPdu) -> Self {
+        let mut target_addresses = Vec::new();
+
+        if !pdu.flags.contains(ServerRedirectionFlags::NO_REDIRECT) {
+            let candidates = pdu
+                .target_net_address
+                .into_iter()
+                .chain(pdu.target_net_addresses.into_iter().flatten())
+                .chain(pdu.target_fqdn.clone())
+                .chain(pdu.target_netbios_name.clone());
+
+            for address in candidates {
+                if !address.is_empty() && !target_addresses.contains(&address) {
+                    target_addresses.push(address);
+                }
+            }
+        }
+
+        Self {
+            session_id: pdu.session_id,
+            flags: pdu.flags,
+            target_addresses,
+            target_fqdn: pdu.target_fqdn,
+            target_netbios_name: pdu.target_netbios_name,
+            load_balance_info: pdu.load_balance_info,
+            username: pdu.username,
+            domain: pdu.domain,
+            password_cookie: pdu.password,
+            redirection_guid: pdu.redirection_guid,
+            tsv_url: pdu.tsv_url,
+            target_certificate: pdu.target_certificate,
+        }
+    }
+}
@@ -24,6 +24,26 @@ const DATA_MAX_SIZE: usize = 1590;
 
 pub trait DvcServerProcessor: DvcProcessor {}
 
+impl DvcProcessor for Box<dyn DvcServerProcessor> {
+    fn channel_name(&self) -> &str {
+        (**self).channel_name()
+    }
+
+    fn start(&mut self, channel_id: u32) -> PduResult<DvcMessages> {
+        (**self).start(channel_id)
+    }
+
+    fn process(&mut self, channel_id: u32, payload: &[u8]) -> PduResult<DvcMessages> {
+        (**self).process(channel_id, payload)
+    }
+
+    fn close(&mut self, channel_id: u32) {
+        (**self).close(channel_id)
+    }
+}
+
+impl DvcServerProcessor for Box<dyn DvcServerProcessor> {}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum ChannelState {
     Closed,
@@ -92,6 +112,26 @@ impl DrdynvcServer {
         self
     }
 
+    /// Encodes messages to send on an opened dynamic channel, outside of a response to the client
+    ///
+    /// The returned messages must be sent on the DRDYNVC static channel.
+    pub fn encode_dvc_messages(&mut self, channel_id: u32, messages: DvcMessages) -> PduResult<Vec<SvcMessage>> {
+        let c = self.channel_by_id(channel_id)?;
+        if c.state != ChannelState::Opened {
+            return Err(invalid_message_err!("DRDYNVC", "", "invalid channel state"));
+        }
+
+        encode_dvc_data(channel_id, messages)
+    }
+
+    /// Returns the ID of the opened dynamic channel with this name
+    pub fn get_channel_id_by_name(&self, name: &str) -> Option<u32> {
+        self.dynamic_channels
+            .iter()
+            .find(|(_, c)| c.state == ChannelState::Opened && c.processor.channel_name() == name)
+            .and_then(|(id, _)| u32::try_from(id).ok())
+    }
+
     fn channel_by_id(&mut self, id: u32) -> PduResult<&mut DynamicChannel> {
         let id = cast_length!("DRDYNVC", "", id)?;
         self.dynamic_channels
@@ -1,6 +1,10 @@
 use std::cmp::{max, min};
 use std::io::{self, Write};
 
+use ironrdp_pdu::geometry::Rectangle as _;
+
+use crate::image_processing::ImageRegion;
+
 const DIVISOR: f32 = (1 << 16) as f32;
 const ALPHA: u8 = 255;
 
@@ -14,6 +18,54 @@ pub fn ycbcr_to_bgra(input: YCbCrBuffer<'_>, mut output: &mut [u8]) -> io::Resul
     Ok(())
 }
 
+/// Converts a region of at most 64x64 pixels into the YCbCr planes used by the RemoteFX encoder
+///
+/// Each plane must hold 64x64 values. The tile is padded by repeating the last column and last row
+/// of the region.
+pub fn to_64x64_ycbcr_tile(input: &ImageRegion<'_>, y: &mut [i16], cb: &mut [i16], cr: &mut [i16]) -> io::Result<()> {
+    let width = usize::from(input.region.width());
+    let height = usize::from(input.region.height());
+
+    if width > 64 || height > 64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "region is larger than a tile",
+        ));
+    }
+
+    let bpp = usize::from(input.pixel_format.bytes_per_pixel());
+    let left = usize::from(input.region.left);
+    let top = usize::from(input.region.top);
+    let step = if input.step == 0 {
+        width * bpp
+    } else {
+        usize::from(input.step)
+    };
+
+    for row in 0..64 {
+        let src = &input.data[(top + min(row, height - 1)) * step..];
+
+        for column in 0..64 {
+            let color = input
+                .pixel_format
+                .read_color(&src[(left + min(column, width - 1)) * bpp..])?;
+
+            let ycbcr = YCbCr::from(Rgb {
+                r: color.r,
+                g: color.g,
+                b: color.b,
+            });
+
+            let i = row * 64 + column;
+            y[i] = ycbcr.y;
+            cb[i] = ycbcr.cb;
+            cr[i] = ycbcr.cr;
+        }
+    }
+
+    Ok(())
+}
+
 /// Convert a 16-bit RDP color to RGB representation. Input value should be represented in
 /// little-endian format.
 pub fn rdp_16bit_to_rgb(color: u16) -> [u8; 3] {
@@ -68,6 +120,25 @@ pub struct Rgb {
     pub b: u8,
 }
 
+/// Produces 11.5 fixed-point values, Y being centered on zero
+impl From<Rgb> for YCbCr {
+    fn from(Rgb { r, g, b }: Rgb) -> Self {
+        let r = i32::from(r);
+        let g = i32::from(g);
+        let b = i32::from(b);
+
+        let y = ((r * 9798 + g * 19235 + b * 3735) >> 10) - 4096;
+        let cb = (r * -5535 + g * -10868 + b * 16403) >> 10;
+        let cr = (r * 16377 + g * -13714 + b * -2663) >> 10;
+
+        Self {
+            y: y.clamp(-4096, 4095) as i16,
+            cb: cb.clamp(-4096, 4095) as i16,
+            cr: cr.clamp(-4096, 4095) as i16,
+        }
+    }
+}
+
 impl From<YCbCr> for Rgb {
     fn from(YCbCr { y, cb, cr }: YCbCr) -> Self {
         let y = i32::from(y);
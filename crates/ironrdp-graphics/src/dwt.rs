@@ -6,11 +6,95 @@ pub fn decode(buffer: &mut [i16], temp_buffer: &mut [i16]) {
     decode_block(&mut *buffer, temp_buffer, 32);
 }
 
+pub fn encode(buffer: &mut [i16], temp_buffer: &mut [i16]) {
+    encode_block(&mut *buffer, temp_buffer, 32);
+    encode_block(&mut buffer[3072..], temp_buffer, 16);
+    encode_block(&mut buffer[3840..], temp_buffer, 8);
+}
+
 fn decode_block(buffer: &mut [i16], temp_buffer: &mut [i16], subband_width: usize) {
     inverse_horizontal(buffer, temp_buffer, subband_width);
     inverse_vertical(buffer, temp_buffer, subband_width);
 }
 
+fn encode_block(buffer: &mut [i16], temp_buffer: &mut [i16], subband_width: usize) {
+    forward_vertical(buffer, temp_buffer, subband_width);
+    forward_horizontal(buffer, temp_buffer, subband_width);
+}
+
+// Forward DWT in vertical direction, results in 2 sub-bands in L, H order in temp buffer
+fn forward_vertical(buffer: &[i16], temp_buffer: &mut [i16], subband_width: usize) {
+    let total_width = subband_width * 2;
+
+    for x in 0..total_width {
+        for n in 0..subband_width {
+            let y = n * 2;
+            let l = n * total_width + x;
+            let h = l + subband_width * total_width;
+            let src = y * total_width + x;
+
+            let next = if n < subband_width - 1 {
+                src + 2 * total_width
+            } else {
+                src
+            };
+
+            temp_buffer[h] = ((i32::from(buffer[src + total_width])
+                - ((i32::from(buffer[src]) + i32::from(buffer[next])) >> 1))
+                >> 1) as i16;
+
+            let h_sum = if n == 0 {
+                i32::from(temp_buffer[h])
+            } else {
+                (i32::from(temp_buffer[h]) + i32::from(temp_buffer[h - total_width])) >> 1
+            };
+            temp_buffer[l] = (i32::from(buffer[src]) + h_sum) as i16;
+        }
+    }
+}
+
+// Forward DWT in horizontal direction, results in 4 sub-bands in HL(0), LH(1), HH(2), LL(3) order in buffer
+// The lower part L generates LL(3) and HL(0).
+// The higher part H generates LH(1) and HH(2).
+fn forward_horizontal(buffer: &mut [i16], temp_buffer: &[i16], subband_width: usize) {
+    let total_width = subband_width * 2;
+    let squared_subband_width = subband_width.pow(2);
+
+    let (l_src, h_src) = temp_buffer.split_at(squared_subband_width * 2);
+    let (hl, buffer) = buffer.split_at_mut(squared_subband_width);
+    let (lh, buffer) = buffer.split_at_mut(squared_subband_width);
+    let (hh, ll) = buffer.split_at_mut(squared_subband_width);
+
+    for y in 0..subband_width {
+        let l_src = &l_src[y * total_width..];
+        let h_src = &h_src[y * total_width..];
+        let row = y * subband_width;
+
+        for n in 0..subband_width {
+            let x = n * 2;
+            let next = if n < subband_width - 1 { x + 2 } else { x };
+
+            let high = ((i32::from(l_src[x + 1]) - ((i32::from(l_src[x]) + i32::from(l_src[next])) >> 1)) >> 1) as i16;
+            let high_sum = if n == 0 {
+                i32::from(high)
+            } else {
+                (i32::from(high) + i32::from(hl[row + n - 1])) >> 1
+            };
+            hl[row + n] = high;
+            ll[row + n] = (i32::from(l_src[x]) + high_sum) as i16;
+
+            let high = ((i32::from(h_src[x + 1]) - ((i32::from(h_src[x]) + i32::from(h_src[next])) >> 1)) >> 1) as i16;
+            let high_sum = if n == 0 {
+                i32::from(high)
+            } else {
+                (i32::from(high) + i32::from(hh[row + n - 1])) >> 1
+            };
+            hh[row + n] = high;
+            lh[row + n] = (i32::from(h_src[x]) + high_sum) as i16;
+        }
+    }
+}
+
 // Inverse DWT in horizontal direction, results in 2 sub-bands in L, H order in output buffer
 // The 4 sub-bands are stored in HL(0), LH(1), HH(2), LL(3) order.
 // The lower part L uses LL(3) and HL(0).
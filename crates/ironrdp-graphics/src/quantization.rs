@@ -29,6 +29,43 @@ pub fn decode(buffer: &mut [i16], quant: &Quant) {
         .for_each(decode_chunk);
 }
 
+/// Quantizes the coefficients of a tile, as the inverse of [`decode`]
+///
+/// Each sub-band is divided by 2^(quant - 1), rounding to the nearest integer. The coefficients are expected
+/// to be scaled by 32, as produced by the RGB to YCbCr conversion, which is also what the decoder restores.
+pub fn encode(buffer: &mut [i16], quant: &Quant) {
+    let (first_level, second_and_third_level) = buffer.split_at_mut(FIRST_LEVEL_SUBBANDS_COUNT * FIRST_LEVEL_SIZE);
+    let (second_level, third_level) =
+        second_and_third_level.split_at_mut(SECOND_LEVEL_SUBBANDS_COUNT * SECOND_LEVEL_SIZE);
+
+    let encode_chunk = |a: (&mut [i16], u8)| encode_block(a.0, a.1.saturating_sub(1));
+
+    first_level
+        .chunks_mut(FIRST_LEVEL_SIZE)
+        .zip([quant.hl1, quant.lh1, quant.hh1].iter().copied())
+        .for_each(encode_chunk);
+
+    second_level
+        .chunks_mut(SECOND_LEVEL_SIZE)
+        .zip([quant.hl2, quant.lh2, quant.hh2].iter().copied())
+        .for_each(encode_chunk);
+
+    third_level
+        .chunks_mut(THIRD_LEVEL_SIZE)
+        .zip([quant.hl3, quant.lh3, quant.hh3, quant.ll3].iter().copied())
+        .for_each(encode_chunk);
+}
+
+fn encode_block(buffer: &mut [i16], factor: u8) {
+    if factor > 0 {
+        let half = 1 << (factor - 1);
+
+        for value in buffer {
+            *value = ((i32::from(*value) + half) >> factor) as i16;
+        }
+    }
+}
+
 fn decode_block(buffer: &mut [i16], factor: i16) {
     if factor > 0 {
         for value in buffer {
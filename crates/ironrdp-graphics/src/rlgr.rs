@@ -4,6 +4,7 @@ use std::io;
 use bitvec::field::BitField as _;
 use bitvec::order::Msb0;
 use bitvec::slice::BitSlice;
+use bitvec::view::BitView as _;
 use ironrdp_pdu::codecs::rfx::EntropyAlgorithm;
 use thiserror::Error;
 
@@ -116,6 +117,166 @@ pub fn decode(mode: EntropyAlgorithm, tile: &[u8], mut output: &mut [i16]) -> Re
     Ok(())
 }
 
+/// Encodes `input` coefficients into `tile`, returning the number of bytes written.
+pub fn encode(mode: EntropyAlgorithm, mut input: &[i16], tile: &mut [u8]) -> Result<usize, RlgrError> {
+    let mut k: u32 = 1;
+    let mut kp: u32 = k << LS_GR;
+    let mut krp: u32 = 1 << LS_GR;
+
+    let mut bits = BitStream::new(tile);
+
+    while !input.is_empty() {
+        match CompressionMode::from(k) {
+            CompressionMode::RunLength => {
+                let mut number_of_zeros = 0;
+                let mut value = next_input(&mut input);
+                while value == 0 && !input.is_empty() {
+                    number_of_zeros += 1;
+                    value = next_input(&mut input);
+                }
+
+                // The input ends with a run of zeros: the last coefficient is counted in the run,
+                // and the terminating value only lets the decoder finish the run.
+                if value == 0 {
+                    number_of_zeros += 1;
+                }
+
+                let mut run_max = 1 << k;
+                while number_of_zeros >= run_max {
+                    bits.output_bit(1, false)?;
+                    number_of_zeros -= run_max;
+                    kp = min(kp + UP_GR, KP_MAX);
+                    k = kp >> LS_GR;
+                    run_max = 1 << k;
+                }
+
+                bits.output_bit(1, true)?;
+                bits.output_bits(k as usize, number_of_zeros)?;
+
+                let magnitude = u32::from(value.unsigned_abs());
+                bits.output_bit(1, value < 0)?;
+                code_gr(&mut bits, &mut krp, magnitude.saturating_sub(1))?;
+
+                kp = kp.saturating_sub(DN_GR);
+                k = kp >> LS_GR;
+            }
+            CompressionMode::GolombRice => match mode {
+                EntropyAlgorithm::Rlgr1 => {
+                    let two_ms = two_magnitude_sign(next_input(&mut input));
+                    code_gr(&mut bits, &mut krp, two_ms)?;
+
+                    if two_ms == 0 {
+                        kp = min(kp + UQ_GR, KP_MAX);
+                    } else {
+                        kp = kp.saturating_sub(DQ_GR);
+                    }
+                    k = kp >> LS_GR;
+                }
+                EntropyAlgorithm::Rlgr3 => {
+                    let two_ms1 = two_magnitude_sign(next_input(&mut input));
+                    let two_ms2 = two_magnitude_sign(next_input(&mut input));
+                    let sum = two_ms1 + two_ms2;
+
+                    code_gr(&mut bits, &mut krp, sum)?;
+                    bits.output_bits(compute_n_index(sum), two_ms1)?;
+
+                    if two_ms1 != 0 && two_ms2 != 0 {
+                        kp = kp.saturating_sub(2 * DQ_GR);
+                        k = kp >> LS_GR;
+                    } else if two_ms1 == 0 && two_ms2 == 0 {
+                        kp = min(kp + 2 * UQ_GR, KP_MAX);
+                        k = kp >> LS_GR;
+                    }
+                }
+            },
+        }
+    }
+
+    Ok(bits.len())
+}
+
+fn next_input(input: &mut &[i16]) -> i16 {
+    match input.split_first() {
+        Some((&value, rest)) => {
+            *input = rest;
+            value
+        }
+        None => 0,
+    }
+}
+
+// Maps a coefficient to (2 * magnitude - sign)
+fn two_magnitude_sign(value: i16) -> u32 {
+    let magnitude = u32::from(value.unsigned_abs());
+    if value < 0 {
+        2 * magnitude - 1
+    } else {
+        2 * magnitude
+    }
+}
+
+// Golomb-Rice code of `value`, adapting the `krp` parameter
+fn code_gr(bits: &mut BitStream<'_>, krp: &mut u32, value: u32) -> Result<(), RlgrError> {
+    let kr = *krp >> LS_GR;
+
+    let unary = value >> kr;
+    bits.output_bit(unary as usize, true)?;
+    bits.output_bit(1, false)?;
+    bits.output_bits(kr as usize, value & ((1 << kr) - 1))?;
+
+    if unary == 0 {
+        *krp = krp.saturating_sub(2);
+    } else if unary > 1 {
+        *krp = min(*krp + unary, KP_MAX);
+    }
+
+    Ok(())
+}
+
+struct BitStream<'a> {
+    bits: &'a mut BitSlice<u8, Msb0>,
+    position: usize,
+}
+
+impl<'a> BitStream<'a> {
+    fn new(buffer: &'a mut [u8]) -> Self {
+        buffer.fill(0);
+
+        Self {
+            bits: buffer.view_bits_mut::<Msb0>(),
+            position: 0,
+        }
+    }
+
+    fn output_bit(&mut self, count: usize, value: bool) -> Result<(), RlgrError> {
+        self.reserve(count)?[..].fill(value);
+        Ok(())
+    }
+
+    fn output_bits(&mut self, count: usize, value: u32) -> Result<(), RlgrError> {
+        if count > 0 {
+            self.reserve(count)?.store_be(value);
+        }
+        Ok(())
+    }
+
+    fn reserve(&mut self, count: usize) -> Result<&mut BitSlice<u8, Msb0>, RlgrError> {
+        let end = self.position + count;
+        if end > self.bits.len() {
+            return Err(RlgrError::BufferTooSmall);
+        }
+
+        let reserved = &mut self.bits[self.position..end];
+        self.position = end;
+
+        Ok(reserved)
+    }
+
+    fn len(&self) -> usize {
+        self.position.div_ceil(8)
+    }
+}
+
 fn fill(buffer: &mut [i16], value: i16) {
     for v in buffer {
         *v = value;
@@ -231,4 +392,6 @@ pub enum RlgrError {
     IoError(#[from] io::Error),
     #[error("the input tile is empty")]
     EmptyTile,
+    #[error("the output buffer is too small")]
+    BufferTooSmall,
 }
@@ -1,6 +1,6 @@
 use bit_field::BitField;
 use bitflags::bitflags;
-use byteorder::{LittleEndian, ReadBytesExt as _};
+use byteorder::{LittleEndian, ReadBytesExt as _, WriteBytesExt as _};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive as _;
 
@@ -44,6 +44,38 @@ impl<'a> SegmentedDataPdu<'a> {
     }
 }
 
+/// Largest amount of data carried by a single segment
+const SEGMENT_MAX_SIZE: usize = 65_535;
+
+/// Writes data as an uncompressed segmented data PDU, split into a multipart PDU when needed.
+pub(crate) fn write_uncompressed(data: &[u8], output: &mut Vec<u8>) -> Result<usize, ZgfxError> {
+    let start = output.len();
+    let header = CompressionType::Rdp8 as u8;
+
+    if data.len() <= SEGMENT_MAX_SIZE {
+        output.write_u8(SegmentedDescriptor::Single as u8)?;
+        output.write_u8(header)?;
+        output.extend_from_slice(data);
+    } else {
+        let segment_count =
+            u16::try_from(data.len().div_ceil(SEGMENT_MAX_SIZE)).map_err(|_| ZgfxError::InputTooLarge)?;
+        let uncompressed_size = u32::try_from(data.len()).map_err(|_| ZgfxError::InputTooLarge)?;
+
+        output.write_u8(SegmentedDescriptor::Multipart as u8)?;
+        output.write_u16::<LittleEndian>(segment_count)?;
+        output.write_u32::<LittleEndian>(uncompressed_size)?;
+
+        for segment in data.chunks(SEGMENT_MAX_SIZE) {
+            // The size includes the compression type and flags
+            output.write_u32::<LittleEndian>(segment.len() as u32 + 1)?;
+            output.write_u8(header)?;
+            output.extend_from_slice(segment);
+        }
+    }
+
+    Ok(output.len() - start)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct BulkEncodedData<'a> {
     pub(crate) compression_flags: CompressionFlags,
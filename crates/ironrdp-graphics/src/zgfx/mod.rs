@@ -13,7 +13,7 @@ use byteorder::WriteBytesExt;
 use thiserror::Error;
 
 use self::circular_buffer::FixedCircularBuffer;
-use self::control_messages::{BulkEncodedData, CompressionFlags, SegmentedDataPdu};
+use self::control_messages::{write_uncompressed, BulkEncodedData, CompressionFlags, SegmentedDataPdu};
 use crate::utils::Bits;
 
 const HISTORY_SIZE: usize = 2_500_000;
@@ -124,6 +124,13 @@ impl Default for Decompressor {
     }
 }
 
+/// Wraps data into uncompressed ZGFX segments, as sent by servers which do not compress the graphics pipeline
+///
+/// Returns the number of bytes written to `output`.
+pub fn wrap_uncompressed(input: &[u8], output: &mut Vec<u8>) -> Result<usize, ZgfxError> {
+    write_uncompressed(input, output)
+}
+
 fn handle_match(
     bits: &mut Bits<'_>,
     distance_value_size: usize,
@@ -445,6 +452,8 @@ pub enum ZgfxError {
     },
     #[error("token bits not found")]
     TokenBitsNotFound,
+    #[error("input is too large for a segmented data PDU")]
+    InputTooLarge,
 }
 
 impl ironrdp_error::legacy::ErrorContext for ZgfxError {
@@ -635,4 +644,30 @@ mod tests {
         zgfx.decompress_segment(buffer.as_ref(), &mut decompressed).unwrap();
         assert_eq!(decompressed, expected);
     }
+
+    #[test]
+    fn zgfx_decompresses_wrapped_single_segment() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+
+        let mut wrapped = Vec::new();
+        wrap_uncompressed(data, &mut wrapped).unwrap();
+        assert_eq!(wrapped[..2], [0xe0, 0x04]);
+
+        let mut decompressed = Vec::new();
+        Decompressor::new().decompress(&wrapped, &mut decompressed).unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn zgfx_decompresses_wrapped_multipart_segments() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+
+        let mut wrapped = Vec::new();
+        wrap_uncompressed(&data, &mut wrapped).unwrap();
+        assert_eq!(wrapped[..3], [0xe1, 0x04, 0x00]);
+
+        let mut decompressed = Vec::new();
+        Decompressor::new().decompress(&wrapped, &mut decompressed).unwrap();
+        assert_eq!(decompressed, data);
+    }
 }
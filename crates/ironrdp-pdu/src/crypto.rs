@@ -1,2 +1,4 @@
+pub mod standard_security;
+
 pub(crate) mod rc4;
 pub(crate) mod rsa;
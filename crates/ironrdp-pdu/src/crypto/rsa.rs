@@ -4,6 +4,28 @@ use der_parser::parse_der;
 use num_bigint::BigUint;
 
 pub(crate) fn encrypt_with_public_key(message: &[u8], public_key_der: &[u8]) -> io::Result<Vec<u8>> {
+    let (n, e) = parse_public_key(public_key_der)?;
+
+    Ok(encrypt(message, &n, &e))
+}
+
+/// Raw RSA encryption of a little-endian message, as used by RDP
+///
+/// The result is little-endian and padded with zeros up to the modulus size plus 8 bytes.
+pub(crate) fn encrypt(message: &[u8], modulus: &BigUint, public_exponent: &BigUint) -> Vec<u8> {
+    let m = BigUint::from_bytes_le(message);
+    let c = m.modpow(public_exponent, modulus);
+
+    let modulus_size = usize::try_from(modulus.bits().div_ceil(8)).expect("modulus size fits in usize");
+
+    let mut result = c.to_bytes_le();
+    result.resize(modulus_size + 8, 0u8);
+
+    result
+}
+
+/// Parses a PKCS#1 DER-encoded RSA public key into its modulus and public exponent
+pub(crate) fn parse_public_key(public_key_der: &[u8]) -> io::Result<(BigUint, BigUint)> {
     let (_, der_object) = parse_der(public_key_der).map_err(|err| {
         io::Error::new(
             io::ErrorKind::InvalidData,
@@ -41,11 +63,6 @@ pub(crate) fn encrypt_with_public_key(message: &[u8], public_key_der: &[u8]) ->
 
     let n = BigUint::from_bytes_be(n);
     let e = BigUint::from_bytes_be(e);
-    let m = BigUint::from_bytes_le(message);
-    let c = m.modpow(&e, &n);
-
-    let mut result = c.to_bytes_le();
-    result.resize(result.len() + 8, 0u8);
 
-    Ok(result)
+    Ok((n, e))
 }
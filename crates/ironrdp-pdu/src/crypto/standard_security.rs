@@ -0,0 +1,410 @@
+//! Standard RDP Security ([MS-RDPBCGR] 5.3)
+//!
+//! Session key derivation, MAC signing and RC4 encryption used when the connection is not
+//! protected by TLS or CredSSP. FIPS encryption is not supported.
+
+use std::io;
+
+use md5::Digest;
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use super::rc4::Rc4;
+use super::rsa;
+use crate::cursor::{ReadCursor, WriteCursor};
+use crate::fast_path::EncryptionFlags;
+use crate::gcc::EncryptionMethod;
+use crate::rdp::headers::{BasicSecurityHeader, BasicSecurityHeaderFlags, BASIC_SECURITY_HEADER_SIZE};
+use crate::rdp::server_license::cert::CertificateType;
+use crate::rdp::server_license::{ServerCertificate, ServerLicenseError};
+use crate::{per, PduParsing};
+
+pub const CLIENT_RANDOM_SIZE: usize = 32;
+pub const SERVER_RANDOM_SIZE: usize = 32;
+pub const MAC_SIGNATURE_SIZE: usize = 8;
+
+/// Number of packets encrypted or decrypted with the same key before it is updated ([MS-RDPBCGR] 5.3.7)
+pub const KEY_UPDATE_INTERVAL: u32 = 4096;
+
+const PAD1: [u8; 40] = [0x36; 40];
+const PAD2: [u8; 48] = [0x5C; 48];
+
+const SALT_40_BIT: [u8; 3] = [0xD1, 0x26, 0x9E];
+const SALT_56_BIT: [u8; 1] = [0xD1];
+
+#[derive(Debug, Error)]
+pub enum StandardSecurityError {
+    #[error("IO error")]
+    IOError(#[from] io::Error),
+    #[error("unsupported encryption method: {0:?}")]
+    UnsupportedEncryptionMethod(EncryptionMethod),
+    #[error("invalid server certificate")]
+    InvalidServerCertificate(#[from] ServerLicenseError),
+    #[error("invalid RDP security header")]
+    InvalidSecurityHeader,
+    #[error("MAC signature mismatch")]
+    InvalidMacSignature,
+    #[error("not enough bytes")]
+    NotEnoughBytes,
+    #[error("encrypted PDU is too big")]
+    PduTooBig,
+}
+
+/// Initial session keys, from the point of view of the client ([MS-RDPBCGR] 5.3.5.1)
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionKeys {
+    pub method: EncryptionMethod,
+    pub mac_key: Vec<u8>,
+    pub encrypt_key: Vec<u8>,
+    pub decrypt_key: Vec<u8>,
+}
+
+impl core::fmt::Debug for SessionKeys {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SessionKeys")
+            .field("method", &self.method)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionKeys {
+    pub fn derive(
+        client_random: &[u8; CLIENT_RANDOM_SIZE],
+        server_random: &[u8; SERVER_RANDOM_SIZE],
+        method: EncryptionMethod,
+    ) -> Result<Self, StandardSecurityError> {
+        let salt: &[u8] = match method {
+            EncryptionMethod::BIT_40 => &SALT_40_BIT,
+            EncryptionMethod::BIT_56 => &SALT_56_BIT,
+            EncryptionMethod::BIT_128 => &[],
+            _ => return Err(StandardSecurityError::UnsupportedEncryptionMethod(method)),
+        };
+
+        let pre_master_secret = [&client_random[..24], &server_random[..24]].concat();
+
+        let master_secret = [b"A".as_slice(), b"BB", b"CCC"]
+            .iter()
+            .flat_map(|input| salted_hash(&pre_master_secret, input, client_random, server_random))
+            .collect::<Vec<u8>>();
+
+        let session_key_blob = [b"X".as_slice(), b"YY", b"ZZZ"]
+            .iter()
+            .flat_map(|input| salted_hash(&master_secret, input, client_random, server_random))
+            .collect::<Vec<u8>>();
+
+        let final_hash = |key: &[u8]| md5::Md5::digest([key, client_random, server_random].concat()).to_vec();
+
+        let mac_key = session_key_blob[..16].to_vec();
+        let decrypt_key = final_hash(&session_key_blob[16..32]);
+        let encrypt_key = final_hash(&session_key_blob[32..48]);
+
+        Ok(Self {
+            method,
+            mac_key: apply_salt(mac_key, salt),
+            encrypt_key: apply_salt(encrypt_key, salt),
+            decrypt_key: apply_salt(decrypt_key, salt),
+        })
+    }
+}
+
+/// RC4 encryption state of a connection using Standard RDP Security
+///
+/// Keys are updated every [`KEY_UPDATE_INTERVAL`] packets in each direction.
+#[derive(Clone)]
+pub struct StandardSecurity {
+    method: EncryptionMethod,
+    mac_key: Vec<u8>,
+    encrypt: CipherState,
+    decrypt: CipherState,
+}
+
+impl core::fmt::Debug for StandardSecurity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StandardSecurity")
+            .field("method", &self.method)
+            .field("encrypt", &self.encrypt)
+            .field("decrypt", &self.decrypt)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StandardSecurity {
+    pub fn new(keys: SessionKeys) -> Self {
+        Self {
+            method: keys.method,
+            encrypt: CipherState::new(keys.encrypt_key),
+            decrypt: CipherState::new(keys.decrypt_key),
+            mac_key: keys.mac_key,
+        }
+    }
+
+    pub fn method(&self) -> EncryptionMethod {
+        self.method
+    }
+
+    /// Signs and encrypts `data`, returning the MAC signature followed by the encrypted data
+    pub fn encrypt(&mut self, data: &[u8]) -> Vec<u8> {
+        let signature = mac_signature(&self.mac_key, data, None);
+        let encrypted = self.encrypt.process(self.method, data);
+
+        [signature.as_slice(), &encrypted].concat()
+    }
+
+    /// Decrypts the MAC signature and encrypted data following a security header, and checks the signature
+    ///
+    /// `salted_checksum` is set when the sender used the salted MAC ([MS-RDPBCGR] 5.3.6.1.1).
+    pub fn decrypt(&mut self, data: &[u8], salted_checksum: bool) -> Result<Vec<u8>, StandardSecurityError> {
+        if data.len() < MAC_SIGNATURE_SIZE {
+            return Err(StandardSecurityError::NotEnoughBytes);
+        }
+
+        let (signature, encrypted) = data.split_at(MAC_SIGNATURE_SIZE);
+
+        let encryption_count = self.decrypt.total_count;
+        let decrypted = self.decrypt.process(self.method, encrypted);
+
+        let expected = mac_signature(&self.mac_key, &decrypted, salted_checksum.then_some(encryption_count));
+
+        if signature != expected {
+            return Err(StandardSecurityError::InvalidMacSignature);
+        }
+
+        Ok(decrypted)
+    }
+
+    /// Encrypts MCS user data, prepending a basic security header with the `ENCRYPT` flag set
+    pub fn encrypt_user_data(&mut self, flags: BasicSecurityHeaderFlags, data: &[u8]) -> Vec<u8> {
+        let header = BasicSecurityHeader {
+            flags: flags | BasicSecurityHeaderFlags::ENCRYPT,
+        };
+
+        let mut user_data = Vec::with_capacity(BASIC_SECURITY_HEADER_SIZE + MAC_SIGNATURE_SIZE + data.len());
+        header
+            .to_buffer(&mut user_data)
+            .expect("writing into a Vec never fails");
+        user_data.extend_from_slice(&self.encrypt(data));
+
+        user_data
+    }
+
+    /// Strips the basic security header from MCS user data, decrypting the remaining data when required
+    pub fn decrypt_user_data(
+        &mut self,
+        user_data: &[u8],
+    ) -> Result<(BasicSecurityHeaderFlags, Vec<u8>), StandardSecurityError> {
+        if user_data.len() < BASIC_SECURITY_HEADER_SIZE {
+            return Err(StandardSecurityError::NotEnoughBytes);
+        }
+
+        let header =
+            BasicSecurityHeader::from_buffer(user_data).map_err(|_| StandardSecurityError::InvalidSecurityHeader)?;
+        let data = &user_data[BASIC_SECURITY_HEADER_SIZE..];
+
+        let data = if header.flags.contains(BasicSecurityHeaderFlags::ENCRYPT) {
+            self.decrypt(data, header.flags.contains(BasicSecurityHeaderFlags::SECURE_CHECKSUM))?
+        } else {
+            data.to_vec()
+        };
+
+        Ok((header.flags, data))
+    }
+
+    /// Encrypts a fast-path PDU, setting the `ENCRYPTED` flag of its header ([MS-RDPBCGR] 2.2.8.1.2, 2.2.9.1.2)
+    ///
+    /// Everything following the length field is encrypted, including the number of input events when present.
+    pub fn encrypt_fast_path(&mut self, pdu: &[u8]) -> Result<Vec<u8>, StandardSecurityError> {
+        let (header, data) = split_fast_path(pdu)?;
+
+        let encrypted = self.encrypt(data);
+        let flags = EncryptionFlags::ENCRYPTED.bits() << FAST_PATH_FLAGS_SHIFT;
+
+        encode_fast_path(header | flags, &encrypted)
+    }
+
+    /// Decrypts a fast-path PDU and checks its signature, clearing the encryption flags of its header
+    ///
+    /// PDUs without the `ENCRYPTED` flag are returned as is.
+    pub fn decrypt_fast_path(&mut self, pdu: &[u8]) -> Result<Vec<u8>, StandardSecurityError> {
+        let (header, data) = split_fast_path(pdu)?;
+        let flags = EncryptionFlags::from_bits_truncate(header >> FAST_PATH_FLAGS_SHIFT);
+
+        if !flags.contains(EncryptionFlags::ENCRYPTED) {
+            return Ok(pdu.to_vec());
+        }
+
+        let decrypted = self.decrypt(data, flags.contains(EncryptionFlags::SECURE_CHECKSUM))?;
+        let header = header & !(u8::MAX << FAST_PATH_FLAGS_SHIFT);
+
+        encode_fast_path(header, &decrypted)
+    }
+}
+
+/// Position of the encryption flags in the first byte of fast-path PDUs
+const FAST_PATH_FLAGS_SHIFT: u8 = 6;
+
+/// Splits a fast-path PDU into its first header byte and the data following the length field
+fn split_fast_path(pdu: &[u8]) -> Result<(u8, &[u8]), StandardSecurityError> {
+    let mut src = ReadCursor::new(pdu);
+
+    if src.is_empty() {
+        return Err(StandardSecurityError::NotEnoughBytes);
+    }
+
+    let header = src.read_u8();
+    let (length, _) = per::read_length(&mut src).map_err(|_| StandardSecurityError::NotEnoughBytes)?;
+
+    let data = pdu
+        .get(src.pos()..usize::from(length))
+        .ok_or(StandardSecurityError::NotEnoughBytes)?;
+
+    Ok((header, data))
+}
+
+fn encode_fast_path(header: u8, data: &[u8]) -> Result<Vec<u8>, StandardSecurityError> {
+    let length_without_field = 1 + data.len();
+    let length = u16::try_from(length_without_field + 1)
+        .ok()
+        .map(per::sizeof_length)
+        .and_then(|sizeof_length| u16::try_from(length_without_field + sizeof_length).ok())
+        .filter(|length| *length <= 0x7FFF)
+        .ok_or(StandardSecurityError::PduTooBig)?;
+
+    let mut pdu = vec![0; usize::from(length)];
+    let mut dst = WriteCursor::new(&mut pdu);
+    dst.write_u8(header);
+    per::write_length(&mut dst, length);
+    dst.write_slice(data);
+
+    Ok(pdu)
+}
+
+#[derive(Clone)]
+struct CipherState {
+    initial_key: Vec<u8>,
+    current_key: Vec<u8>,
+    rc4: Rc4,
+    use_count: u32,
+    total_count: u32,
+}
+
+impl core::fmt::Debug for CipherState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CipherState")
+            .field("use_count", &self.use_count)
+            .field("total_count", &self.total_count)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CipherState {
+    fn new(key: Vec<u8>) -> Self {
+        Self {
+            rc4: Rc4::new(&key),
+            initial_key: key.clone(),
+            current_key: key,
+            use_count: 0,
+            total_count: 0,
+        }
+    }
+
+    fn process(&mut self, method: EncryptionMethod, data: &[u8]) -> Vec<u8> {
+        if self.use_count == KEY_UPDATE_INTERVAL {
+            self.current_key = update_key(&self.initial_key, &self.current_key, method);
+            self.rc4 = Rc4::new(&self.current_key);
+            self.use_count = 0;
+        }
+
+        self.use_count += 1;
+        self.total_count = self.total_count.wrapping_add(1);
+
+        self.rc4.process(data)
+    }
+}
+
+/// Encrypts the client random with the public key found in the server certificate ([MS-RDPBCGR] 5.3.4.1)
+///
+/// `server_certificate` is the raw certificate of the Server Security Data.
+pub fn encrypt_client_random(
+    client_random: &[u8; CLIENT_RANDOM_SIZE],
+    server_certificate: &[u8],
+) -> Result<Vec<u8>, StandardSecurityError> {
+    let certificate = ServerCertificate::from_buffer(server_certificate)?;
+
+    let (modulus, public_exponent) = match &certificate.certificate {
+        CertificateType::Proprietary(certificate) => (
+            BigUint::from_bytes_le(&certificate.public_key.modulus),
+            BigUint::from(certificate.public_key.public_exponent),
+        ),
+        CertificateType::X509(_) => rsa::parse_public_key(&certificate.get_public_key()?)?,
+    };
+
+    Ok(rsa::encrypt(client_random, &modulus, &public_exponent))
+}
+
+/// Computes the MAC signature of `data` ([MS-RDPBCGR] 5.3.6.1)
+///
+/// The salted variant ([MS-RDPBCGR] 5.3.6.1.1) is used when the encryption count is provided.
+pub fn mac_signature(mac_key: &[u8], data: &[u8], encryption_count: Option<u32>) -> [u8; MAC_SIGNATURE_SIZE] {
+    let data_length = u32::try_from(data.len()).expect("data length fits in u32");
+
+    let mut sha1 = sha1::Sha1::new();
+    sha1.update(mac_key);
+    sha1.update(PAD1);
+    sha1.update(data_length.to_le_bytes());
+    sha1.update(data);
+    if let Some(encryption_count) = encryption_count {
+        sha1.update(encryption_count.to_le_bytes());
+    }
+    let sha_component = sha1.finalize();
+
+    let mut md5 = md5::Md5::new();
+    md5.update(mac_key);
+    md5.update(PAD2);
+    md5.update(sha_component);
+    let digest = md5.finalize();
+
+    let mut signature = [0; MAC_SIGNATURE_SIZE];
+    signature.copy_from_slice(&digest[..MAC_SIGNATURE_SIZE]);
+
+    signature
+}
+
+/// Derives the next encryption or decryption key ([MS-RDPBCGR] 5.3.7.1)
+pub fn update_key(initial_key: &[u8], current_key: &[u8], method: EncryptionMethod) -> Vec<u8> {
+    let mut sha1 = sha1::Sha1::new();
+    sha1.update(initial_key);
+    sha1.update(PAD1);
+    sha1.update(current_key);
+    let sha_component = sha1.finalize();
+
+    let mut md5 = md5::Md5::new();
+    md5.update(initial_key);
+    md5.update(PAD2);
+    md5.update(sha_component);
+    let temp_key = md5.finalize();
+
+    let temp_key = &temp_key[..initial_key.len()];
+    let new_key = Rc4::new(temp_key).process(temp_key);
+
+    match method {
+        EncryptionMethod::BIT_40 => apply_salt(new_key, &SALT_40_BIT),
+        EncryptionMethod::BIT_56 => apply_salt(new_key, &SALT_56_BIT),
+        _ => new_key,
+    }
+}
+
+fn salted_hash(secret: &[u8], input: &[u8], client_random: &[u8], server_random: &[u8]) -> Vec<u8> {
+    let sha_component = sha1::Sha1::digest([input, secret, client_random, server_random].concat());
+
+    md5::Md5::digest([secret, sha_component.as_slice()].concat()).to_vec()
+}
+
+/// Reduces a 128-bit key to a salted 64-bit key for 40-bit and 56-bit encryption
+fn apply_salt(mut key: Vec<u8>, salt: &[u8]) -> Vec<u8> {
+    if !salt.is_empty() {
+        key.truncate(8);
+        key[..salt.len()].copy_from_slice(salt);
+    }
+
+    key
+}
@@ -16,6 +16,7 @@ use crate::cursor::ReadCursor;
 mod macros;
 
 pub mod codecs;
+pub mod crypto;
 pub mod cursor;
 pub mod gcc;
 pub mod geometry;
@@ -25,6 +26,7 @@ pub mod nego;
 pub mod padding;
 pub mod pcb;
 pub mod rdp;
+pub mod rdstls;
 pub mod tpdu;
 pub mod tpkt;
 pub mod utf16;
@@ -35,7 +37,6 @@ pub mod x224;
 
 pub(crate) mod basic_output;
 pub(crate) mod ber;
-pub(crate) mod crypto;
 pub(crate) mod per;
 
 pub use crate::basic_output::{bitmap, fast_path, pointer, surface_commands};
@@ -386,9 +387,10 @@ pub use legacy::*;
 
 // TODO: Delete these traits at some point
 mod legacy {
-    use crate::{PduEncode, PduResult, WriteCursor};
     use thiserror::Error;
 
+    use crate::{PduEncode, PduResult, WriteCursor};
+
     pub trait PduParsing {
         type Error;
 
@@ -160,6 +160,8 @@ impl fmt::Display for FailureCode {
 pub enum NegoRequestData {
     RoutingToken(RoutingToken),
     Cookie(Cookie),
+    /// Load balance info of a server redirection, sent back verbatim as routing token
+    LoadBalanceInfo(LoadBalanceInfo),
 }
 
 impl NegoRequestData {
@@ -171,17 +173,27 @@ impl NegoRequestData {
         Self::Cookie(Cookie(value))
     }
 
+    pub fn load_balance_info(value: Vec<u8>) -> Self {
+        Self::LoadBalanceInfo(LoadBalanceInfo(value))
+    }
+
     pub fn read(src: &mut ReadCursor<'_>) -> PduResult<Option<Self>> {
-        match RoutingToken::read(src)? {
-            Some(token) => Ok(Some(Self::RoutingToken(token))),
-            None => Cookie::read(src)?.map(Self::Cookie).pipe(Ok),
+        if let Some(token) = RoutingToken::read(src)? {
+            return Ok(Some(Self::RoutingToken(token)));
         }
+
+        if let Some(cookie) = Cookie::read(src)? {
+            return Ok(Some(Self::Cookie(cookie)));
+        }
+
+        LoadBalanceInfo::read(src)?.map(Self::LoadBalanceInfo).pipe(Ok)
     }
 
     pub fn write(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
         match self {
             NegoRequestData::RoutingToken(token) => token.write(dst),
             NegoRequestData::Cookie(cookie) => cookie.write(dst),
+            NegoRequestData::LoadBalanceInfo(info) => info.write(dst),
         }
     }
 
@@ -189,6 +201,7 @@ impl NegoRequestData {
         match self {
             NegoRequestData::RoutingToken(token) => token.size(),
             NegoRequestData::Cookie(cookie) => cookie.size(),
+            NegoRequestData::LoadBalanceInfo(info) => info.size(),
         }
     }
 }
@@ -231,6 +244,58 @@ impl RoutingToken {
     }
 }
 
+/// Routing token of any format, such as the `tsv://` URLs of connection brokers
+///
+/// Holds the bytes up to and including the CR LF terminator, which is added when encoding a value lacking it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadBalanceInfo(pub Vec<u8>);
+
+impl LoadBalanceInfo {
+    const CTX: &'static str = "LoadBalanceInfo";
+
+    const TERMINATOR: &'static [u8] = b"\r\n";
+
+    pub fn read(src: &mut ReadCursor<'_>) -> PduResult<Option<Self>> {
+        // Without routing token, the RDP Negotiation Request comes right away
+        if src.is_empty() || src.peek_u8() == u8::from(NegoMsgType::REQUEST) {
+            return Ok(None);
+        }
+
+        let Some(end) = src
+            .remaining()
+            .windows(Self::TERMINATOR.len())
+            .position(|window| window == Self::TERMINATOR)
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self(src.read_slice(end + Self::TERMINATOR.len()).to_vec())))
+    }
+
+    pub fn write(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_size!(ctx: Self::CTX, in: dst, size: self.size());
+
+        dst.write_slice(&self.0);
+        if !self.is_terminated() {
+            dst.write_slice(Self::TERMINATOR);
+        }
+
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        if self.is_terminated() {
+            self.0.len()
+        } else {
+            self.0.len() + Self::TERMINATOR.len()
+        }
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.0.ends_with(Self::TERMINATOR)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 struct NegoMsgType(u8);
 
@@ -1,5 +1,6 @@
 use std::io;
 
+use byteorder::{LittleEndian, ReadBytesExt as _, WriteBytesExt as _};
 use thiserror::Error;
 
 use crate::input::InputEventError;
@@ -18,6 +19,7 @@ pub mod headers;
 pub mod refresh_rectangle;
 pub mod server_error_info;
 pub mod server_license;
+pub mod server_redirection;
 pub mod session_info;
 pub mod suppress_output;
 pub mod vc;
@@ -59,6 +61,50 @@ impl PduParsing for ClientInfoPdu {
     }
 }
 
+/// Client Security Exchange PDU ([MS-RDPBCGR] 2.2.1.10)
+///
+/// Sent when Standard RDP Security is used, carrying the client random encrypted with the server public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityExchangePdu {
+    pub encrypted_client_random: Vec<u8>,
+}
+
+impl PduParsing for SecurityExchangePdu {
+    type Error = RdpError;
+
+    fn from_buffer(mut stream: impl io::Read) -> Result<Self, Self::Error> {
+        let security_header = BasicSecurityHeader::from_buffer(&mut stream)?;
+        if !security_header.flags.contains(BasicSecurityHeaderFlags::EXCHANGE_PKT) {
+            return Err(RdpError::InvalidPdu(String::from(
+                "Expected Security Exchange PDU, got invalid SecurityHeader flags",
+            )));
+        }
+
+        let length = stream.read_u32::<LittleEndian>()?;
+        let mut encrypted_client_random = vec![0; length as usize];
+        stream.read_exact(&mut encrypted_client_random)?;
+
+        Ok(Self {
+            encrypted_client_random,
+        })
+    }
+
+    fn to_buffer(&self, mut stream: impl io::Write) -> Result<(), Self::Error> {
+        let security_header = BasicSecurityHeader {
+            flags: BasicSecurityHeaderFlags::EXCHANGE_PKT,
+        };
+        security_header.to_buffer(&mut stream)?;
+        stream.write_u32::<LittleEndian>(self.encrypted_client_random.len() as u32)?;
+        stream.write_all(&self.encrypted_client_random)?;
+
+        Ok(())
+    }
+
+    fn buffer_length(&self) -> usize {
+        crate::rdp::headers::BASIC_SECURITY_HEADER_SIZE + 4 + self.encrypted_client_random.len()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum RdpError {
     #[error("IO error")]
@@ -11,6 +11,7 @@ use crate::rdp::capability_sets::{ClientConfirmActive, ServerDemandActive};
 use crate::rdp::finalization_messages::{ControlPdu, FontPdu, MonitorLayoutPdu, SynchronizePdu};
 use crate::rdp::refresh_rectangle::RefreshRectanglePdu;
 use crate::rdp::server_error_info::ServerSetErrorInfoPdu;
+use crate::rdp::server_redirection::ServerRedirectionPdu;
 use crate::rdp::session_info::SaveSessionInfoPdu;
 use crate::rdp::suppress_output::SuppressOutputPdu;
 use crate::rdp::{client_info, RdpError};
@@ -20,9 +21,16 @@ pub const BASIC_SECURITY_HEADER_SIZE: usize = 4;
 pub const SHARE_DATA_HEADER_COMPRESSION_MASK: u8 = 0xF;
 const SHARE_CONTROL_HEADER_MASK: u16 = 0xF;
 const SHARE_CONTROL_HEADER_SIZE: usize = 2 * 3 + 4;
+const SHARE_ID_FIELD_SIZE: usize = 4;
 
 const PROTOCOL_VERSION: u16 = 0x10;
 
+// Padding preceding the packet of the Enhanced Security Server Redirection PDU
+const SERVER_REDIRECTION_PADDING_SIZE: usize = 2;
+
+// Windows servers send a single null byte as source descriptor of the Deactivate All PDU
+const DEACTIVATE_ALL_SOURCE_DESCRIPTOR: &[u8] = &[0x00];
+
 // ShareDataHeader
 const PADDING_FIELD_SIZE: usize = 1;
 const STREAM_ID_FIELD_SIZE: usize = 1;
@@ -63,9 +71,20 @@ impl PduParsing for BasicSecurityHeader {
 pub struct ShareControlHeader {
     pub share_control_pdu: ShareControlPdu,
     pub pdu_source: u16,
+    /// Absent from the Enhanced Security Server Redirection PDU, where it is always 0
     pub share_id: u32,
 }
 
+impl ShareControlHeader {
+    fn header_length(&self) -> usize {
+        if matches!(self.share_control_pdu, ShareControlPdu::ServerRedirect(_)) {
+            SHARE_CONTROL_HEADER_SIZE - SHARE_ID_FIELD_SIZE
+        } else {
+            SHARE_CONTROL_HEADER_SIZE
+        }
+    }
+}
+
 impl PduParsing for ShareControlHeader {
     type Error = RdpError;
 
@@ -73,7 +92,6 @@ impl PduParsing for ShareControlHeader {
         let total_length = stream.read_u16::<LittleEndian>()? as usize;
         let pdu_type_with_version = stream.read_u16::<LittleEndian>()?;
         let pdu_source = stream.read_u16::<LittleEndian>()?;
-        let share_id = stream.read_u32::<LittleEndian>()?;
 
         let pdu_type = ShareControlPduType::from_u16(pdu_type_with_version & SHARE_CONTROL_HEADER_MASK)
             .ok_or_else(|| RdpError::InvalidShareControlHeader(format!("invalid pdu type: {pdu_type_with_version}")))?;
@@ -84,6 +102,13 @@ impl PduParsing for ShareControlHeader {
             )));
         }
 
+        // The Enhanced Security Server Redirection PDU has no share ID ([MS-RDPBCGR] 2.2.13.3.1)
+        let share_id = if pdu_type == ShareControlPduType::ServerRedirect {
+            0
+        } else {
+            stream.read_u32::<LittleEndian>()?
+        };
+
         let share_pdu = ShareControlPdu::from_type(&mut stream, pdu_type)?;
         let header = Self {
             share_control_pdu: share_pdu,
@@ -114,17 +139,18 @@ impl PduParsing for ShareControlHeader {
     fn to_buffer(&self, mut stream: impl io::Write) -> Result<(), Self::Error> {
         let pdu_type_with_version = PROTOCOL_VERSION | self.share_control_pdu.share_header_type().to_u16().unwrap();
 
-        stream
-            .write_u16::<LittleEndian>((self.share_control_pdu.buffer_length() + SHARE_CONTROL_HEADER_SIZE) as u16)?;
+        stream.write_u16::<LittleEndian>(self.buffer_length() as u16)?;
         stream.write_u16::<LittleEndian>(pdu_type_with_version)?;
         stream.write_u16::<LittleEndian>(self.pdu_source)?;
-        stream.write_u32::<LittleEndian>(self.share_id)?;
+        if !matches!(self.share_control_pdu, ShareControlPdu::ServerRedirect(_)) {
+            stream.write_u32::<LittleEndian>(self.share_id)?;
+        }
 
         self.share_control_pdu.to_buffer(&mut stream)
     }
 
     fn buffer_length(&self) -> usize {
-        SHARE_CONTROL_HEADER_SIZE + self.share_control_pdu.buffer_length()
+        self.header_length() + self.share_control_pdu.buffer_length()
     }
 }
 
@@ -132,7 +158,9 @@ impl PduParsing for ShareControlHeader {
 pub enum ShareControlPdu {
     ServerDemandActive(ServerDemandActive),
     ClientConfirmActive(ClientConfirmActive),
+    ServerDeactivateAll(ServerDeactivateAll),
     Data(ShareDataHeader),
+    ServerRedirect(ServerRedirectionPdu),
 }
 
 impl ShareControlPdu {
@@ -140,7 +168,9 @@ impl ShareControlPdu {
         match self {
             ShareControlPdu::ServerDemandActive(_) => "Server Demand Active PDU",
             ShareControlPdu::ClientConfirmActive(_) => "Client Confirm Active PDU",
+            ShareControlPdu::ServerDeactivateAll(_) => "Server Deactivate All PDU",
             ShareControlPdu::Data(_) => "Data PDU",
+            ShareControlPdu::ServerRedirect(_) => "Server Redirection PDU",
         }
     }
 }
@@ -154,33 +184,81 @@ impl ShareControlPdu {
             ShareControlPduType::ConfirmActivePdu => Ok(ShareControlPdu::ClientConfirmActive(
                 ClientConfirmActive::from_buffer(&mut stream)?,
             )),
+            ShareControlPduType::DeactivateAllPdu => Ok(ShareControlPdu::ServerDeactivateAll(
+                ServerDeactivateAll::from_buffer(&mut stream)?,
+            )),
             ShareControlPduType::DataPdu => Ok(ShareControlPdu::Data(ShareDataHeader::from_buffer(&mut stream)?)),
-            _ => Err(RdpError::UnexpectedShareControlPdu(share_type)),
+            ShareControlPduType::ServerRedirect => {
+                let _padding = stream.read_u16::<LittleEndian>()?;
+                Ok(ShareControlPdu::ServerRedirect(ServerRedirectionPdu::from_buffer(
+                    &mut stream,
+                )?))
+            }
         }
     }
     pub fn to_buffer(&self, mut stream: impl io::Write) -> Result<(), RdpError> {
         match self {
             ShareControlPdu::ServerDemandActive(pdu) => pdu.to_buffer(&mut stream).map_err(RdpError::from),
             ShareControlPdu::ClientConfirmActive(pdu) => pdu.to_buffer(&mut stream).map_err(RdpError::from),
+            ShareControlPdu::ServerDeactivateAll(pdu) => pdu.to_buffer(&mut stream),
             ShareControlPdu::Data(share_data_header) => share_data_header.to_buffer(&mut stream),
+            ShareControlPdu::ServerRedirect(pdu) => {
+                stream.write_u16::<LittleEndian>(0)?; // padding
+                pdu.to_buffer(&mut stream)
+            }
         }
     }
     pub fn buffer_length(&self) -> usize {
         match self {
             ShareControlPdu::ServerDemandActive(pdu) => pdu.buffer_length(),
             ShareControlPdu::ClientConfirmActive(pdu) => pdu.buffer_length(),
+            ShareControlPdu::ServerDeactivateAll(pdu) => pdu.buffer_length(),
             ShareControlPdu::Data(share_data_header) => share_data_header.buffer_length(),
+            ShareControlPdu::ServerRedirect(pdu) => SERVER_REDIRECTION_PADDING_SIZE + pdu.buffer_length(),
         }
     }
     pub fn share_header_type(&self) -> ShareControlPduType {
         match self {
             ShareControlPdu::ServerDemandActive(_) => ShareControlPduType::DemandActivePdu,
             ShareControlPdu::ClientConfirmActive(_) => ShareControlPduType::ConfirmActivePdu,
+            ShareControlPdu::ServerDeactivateAll(_) => ShareControlPduType::DeactivateAllPdu,
             ShareControlPdu::Data(_) => ShareControlPduType::DataPdu,
+            ShareControlPdu::ServerRedirect(_) => ShareControlPduType::ServerRedirect,
         }
     }
 }
 
+/// [2.2.3.1] Server Deactivate All PDU
+///
+/// Sent by the server to start a deactivation-reactivation sequence, for instance on desktop resize.
+///
+/// [2.2.3.1]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpbcgr/8a29971a-df3c-48da-add2-8ed9a05edc89
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerDeactivateAll;
+
+impl PduParsing for ServerDeactivateAll {
+    type Error = RdpError;
+
+    fn from_buffer(mut stream: impl io::Read) -> Result<Self, Self::Error> {
+        let length_source_descriptor = stream.read_u16::<LittleEndian>()?;
+        // The source descriptor is ignored by the client
+        io::copy(&mut stream.take(u64::from(length_source_descriptor)), &mut io::sink())?;
+
+        Ok(Self)
+    }
+
+    fn to_buffer(&self, mut stream: impl io::Write) -> Result<(), Self::Error> {
+        stream.write_u16::<LittleEndian>(DEACTIVATE_ALL_SOURCE_DESCRIPTOR.len() as u16)?;
+        stream.write_all(DEACTIVATE_ALL_SOURCE_DESCRIPTOR)?;
+
+        Ok(())
+    }
+
+    fn buffer_length(&self) -> usize {
+        2 + DEACTIVATE_ALL_SOURCE_DESCRIPTOR.len()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ShareDataHeader {
     pub share_data_pdu: ShareDataPdu,
@@ -0,0 +1,305 @@
+use std::io;
+
+use bitflags::bitflags;
+use byteorder::{LittleEndian, ReadBytesExt as _, WriteBytesExt as _};
+
+use crate::rdp::headers::BasicSecurityHeaderFlags;
+use crate::rdp::RdpError;
+use crate::{utils, PduParsing};
+
+const FLAGS_FIELD_SIZE: usize = 2;
+const LENGTH_FIELD_SIZE: usize = 2;
+const SESSION_ID_FIELD_SIZE: usize = 4;
+const REDIRECTION_FLAGS_FIELD_SIZE: usize = 4;
+const FIELD_LENGTH_SIZE: usize = 4;
+const ADDRESS_COUNT_FIELD_SIZE: usize = 4;
+
+const HEADER_SIZE: usize = FLAGS_FIELD_SIZE + LENGTH_FIELD_SIZE + SESSION_ID_FIELD_SIZE + REDIRECTION_FLAGS_FIELD_SIZE;
+
+/// [MS-RDPBCGR] 2.2.13.1 Server Redirection Packet (RDP_SERVER_REDIRECTION_PACKET)
+///
+/// Sent by the server to make the client reconnect to another server, typically by a load balancer
+/// or a connection broker. When Enhanced RDP Security is in effect, it is wrapped in a Share Control
+/// PDU (2.2.13.3.1 TS_ENHANCED_SECURITY_SERVER_REDIRECTION).
+///
+/// The flags telling which fields are present are derived from the `Option`s when encoding, and
+/// removed from `flags` when decoding.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerRedirectionPdu {
+    /// Session to reconnect to on the target server
+    pub session_id: u32,
+    /// Flags which are not tied to a field, such as [`ServerRedirectionFlags::NO_REDIRECT`]
+    pub flags: ServerRedirectionFlags,
+    /// IP address of the target server
+    pub target_net_address: Option<String>,
+    /// Sent back by the client as the routing token of the X.224 Connection Request PDU
+    pub load_balance_info: Option<Vec<u8>>,
+    pub username: Option<String>,
+    pub domain: Option<String>,
+    /// Password, or encrypted logon cookie with [`ServerRedirectionFlags::PASSWORD_IS_PK_ENCRYPTED`]
+    pub password: Option<Vec<u8>>,
+    pub target_fqdn: Option<String>,
+    pub target_netbios_name: Option<String>,
+    pub tsv_url: Option<Vec<u8>>,
+    /// Identifies the redirected connection, sent as is
+    pub redirection_guid: Option<Vec<u8>>,
+    pub target_certificate: Option<Vec<u8>>,
+    /// IP addresses of the target server, the client tries them in order
+    pub target_net_addresses: Option<Vec<String>>,
+}
+
+impl ServerRedirectionPdu {
+    fn redirection_flags(&self) -> ServerRedirectionFlags {
+        let mut flags = self.flags & !ServerRedirectionFlags::FIELDS;
+
+        flags.set(
+            ServerRedirectionFlags::TARGET_NET_ADDRESS,
+            self.target_net_address.is_some(),
+        );
+        flags.set(
+            ServerRedirectionFlags::LOAD_BALANCE_INFO,
+            self.load_balance_info.is_some(),
+        );
+        flags.set(ServerRedirectionFlags::USERNAME, self.username.is_some());
+        flags.set(ServerRedirectionFlags::DOMAIN, self.domain.is_some());
+        flags.set(ServerRedirectionFlags::PASSWORD, self.password.is_some());
+        flags.set(ServerRedirectionFlags::TARGET_FQDN, self.target_fqdn.is_some());
+        flags.set(
+            ServerRedirectionFlags::TARGET_NETBIOS_NAME,
+            self.target_netbios_name.is_some(),
+        );
+        flags.set(ServerRedirectionFlags::CLIENT_TSV_URL, self.tsv_url.is_some());
+        flags.set(
+            ServerRedirectionFlags::REDIRECTION_GUID,
+            self.redirection_guid.is_some(),
+        );
+        flags.set(
+            ServerRedirectionFlags::TARGET_CERTIFICATE,
+            self.target_certificate.is_some(),
+        );
+        flags.set(
+            ServerRedirectionFlags::TARGET_NET_ADDRESSES,
+            self.target_net_addresses.is_some(),
+        );
+
+        flags
+    }
+}
+
+impl PduParsing for ServerRedirectionPdu {
+    type Error = RdpError;
+
+    fn from_buffer(mut stream: impl io::Read) -> Result<Self, Self::Error> {
+        let flags = stream.read_u16::<LittleEndian>()?;
+        if flags != BasicSecurityHeaderFlags::REDIRECTION_PKT.bits() {
+            return Err(RdpError::InvalidPdu(format!(
+                "invalid server redirection flags: {flags:#06x}"
+            )));
+        }
+
+        let length = usize::from(stream.read_u16::<LittleEndian>()?);
+        let session_id = stream.read_u32::<LittleEndian>()?;
+        let redirection_flags = ServerRedirectionFlags::from_bits_truncate(stream.read_u32::<LittleEndian>()?);
+
+        let mut read = HEADER_SIZE;
+        let mut field = |present: ServerRedirectionFlags| -> Result<Option<Vec<u8>>, RdpError> {
+            if !redirection_flags.contains(present) {
+                return Ok(None);
+            }
+
+            let field_length = stream.read_u32::<LittleEndian>()? as usize;
+            if field_length > length.saturating_sub(read + FIELD_LENGTH_SIZE) {
+                return Err(RdpError::InvalidPdu(format!(
+                    "server redirection field too long: {field_length}"
+                )));
+            }
+
+            let mut value = vec![0; field_length];
+            stream.read_exact(&mut value)?;
+            read += FIELD_LENGTH_SIZE + field_length;
+
+            Ok(Some(value))
+        };
+
+        let target_net_address = field(ServerRedirectionFlags::TARGET_NET_ADDRESS)?.map(|v| decode_unicode(&v));
+        let load_balance_info = field(ServerRedirectionFlags::LOAD_BALANCE_INFO)?;
+        let username = field(ServerRedirectionFlags::USERNAME)?.map(|v| decode_unicode(&v));
+        let domain = field(ServerRedirectionFlags::DOMAIN)?.map(|v| decode_unicode(&v));
+        let password = field(ServerRedirectionFlags::PASSWORD)?;
+        let target_fqdn = field(ServerRedirectionFlags::TARGET_FQDN)?.map(|v| decode_unicode(&v));
+        let target_netbios_name = field(ServerRedirectionFlags::TARGET_NETBIOS_NAME)?.map(|v| decode_unicode(&v));
+        let tsv_url = field(ServerRedirectionFlags::CLIENT_TSV_URL)?;
+        let redirection_guid = field(ServerRedirectionFlags::REDIRECTION_GUID)?;
+        let target_certificate = field(ServerRedirectionFlags::TARGET_CERTIFICATE)?;
+        let target_net_addresses = field(ServerRedirectionFlags::TARGET_NET_ADDRESSES)?
+            .map(|v| decode_net_addresses(&v))
+            .transpose()?;
+
+        // Optional trailing padding
+        if length > read {
+            io::copy(&mut stream.take((length - read) as u64), &mut io::sink())?;
+        }
+
+        Ok(Self {
+            session_id,
+            flags: redirection_flags & !ServerRedirectionFlags::FIELDS,
+            target_net_address,
+            load_balance_info,
+            username,
+            domain,
+            password,
+            target_fqdn,
+            target_netbios_name,
+            tsv_url,
+            redirection_guid,
+            target_certificate,
+            target_net_addresses,
+        })
+    }
+
+    fn to_buffer(&self, mut stream: impl io::Write) -> Result<(), Self::Error> {
+        let length = u16::try_from(self.buffer_length())
+            .map_err(|_| RdpError::InvalidPdu(String::from("server redirection packet too long")))?;
+
+        stream.write_u16::<LittleEndian>(BasicSecurityHeaderFlags::REDIRECTION_PKT.bits())?;
+        stream.write_u16::<LittleEndian>(length)?;
+        stream.write_u32::<LittleEndian>(self.session_id)?;
+        stream.write_u32::<LittleEndian>(self.redirection_flags().bits())?;
+
+        let mut field = |value: Option<&[u8]>| -> io::Result<()> {
+            if let Some(value) = value {
+                stream.write_u32::<LittleEndian>(value.len() as u32)?;
+                stream.write_all(value)?;
+            }
+
+            Ok(())
+        };
+
+        field(self.target_net_address.as_deref().map(encode_unicode).as_deref())?;
+        field(self.load_balance_info.as_deref())?;
+        field(self.username.as_deref().map(encode_unicode).as_deref())?;
+        field(self.domain.as_deref().map(encode_unicode).as_deref())?;
+        field(self.password.as_deref())?;
+        field(self.target_fqdn.as_deref().map(encode_unicode).as_deref())?;
+        field(self.target_netbios_name.as_deref().map(encode_unicode).as_deref())?;
+        field(self.tsv_url.as_deref())?;
+        field(self.redirection_guid.as_deref())?;
+        field(self.target_certificate.as_deref())?;
+        field(
+            self.target_net_addresses
+                .as_deref()
+                .map(encode_net_addresses)
+                .as_deref(),
+        )?;
+
+        Ok(())
+    }
+
+    fn buffer_length(&self) -> usize {
+        let unicode = |value: &Option<String>| value.as_deref().map_or(0, |v| FIELD_LENGTH_SIZE + unicode_size(v));
+        let bytes = |value: &Option<Vec<u8>>| value.as_ref().map_or(0, |v| FIELD_LENGTH_SIZE + v.len());
+
+        HEADER_SIZE
+            + unicode(&self.target_net_address)
+            + bytes(&self.load_balance_info)
+            + unicode(&self.username)
+            + unicode(&self.domain)
+            + bytes(&self.password)
+            + unicode(&self.target_fqdn)
+            + unicode(&self.target_netbios_name)
+            + bytes(&self.tsv_url)
+            + bytes(&self.redirection_guid)
+            + bytes(&self.target_certificate)
+            + self.target_net_addresses.as_ref().map_or(0, |addresses| {
+                FIELD_LENGTH_SIZE
+                    + ADDRESS_COUNT_FIELD_SIZE
+                    + addresses
+                        .iter()
+                        .map(|a| FIELD_LENGTH_SIZE + unicode_size(a))
+                        .sum::<usize>()
+            })
+    }
+}
+
+/// Null-terminated UTF-16 string
+fn encode_unicode(value: &str) -> Vec<u8> {
+    let mut encoded = utils::to_utf16_bytes(value);
+    encoded.extend_from_slice(&[0, 0]);
+    encoded
+}
+
+fn unicode_size(value: &str) -> usize {
+    (value.encode_utf16().count() + 1) * 2
+}
+
+fn decode_unicode(value: &[u8]) -> String {
+    utils::from_utf16_bytes(value).trim_end_matches('\0').to_owned()
+}
+
+/// 2.2.13.1.1 Target Net Addresses (TARGET_NET_ADDRESSES)
+fn encode_net_addresses(addresses: &[String]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    encoded.extend_from_slice(&(addresses.len() as u32).to_le_bytes());
+
+    for address in addresses {
+        let address = encode_unicode(address);
+        encoded.extend_from_slice(&(address.len() as u32).to_le_bytes());
+        encoded.extend_from_slice(&address);
+    }
+
+    encoded
+}
+
+fn decode_net_addresses(mut value: &[u8]) -> Result<Vec<String>, RdpError> {
+    let count = value.read_u32::<LittleEndian>()?;
+
+    let mut addresses = Vec::new();
+    for _ in 0..count {
+        let length = value.read_u32::<LittleEndian>()? as usize;
+        if length > value.len() {
+            return Err(RdpError::NotEnoughBytes);
+        }
+
+        let (address, rest) = value.split_at(length);
+        addresses.push(decode_unicode(address));
+        value = rest;
+    }
+
+    Ok(addresses)
+}
+
+bitflags! {
+    /// Redirection flags (RedirFlags) of the [`ServerRedirectionPdu`]
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct ServerRedirectionFlags: u32 {
+        const TARGET_NET_ADDRESS = 0x0000_0001;
+        const LOAD_BALANCE_INFO = 0x0000_0002;
+        const USERNAME = 0x0000_0004;
+        const DOMAIN = 0x0000_0008;
+        const PASSWORD = 0x0000_0010;
+        const DONT_STORE_USERNAME = 0x0000_0020;
+        const SMARTCARD_LOGON = 0x0000_0040;
+        const NO_REDIRECT = 0x0000_0080;
+        const TARGET_FQDN = 0x0000_0100;
+        const TARGET_NETBIOS_NAME = 0x0000_0200;
+        const TARGET_NET_ADDRESSES = 0x0000_0800;
+        const CLIENT_TSV_URL = 0x0000_1000;
+        const SERVER_TSV_CAPABLE = 0x0000_2000;
+        const PASSWORD_IS_PK_ENCRYPTED = 0x0000_4000;
+        const REDIRECTION_GUID = 0x0000_8000;
+        const TARGET_CERTIFICATE = 0x0001_0000;
+
+        /// Flags telling which fields are present
+        const FIELDS = Self::TARGET_NET_ADDRESS.bits()
+            | Self::LOAD_BALANCE_INFO.bits()
+            | Self::USERNAME.bits()
+            | Self::DOMAIN.bits()
+            | Self::PASSWORD.bits()
+            | Self::TARGET_FQDN.bits()
+            | Self::TARGET_NETBIOS_NAME.bits()
+            | Self::TARGET_NET_ADDRESSES.bits()
+            | Self::CLIENT_TSV_URL.bits()
+            | Self::REDIRECTION_GUID.bits()
+            | Self::TARGET_CERTIFICATE.bits();
+    }
+}
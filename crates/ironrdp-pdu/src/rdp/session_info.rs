@@ -14,8 +14,8 @@ mod logon_extended;
 mod logon_info;
 
 pub use self::logon_extended::{
-    LogonErrorNotificationData, LogonErrorNotificationDataErrorCode, LogonErrorNotificationType, LogonErrorsInfo,
-    LogonExFlags, LogonInfoExtended, ServerAutoReconnect,
+    ClientAutoReconnect, LogonErrorNotificationData, LogonErrorNotificationDataErrorCode, LogonErrorNotificationType,
+    LogonErrorsInfo, LogonExFlags, LogonInfoExtended, ServerAutoReconnect, ENHANCED_SECURITY_CLIENT_RANDOM,
 };
 pub use self::logon_info::{LogonInfo, LogonInfoVersion1, LogonInfoVersion2};
 
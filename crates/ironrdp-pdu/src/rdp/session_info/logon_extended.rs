@@ -2,6 +2,7 @@ use std::io;
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use hmac::{Hmac, Mac as _};
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::{FromPrimitive, ToPrimitive};
 
@@ -17,8 +18,13 @@ const LOGON_INFO_FIELD_DATA_SIZE: usize = 4;
 const AUTO_RECONNECT_VERSION_1: u32 = 0x0000_0001;
 const AUTO_RECONNECT_PACKET_SIZE: usize = 28;
 const AUTO_RECONNECT_RANDOM_BITS_SIZE: usize = 16;
+const AUTO_RECONNECT_VERIFIER_SIZE: usize = 16;
+const AUTO_RECONNECT_COOKIE_SIZE: usize = 28;
 const LOGON_ERRORS_INFO_SIZE: usize = 8;
 
+/// Client random to use for the auto-reconnect security verifier when Enhanced RDP Security is in effect
+pub const ENHANCED_SECURITY_CLIENT_RANDOM: [u8; 32] = [0; 32];
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LogonInfoExtended {
     pub present_fields_flags: LogonExFlags,
@@ -86,12 +92,90 @@ impl PduParsing for LogonInfoExtended {
     }
 }
 
+/// ARC_SC_PRIVATE_PACKET
+///
+/// [Doc](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpbcgr/9f1a7ef5-ff4d-4f11-a5de-e0fefc6dc7b2)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ServerAutoReconnect {
     pub logon_id: u32,
     pub random_bits: [u8; AUTO_RECONNECT_RANDOM_BITS_SIZE],
 }
 
+impl ServerAutoReconnect {
+    /// Computes the security verifier expected from a client reconnecting with this auto-reconnect random.
+    ///
+    /// `client_random` is the client random of Standard RDP Security, or 32 zero bytes with Enhanced RDP Security.
+    pub fn security_verifier(&self, client_random: &[u8]) -> [u8; AUTO_RECONNECT_VERIFIER_SIZE] {
+        let mut hmac = Hmac::<md5::Md5>::new_from_slice(&self.random_bits).expect("HMAC accepts any key size");
+        hmac.update(client_random);
+        hmac.finalize().into_bytes().into()
+    }
+}
+
+/// ARC_CS_PRIVATE_PACKET, the auto-reconnect cookie sent in the Client Info PDU
+///
+/// [Doc](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpbcgr/cbe1ed0a-d320-4ea5-be5a-f2eb3e032e91)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientAutoReconnect {
+    pub logon_id: u32,
+    pub security_verifier: [u8; AUTO_RECONNECT_VERIFIER_SIZE],
+}
+
+impl ClientAutoReconnect {
+    /// Builds the cookie of a client reconnecting to the session of `server`.
+    pub fn new(server: &ServerAutoReconnect, client_random: &[u8]) -> Self {
+        Self {
+            logon_id: server.logon_id,
+            security_verifier: server.security_verifier(client_random),
+        }
+    }
+
+    /// Whether the cookie was built from the auto-reconnect random of `server`.
+    pub fn verify(&self, server: &ServerAutoReconnect, client_random: &[u8]) -> bool {
+        let expected = server.security_verifier(client_random);
+
+        // Constant time comparison
+        self.logon_id == server.logon_id
+            && expected
+                .iter()
+                .zip(self.security_verifier.iter())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    pub fn from_cookie(cookie: &[u8; AUTO_RECONNECT_COOKIE_SIZE]) -> Result<Self, SessionError> {
+        let mut stream = cookie.as_slice();
+
+        let length = stream.read_u32::<LittleEndian>()?;
+        if length != AUTO_RECONNECT_COOKIE_SIZE as u32 {
+            return Err(SessionError::InvalidAutoReconnectPacketSize);
+        }
+
+        let version = stream.read_u32::<LittleEndian>()?;
+        if version != AUTO_RECONNECT_VERSION_1 {
+            return Err(SessionError::InvalidAutoReconnectVersion);
+        }
+
+        let logon_id = stream.read_u32::<LittleEndian>()?;
+        let mut security_verifier = [0; AUTO_RECONNECT_VERIFIER_SIZE];
+        io::Read::read_exact(&mut stream, &mut security_verifier)?;
+
+        Ok(Self {
+            logon_id,
+            security_verifier,
+        })
+    }
+
+    pub fn to_cookie(&self) -> [u8; AUTO_RECONNECT_COOKIE_SIZE] {
+        let mut cookie = [0; AUTO_RECONNECT_COOKIE_SIZE];
+        cookie[..4].copy_from_slice(&(AUTO_RECONNECT_COOKIE_SIZE as u32).to_le_bytes());
+        cookie[4..8].copy_from_slice(&AUTO_RECONNECT_VERSION_1.to_le_bytes());
+        cookie[8..12].copy_from_slice(&self.logon_id.to_le_bytes());
+        cookie[12..].copy_from_slice(&self.security_verifier);
+        cookie
+    }
+}
+
 impl PduParsing for ServerAutoReconnect {
     type Error = SessionError;
 
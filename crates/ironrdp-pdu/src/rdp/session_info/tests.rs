@@ -383,3 +383,54 @@ fn from_buffer_parsing_with_invalid_logon_error_type_fails() {
         res => panic!("Expected InvalidLogonErrorType error, got: {res:?}"),
     };
 }
+
+#[test]
+fn security_verifier_is_hmac_md5_of_client_random() {
+    // RFC 2202, HMAC-MD5 test case 1
+    let server = ServerAutoReconnect {
+        logon_id: 1,
+        random_bits: [0x0b; 16],
+    };
+
+    assert_eq!(
+        server.security_verifier(b"Hi There"),
+        [0x92, 0x94, 0x72, 0x7a, 0x36, 0x38, 0xbb, 0x1c, 0x13, 0xf4, 0x8e, 0xf8, 0x15, 0x8b, 0xfc, 0x9d]
+    );
+}
+
+#[test]
+fn client_auto_reconnect_cookie_round_trip() {
+    let server = ServerAutoReconnect {
+        logon_id: 0x0102_0304,
+        random_bits: [0xa5; 16],
+    };
+    let client = ClientAutoReconnect::new(&server, &[0; 32]);
+
+    let cookie = client.to_cookie();
+    assert_eq!(cookie[..12], [28, 0, 0, 0, 1, 0, 0, 0, 4, 3, 2, 1]);
+
+    let decoded = ClientAutoReconnect::from_cookie(&cookie).unwrap();
+    assert_eq!(decoded, client);
+    assert!(decoded.verify(&server, &[0; 32]));
+}
+
+#[test]
+fn client_auto_reconnect_with_other_random_is_rejected() {
+    let server = ServerAutoReconnect {
+        logon_id: 7,
+        random_bits: [0xa5; 16],
+    };
+    let client = ClientAutoReconnect::new(&server, &[0; 32]);
+
+    let other_random = ServerAutoReconnect {
+        logon_id: 7,
+        random_bits: [0x5a; 16],
+    };
+    assert!(!client.verify(&other_random, &[0; 32]));
+
+    let other_logon = ServerAutoReconnect {
+        logon_id: 8,
+        random_bits: [0xa5; 16],
+    };
+    assert!(!client.verify(&other_logon, &[0; 32]));
+}
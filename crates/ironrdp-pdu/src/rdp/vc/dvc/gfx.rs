@@ -19,6 +19,8 @@ use thiserror::Error;
 
 use crate::PduParsing;
 
+pub const CHANNEL_NAME: &str = "Microsoft::Windows::RDS::Graphics";
+
 const RDP_GFX_HEADER_SIZE: usize = 8;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
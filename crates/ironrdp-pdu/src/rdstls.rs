@@ -0,0 +1,413 @@
+//! RDSTLS PDUs, exchanged over TLS when the PROTOCOL_RDSTLS security protocol is selected ([MS-RDPBCGR] 2.2.17)
+
+use core::fmt;
+
+use crate::cursor::{ReadCursor, WriteCursor};
+use crate::{Pdu, PduDecode, PduEncode, PduError, PduErrorExt as _, PduHint, PduResult};
+
+pub const RDSTLS_VERSION_1: u16 = 0x0001;
+
+const PDU_TYPE_CAPABILITIES: u16 = 0x0001;
+const PDU_TYPE_AUTHENTICATION_REQUEST: u16 = 0x0002;
+const PDU_TYPE_AUTHENTICATION_RESPONSE: u16 = 0x0004;
+
+const DATA_TYPE_CAPABILITIES: u16 = 0x0001;
+const DATA_TYPE_PASSWORD_CREDENTIALS: u16 = 0x0001;
+const DATA_TYPE_AUTO_RECONNECT_COOKIE: u16 = 0x0002;
+const DATA_TYPE_RESULT_CODE: u16 = 0x0001;
+
+/// Version, PDU type and data type
+const HEADER_SIZE: usize = 6;
+
+/// RDSTLS Capabilities PDU, sent by the server once the TLS handshake is done
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RdstlsCapabilities {
+    pub supported_versions: u16,
+}
+
+impl RdstlsCapabilities {
+    const NAME: &'static str = "RdstlsCapabilities";
+
+    const FIXED_PART_SIZE: usize = HEADER_SIZE + 2;
+}
+
+impl Default for RdstlsCapabilities {
+    fn default() -> Self {
+        Self {
+            supported_versions: RDSTLS_VERSION_1,
+        }
+    }
+}
+
+impl Pdu for RdstlsCapabilities {
+    const NAME: &'static str = Self::NAME;
+}
+
+impl PduEncode for RdstlsCapabilities {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        write_header(dst, PDU_TYPE_CAPABILITIES, DATA_TYPE_CAPABILITIES);
+        dst.write_u16(self.supported_versions);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+impl<'de> PduDecode<'de> for RdstlsCapabilities {
+    fn decode(src: &mut ReadCursor<'de>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        read_header(src, Self::NAME, PDU_TYPE_CAPABILITIES, &[DATA_TYPE_CAPABILITIES])?;
+        let supported_versions = src.read_u16();
+
+        Ok(Self { supported_versions })
+    }
+}
+
+/// RDSTLS Authentication Request PDU, sent by the client in response to the capabilities
+#[derive(Clone, PartialEq, Eq)]
+pub enum RdstlsAuthenticationRequest {
+    /// Credentials given to the client in a Server Redirection PDU ([MS-RDPBCGR] 2.2.17.2)
+    Password {
+        redirection_guid: Vec<u8>,
+        username: String,
+        domain: String,
+        /// Encrypted password cookie, opaque to the client
+        password: Vec<u8>,
+    },
+    /// Auto-reconnect cookie of a previous session ([MS-RDPBCGR] 2.2.17.3)
+    AutoReconnectCookie {
+        session_id: u32,
+        auto_reconnect_cookie: Vec<u8>,
+    },
+}
+
+impl fmt::Debug for RdstlsAuthenticationRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The password and auto-reconnect cookies are not printed
+        match self {
+            Self::Password { username, domain, .. } => f
+                .debug_struct("Password")
+                .field("username", username)
+                .field("domain", domain)
+                .finish_non_exhaustive(),
+            Self::AutoReconnectCookie { session_id, .. } => f
+                .debug_struct("AutoReconnectCookie")
+                .field("session_id", session_id)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl RdstlsAuthenticationRequest {
+    const NAME: &'static str = "RdstlsAuthenticationRequest";
+
+    const FIXED_PART_SIZE: usize = HEADER_SIZE;
+}
+
+impl Pdu for RdstlsAuthenticationRequest {
+    const NAME: &'static str = Self::NAME;
+}
+
+impl PduEncode for RdstlsAuthenticationRequest {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        match self {
+            Self::Password {
+                redirection_guid,
+                username,
+                domain,
+                password,
+            } => {
+                write_header(dst, PDU_TYPE_AUTHENTICATION_REQUEST, DATA_TYPE_PASSWORD_CREDENTIALS);
+                write_data(dst, "RedirectionGuidLength", redirection_guid)?;
+                write_string(dst, "UserNameLength", username)?;
+                write_string(dst, "DomainLength", domain)?;
+                write_data(dst, "PasswordLength", password)?;
+            }
+            Self::AutoReconnectCookie {
+                session_id,
+                auto_reconnect_cookie,
+            } => {
+                write_header(dst, PDU_TYPE_AUTHENTICATION_REQUEST, DATA_TYPE_AUTO_RECONNECT_COOKIE);
+                dst.write_u32(*session_id);
+                write_data(dst, "AutoReconnectCookieLength", auto_reconnect_cookie)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        let variable_part = match self {
+            Self::Password {
+                redirection_guid,
+                username,
+                domain,
+                password,
+            } => {
+                2 + redirection_guid.len()
+                    + 2
+                    + crate::utf16::null_terminated_utf16_encoded_len(username)
+                    + 2
+                    + crate::utf16::null_terminated_utf16_encoded_len(domain)
+                    + 2
+                    + password.len()
+            }
+            Self::AutoReconnectCookie {
+                auto_reconnect_cookie, ..
+            } => 4 + 2 + auto_reconnect_cookie.len(),
+        };
+
+        Self::FIXED_PART_SIZE + variable_part
+    }
+}
+
+impl<'de> PduDecode<'de> for RdstlsAuthenticationRequest {
+    fn decode(src: &mut ReadCursor<'de>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let data_type = read_header(
+            src,
+            Self::NAME,
+            PDU_TYPE_AUTHENTICATION_REQUEST,
+            &[DATA_TYPE_PASSWORD_CREDENTIALS, DATA_TYPE_AUTO_RECONNECT_COOKIE],
+        )?;
+
+        if data_type == DATA_TYPE_PASSWORD_CREDENTIALS {
+            let redirection_guid = read_data(src)?.to_vec();
+            let username = read_string(src, "UserName")?;
+            let domain = read_string(src, "Domain")?;
+            let password = read_data(src)?.to_vec();
+
+            Ok(Self::Password {
+                redirection_guid,
+                username,
+                domain,
+                password,
+            })
+        } else {
+            ensure_size!(in: src, size: 4);
+            let session_id = src.read_u32();
+            let auto_reconnect_cookie = read_data(src)?.to_vec();
+
+            Ok(Self::AutoReconnectCookie {
+                session_id,
+                auto_reconnect_cookie,
+            })
+        }
+    }
+}
+
+/// RDSTLS Authentication Response PDU, sent by the server with the result of the authentication
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RdstlsAuthenticationResponse {
+    pub result_code: RdstlsResultCode,
+}
+
+impl RdstlsAuthenticationResponse {
+    const NAME: &'static str = "RdstlsAuthenticationResponse";
+
+    const FIXED_PART_SIZE: usize = HEADER_SIZE + 4;
+}
+
+impl Pdu for RdstlsAuthenticationResponse {
+    const NAME: &'static str = Self::NAME;
+}
+
+impl PduEncode for RdstlsAuthenticationResponse {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        write_header(dst, PDU_TYPE_AUTHENTICATION_RESPONSE, DATA_TYPE_RESULT_CODE);
+        dst.write_u32(self.result_code.0);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+impl<'de> PduDecode<'de> for RdstlsAuthenticationResponse {
+    fn decode(src: &mut ReadCursor<'de>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        read_header(
+            src,
+            Self::NAME,
+            PDU_TYPE_AUTHENTICATION_RESPONSE,
+            &[DATA_TYPE_RESULT_CODE],
+        )?;
+        let result_code = RdstlsResultCode(src.read_u32());
+
+        Ok(Self { result_code })
+    }
+}
+
+/// Result of the RDSTLS authentication
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RdstlsResultCode(pub u32);
+
+impl RdstlsResultCode {
+    pub const SUCCESS: Self = Self(0x0000_0000);
+    pub const ACCESS_DENIED: Self = Self(0x0000_0005);
+    pub const LOGON_FAILURE: Self = Self(0x0000_052E);
+    pub const INVALID_LOGON_HOURS: Self = Self(0x0000_0530);
+    pub const PASSWORD_EXPIRED: Self = Self(0x0000_0532);
+    pub const ACCOUNT_DISABLED: Self = Self(0x0000_0533);
+    pub const PASSWORD_MUST_CHANGE: Self = Self(0x0000_0773);
+    pub const ACCOUNT_LOCKED_OUT: Self = Self(0x0000_0775);
+}
+
+impl fmt::Display for RdstlsResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match *self {
+            Self::SUCCESS => "success",
+            Self::ACCESS_DENIED => "access denied",
+            Self::LOGON_FAILURE => "logon failure",
+            Self::INVALID_LOGON_HOURS => "invalid logon hours",
+            Self::PASSWORD_EXPIRED => "password expired",
+            Self::ACCOUNT_DISABLED => "account disabled",
+            Self::PASSWORD_MUST_CHANGE => "password must change",
+            Self::ACCOUNT_LOCKED_OUT => "account locked out",
+            Self(code) => return write!(f, "unknown result code {code:#010X}"),
+        };
+
+        f.write_str(description)
+    }
+}
+
+/// Finds the size of the next RDSTLS PDU
+#[derive(Clone, Copy, Debug)]
+pub struct RdstlsHint;
+
+pub const RDSTLS_HINT: RdstlsHint = RdstlsHint;
+
+impl PduHint for RdstlsHint {
+    fn find_size(&self, bytes: &[u8]) -> PduResult<Option<usize>> {
+        if bytes.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let pdu_type = u16::from_le_bytes([bytes[2], bytes[3]]);
+        let data_type = u16::from_le_bytes([bytes[4], bytes[5]]);
+
+        // Offset and number of the length-prefixed fields following the fixed part
+        let (fixed_part_size, field_count) = match (pdu_type, data_type) {
+            (PDU_TYPE_CAPABILITIES, _) => (RdstlsCapabilities::FIXED_PART_SIZE, 0),
+            (PDU_TYPE_AUTHENTICATION_RESPONSE, _) => (RdstlsAuthenticationResponse::FIXED_PART_SIZE, 0),
+            (PDU_TYPE_AUTHENTICATION_REQUEST, DATA_TYPE_PASSWORD_CREDENTIALS) => (HEADER_SIZE, 4),
+            (PDU_TYPE_AUTHENTICATION_REQUEST, DATA_TYPE_AUTO_RECONNECT_COOKIE) => (HEADER_SIZE + 4, 1),
+            _ => {
+                return Err(PduError::invalid_message(
+                    "RdstlsHint",
+                    "PduType",
+                    "unknown RDSTLS PDU type",
+                ))
+            }
+        };
+
+        let mut size = fixed_part_size;
+
+        for _ in 0..field_count {
+            let Some(length) = bytes.get(size..size + 2) else {
+                return Ok(None);
+            };
+
+            size += 2 + usize::from(u16::from_le_bytes([length[0], length[1]]));
+        }
+
+        Ok(Some(size))
+    }
+}
+
+fn write_header(dst: &mut WriteCursor<'_>, pdu_type: u16, data_type: u16) {
+    dst.write_u16(RDSTLS_VERSION_1);
+    dst.write_u16(pdu_type);
+    dst.write_u16(data_type);
+}
+
+/// Reads the header, returning the data type
+fn read_header(
+    src: &mut ReadCursor<'_>,
+    context: &'static str,
+    expected_pdu_type: u16,
+    expected_data_types: &[u16],
+) -> PduResult<u16> {
+    let version = src.read_u16();
+    if version != RDSTLS_VERSION_1 {
+        return Err(PduError::invalid_message(
+            context,
+            "Version",
+            "unsupported RDSTLS version",
+        ));
+    }
+
+    let pdu_type = src.read_u16();
+    if pdu_type != expected_pdu_type {
+        return Err(PduError::invalid_message(context, "PduType", "unexpected PDU type"));
+    }
+
+    let data_type = src.read_u16();
+    if !expected_data_types.contains(&data_type) {
+        return Err(PduError::invalid_message(context, "DataType", "unexpected data type"));
+    }
+
+    Ok(data_type)
+}
+
+fn write_data(dst: &mut WriteCursor<'_>, field: &'static str, data: &[u8]) -> PduResult<()> {
+    dst.write_u16(cast_length!(field, data.len())?);
+    dst.write_slice(data);
+
+    Ok(())
+}
+
+fn read_data<'de>(src: &mut ReadCursor<'de>) -> PduResult<&'de [u8]> {
+    ensure_size!(in: src, size: 2);
+    let length = usize::from(src.read_u16());
+
+    ensure_size!(in: src, size: length);
+
+    Ok(src.read_slice(length))
+}
+
+/// Writes a null-terminated UTF-16 string, prefixed with its size in bytes
+fn write_string(dst: &mut WriteCursor<'_>, field: &'static str, value: &str) -> PduResult<()> {
+    dst.write_u16(cast_length!(
+        field,
+        crate::utf16::null_terminated_utf16_encoded_len(value)
+    )?);
+    value.encode_utf16().for_each(|c| dst.write_u16(c));
+    dst.write_u16(0); // null terminator
+
+    Ok(())
+}
+
+fn read_string(src: &mut ReadCursor<'_>, field: &'static str) -> PduResult<String> {
+    let data = read_data(src)?;
+
+    crate::utf16::read_utf16_string(data, None).map_err(|e| {
+        PduError::invalid_message("RdstlsAuthenticationRequest", field, "bad UTF-16 string").with_source(e)
+    })
+}
@@ -1,8 +1,11 @@
+pub mod pdu;
+pub mod server;
+
 use ironrdp_pdu::gcc::ChannelName;
 use ironrdp_pdu::PduResult;
 use ironrdp_svc::{impl_as_any, CompressionCondition, SvcClientProcessor, SvcMessage, SvcProcessor};
 
-/// We currently don't implement any of rdpsnd, however it's required
+/// We currently don't implement the client side of rdpsnd, however it's required
 /// for rdpdr to work: [\[MS-RDPEFS\] Appendix A<1>]
 ///
 /// [\[MS-RDPEFS\] Appendix A<1>]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/fd28bfd9-dae2-4a78-abe1-b4efa208b7aa#Appendix_A_1
@@ -0,0 +1,756 @@
+//! This module implements RDP audio output channel PDUs encode/decode logic as defined in
+//! [MS-RDPEA]: Remote Desktop Protocol: Audio Output Virtual Channel Extension
+
+use bitflags::bitflags;
+use ironrdp_pdu::cursor::{ReadCursor, WriteCursor};
+use ironrdp_pdu::{
+    cast_length, ensure_fixed_part_size, ensure_size, invalid_message_err, read_padding, write_padding, PduDecode,
+    PduEncode, PduResult,
+};
+
+const SNDC_CLOSE: u8 = 0x01;
+const SNDC_WAVE: u8 = 0x02;
+const SNDC_WAVECONFIRM: u8 = 0x05;
+const SNDC_TRAINING: u8 = 0x06;
+const SNDC_FORMATS: u8 = 0x07;
+const SNDC_QUALITYMODE: u8 = 0x0C;
+const SNDC_WAVE2: u8 = 0x0D;
+
+/// Represents `SNDPROLOG`, the header of all audio output PDUs
+struct PduHeader {
+    msg_type: u8,
+    body_size: u16,
+}
+
+impl PduHeader {
+    const NAME: &'static str = "SNDPROLOG";
+    const FIXED_PART_SIZE: usize = 1 /* msgType */ + 1 /* bPad */ + 2 /* BodySize */;
+
+    fn new(msg_type: u8, body_size: usize) -> PduResult<Self> {
+        Ok(Self {
+            msg_type,
+            body_size: cast_length!(Self::NAME, "BodySize", body_size)?,
+        })
+    }
+}
+
+impl PduEncode for PduHeader {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u8(self.msg_type);
+        write_padding!(dst, 1);
+        dst.write_u16(self.body_size);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+impl<'de> PduDecode<'de> for PduHeader {
+    fn decode(src: &mut ReadCursor<'de>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let msg_type = src.read_u8();
+        read_padding!(src, 1);
+        let body_size = src.read_u16();
+
+        Ok(Self { msg_type, body_size })
+    }
+}
+
+/// Represents the `wFormatTag` field of `AUDIO_FORMAT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WaveFormat(pub u16);
+
+impl WaveFormat {
+    pub const PCM: Self = Self(0x0001);
+    pub const ADPCM: Self = Self(0x0002);
+    pub const ALAW: Self = Self(0x0006);
+    pub const MULAW: Self = Self(0x0007);
+    pub const MPEGLAYER3: Self = Self(0x0055);
+    pub const AAC_MS: Self = Self(0xA106);
+}
+
+/// Represents `AUDIO_FORMAT`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub format: WaveFormat,
+    pub n_channels: u16,
+    pub n_samples_per_sec: u32,
+    pub n_avg_bytes_per_sec: u32,
+    pub n_block_align: u16,
+    pub bits_per_sample: u16,
+    /// Extra format-specific data
+    pub data: Option<Vec<u8>>,
+}
+
+impl AudioFormat {
+    const NAME: &'static str = "AUDIO_FORMAT";
+    const FIXED_PART_SIZE: usize = 2 /* wFormatTag */ + 2 /* nChannels */ + 4 /* nSamplesPerSec */
+        + 4 /* nAvgBytesPerSec */ + 2 /* nBlockAlign */ + 2 /* wBitsPerSample */ + 2 /* cbSize */;
+
+    /// Builds an uncompressed PCM format.
+    pub fn pcm(n_channels: u16, n_samples_per_sec: u32, bits_per_sample: u16) -> Self {
+        let n_block_align = n_channels * bits_per_sample / 8;
+
+        Self {
+            format: WaveFormat::PCM,
+            n_channels,
+            n_samples_per_sec,
+            n_avg_bytes_per_sec: n_samples_per_sec * u32::from(n_block_align),
+            n_block_align,
+            bits_per_sample,
+            data: None,
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        self.data.as_ref().map_or(0, Vec::len)
+    }
+}
+
+impl PduEncode for AudioFormat {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_u16(self.format.0);
+        dst.write_u16(self.n_channels);
+        dst.write_u32(self.n_samples_per_sec);
+        dst.write_u32(self.n_avg_bytes_per_sec);
+        dst.write_u16(self.n_block_align);
+        dst.write_u16(self.bits_per_sample);
+        dst.write_u16(cast_length!("cbSize", self.data_len())?);
+        if let Some(data) = &self.data {
+            dst.write_slice(data);
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE + self.data_len()
+    }
+}
+
+impl<'de> PduDecode<'de> for AudioFormat {
+    fn decode(src: &mut ReadCursor<'de>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let format = WaveFormat(src.read_u16());
+        let n_channels = src.read_u16();
+        let n_samples_per_sec = src.read_u32();
+        let n_avg_bytes_per_sec = src.read_u32();
+        let n_block_align = src.read_u16();
+        let bits_per_sample = src.read_u16();
+        let cb_size = usize::from(src.read_u16());
+
+        let data = if cb_size > 0 {
+            ensure_size!(in: src, size: cb_size);
+            Some(src.read_slice(cb_size).to_vec())
+        } else {
+            None
+        };
+
+        Ok(Self {
+            format,
+            n_channels,
+            n_samples_per_sec,
+            n_avg_bytes_per_sec,
+            n_block_align,
+            bits_per_sample,
+            data,
+        })
+    }
+}
+
+/// Represents the `wVersion` field of the audio formats PDUs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(pub u16);
+
+impl Version {
+    pub const V2: Self = Self(0x02);
+    pub const V5: Self = Self(0x05);
+    pub const V6: Self = Self(0x06);
+    /// Wave2 PDUs are supported starting with this version
+    pub const V8: Self = Self(0x08);
+}
+
+bitflags! {
+    /// Represents the `dwFlags` field of the Client Audio Formats and Version PDU
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct AudioFormatFlags: u32 {
+        /// The client is capable of consuming audio data
+        const ALIVE = 0x0000_0001;
+        const VOLUME = 0x0000_0002;
+        const PITCH = 0x0000_0004;
+    }
+}
+
+const AUDIO_FORMATS_FIXED_PART_SIZE: usize = 4 /* dwFlags */ + 4 /* dwVolume */ + 4 /* dwPitch */
+    + 2 /* wDGramPort */ + 2 /* wNumberOfFormats */ + 1 /* cLastBlockConfirmed */ + 2 /* wVersion */ + 1 /* bPad */;
+
+/// Represents `SERVER_AUDIO_VERSION_AND_FORMATS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerAudioFormatPdu {
+    pub version: Version,
+    pub formats: Vec<AudioFormat>,
+}
+
+impl ServerAudioFormatPdu {
+    const NAME: &'static str = "SERVER_AUDIO_VERSION_AND_FORMATS";
+    const FIXED_PART_SIZE: usize = AUDIO_FORMATS_FIXED_PART_SIZE;
+
+    fn body_size(&self) -> usize {
+        Self::FIXED_PART_SIZE + self.formats.iter().map(PduEncode::size).sum::<usize>()
+    }
+
+    fn encode_body(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u32(0); // dwFlags
+        dst.write_u32(0); // dwVolume
+        dst.write_u32(0); // dwPitch
+        dst.write_u16(0); // wDGramPort
+        dst.write_u16(cast_length!("wNumberOfFormats", self.formats.len())?);
+        dst.write_u8(0); // cLastBlockConfirmed
+        dst.write_u16(self.version.0);
+        write_padding!(dst, 1);
+
+        for format in &self.formats {
+            format.encode(dst)?;
+        }
+
+        Ok(())
+    }
+
+    fn decode_body(src: &mut ReadCursor<'_>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        read_padding!(
+            src,
+            4 /* dwFlags */ + 4 /* dwVolume */ + 4 /* dwPitch */ + 2 /* wDGramPort */
+        );
+        let count = src.read_u16();
+        read_padding!(src, 1 /* cLastBlockConfirmed */);
+        let version = Version(src.read_u16());
+        read_padding!(src, 1);
+
+        let formats = (0..count).map(|_| AudioFormat::decode(src)).collect::<PduResult<_>>()?;
+
+        Ok(Self { version, formats })
+    }
+}
+
+/// Represents `CLIENT_AUDIO_VERSION_AND_FORMATS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientAudioFormatPdu {
+    pub version: Version,
+    pub flags: AudioFormatFlags,
+    /// Formats supported by both the client and the server, referenced by index in Wave PDUs
+    pub formats: Vec<AudioFormat>,
+    pub volume: u32,
+    pub pitch: u32,
+    pub dgram_port: u16,
+}
+
+impl ClientAudioFormatPdu {
+    const NAME: &'static str = "CLIENT_AUDIO_VERSION_AND_FORMATS";
+    const FIXED_PART_SIZE: usize = AUDIO_FORMATS_FIXED_PART_SIZE;
+
+    fn body_size(&self) -> usize {
+        Self::FIXED_PART_SIZE + self.formats.iter().map(PduEncode::size).sum::<usize>()
+    }
+
+    fn encode_body(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u32(self.flags.bits());
+        dst.write_u32(self.volume);
+        dst.write_u32(self.pitch);
+        dst.write_u16(self.dgram_port);
+        dst.write_u16(cast_length!("wNumberOfFormats", self.formats.len())?);
+        dst.write_u8(0); // cLastBlockConfirmed
+        dst.write_u16(self.version.0);
+        write_padding!(dst, 1);
+
+        for format in &self.formats {
+            format.encode(dst)?;
+        }
+
+        Ok(())
+    }
+
+    fn decode_body(src: &mut ReadCursor<'_>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let flags = AudioFormatFlags::from_bits_truncate(src.read_u32());
+        let volume = src.read_u32();
+        let pitch = src.read_u32();
+        let dgram_port = src.read_u16();
+        let count = src.read_u16();
+        read_padding!(src, 1 /* cLastBlockConfirmed */);
+        let version = Version(src.read_u16());
+        read_padding!(src, 1);
+
+        let formats = (0..count).map(|_| AudioFormat::decode(src)).collect::<PduResult<_>>()?;
+
+        Ok(Self {
+            version,
+            flags,
+            formats,
+            volume,
+            pitch,
+            dgram_port,
+        })
+    }
+}
+
+/// Represents the `wQualityMode` field of the Quality Mode PDU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityMode {
+    Dynamic,
+    Medium,
+    High,
+}
+
+/// Represents `AUDIO_FORMAT_QUALITY_MODE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityModePdu {
+    pub quality_mode: QualityMode,
+}
+
+impl QualityModePdu {
+    const NAME: &'static str = "AUDIO_FORMAT_QUALITY_MODE";
+    const FIXED_PART_SIZE: usize = 2 /* wQualityMode */ + 2 /* Reserved */;
+
+    fn encode_body(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u16(match self.quality_mode {
+            QualityMode::Dynamic => 0x0000,
+            QualityMode::Medium => 0x0001,
+            QualityMode::High => 0x0002,
+        });
+        write_padding!(dst, 2);
+
+        Ok(())
+    }
+
+    fn decode_body(src: &mut ReadCursor<'_>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let quality_mode = match src.read_u16() {
+            0x0000 => QualityMode::Dynamic,
+            0x0001 => QualityMode::Medium,
+            0x0002 => QualityMode::High,
+            _ => return Err(invalid_message_err!("wQualityMode", "unknown quality mode")),
+        };
+        read_padding!(src, 2);
+
+        Ok(Self { quality_mode })
+    }
+}
+
+/// Represents `SNDTRAINING`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrainingPdu {
+    pub timestamp: u16,
+    pub data: Vec<u8>,
+}
+
+impl TrainingPdu {
+    const NAME: &'static str = "SNDTRAINING";
+    const FIXED_PART_SIZE: usize = 2 /* wTimeStamp */ + 2 /* wPackSize */;
+
+    fn body_size(&self) -> usize {
+        Self::FIXED_PART_SIZE + self.data.len()
+    }
+
+    fn encode_body(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_size!(in: dst, size: self.body_size());
+
+        // The pack size is the size of the whole PDU, or 0 when there is no data
+        let pack_size = if self.data.is_empty() {
+            0
+        } else {
+            PduHeader::FIXED_PART_SIZE + self.body_size()
+        };
+
+        dst.write_u16(self.timestamp);
+        dst.write_u16(cast_length!("wPackSize", pack_size)?);
+        dst.write_slice(&self.data);
+
+        Ok(())
+    }
+
+    fn decode_body(src: &mut ReadCursor<'_>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let timestamp = src.read_u16();
+        let _pack_size = src.read_u16();
+        let data = src.remaining().to_vec();
+
+        Ok(Self { timestamp, data })
+    }
+}
+
+/// Represents `SNDTRAININGCONFIRM`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrainingConfirmPdu {
+    pub timestamp: u16,
+    pub pack_size: u16,
+}
+
+impl TrainingConfirmPdu {
+    const NAME: &'static str = "SNDTRAININGCONFIRM";
+    const FIXED_PART_SIZE: usize = 2 /* wTimeStamp */ + 2 /* wPackSize */;
+
+    fn encode_body(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u16(self.timestamp);
+        dst.write_u16(self.pack_size);
+
+        Ok(())
+    }
+
+    fn decode_body(src: &mut ReadCursor<'_>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let timestamp = src.read_u16();
+        let pack_size = src.read_u16();
+
+        Ok(Self { timestamp, pack_size })
+    }
+}
+
+/// Represents `SNDWAVINFO` followed by `SNDWAV`
+///
+/// The first four bytes of the audio data are carried by the Wave Info PDU, and the rest by the
+/// Wave PDU. Both are encoded back to back, but must be sent as separate messages: the Wave Info PDU
+/// is the first [`WavePdu::INFO_SIZE`] bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WavePdu {
+    pub timestamp: u16,
+    /// Index of the format in the client formats list
+    pub format_no: u16,
+    pub block_no: u8,
+    pub data: Vec<u8>,
+}
+
+impl WavePdu {
+    const NAME: &'static str = "SNDWAVINFO";
+    const FIXED_PART_SIZE: usize = 2 /* wTimeStamp */ + 2 /* wFormatNo */ + 1 /* cBlockNo */ + 3 /* bPad */
+        + 4 /* Data */;
+
+    /// Size of the Wave Info PDU, including its header
+    pub const INFO_SIZE: usize = PduHeader::FIXED_PART_SIZE + Self::FIXED_PART_SIZE;
+
+    fn body_size(&self) -> usize {
+        // The Wave PDU replaces the first four bytes of data with padding
+        Self::FIXED_PART_SIZE + self.data.len()
+    }
+
+    fn info_body_size(&self) -> usize {
+        // Body of the Wave Info PDU, followed by the data remaining in the Wave PDU
+        Self::FIXED_PART_SIZE + self.data.len() - 4
+    }
+
+    fn encode_body(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        if self.data.len() < 4 {
+            return Err(invalid_message_err!("Data", "wave data is shorter than 4 bytes"));
+        }
+
+        ensure_size!(in: dst, size: self.body_size());
+
+        let (head, tail) = self.data.split_at(4);
+
+        dst.write_u16(self.timestamp);
+        dst.write_u16(self.format_no);
+        dst.write_u8(self.block_no);
+        write_padding!(dst, 3);
+        dst.write_slice(head);
+
+        write_padding!(dst, 4);
+        dst.write_slice(tail);
+
+        Ok(())
+    }
+
+    fn decode_body(src: &mut ReadCursor<'_>, info_body_size: usize) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let timestamp = src.read_u16();
+        let format_no = src.read_u16();
+        let block_no = src.read_u8();
+        read_padding!(src, 3);
+        let head = src.read_array::<4>();
+
+        let tail_len = info_body_size
+            .checked_sub(Self::FIXED_PART_SIZE)
+            .ok_or_else(|| invalid_message_err!("BodySize", "wave info body is too short"))?;
+        ensure_size!(in: src, size: 4 + tail_len);
+        read_padding!(src, 4);
+
+        let mut data = head.to_vec();
+        data.extend_from_slice(src.read_slice(tail_len));
+
+        Ok(Self {
+            timestamp,
+            format_no,
+            block_no,
+            data,
+        })
+    }
+}
+
+/// Represents `SNDWAVE2`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wave2Pdu {
+    pub timestamp: u16,
+    /// Index of the format in the client formats list
+    pub format_no: u16,
+    pub block_no: u8,
+    /// Timestamp of the audio data, in milliseconds
+    pub audio_timestamp: u32,
+    pub data: Vec<u8>,
+}
+
+impl Wave2Pdu {
+    const NAME: &'static str = "SNDWAVE2";
+    const FIXED_PART_SIZE: usize = 2 /* wTimeStamp */ + 2 /* wFormatNo */ + 1 /* cBlockNo */ + 3 /* bPad */
+        + 4 /* dwAudioTimeStamp */;
+
+    fn body_size(&self) -> usize {
+        Self::FIXED_PART_SIZE + self.data.len()
+    }
+
+    fn encode_body(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_size!(in: dst, size: self.body_size());
+
+        dst.write_u16(self.timestamp);
+        dst.write_u16(self.format_no);
+        dst.write_u8(self.block_no);
+        write_padding!(dst, 3);
+        dst.write_u32(self.audio_timestamp);
+        dst.write_slice(&self.data);
+
+        Ok(())
+    }
+
+    fn decode_body(src: &mut ReadCursor<'_>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let timestamp = src.read_u16();
+        let format_no = src.read_u16();
+        let block_no = src.read_u8();
+        read_padding!(src, 3);
+        let audio_timestamp = src.read_u32();
+        let data = src.remaining().to_vec();
+
+        Ok(Self {
+            timestamp,
+            format_no,
+            block_no,
+            audio_timestamp,
+            data,
+        })
+    }
+}
+
+/// Represents `SNDWAV_CONFIRM`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveConfirmPdu {
+    pub timestamp: u16,
+    pub block_no: u8,
+}
+
+impl WaveConfirmPdu {
+    const NAME: &'static str = "SNDWAV_CONFIRM";
+    const FIXED_PART_SIZE: usize = 2 /* wTimeStamp */ + 1 /* cConfirmedBlockNo */ + 1 /* bPad */;
+
+    fn encode_body(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u16(self.timestamp);
+        dst.write_u8(self.block_no);
+        write_padding!(dst, 1);
+
+        Ok(())
+    }
+
+    fn decode_body(src: &mut ReadCursor<'_>) -> PduResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let timestamp = src.read_u16();
+        let block_no = src.read_u8();
+        read_padding!(src, 1);
+
+        Ok(Self { timestamp, block_no })
+    }
+}
+
+/// Audio output PDU sent by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerAudioOutputPdu {
+    AudioFormat(ServerAudioFormatPdu),
+    Training(TrainingPdu),
+    Wave(WavePdu),
+    Wave2(Wave2Pdu),
+    Close,
+}
+
+impl ServerAudioOutputPdu {
+    const NAME: &'static str = "ServerAudioOutputPdu";
+
+    fn header(&self) -> PduResult<PduHeader> {
+        match self {
+            Self::AudioFormat(pdu) => PduHeader::new(SNDC_FORMATS, pdu.body_size()),
+            Self::Training(pdu) => PduHeader::new(SNDC_TRAINING, pdu.body_size()),
+            Self::Wave(pdu) => PduHeader::new(SNDC_WAVE, pdu.info_body_size()),
+            Self::Wave2(pdu) => PduHeader::new(SNDC_WAVE2, pdu.body_size()),
+            Self::Close => PduHeader::new(SNDC_CLOSE, 0),
+        }
+    }
+}
+
+impl PduEncode for ServerAudioOutputPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        self.header()?.encode(dst)?;
+
+        match self {
+            Self::AudioFormat(pdu) => pdu.encode_body(dst),
+            Self::Training(pdu) => pdu.encode_body(dst),
+            Self::Wave(pdu) => pdu.encode_body(dst),
+            Self::Wave2(pdu) => pdu.encode_body(dst),
+            Self::Close => Ok(()),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::AudioFormat(_) => ServerAudioFormatPdu::NAME,
+            Self::Training(_) => TrainingPdu::NAME,
+            Self::Wave(_) => WavePdu::NAME,
+            Self::Wave2(_) => Wave2Pdu::NAME,
+            Self::Close => Self::NAME,
+        }
+    }
+
+    fn size(&self) -> usize {
+        PduHeader::FIXED_PART_SIZE
+            + match self {
+                Self::AudioFormat(pdu) => pdu.body_size(),
+                Self::Training(pdu) => pdu.body_size(),
+                Self::Wave(pdu) => pdu.body_size(),
+                Self::Wave2(pdu) => pdu.body_size(),
+                Self::Close => 0,
+            }
+    }
+}
+
+impl<'de> PduDecode<'de> for ServerAudioOutputPdu {
+    fn decode(src: &mut ReadCursor<'de>) -> PduResult<Self> {
+        let header = PduHeader::decode(src)?;
+        let body_size = usize::from(header.body_size);
+
+        let pdu = match header.msg_type {
+            SNDC_FORMATS => Self::AudioFormat(ServerAudioFormatPdu::decode_body(src)?),
+            SNDC_TRAINING => {
+                ensure_size!(in: src, size: body_size);
+                Self::Training(TrainingPdu::decode_body(&mut ReadCursor::new(
+                    src.read_slice(body_size),
+                ))?)
+            }
+            SNDC_WAVE => Self::Wave(WavePdu::decode_body(src, body_size)?),
+            SNDC_WAVE2 => {
+                ensure_size!(in: src, size: body_size);
+                Self::Wave2(Wave2Pdu::decode_body(&mut ReadCursor::new(src.read_slice(body_size)))?)
+            }
+            SNDC_CLOSE => Self::Close,
+            _ => return Err(invalid_message_err!("msgType", "unknown audio output PDU type")),
+        };
+
+        Ok(pdu)
+    }
+}
+
+/// Audio output PDU sent by the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientAudioOutputPdu {
+    AudioFormat(ClientAudioFormatPdu),
+    QualityMode(QualityModePdu),
+    TrainingConfirm(TrainingConfirmPdu),
+    WaveConfirm(WaveConfirmPdu),
+}
+
+impl ClientAudioOutputPdu {
+    fn header(&self) -> PduResult<PduHeader> {
+        match self {
+            Self::AudioFormat(pdu) => PduHeader::new(SNDC_FORMATS, pdu.body_size()),
+            Self::QualityMode(_) => PduHeader::new(SNDC_QUALITYMODE, QualityModePdu::FIXED_PART_SIZE),
+            Self::TrainingConfirm(_) => PduHeader::new(SNDC_TRAINING, TrainingConfirmPdu::FIXED_PART_SIZE),
+            Self::WaveConfirm(_) => PduHeader::new(SNDC_WAVECONFIRM, WaveConfirmPdu::FIXED_PART_SIZE),
+        }
+    }
+}
+
+impl PduEncode for ClientAudioOutputPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> PduResult<()> {
+        self.header()?.encode(dst)?;
+
+        match self {
+            Self::AudioFormat(pdu) => pdu.encode_body(dst),
+            Self::QualityMode(pdu) => pdu.encode_body(dst),
+            Self::TrainingConfirm(pdu) => pdu.encode_body(dst),
+            Self::WaveConfirm(pdu) => pdu.encode_body(dst),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::AudioFormat(_) => ClientAudioFormatPdu::NAME,
+            Self::QualityMode(_) => QualityModePdu::NAME,
+            Self::TrainingConfirm(_) => TrainingConfirmPdu::NAME,
+            Self::WaveConfirm(_) => WaveConfirmPdu::NAME,
+        }
+    }
+
+    fn size(&self) -> usize {
+        PduHeader::FIXED_PART_SIZE
+            + match self {
+                Self::AudioFormat(pdu) => pdu.body_size(),
+                Self::QualityMode(_) => QualityModePdu::FIXED_PART_SIZE,
+                Self::TrainingConfirm(_) => TrainingConfirmPdu::FIXED_PART_SIZE,
+                Self::WaveConfirm(_) => WaveConfirmPdu::FIXED_PART_SIZE,
+            }
+    }
+}
+
+impl<'de> PduDecode<'de> for ClientAudioOutputPdu {
+    fn decode(src: &mut ReadCursor<'de>) -> PduResult<Self> {
+        let header = PduHeader::decode(src)?;
+
+        let pdu = match header.msg_type {
+            SNDC_FORMATS => Self::AudioFormat(ClientAudioFormatPdu::decode_body(src)?),
+            SNDC_QUALITYMODE => Self::QualityMode(QualityModePdu::decode_body(src)?),
+            SNDC_TRAINING => Self::TrainingConfirm(TrainingConfirmPdu::decode_body(src)?),
+            SNDC_WAVECONFIRM => Self::WaveConfirm(WaveConfirmPdu::decode_body(src)?),
+            _ => return Err(invalid_message_err!("msgType", "unknown audio output PDU type")),
+        };
+
+        Ok(pdu)
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::VecDeque;
+
+use ironrdp_pdu::gcc::ChannelName;
+use ironrdp_pdu::{decode, PduResult};
+use ironrdp_svc::{
+    impl_as_any, CompressionCondition, SvcMessage, SvcProcessor, SvcProcessorMessages, SvcServerProcessor,
+};
+use tracing::{debug, error, trace, warn};
+
+use crate::pdu::{
+    AudioFormat, AudioFormatFlags, ClientAudioFormatPdu, ClientAudioOutputPdu, ServerAudioFormatPdu,
+    ServerAudioOutputPdu, TrainingPdu, Version, Wave2Pdu, WavePdu,
+};
+
+/// PDUs for sending to the client on the RDPSND channel.
+pub type RdpsndSvcMessages = SvcProcessorMessages<RdpsndServer>;
+
+/// Largest amount of audio data sent in a single Wave or Wave2 PDU
+const MAX_WAVE_DATA_SIZE: usize = 32_768;
+
+/// Audio output source of an RDPSND server
+pub trait RdpsndServerHandler: Send + core::fmt::Debug {
+    /// Returns the formats the server can produce, in order of preference.
+    fn get_formats(&self) -> &[AudioFormat];
+
+    /// Called once the client is ready to play audio, with the formats it supports.
+    ///
+    /// Returns the index of the client format to use, if any.
+    fn start(&mut self, client_format: &ClientAudioFormatPdu) -> Option<u16>;
+
+    /// Called when the channel is closed.
+    fn stop(&mut self);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RdpsndState {
+    Start,
+    WaitingForClientFormats,
+    WaitingForTrainingConfirm,
+    Ready,
+    Stop,
+}
+
+/// RDPSND static virtual channel server, sending audio output to the client
+#[derive(Debug)]
+pub struct RdpsndServer {
+    handler: Box<dyn RdpsndServerHandler>,
+    state: RdpsndState,
+    client_format: Option<ClientAudioFormatPdu>,
+    format_no: Option<u16>,
+    block_no: u8,
+    /// Blocks sent to the client and not yet confirmed, with their timestamp
+    blocks_in_flight: VecDeque<(u8, u16)>,
+}
+
+impl RdpsndServer {
+    pub const NAME: ChannelName = ChannelName::from_static(b"rdpsnd\0\0");
+
+    /// Version announced to the client, Wave2 PDUs are used when the client supports them
+    const VERSION: Version = Version::V8;
+
+    pub fn new(handler: Box<dyn RdpsndServerHandler>) -> Self {
+        Self {
+            handler,
+            state: RdpsndState::Start,
+            client_format: None,
+            format_no: None,
+            block_no: 0,
+            blocks_in_flight: VecDeque::new(),
+        }
+    }
+
+    /// Returns the version negotiated with the client.
+    pub fn version(&self) -> Option<Version> {
+        self.client_format
+            .as_ref()
+            .map(|client_format| client_format.version.min(Self::VERSION))
+    }
+
+    /// Returns the client format audio is sent with, once the client is ready.
+    pub fn format(&self) -> Option<&AudioFormat> {
+        let format_no = usize::from(self.format_no?);
+
+        self.client_format.as_ref()?.formats.get(format_no)
+    }
+
+    /// Returns the number of audio blocks not yet played by the client.
+    pub fn blocks_in_flight(&self) -> usize {
+        self.blocks_in_flight.len()
+    }
+
+    /// Sends audio data, in the format returned by [`RdpsndServer::format`].
+    ///
+    /// `timestamp` is the presentation time of the data, in milliseconds.
+    /// Large data is split over several Wave PDUs.
+    pub fn wave(&mut self, data: &[u8], timestamp: u32) -> PduResult<RdpsndSvcMessages> {
+        let Some(format_no) = self.format_no.filter(|_| self.state == RdpsndState::Ready) else {
+            trace!(state = ?self.state, "Audio output is not ready, dropping wave data");
+            return Ok(Vec::new().into());
+        };
+
+        let wave2 = self.version().is_some_and(|version| version >= Version::V8);
+        // Truncation is intended, the timestamps wrap around
+        #[allow(clippy::cast_possible_truncation)]
+        let wave_timestamp = timestamp as u16;
+
+        let mut messages = Vec::new();
+        // Wave PDUs carry at least 4 bytes of data
+        for chunk in data
+            .chunks(MAX_WAVE_DATA_SIZE)
+            .filter(|chunk| wave2 || chunk.len() >= 4)
+        {
+            let block_no = self.block_no;
+            self.block_no = self.block_no.wrapping_add(1);
+            self.blocks_in_flight.push_back((block_no, wave_timestamp));
+
+            if wave2 {
+                let pdu = ServerAudioOutputPdu::Wave2(Wave2Pdu {
+                    timestamp: wave_timestamp,
+                    format_no,
+                    block_no,
+                    audio_timestamp: timestamp,
+                    data: chunk.to_vec(),
+                });
+                messages.push(SvcMessage::from(pdu));
+            } else {
+                let pdu = ServerAudioOutputPdu::Wave(WavePdu {
+                    timestamp: wave_timestamp,
+                    format_no,
+                    block_no,
+                    data: chunk.to_vec(),
+                });
+
+                // The Wave Info PDU and the Wave PDU are sent as separate messages
+                let mut encoded = ironrdp_pdu::encode_vec(&pdu)?;
+                let wave = encoded.split_off(WavePdu::INFO_SIZE);
+                messages.push(SvcMessage::from(encoded));
+                messages.push(SvcMessage::from(wave));
+            }
+        }
+
+        Ok(messages.into())
+    }
+
+    /// Stops the audio output, returning the Close PDU to send on the channel.
+    pub fn close(&mut self) -> PduResult<RdpsndSvcMessages> {
+        if self.state == RdpsndState::Stop {
+            return Ok(Vec::new().into());
+        }
+
+        self.stop();
+
+        Ok(vec![SvcMessage::from(ServerAudioOutputPdu::Close)].into())
+    }
+
+    fn stop(&mut self) {
+        if self.state == RdpsndState::Ready {
+            self.handler.stop();
+        }
+
+        self.state = RdpsndState::Stop;
+        self.format_no = None;
+        self.blocks_in_flight.clear();
+    }
+
+    fn handle_client_format(&mut self, client_format: ClientAudioFormatPdu) -> PduResult<Vec<SvcMessage>> {
+        if self.state != RdpsndState::WaitingForClientFormats {
+            warn!(state = ?self.state, "Unexpected client audio formats");
+            return Ok(Vec::new());
+        }
+
+        if !client_format.flags.contains(AudioFormatFlags::ALIVE) || client_format.formats.is_empty() {
+            debug!(?client_format, "Client does not play audio");
+            self.state = RdpsndState::Stop;
+            return Ok(Vec::new());
+        }
+
+        self.client_format = Some(client_format);
+        self.state = RdpsndState::WaitingForTrainingConfirm;
+
+        let pdu = ServerAudioOutputPdu::Training(TrainingPdu {
+            timestamp: 0,
+            data: Vec::new(),
+        });
+
+        Ok(vec![SvcMessage::from(pdu)])
+    }
+
+    fn handle_training_confirm(&mut self) {
+        if self.state != RdpsndState::WaitingForTrainingConfirm {
+            warn!(state = ?self.state, "Unexpected training confirm");
+            return;
+        }
+
+        let client_format = self.client_format.as_ref().expect("client formats received");
+        let Some(format_no) = self.handler.start(client_format) else {
+            debug!("No audio format selected");
+            self.state = RdpsndState::Stop;
+            return;
+        };
+
+        if usize::from(format_no) >= client_format.formats.len() {
+            error!(format_no, "Invalid audio format selected");
+            self.handler.stop();
+            self.state = RdpsndState::Stop;
+            return;
+        }
+
+        debug!(format = ?client_format.formats[usize::from(format_no)], "Audio output ready");
+        self.format_no = Some(format_no);
+        self.state = RdpsndState::Ready;
+    }
+
+    fn handle_wave_confirm(&mut self, block_no: u8, timestamp: u16) {
+        let Some(position) = self.blocks_in_flight.iter().position(|&(block, _)| block == block_no) else {
+            warn!(block_no, "Unexpected wave confirm");
+            return;
+        };
+
+        // Blocks are played in order, so earlier blocks are implicitly confirmed
+        for (block_no, sent) in self.blocks_in_flight.drain(..=position) {
+            trace!(block_no, latency = timestamp.wrapping_sub(sent), "Audio block played");
+        }
+    }
+}
+
+impl_as_any!(RdpsndServer);
+
+impl SvcProcessor for RdpsndServer {
+    fn channel_name(&self) -> ChannelName {
+        Self::NAME
+    }
+
+    fn compression_condition(&self) -> CompressionCondition {
+        CompressionCondition::Never
+    }
+
+    fn start(&mut self) -> PduResult<Vec<SvcMessage>> {
+        if self.state != RdpsndState::Start {
+            return Ok(Vec::new());
+        }
+
+        let pdu = ServerAudioOutputPdu::AudioFormat(ServerAudioFormatPdu {
+            version: Self::VERSION,
+            formats: self.handler.get_formats().to_vec(),
+        });
+
+        self.state = RdpsndState::WaitingForClientFormats;
+
+        Ok(vec![SvcMessage::from(pdu)])
+    }
+
+    fn process(&mut self, payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
+        let pdu = decode::<ClientAudioOutputPdu>(payload)?;
+        trace!(?pdu, "Received audio output PDU");
+
+        match pdu {
+            ClientAudioOutputPdu::AudioFormat(client_format) => self.handle_client_format(client_format),
+            ClientAudioOutputPdu::QualityMode(quality_mode) => {
+                debug!(?quality_mode);
+                Ok(Vec::new())
+            }
+            ClientAudioOutputPdu::TrainingConfirm(_) => {
+                self.handle_training_confirm();
+                Ok(Vec::new())
+            }
+            ClientAudioOutputPdu::WaveConfirm(confirm) => {
+                self.handle_wave_confirm(confirm.block_no, confirm.timestamp);
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+impl SvcServerProcessor for RdpsndServer {}
@@ -1,14 +1,21 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
+use ironrdp_acceptor::{ConnectionRedirector, CredentialStore, CredentialValidator};
 use ironrdp_cliprdr::backend::CliprdrBackendFactory;
 use tokio_rustls::TlsAcceptor;
 
+use super::channel::{DynamicChannelFactory, StaticChannelFactory};
+use super::display::{DesktopSize, RdpServerDisplay, RdpServerDisplayFactory};
+use super::handler::{KeyboardEvent, MouseEvent, RdpServerInputHandler, RdpServerInputHandlerFactory};
+use super::server::*;
+use super::sound::RdpServerSoundFactory;
 use crate::{DisplayUpdate, RdpServerDisplayUpdates};
 
-use super::display::{DesktopSize, RdpServerDisplay};
-use super::handler::{KeyboardEvent, MouseEvent, RdpServerInputHandler};
-use super::server::*;
+/// Number of monitors advertised to clients, unless configured with [`RdpServerBuilder::with_max_monitors`]
+const DEFAULT_MAX_MONITORS: u32 = 16;
 
 pub struct WantsAddr {}
 pub struct WantsSecurity {
@@ -21,14 +28,23 @@ pub struct WantsHandler {
 pub struct WantsDisplay {
     addr: SocketAddr,
     security: RdpServerSecurity,
-    handler: Box<dyn RdpServerInputHandler>,
+    handler_factory: Box<dyn RdpServerInputHandlerFactory>,
 }
 pub struct BuilderDone {
     addr: SocketAddr,
     security: RdpServerSecurity,
-    handler: Box<dyn RdpServerInputHandler>,
-    display: Box<dyn RdpServerDisplay>,
+    handler_factory: Box<dyn RdpServerInputHandlerFactory>,
+    display_factory: Box<dyn RdpServerDisplayFactory>,
     cliprdr_factory: Option<Box<dyn CliprdrBackendFactory + Send>>,
+    sound_factory: Option<Box<dyn RdpServerSoundFactory>>,
+    static_channel_factories: Vec<Box<dyn StaticChannelFactory>>,
+    dynamic_channel_factories: Vec<Box<dyn DynamicChannelFactory>>,
+    credential_validator: Option<Arc<dyn CredentialValidator>>,
+    redirector: Option<Arc<dyn ConnectionRedirector>>,
+    idle_timeout: Option<Duration>,
+    max_session_duration: Option<Duration>,
+    auto_reconnect: Option<Duration>,
+    max_monitors: u32,
 }
 
 pub struct RdpServerBuilder<State> {
@@ -74,59 +90,100 @@ impl RdpServerBuilder<WantsSecurity> {
             },
         }
     }
-}
 
-impl RdpServerBuilder<WantsHandler> {
-    pub fn with_input_handler<H>(self, handler: H) -> RdpServerBuilder<WantsDisplay>
+    /// Requires Network Level Authentication (NLA) with CredSSP on top of TLS.
+    ///
+    /// `public_key` is the public key of the certificate used by `acceptor`.
+    /// Clients are authenticated against `credentials` before the connection sequence starts,
+    /// with Kerberos when `kerberos_config` is set and NTLM otherwise.
+    pub fn with_hybrid<C>(
+        self,
+        acceptor: impl Into<TlsAcceptor>,
+        public_key: Vec<u8>,
+        credentials: C,
+        kerberos_config: Option<KerberosConfig>,
+    ) -> RdpServerBuilder<WantsHandler>
     where
-        H: RdpServerInputHandler + 'static,
+        C: CredentialStore + 'static,
     {
         RdpServerBuilder {
-            state: WantsDisplay {
+            state: WantsHandler {
                 addr: self.state.addr,
-                security: self.state.security,
-                handler: Box::new(handler),
+                security: RdpServerSecurity::Hybrid {
+                    tls: acceptor.into(),
+                    public_key,
+                    credentials: Arc::new(credentials),
+                    kerberos_config,
+                },
             },
         }
     }
+}
 
-    pub fn with_no_input(self) -> RdpServerBuilder<WantsDisplay> {
+impl RdpServerBuilder<WantsHandler> {
+    /// Uses a clone of `handler` for each accepted connection.
+    pub fn with_input_handler<H>(self, handler: H) -> RdpServerBuilder<WantsDisplay>
+    where
+        H: RdpServerInputHandler + Clone + 'static,
+    {
+        self.with_input_factory(CloneFactory(handler))
+    }
+
+    /// Builds a new input handler with `factory` for each accepted connection.
+    pub fn with_input_factory<F>(self, factory: F) -> RdpServerBuilder<WantsDisplay>
+    where
+        F: RdpServerInputHandlerFactory + 'static,
+    {
         RdpServerBuilder {
             state: WantsDisplay {
                 addr: self.state.addr,
                 security: self.state.security,
-                handler: Box::new(NoopInputHandler),
+                handler_factory: Box::new(factory),
             },
         }
     }
+
+    pub fn with_no_input(self) -> RdpServerBuilder<WantsDisplay> {
+        self.with_input_factory(CloneFactory(NoopInputHandler))
+    }
 }
 
 impl RdpServerBuilder<WantsDisplay> {
+    /// Uses a clone of `display` for each accepted connection.
     pub fn with_display_handler<D>(self, display: D) -> RdpServerBuilder<BuilderDone>
     where
-        D: RdpServerDisplay + 'static,
+        D: RdpServerDisplay + Clone + 'static,
+    {
+        self.with_display_factory(CloneFactory(display))
+    }
+
+    /// Builds a new display handler with `factory` for each accepted connection.
+    pub fn with_display_factory<F>(self, factory: F) -> RdpServerBuilder<BuilderDone>
+    where
+        F: RdpServerDisplayFactory + 'static,
     {
         RdpServerBuilder {
             state: BuilderDone {
                 addr: self.state.addr,
                 security: self.state.security,
-                handler: self.state.handler,
-                display: Box::new(display),
+                handler_factory: self.state.handler_factory,
+                display_factory: Box::new(factory),
                 cliprdr_factory: None,
+                sound_factory: None,
+                static_channel_factories: Vec::new(),
+                dynamic_channel_factories: Vec::new(),
+                credential_validator: None,
+                redirector: None,
+                idle_timeout: None,
+                max_session_duration: None,
+                auto_reconnect: None,
+                max_monitors: DEFAULT_MAX_MONITORS,
             },
         }
     }
 
     pub fn with_no_display(self) -> RdpServerBuilder<BuilderDone> {
-        RdpServerBuilder {
-            state: BuilderDone {
-                addr: self.state.addr,
-                security: self.state.security,
-                handler: self.state.handler,
-                display: Box::new(NoopDisplay),
-                cliprdr_factory: None,
-            },
-        }
+        self.with_display_factory(CloneFactory(NoopDisplay))
     }
 }
 
@@ -136,19 +193,125 @@ impl RdpServerBuilder<BuilderDone> {
         self
     }
 
+    /// Sends audio output to clients supporting the RDPSND channel.
+    pub fn with_sound_factory(mut self, sound_factory: Option<Box<dyn RdpServerSoundFactory>>) -> Self {
+        self.state.sound_factory = sound_factory;
+        self
+    }
+
+    /// Adds a static virtual channel, built with `factory` for each accepted connection.
+    pub fn with_static_channel_factory<F>(mut self, factory: F) -> Self
+    where
+        F: StaticChannelFactory + 'static,
+    {
+        self.state.static_channel_factories.push(Box::new(factory));
+        self
+    }
+
+    /// Adds a dynamic virtual channel, built with `factory` for each accepted connection.
+    pub fn with_dynamic_channel_factory<F>(mut self, factory: F) -> Self
+    where
+        F: DynamicChannelFactory + 'static,
+    {
+        self.state.dynamic_channel_factories.push(Box::new(factory));
+        self
+    }
+
+    /// Checks the credentials sent by each client before the connection is accepted.
+    ///
+    /// A rejected client is sent the reason returned by `validator` and disconnected.
+    /// The identity of an accepted user is given to the handlers of its session.
+    pub fn with_credential_validator<V>(mut self, validator: V) -> Self
+    where
+        V: CredentialValidator + 'static,
+    {
+        self.state.credential_validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Decides for each client whether it is redirected to another server once its logon is accepted.
+    ///
+    /// A redirected client is sent the Server Redirection PDU returned by `redirector`, and reconnects
+    /// to the target server instead of starting a session.
+    pub fn with_redirector<R>(mut self, redirector: R) -> Self
+    where
+        R: ConnectionRedirector + 'static,
+    {
+        self.state.redirector = Some(Arc::new(redirector));
+        self
+    }
+
+    /// Disconnects sessions which received no input for `timeout`.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.state.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Disconnects sessions once they are connected for `duration`.
+    pub fn with_max_session_duration(mut self, duration: Duration) -> Self {
+        self.state.max_session_duration = Some(duration);
+        self
+    }
+
+    /// Sends an auto-reconnect cookie to clients, and keeps sessions for `grace_period` after their connection
+    /// dropped.
+    ///
+    /// A client reconnecting with a valid cookie is attached to its existing display and input handler,
+    /// without calling `connected` and `logged_on` again.
+    pub fn with_auto_reconnect(mut self, grace_period: Duration) -> Self {
+        self.state.auto_reconnect = Some(grace_period);
+        self
+    }
+
+    /// Allows clients to lay out the desktop on up to `count` monitors.
+    pub fn with_max_monitors(mut self, count: u32) -> Self {
+        self.state.max_monitors = count;
+        self
+    }
+
     pub fn build(self) -> RdpServer {
         RdpServer::new(
             RdpServerOptions {
                 addr: self.state.addr,
                 security: self.state.security,
+                credential_validator: self.state.credential_validator,
+                redirector: self.state.redirector,
+                idle_timeout: self.state.idle_timeout,
+                max_session_duration: self.state.max_session_duration,
+                auto_reconnect: self.state.auto_reconnect,
+                max_monitors: self.state.max_monitors,
             },
-            self.state.handler,
-            self.state.display,
+            self.state.handler_factory,
+            self.state.display_factory,
             self.state.cliprdr_factory,
+            self.state.sound_factory,
+            self.state.static_channel_factories,
+            self.state.dynamic_channel_factories,
         )
     }
 }
 
+struct CloneFactory<T>(T);
+
+impl<H> RdpServerInputHandlerFactory for CloneFactory<H>
+where
+    H: RdpServerInputHandler + Clone + 'static,
+{
+    fn build_input_handler(&self) -> Box<dyn RdpServerInputHandler> {
+        Box::new(self.0.clone())
+    }
+}
+
+impl<D> RdpServerDisplayFactory for CloneFactory<D>
+where
+    D: RdpServerDisplay + Clone + 'static,
+{
+    fn build_display(&self) -> Box<dyn RdpServerDisplay> {
+        Box::new(self.0.clone())
+    }
+}
+
+#[derive(Clone)]
 struct NoopInputHandler;
 
 impl RdpServerInputHandler for NoopInputHandler {
@@ -166,6 +329,7 @@ impl RdpServerDisplayUpdates for NoopDisplayUpdates {
     }
 }
 
+#[derive(Clone)]
 struct NoopDisplay;
 
 #[async_trait::async_trait]
@@ -2,22 +2,32 @@ use ironrdp_pdu::rdp::capability_sets::{self, GeneralExtraFlags};
 
 use crate::{DesktopSize, RdpServerOptions};
 
+pub(crate) const POINTER_CACHE_SIZE: u16 = 2048;
+
+/// Frames sent without being acknowledged, the client may ask for a different limit
+pub(crate) const MAX_UNACKNOWLEDGED_FRAMES: u32 = 2;
+
 pub(crate) fn capabilities(_opts: &RdpServerOptions, size: DesktopSize) -> Vec<capability_sets::CapabilitySet> {
     vec![
         capability_sets::CapabilitySet::General(general_capabilities()),
         capability_sets::CapabilitySet::Bitmap(bitmap_capabilities(&size)),
         capability_sets::CapabilitySet::Order(order_capabilities()),
         capability_sets::CapabilitySet::SurfaceCommands(surface_capabilities()),
+        capability_sets::CapabilitySet::BitmapCodecs(bitmap_codecs()),
         capability_sets::CapabilitySet::Pointer(pointer_capabilities()),
+        capability_sets::CapabilitySet::LargePointer(large_pointer_capabilities()),
         capability_sets::CapabilitySet::Input(input_capabilities()),
         capability_sets::CapabilitySet::VirtualChannel(virtual_channel_capabilities()),
         capability_sets::CapabilitySet::MultiFragmentUpdate(multifragment_update()),
+        capability_sets::CapabilitySet::FrameAcknowledge(frame_acknowledge()),
     ]
 }
 
 fn general_capabilities() -> capability_sets::General {
     capability_sets::General {
         extra_flags: GeneralExtraFlags::FASTPATH_OUTPUT_SUPPORTED,
+        refresh_rect_support: true,
+        suppress_output_support: true,
         ..Default::default()
     }
 }
@@ -47,10 +57,24 @@ fn surface_capabilities() -> capability_sets::SurfaceCommands {
     }
 }
 
+fn bitmap_codecs() -> capability_sets::BitmapCodecs {
+    capability_sets::BitmapCodecs(vec![capability_sets::Codec {
+        // The codec ID is assigned by the client
+        id: 0,
+        property: capability_sets::CodecProperty::RemoteFx(capability_sets::RemoteFxContainer::ServerContainer(4)),
+    }])
+}
+
 fn pointer_capabilities() -> capability_sets::Pointer {
     capability_sets::Pointer {
-        color_pointer_cache_size: 2048,
-        pointer_cache_size: 2048,
+        color_pointer_cache_size: POINTER_CACHE_SIZE,
+        pointer_cache_size: POINTER_CACHE_SIZE,
+    }
+}
+
+fn large_pointer_capabilities() -> capability_sets::LargePointer {
+    capability_sets::LargePointer {
+        flags: capability_sets::LargePointerSupportFlags::UP_TO_384X384_PIXELS,
     }
 }
 
@@ -84,3 +108,9 @@ fn multifragment_update() -> capability_sets::MultifragmentUpdate {
         max_request_size: 16_777_215,
     }
 }
+
+fn frame_acknowledge() -> capability_sets::FrameAcknowledge {
+    capability_sets::FrameAcknowledge {
+        max_unacknowledged_frame_count: MAX_UNACKNOWLEDGED_FRAMES,
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+pub use ironrdp_dvc::{DvcMessages, DvcProcessor, DvcServerProcessor};
+pub use ironrdp_svc::{SvcMessage, SvcProcessor, SvcServerProcessor};
+use tokio::sync::mpsc;
+
+/// Messages pushed by the application into a running session
+pub(crate) enum ChannelEvent {
+    /// Messages for the static channel built by the factory at this index
+    Static { index: usize, messages: Vec<SvcMessage> },
+    /// Messages for the dynamic channel built by the factory at this index
+    Dynamic { index: usize, messages: DvcMessages },
+}
+
+/// Sends messages on a custom static virtual channel of a running session
+#[derive(Debug, Clone)]
+pub struct SvcMessageSender {
+    index: usize,
+    events: mpsc::UnboundedSender<ChannelEvent>,
+}
+
+impl SvcMessageSender {
+    pub(crate) fn new(index: usize, events: mpsc::UnboundedSender<ChannelEvent>) -> Self {
+        Self { index, events }
+    }
+
+    /// Sends messages to the client, outside of a response to a client PDU.
+    ///
+    /// Messages are dropped if the client did not join the channel.
+    /// Fails once the session is closed.
+    pub fn send(&self, messages: Vec<SvcMessage>) -> Result<()> {
+        self.events
+            .send(ChannelEvent::Static {
+                index: self.index,
+                messages,
+            })
+            .map_err(|_| anyhow!("session is closed"))
+    }
+}
+
+/// Sends messages on a custom dynamic virtual channel of a running session
+#[derive(Debug, Clone)]
+pub struct DvcMessageSender {
+    index: usize,
+    events: mpsc::UnboundedSender<ChannelEvent>,
+}
+
+impl DvcMessageSender {
+    pub(crate) fn new(index: usize, events: mpsc::UnboundedSender<ChannelEvent>) -> Self {
+        Self { index, events }
+    }
+
+    /// Sends messages to the client, outside of a response to a client PDU.
+    ///
+    /// Messages are dropped if the channel is not opened.
+    /// Fails once the session is closed.
+    pub fn send(&self, messages: DvcMessages) -> Result<()> {
+        self.events
+            .send(ChannelEvent::Dynamic {
+                index: self.index,
+                messages,
+            })
+            .map_err(|_| anyhow!("session is closed"))
+    }
+}
+
+/// Static virtual channel factory for an RDP server
+///
+/// The RDP server calls this factory once per accepted connection, to build a channel dedicated to
+/// that connection. Channels built by different factories must be of different types.
+pub trait StaticChannelFactory: Send {
+    /// Builds a new channel for a freshly accepted connection.
+    ///
+    /// `sender` pushes messages on the channel while the session runs.
+    fn build_static_channel(&self, sender: SvcMessageSender) -> Box<dyn SvcServerProcessor>;
+}
+
+/// Dynamic virtual channel factory for an RDP server
+///
+/// The RDP server calls this factory once per accepted connection, to build a channel dedicated to
+/// that connection. The channel is opened once the client joined the DRDYNVC static channel.
+pub trait DynamicChannelFactory: Send {
+    /// Builds a new channel for a freshly accepted connection.
+    ///
+    /// `sender` pushes messages on the channel while the session runs.
+    fn build_dynamic_channel(&self, sender: DvcMessageSender) -> Box<dyn DvcServerProcessor>;
+}
@@ -1,8 +1,11 @@
-use anyhow::Result;
 use std::num::NonZeroU16;
 
+use anyhow::Result;
 pub use ironrdp_acceptor::DesktopSize;
+use ironrdp_acceptor::{ClientConnectionData, UserIdentity};
 pub use ironrdp_graphics::image_processing::PixelFormat;
+pub use ironrdp_pdu::dvc::display::{Monitor, MonitorLayoutPdu};
+pub use ironrdp_pdu::geometry::InclusiveRectangle;
 
 /// Display Update
 ///
@@ -12,6 +15,24 @@ pub use ironrdp_graphics::image_processing::PixelFormat;
 #[derive(Debug, Clone)]
 pub enum DisplayUpdate {
     Bitmap(BitmapUpdate),
+    /// The desktop was resized
+    ///
+    /// The server runs the deactivation-reactivation sequence to announce the new size to the client.
+    /// Bitmap updates following this one must fit the new desktop size.
+    Resize(DesktopSize),
+    /// Moves the pointer to the given position of the desktop
+    PointerPosition(PointerPosition),
+    /// Sets a new pointer shape, which is cached by the client for later [`DisplayUpdate::PointerCached`] updates
+    PointerBitmap(PointerBitmap),
+    /// Restores the default system pointer
+    PointerDefault,
+    /// Hides the pointer
+    PointerHidden,
+    /// Sets the pointer shape previously sent with the given [`PointerBitmap::id`]
+    ///
+    /// The server keeps track of the pointers cached by the client, within the negotiated pointer cache size.
+    /// Updates referring to a pointer evicted from the cache are ignored.
+    PointerCached(u32),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -49,6 +70,41 @@ impl std::fmt::Debug for BitmapUpdate {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerPosition {
+    pub x: u16,
+    pub y: u16,
+}
+
+/// Pointer Bitmap Update
+///
+/// Pointers of up to 96x96 pixels are supported by all clients, and up to 384x384 pixels
+/// by clients supporting large pointers.
+///
+#[derive(Clone)]
+pub struct PointerBitmap {
+    /// Application-defined identifier of the pointer shape
+    pub id: u32,
+    pub width: u16,
+    pub height: u16,
+    pub hot_x: u16,
+    pub hot_y: u16,
+    /// RGBA pixels, ordered from top to bottom
+    pub data: Vec<u8>,
+}
+
+impl std::fmt::Debug for PointerBitmap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PointerBitmap")
+            .field("id", &self.id)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("hot_x", &self.hot_x)
+            .field("hot_y", &self.hot_y)
+            .finish()
+    }
+}
+
 /// Display Updates receiver for an RDP server
 ///
 /// The RDP server will repeatedly call the `next_update` method to receive
@@ -56,7 +112,7 @@ impl std::fmt::Debug for BitmapUpdate {
 ///
 /// See [`RdpServerDisplay`] example.
 #[async_trait::async_trait]
-pub trait RdpServerDisplayUpdates {
+pub trait RdpServerDisplayUpdates: Send {
     /// # Cancel safety
     ///
     /// This method MUST be cancellation safe because it is used in a
@@ -101,12 +157,49 @@ pub trait RdpServerDisplayUpdates {
 /// }
 /// ```
 #[async_trait::async_trait]
-pub trait RdpServerDisplay {
+pub trait RdpServerDisplay: Send {
     /// This method should return the current size of the display.
     /// Currently, there is no way for the client to negotiate resolution,
     /// so the size returned by this method will be enforced.
+    /// Later size changes are announced with [`DisplayUpdate::Resize`].
     async fn size(&mut self) -> DesktopSize;
 
     /// Return a display updates receiver
     async fn updates(&mut self) -> Result<Box<dyn RdpServerDisplayUpdates>>;
+
+    /// Called with the settings sent by the client, such as its monitor layout, before display updates are requested.
+    fn connected(&mut self, _client: &ClientConnectionData) {}
+
+    /// Called with the identity of the authenticated user, before display updates are requested.
+    fn logged_on(&mut self, _identity: &UserIdentity) {}
+
+    /// Called when the client requests a new monitor layout over the Display Control channel,
+    /// for instance after its window was resized.
+    ///
+    /// The display may reconfigure itself and report its new size with [`DisplayUpdate::Resize`].
+    /// Requests are ignored by default.
+    async fn request_layout(&mut self, _layout: MonitorLayoutPdu) {}
+
+    /// Called when the client stops or resumes displaying the desktop, for instance when its
+    /// window is minimized and restored.
+    ///
+    /// While the output is suppressed, bitmap updates are merged and only sent once the client
+    /// resumes, so the display may stop producing them as well.
+    async fn suppress_output(&mut self, _suppressed: bool) {}
+
+    /// Called when the client asks for areas of the desktop to be repainted.
+    ///
+    /// The server repaints them with the content of the last bitmap updates if it keeps a copy of the
+    /// desktop, which it does for clients acknowledging frames or supporting output suppression.
+    /// The display may also send fresh updates for these areas, and should do so for other clients.
+    async fn refresh(&mut self, _areas: &[InclusiveRectangle]) {}
+}
+
+/// Display factory for an RDP server
+///
+/// The RDP server serves each client on its own task, and calls this factory once per accepted
+/// connection to build the [`RdpServerDisplay`] dedicated to that connection.
+pub trait RdpServerDisplayFactory: Send {
+    /// Builds a new display handler for a freshly accepted connection.
+    fn build_display(&self) -> Box<dyn RdpServerDisplay>;
 }
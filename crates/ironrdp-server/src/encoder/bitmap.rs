@@ -73,7 +73,12 @@ impl BitmapEncoder {
         Ok(cursor.pos())
     }
 
-    fn encode_slice(mut encoder: BitmapStreamEncoder, format: PixelFormat, src: &[u8], dst: &mut [u8]) -> usize {
+    pub(crate) fn encode_slice(
+        mut encoder: BitmapStreamEncoder,
+        format: PixelFormat,
+        src: &[u8],
+        dst: &mut [u8],
+    ) -> usize {
         match format {
             PixelFormat::ARgb32 | PixelFormat::XRgb32 => encoder.encode_bitmap::<ARgbChannels>(src, dst, true).unwrap(),
             PixelFormat::RgbA32 | PixelFormat::RgbX32 => encoder.encode_bitmap::<RgbAChannels>(src, dst, true).unwrap(),
@@ -82,7 +87,12 @@ impl BitmapEncoder {
         }
     }
 
-    fn encode_iter<'a, P>(mut encoder: BitmapStreamEncoder, format: PixelFormat, src: P, dst: &mut [u8]) -> usize
+    pub(crate) fn encode_iter<'a, P>(
+        mut encoder: BitmapStreamEncoder,
+        format: PixelFormat,
+        src: P,
+        dst: &mut [u8],
+    ) -> usize
     where
         P: Iterator<Item = &'a [u8]> + Clone,
     {
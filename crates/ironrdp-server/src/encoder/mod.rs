@@ -1,37 +1,177 @@
 pub(crate) mod bitmap;
+pub(crate) mod pointer;
+pub(crate) mod rfx;
 
+use std::borrow::Cow;
 use std::cmp;
 
+use ironrdp_pdu::codecs::rfx::EntropyAlgorithm;
 use ironrdp_pdu::cursor::WriteCursor;
 use ironrdp_pdu::fast_path::{EncryptionFlags, FastPathHeader, FastPathUpdatePdu, Fragmentation, UpdateCode};
 use ironrdp_pdu::geometry::ExclusiveRectangle;
+use ironrdp_pdu::pointer::{
+    CachedPointerAttribute, ColorPointerAttribute, LargePointerAttribute, Point16, PointerAttribute,
+    PointerPositionAttribute,
+};
 use ironrdp_pdu::rdp::capability_sets::CmdFlags;
-use ironrdp_pdu::surface_commands::{ExtendedBitmapDataPdu, SurfaceBitsPdu, SurfaceCommand};
+use ironrdp_pdu::surface_commands::{
+    ExtendedBitmapDataPdu, FrameAction, FrameMarkerPdu, SurfaceBitsPdu, SurfaceCommand,
+};
 use ironrdp_pdu::PduEncode;
 
-use crate::PixelOrder;
-
 use self::bitmap::BitmapEncoder;
+use self::pointer::{pointer_masks, PointerCache};
+use self::rfx::RfxEncoder;
 use super::BitmapUpdate;
+use crate::{DesktopSize, PixelOrder, PointerBitmap, PointerPosition};
 
 // this is the maximum amount of data (not including headers) we can send in a single TS_FP_UPDATE_PDU
 const MAX_FASTPATH_UPDATE_SIZE: usize = 16_374;
 
 const FASTPATH_HEADER_SIZE: usize = 6;
 
+const MAX_POINTER_SIZE: u16 = 96;
+
+const MAX_LARGE_POINTER_SIZE: u16 = 384;
+
+/// RemoteFX codec negotiated with the client
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RfxCodec {
+    /// Codec ID assigned by the client
+    pub(crate) id: u8,
+    pub(crate) entropy_algorithm: EntropyAlgorithm,
+}
+
+/// Pointer capabilities negotiated with the client
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PointerSettings {
+    pub(crate) cache_size: u16,
+    /// Whether the client supports 32 bpp pointers with an alpha channel
+    pub(crate) alpha: bool,
+    /// Whether the client supports pointers of up to 384x384 pixels
+    pub(crate) large: bool,
+}
+
 pub(crate) struct UpdateEncoder {
     buffer: Vec<u8>,
     bitmap: BitmapEncoder,
+    rfx: Option<(u8, RfxEncoder)>,
     surface_flags: CmdFlags,
+    pointer: PointerSettings,
+    pointer_cache: PointerCache,
 }
 
 impl UpdateEncoder {
-    pub(crate) fn new(surface_flags: CmdFlags) -> Self {
+    pub(crate) fn new(
+        surface_flags: CmdFlags,
+        rfx_codec: Option<RfxCodec>,
+        pointer: PointerSettings,
+        desktop_size: DesktopSize,
+    ) -> Self {
+        let rfx = rfx_codec
+            .filter(|_| surface_flags.contains(CmdFlags::SET_SURFACE_BITS))
+            .map(|codec| (codec.id, RfxEncoder::new(codec.entropy_algorithm, desktop_size)));
+
         Self {
             buffer: vec![0; 16384],
             bitmap: BitmapEncoder::new(),
+            rfx,
             surface_flags,
+            pointer,
+            pointer_cache: PointerCache::new(pointer.cache_size),
+        }
+    }
+
+    pub(crate) fn pointer_position(&mut self, position: PointerPosition) -> Option<UpdateFragmenter<'_>> {
+        let pdu = PointerPositionAttribute {
+            x: position.x,
+            y: position.y,
+        };
+
+        self.encode_pdu(UpdateCode::PositionPointer, &pdu)
+    }
+
+    pub(crate) fn pointer_default(&mut self) -> Option<UpdateFragmenter<'_>> {
+        Some(UpdateFragmenter::new(UpdateCode::DefaultPointer, &[]))
+    }
+
+    pub(crate) fn pointer_hidden(&mut self) -> Option<UpdateFragmenter<'_>> {
+        Some(UpdateFragmenter::new(UpdateCode::HiddenPointer, &[]))
+    }
+
+    pub(crate) fn pointer_cached(&mut self, id: u32) -> Option<UpdateFragmenter<'_>> {
+        let Some(cache_index) = self.pointer_cache.get(id) else {
+            warn!(id, "Pointer is not cached by the client");
+            return None;
+        };
+
+        self.encode_pdu(UpdateCode::CachedPointer, &CachedPointerAttribute { cache_index })
+    }
+
+    pub(crate) fn pointer_bitmap(&mut self, pointer: PointerBitmap) -> Option<UpdateFragmenter<'_>> {
+        let max_size = if self.pointer.large {
+            MAX_LARGE_POINTER_SIZE
+        } else {
+            MAX_POINTER_SIZE
+        };
+
+        if pointer.width == 0 || pointer.height == 0 || pointer.width > max_size || pointer.height > max_size {
+            warn!(?pointer, max_size, "Unsupported pointer size");
+            return None;
+        }
+
+        if pointer.data.len() != usize::from(pointer.width) * usize::from(pointer.height) * 4 {
+            warn!(?pointer, len = pointer.data.len(), "Invalid pointer data length");
+            return None;
         }
+
+        let large = pointer.width > MAX_POINTER_SIZE || pointer.height > MAX_POINTER_SIZE;
+        let xor_bpp = if self.pointer.alpha || large { 32 } else { 24 };
+
+        let cache_index = self.pointer_cache.insert(pointer.id);
+        let masks = pointer_masks(&pointer, xor_bpp);
+        let hot_spot = Point16 {
+            x: pointer.hot_x,
+            y: pointer.hot_y,
+        };
+
+        if large {
+            let pdu = LargePointerAttribute {
+                xor_bpp,
+                cache_index,
+                hot_spot,
+                width: pointer.width,
+                height: pointer.height,
+                xor_mask: &masks.xor_mask,
+                and_mask: &masks.and_mask,
+            };
+
+            return self.encode_pdu(UpdateCode::LargePointer, &pdu);
+        }
+
+        let color_pointer = ColorPointerAttribute {
+            cache_index,
+            hot_spot,
+            width: pointer.width,
+            height: pointer.height,
+            xor_mask: &masks.xor_mask,
+            and_mask: &masks.and_mask,
+        };
+
+        if xor_bpp == 32 {
+            self.encode_pdu(UpdateCode::NewPointer, &PointerAttribute { xor_bpp, color_pointer })
+        } else {
+            self.encode_pdu(UpdateCode::ColorPointer, &color_pointer)
+        }
+    }
+
+    pub(crate) fn frame_marker(&mut self, frame_action: FrameAction, frame_id: u32) -> Option<UpdateFragmenter<'_>> {
+        let cmd = SurfaceCommand::FrameMarker(FrameMarkerPdu {
+            frame_action,
+            frame_id: Some(frame_id),
+        });
+
+        self.encode_pdu(UpdateCode::SurfaceCommands, &cmd)
     }
 
     pub(crate) fn bitmap(&mut self, bitmap: BitmapUpdate) -> Option<UpdateFragmenter<'_>> {
@@ -56,17 +196,35 @@ impl UpdateEncoder {
             return Some(UpdateFragmenter::new(UpdateCode::Bitmap, &self.buffer[..len]));
         }
 
+        if let Some((codec_id, encoder)) = self.rfx.as_mut() {
+            let codec_id = *codec_id;
+            let data = match encoder.encode(&bitmap) {
+                Ok(data) => data,
+                Err(e) => {
+                    debug!("RemoteFX encode error: {:?}", e);
+                    return None;
+                }
+            };
+
+            return self.surface_bits(&bitmap, codec_id, &data);
+        }
+
         let data = match bitmap.order {
-            PixelOrder::BottomToTop => bitmap.data,
+            PixelOrder::BottomToTop => Cow::Borrowed(bitmap.data.as_slice()),
             PixelOrder::TopToBottom => {
                 let row_len = usize::from(bitmap.width.get()) * usize::from(bitmap.format.bytes_per_pixel());
                 let mut data = Vec::with_capacity(bitmap.data.len());
                 for row in bitmap.data.chunks(row_len).rev() {
                     data.extend_from_slice(row);
                 }
-                data
+                Cow::Owned(data)
             }
         };
+
+        self.surface_bits(&bitmap, 0, &data)
+    }
+
+    fn surface_bits(&mut self, bitmap: &BitmapUpdate, codec_id: u8, data: &[u8]) -> Option<UpdateFragmenter<'_>> {
         let destination = ExclusiveRectangle {
             left: bitmap.left,
             top: bitmap.top,
@@ -77,18 +235,23 @@ impl UpdateEncoder {
             bpp: bitmap.format.bytes_per_pixel() * 8,
             width: bitmap.width.get(),
             height: bitmap.height.get(),
-            codec_id: 0,
+            codec_id,
             header: None,
-            data: &data,
+            data,
         };
         let pdu = SurfaceBitsPdu {
             destination,
             extended_bitmap_data,
         };
         let cmd = SurfaceCommand::SetSurfaceBits(pdu);
+
+        self.encode_pdu(UpdateCode::SurfaceCommands, &cmd)
+    }
+
+    fn encode_pdu(&mut self, code: UpdateCode, pdu: &impl PduEncode) -> Option<UpdateFragmenter<'_>> {
         let len = loop {
             let mut cursor = WriteCursor::new(self.buffer.as_mut_slice());
-            match cmd.encode(&mut cursor) {
+            match pdu.encode(&mut cursor) {
                 Err(e) => match e.kind() {
                     ironrdp_pdu::PduErrorKind::NotEnoughBytes { .. } => {
                         self.buffer.resize(self.buffer.len() * 2, 0);
@@ -96,14 +259,15 @@ impl UpdateEncoder {
                     }
 
                     _ => {
-                        debug!("bitmap encode error: {:?}", e);
+                        debug!("{} encode error: {:?}", pdu.name(), e);
                         return None;
                     }
                 },
                 Ok(()) => break cursor.pos(),
             }
         };
-        Some(UpdateFragmenter::new(UpdateCode::SurfaceCommands, &self.buffer[..len]))
+
+        Some(UpdateFragmenter::new(code, &self.buffer[..len]))
     }
 }
 
@@ -131,9 +295,10 @@ impl<'a> UpdateFragmenter<'a> {
 
     fn encode_next(&mut self, dst: &mut [u8]) -> Option<(usize, usize)> {
         match self.data.len() {
-            0 => None,
+            // Updates without data, such as pointer visibility changes, are still sent once
+            0 if self.index > 0 => None,
 
-            1..=MAX_FASTPATH_UPDATE_SIZE => {
+            0..=MAX_FASTPATH_UPDATE_SIZE => {
                 let frag = if self.index > 0 {
                     Fragmentation::Last
                 } else {
@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+use crate::PointerBitmap;
+
+/// Pointer shapes cached by the client
+///
+/// Cache indices are reused from the least recently used pointer once the cache is full.
+#[doc(hidden)]
+pub struct PointerCache {
+    size: usize,
+    // Pointer IDs with their cache index, from the least to the most recently used
+    entries: VecDeque<(u32, u16)>,
+}
+
+impl PointerCache {
+    pub fn new(size: u16) -> Self {
+        let size = usize::from(size.max(1));
+
+        Self {
+            size,
+            entries: VecDeque::with_capacity(size),
+        }
+    }
+
+    /// Returns the cache index of the pointer, if it is still cached by the client.
+    pub fn get(&mut self, id: u32) -> Option<u16> {
+        let position = self.entries.iter().position(|&(cached, _)| cached == id)?;
+        let entry = self.entries.remove(position)?;
+        self.entries.push_back(entry);
+
+        Some(entry.1)
+    }
+
+    /// Returns the cache index to store a new pointer shape at.
+    pub fn insert(&mut self, id: u32) -> u16 {
+        let index = if let Some(position) = self.entries.iter().position(|&(cached, _)| cached == id) {
+            self.entries.remove(position).map(|(_, index)| index)
+        } else if self.entries.len() >= self.size {
+            self.entries.pop_front().map(|(_, index)| index)
+        } else {
+            None
+        };
+
+        let index = index.unwrap_or_else(|| u16::try_from(self.entries.len()).expect("cache size fits in u16"));
+        self.entries.push_back((id, index));
+
+        index
+    }
+}
+
+#[doc(hidden)]
+pub struct PointerMasks {
+    pub xor_mask: Vec<u8>,
+    pub and_mask: Vec<u8>,
+}
+
+/// Converts a RGBA pointer into bottom-up XOR and AND masks
+///
+/// 32 bpp XOR masks carry the alpha channel, so the AND mask is left empty. At 24 bpp, pixels which are
+/// mostly transparent are set in the AND mask over a black XOR pixel, which clients render as transparent.
+#[doc(hidden)]
+pub fn pointer_masks(pointer: &PointerBitmap, xor_bpp: u16) -> PointerMasks {
+    let width = usize::from(pointer.width);
+    let height = usize::from(pointer.height);
+    let xor_stride = stride(width * usize::from(xor_bpp));
+    let and_stride = stride(width);
+
+    let mut xor_mask = vec![0; xor_stride * height];
+    let mut and_mask = vec![0; and_stride * height];
+
+    let rows = pointer.data.chunks_exact(width * 4).rev();
+    let xor_rows = xor_mask.chunks_exact_mut(xor_stride);
+    let and_rows = and_mask.chunks_exact_mut(and_stride);
+
+    for ((row, xor_row), and_row) in rows.zip(xor_rows).zip(and_rows) {
+        for (x, pixel) in row.chunks_exact(4).enumerate() {
+            let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+
+            if xor_bpp == 32 {
+                xor_row[x * 4..][..4].copy_from_slice(&[b, g, r, a]);
+            } else if a < 0x80 {
+                and_row[x / 8] |= 0x80 >> (x % 8);
+            } else {
+                xor_row[x * 3..][..3].copy_from_slice(&[b, g, r]);
+            }
+        }
+    }
+
+    PointerMasks { xor_mask, and_mask }
+}
+
+// Mask scanlines are padded to 16 bits
+fn stride(bits: usize) -> usize {
+    bits.div_ceil(16) * 2
+}
@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+use std::cmp;
+
+use ironrdp_graphics::color_conversion::to_64x64_ycbcr_tile;
+use ironrdp_graphics::image_processing::ImageRegion;
+use ironrdp_graphics::{dwt, quantization, rlgr, subband_reconstruction};
+use ironrdp_pdu::codecs::rfx::{self, EntropyAlgorithm, Quant, RfxChannel, RfxChannelHeight, RfxChannelWidth};
+use ironrdp_pdu::geometry::InclusiveRectangle;
+use ironrdp_pdu::{custom_err, PduBufferParsing, PduResult};
+
+use crate::{BitmapUpdate, DesktopSize, PixelOrder};
+
+const TILE_SIZE: u16 = 64;
+
+// RLGR output buffer of a single tile component, largely enough for a 64x64 tile
+const COMPONENT_BUFFER_SIZE: usize = 16_384;
+
+// Default quantization values used by Windows and FreeRDP servers
+const QUANT: Quant = Quant {
+    ll3: 6,
+    lh3: 6,
+    hl3: 6,
+    hh3: 6,
+    lh2: 7,
+    hl2: 7,
+    hh2: 8,
+    lh1: 8,
+    hl1: 8,
+    hh1: 9,
+};
+
+pub(crate) struct RfxEncoder {
+    entropy_algorithm: EntropyAlgorithm,
+    desktop_size: DesktopSize,
+    frame_index: u32,
+    headers_sent: bool,
+    planes: [Vec<i16>; 3],
+    temp: Vec<i16>,
+}
+
+impl RfxEncoder {
+    pub(crate) fn new(entropy_algorithm: EntropyAlgorithm, desktop_size: DesktopSize) -> Self {
+        Self {
+            entropy_algorithm,
+            desktop_size,
+            frame_index: 0,
+            headers_sent: false,
+            planes: [vec![0; 4096], vec![0; 4096], vec![0; 4096]],
+            temp: vec![0; 4096],
+        }
+    }
+
+    /// Encodes the bitmap as a RemoteFX frame, preceded by the header messages for the first frame.
+    ///
+    /// Tiles and region are positioned relatively to the bitmap, which is the destination of the surface command.
+    pub(crate) fn encode(&mut self, bitmap: &BitmapUpdate) -> PduResult<Vec<u8>> {
+        let width = bitmap.width.get();
+        let height = bitmap.height.get();
+        let bytes_per_pixel = usize::from(bitmap.format.bytes_per_pixel());
+        let row_len = usize::from(width) * bytes_per_pixel;
+
+        let data = match bitmap.order {
+            PixelOrder::TopToBottom => Cow::Borrowed(bitmap.data.as_slice()),
+            PixelOrder::BottomToTop => Cow::Owned(bitmap.data.chunks(row_len).rev().flatten().copied().collect()),
+        };
+
+        let step = u16::try_from(row_len).map_err(|e| custom_err!("RFX bitmap step", e))?;
+
+        let mut tiles_data = Vec::new();
+
+        for tile_y in 0..height.div_ceil(TILE_SIZE) {
+            for tile_x in 0..width.div_ceil(TILE_SIZE) {
+                let left = tile_x * TILE_SIZE;
+                let top = tile_y * TILE_SIZE;
+
+                let region = ImageRegion {
+                    region: InclusiveRectangle {
+                        left,
+                        top,
+                        right: cmp::min(left + TILE_SIZE, width) - 1,
+                        bottom: cmp::min(top + TILE_SIZE, height) - 1,
+                    },
+                    step,
+                    pixel_format: bitmap.format,
+                    data: &data,
+                };
+
+                let [y, cb, cr] = &mut self.planes;
+                to_64x64_ycbcr_tile(&region, y, cb, cr).map_err(|e| custom_err!("RFX color conversion", e))?;
+
+                let mut components = [Vec::new(), Vec::new(), Vec::new()];
+                for (plane, component) in self.planes.iter_mut().zip(components.iter_mut()) {
+                    *component = encode_component(self.entropy_algorithm, plane, &mut self.temp)?;
+                }
+
+                tiles_data.push((tile_x, tile_y, components));
+            }
+        }
+
+        let tiles = tiles_data
+            .iter()
+            .map(|(x, y, [y_data, cb_data, cr_data])| rfx::Tile {
+                y_quant_index: 0,
+                cb_quant_index: 0,
+                cr_quant_index: 0,
+                x: *x,
+                y: *y,
+                y_data,
+                cb_data,
+                cr_data,
+            })
+            .collect();
+
+        let frame_begin = rfx::FrameBeginPdu {
+            index: self.frame_index,
+            number_of_regions: 1,
+        };
+        let region = rfx::RegionPdu {
+            rectangles: vec![rfx::RfxRectangle {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }],
+        };
+        let tile_set = rfx::TileSetPdu {
+            entropy_algorithm: self.entropy_algorithm,
+            quants: vec![QUANT],
+            tiles,
+        };
+
+        let mut messages: Vec<&dyn RfxMessage> = Vec::new();
+
+        let headers = if self.headers_sent { None } else { Some(self.headers()?) };
+
+        if let Some((sync, codec_versions, channels, context)) = &headers {
+            messages.extend([sync as &dyn RfxMessage, codec_versions, channels, context]);
+        }
+        messages.extend([&frame_begin as &dyn RfxMessage, &region, &tile_set, &rfx::FrameEndPdu]);
+
+        let mut output = vec![0; messages.iter().map(|message| message.size()).sum()];
+        let mut cursor = output.as_mut_slice();
+        for message in messages {
+            message.write(&mut cursor)?;
+        }
+
+        self.headers_sent = true;
+        self.frame_index = self.frame_index.wrapping_add(1);
+
+        Ok(output)
+    }
+
+    fn headers(&self) -> PduResult<(rfx::SyncPdu, rfx::CodecVersionsPdu, rfx::ChannelsPdu, rfx::ContextPdu)> {
+        let width = i16::try_from(self.desktop_size.width).map_err(|e| custom_err!("RFX channel width", e))?;
+        let height = i16::try_from(self.desktop_size.height).map_err(|e| custom_err!("RFX channel height", e))?;
+
+        let channel = RfxChannel {
+            width: RfxChannelWidth::new(width).map_err(|e| custom_err!("RFX channel width", e))?,
+            height: RfxChannelHeight::new(height).map_err(|e| custom_err!("RFX channel height", e))?,
+        };
+
+        Ok((
+            rfx::SyncPdu,
+            rfx::CodecVersionsPdu,
+            rfx::ChannelsPdu(vec![channel]),
+            rfx::ContextPdu {
+                flags: rfx::OperatingMode::empty(),
+                entropy_algorithm: self.entropy_algorithm,
+            },
+        ))
+    }
+}
+
+fn encode_component(entropy_algorithm: EntropyAlgorithm, plane: &mut [i16], temp: &mut [i16]) -> PduResult<Vec<u8>> {
+    dwt::encode(plane, temp);
+    quantization::encode(plane, &QUANT);
+    subband_reconstruction::encode(&mut plane[4032..]);
+
+    let mut data = vec![0; COMPONENT_BUFFER_SIZE];
+    let len = rlgr::encode(entropy_algorithm, plane, &mut data).map_err(|e| custom_err!("RLGR", e))?;
+    data.truncate(len);
+
+    Ok(data)
+}
+
+// Object-safe view over the RFX messages composing a frame
+trait RfxMessage {
+    fn size(&self) -> usize;
+    fn write(&self, buffer: &mut &mut [u8]) -> PduResult<()>;
+}
+
+impl<'a, T> RfxMessage for T
+where
+    T: PduBufferParsing<'a, Error = rfx::RfxError>,
+{
+    fn size(&self) -> usize {
+        self.buffer_length()
+    }
+
+    fn write(&self, buffer: &mut &mut [u8]) -> PduResult<()> {
+        self.to_buffer_consume(buffer).map_err(|e| custom_err!("RFX", e))
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+use std::num::NonZeroU16;
+use std::time::{Duration, Instant};
+
+use ironrdp_graphics::image_processing::PixelFormat;
+use ironrdp_pdu::geometry::{ExclusiveRectangle, InclusiveRectangle, Rectangle as _};
+
+use crate::{BitmapUpdate, DesktopSize, PixelOrder};
+
+/// Number of damaged regions kept apart, after which they are merged into their bounding box
+const MAX_DAMAGE_RECTS: usize = 16;
+
+/// Delay after which unacknowledged frames are considered lost, so that a client which stopped
+/// acknowledging frames doesn't stall the output forever
+const FRAME_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Frames sent to the client and not yet acknowledged
+#[doc(hidden)]
+pub struct FrameTracker {
+    max_in_flight: usize,
+    next_frame_id: u32,
+    /// Frame IDs, with the time they were sent at
+    in_flight: VecDeque<(u32, Instant)>,
+}
+
+impl FrameTracker {
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight: max_in_flight.max(1),
+            next_frame_id: 0,
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    /// Whether the client is behind, in which case frames are held back
+    pub fn is_full(&mut self) -> bool {
+        self.expire(Instant::now());
+        self.in_flight.len() >= self.max_in_flight
+    }
+
+    /// Returns when the frames held back are sent anyway, if the client is behind.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.in_flight.len() < self.max_in_flight {
+            return None;
+        }
+
+        self.in_flight.front().map(|&(_, sent)| sent + FRAME_ACK_TIMEOUT)
+    }
+
+    /// Stops waiting for acknowledgements, once the oldest frame is overdue.
+    pub fn expire(&mut self, now: Instant) {
+        let Some(&(frame_id, sent)) = self.in_flight.front() else {
+            return;
+        };
+
+        if now.saturating_duration_since(sent) >= FRAME_ACK_TIMEOUT {
+            warn!(
+                frame_id,
+                in_flight = self.in_flight.len(),
+                "Frames not acknowledged in time"
+            );
+            self.in_flight.clear();
+        }
+    }
+
+    /// Returns the ID of a new frame, waiting for an acknowledgement when `tracked`.
+    pub fn start_frame(&mut self, tracked: bool) -> u32 {
+        let frame_id = self.next_frame_id;
+        self.next_frame_id = self.next_frame_id.wrapping_add(1);
+
+        if tracked {
+            self.in_flight.push_back((frame_id, Instant::now()));
+        }
+
+        frame_id
+    }
+
+    pub fn acknowledge(&mut self, frame_id: u32) {
+        let Some(position) = self.in_flight.iter().position(|&(id, _)| id == frame_id) else {
+            debug!(frame_id, "Unexpected frame acknowledgement");
+            return;
+        };
+
+        // Frames are decoded in order, so earlier frames are implicitly acknowledged
+        self.in_flight.drain(..=position);
+    }
+
+    pub fn clear(&mut self) {
+        self.in_flight.clear();
+    }
+}
+
+/// Desktop regions updated since the last frame sent to the client
+///
+/// Bitmaps are drawn on a copy of the desktop, and overlapping or adjacent regions are merged,
+/// so that a client falling behind receives the latest content of larger regions instead of
+/// every intermediate update. Parts of the desktop never drawn by the display are sent black.
+#[doc(hidden)]
+pub struct Damage {
+    desktop_size: DesktopSize,
+    /// Format of the desktop copy, allocated with the first bitmap
+    format: Option<PixelFormat>,
+    /// Desktop copy, from top to bottom
+    framebuffer: Vec<u8>,
+    rects: Vec<ExclusiveRectangle>,
+}
+
+impl Damage {
+    pub fn new(desktop_size: DesktopSize) -> Self {
+        Self {
+            desktop_size,
+            format: None,
+            framebuffer: Vec::new(),
+            rects: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn push(&mut self, bitmap: &BitmapUpdate) {
+        if let Some(rect) = self.draw(bitmap) {
+            self.add(rect);
+        }
+    }
+
+    /// Updates the desktop copy with a bitmap already sent to the client.
+    pub fn record(&mut self, bitmap: &BitmapUpdate) {
+        self.draw(bitmap);
+    }
+
+    /// Forgets the damaged regions and the desktop content, which is no longer kept up to date.
+    pub fn clear(&mut self) {
+        self.format = None;
+        self.framebuffer = Vec::new();
+        self.rects.clear();
+    }
+
+    /// Marks an area to be sent again, if the desktop content is known.
+    pub fn invalidate(&mut self, area: &InclusiveRectangle) {
+        if self.format.is_none() {
+            return;
+        }
+
+        let DesktopSize { width, height } = self.desktop_size;
+        let rect = ExclusiveRectangle {
+            left: area.left.min(width),
+            top: area.top.min(height),
+            right: area.right.saturating_add(1).min(width),
+            bottom: area.bottom.saturating_add(1).min(height),
+        };
+
+        if rect.left < rect.right && rect.top < rect.bottom {
+            self.add(rect);
+        }
+    }
+
+    fn add(&mut self, mut rect: ExclusiveRectangle) {
+        // Merging may make the bounding box overlap other regions, so repeat until it's stable
+        while let Some(position) = self.rects.iter().position(|other| other.intersect(&rect).is_some()) {
+            rect = rect.union(&self.rects.swap_remove(position));
+        }
+
+        self.rects.push(rect);
+
+        if self.rects.len() > MAX_DAMAGE_RECTS {
+            let bounds = ExclusiveRectangle::union_all(&self.rects);
+            self.rects = vec![bounds];
+        }
+    }
+
+    /// Takes the damaged regions, with their latest content.
+    pub fn take(&mut self) -> Vec<BitmapUpdate> {
+        let Some(format) = self.format else {
+            return Vec::new();
+        };
+
+        let bytes_per_pixel = usize::from(format.bytes_per_pixel());
+        let stride = usize::from(self.desktop_size.width) * bytes_per_pixel;
+
+        self.rects
+            .drain(..)
+            .filter_map(|rect| {
+                let width = NonZeroU16::new(rect.width())?;
+                let height = NonZeroU16::new(rect.height())?;
+                let row_len = usize::from(width.get()) * bytes_per_pixel;
+                let offset = usize::from(rect.left) * bytes_per_pixel;
+
+                let mut data = Vec::with_capacity(row_len * usize::from(height.get()));
+                for row in self
+                    .framebuffer
+                    .chunks_exact(stride)
+                    .skip(usize::from(rect.top))
+                    .take(usize::from(height.get()))
+                {
+                    data.extend_from_slice(&row[offset..offset + row_len]);
+                }
+
+                Some(BitmapUpdate {
+                    top: rect.top,
+                    left: rect.left,
+                    width,
+                    height,
+                    format,
+                    order: PixelOrder::TopToBottom,
+                    data,
+                })
+            })
+            .collect()
+    }
+
+    /// Forgets the damaged regions, and the desktop content if it was resized.
+    pub fn reset(&mut self, desktop_size: DesktopSize) {
+        if self.desktop_size != desktop_size {
+            self.desktop_size = desktop_size;
+            self.format = None;
+            self.framebuffer = Vec::new();
+        }
+
+        self.rects.clear();
+    }
+
+    /// Draws the bitmap on the desktop copy, returning the updated region.
+    fn draw(&mut self, bitmap: &BitmapUpdate) -> Option<ExclusiveRectangle> {
+        let DesktopSize { width, height } = self.desktop_size;
+
+        let rect = ExclusiveRectangle {
+            left: bitmap.left.min(width),
+            top: bitmap.top.min(height),
+            right: bitmap.left.saturating_add(bitmap.width.get()).min(width),
+            bottom: bitmap.top.saturating_add(bitmap.height.get()).min(height),
+        };
+
+        if rect.width() == 0 || rect.height() == 0 {
+            warn!(?bitmap, desktop_size = ?self.desktop_size, "Bitmap is outside of the desktop");
+            return None;
+        }
+
+        let format = *self.format.get_or_insert(bitmap.format);
+        let bytes_per_pixel = usize::from(format.bytes_per_pixel());
+        let stride = usize::from(width) * bytes_per_pixel;
+        self.framebuffer.resize(stride * usize::from(height), 0);
+
+        let src_row_len = usize::from(bitmap.width.get()) * usize::from(bitmap.format.bytes_per_pixel());
+        let rows = bitmap.data.chunks_exact(src_row_len);
+        let rows: Box<dyn Iterator<Item = &[u8]>> = match bitmap.order {
+            PixelOrder::TopToBottom => Box::new(rows),
+            PixelOrder::BottomToTop => Box::new(rows.rev()),
+        };
+
+        let visible_len = usize::from(rect.width()) * usize::from(bitmap.format.bytes_per_pixel());
+        let offset = usize::from(rect.left) * bytes_per_pixel;
+        let dst_rows = self
+            .framebuffer
+            .chunks_exact_mut(stride)
+            .skip(usize::from(rect.top))
+            .take(usize::from(rect.height()));
+
+        for (src, dst) in rows.zip(dst_rows) {
+            let src = &src[..visible_len];
+            let dst = &mut dst[offset..offset + usize::from(rect.width()) * bytes_per_pixel];
+
+            if bitmap.format == format {
+                dst.copy_from_slice(src);
+                continue;
+            }
+
+            let src_pixels = src.chunks_exact(usize::from(bitmap.format.bytes_per_pixel()));
+            for (src, dst) in src_pixels.zip(dst.chunks_exact_mut(bytes_per_pixel)) {
+                let color = bitmap.format.read_color(src).expect("pixel has the format size");
+                format.write_color(color, dst).expect("pixel has the format size");
+            }
+        }
+
+        Some(rect)
+    }
+}
@@ -0,0 +1,383 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use ironrdp_dvc as dvc;
+use ironrdp_graphics::image_processing::PixelFormat;
+use ironrdp_graphics::rdp6::BitmapStreamEncoder;
+use ironrdp_graphics::zgfx;
+use ironrdp_pdu::dvc::gfx::{
+    CapabilitiesConfirmPdu, CapabilitySet, ClientPdu, Codec1Type, CreateSurfacePdu, DeleteSurfacePdu, EndFramePdu,
+    GraphicsPipelineError, MapSurfaceToOutputPdu, PixelFormat as SurfacePixelFormat, QueueDepth, ResetGraphicsPdu,
+    ServerPdu, StartFramePdu, Timestamp, WireToSurface1Pdu,
+};
+use ironrdp_pdu::gcc::{Monitor, MonitorFlags};
+use ironrdp_pdu::geometry::InclusiveRectangle;
+use ironrdp_pdu::{custom_err, PduParsing, PduResult};
+
+use crate::encoder::bitmap::BitmapEncoder;
+use crate::frame::{Damage, FrameTracker};
+use crate::{BitmapUpdate, DesktopSize, PixelOrder};
+
+/// The desktop is drawn on a single surface, mapped to the output at the origin
+const SURFACE_ID: u16 = 0;
+
+/// Frames sent without being acknowledged by the client, after which frames are held back
+const MAX_FRAMES_IN_FLIGHT: usize = 3;
+
+/// Graphics pipeline state, shared by the dynamic channel and the connection loop
+#[doc(hidden)]
+pub struct GfxState {
+    channel_id: Option<u32>,
+    desktop_size: DesktopSize,
+    /// Capability set confirmed to the client, once the surface is created
+    capabilities: Option<CapabilitySet>,
+    frames: FrameTracker,
+    /// The client does not acknowledge frames anymore
+    suspended: bool,
+    /// Updates waiting for the client to catch up
+    damage: Damage,
+}
+
+impl GfxState {
+    pub fn new(desktop_size: DesktopSize) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            channel_id: None,
+            desktop_size,
+            capabilities: None,
+            frames: FrameTracker::new(MAX_FRAMES_IN_FLIGHT),
+            suspended: false,
+            damage: Damage::new(desktop_size),
+        }))
+    }
+
+    /// Whether the client opened the channel, in which case bitmaps are sent as graphics pipeline frames
+    pub fn is_open(&self) -> bool {
+        self.channel_id.is_some()
+    }
+
+    pub fn queue(&mut self, bitmap: BitmapUpdate) {
+        self.damage.push(&bitmap);
+    }
+
+    /// Repaints an area of the surface with the next frame.
+    pub fn invalidate(&mut self, area: &InclusiveRectangle) {
+        self.damage.invalidate(area);
+    }
+
+    /// Takes the damaged regions, once the surface is created and unless too many frames
+    /// are waiting for an acknowledgement.
+    pub fn next_frame(&mut self) -> Option<GfxFrame> {
+        let channel_id = self.capabilities.as_ref().and(self.channel_id)?;
+
+        if self.damage.is_empty() || (!self.suspended && self.frames.is_full()) {
+            return None;
+        }
+
+        let frame_id = self.frames.start_frame(!self.suspended);
+
+        Some(GfxFrame {
+            channel_id,
+            frame_id,
+            bitmaps: self.damage.take(),
+        })
+    }
+
+    /// Returns when the frames held back are sent anyway, if the client is behind.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.frames.deadline()
+    }
+
+    /// Stops waiting for acknowledgements, once the oldest frame is overdue.
+    pub fn expire(&mut self, now: Instant) {
+        self.frames.expire(now);
+    }
+
+    /// Recreates the surface with the new desktop size.
+    ///
+    /// Returns the channel ID and the messages to send, if the surface was already created.
+    pub fn resize(&mut self, desktop_size: DesktopSize) -> PduResult<Option<(u32, dvc::DvcMessages)>> {
+        self.desktop_size = desktop_size;
+        self.damage.reset(desktop_size);
+        // Frames drawn on the previous surface are not waited for
+        self.frames.clear();
+
+        let Some(channel_id) = self.capabilities.as_ref().and(self.channel_id) else {
+            return Ok(None);
+        };
+
+        let mut messages = vec![encode_gfx(ServerPdu::DeleteSurface(DeleteSurfacePdu {
+            surface_id: SURFACE_ID,
+        }))?];
+        messages.extend(self.surface_setup()?);
+
+        Ok(Some((channel_id, messages)))
+    }
+
+    fn surface_setup(&self) -> PduResult<dvc::DvcMessages> {
+        let DesktopSize { width, height } = self.desktop_size;
+
+        let monitor = Monitor {
+            left: 0,
+            top: 0,
+            right: i32::from(width) - 1,
+            bottom: i32::from(height) - 1,
+            flags: MonitorFlags::PRIMARY,
+        };
+
+        Ok(vec![
+            encode_gfx(ServerPdu::ResetGraphics(ResetGraphicsPdu {
+                width: u32::from(width),
+                height: u32::from(height),
+                monitors: vec![monitor],
+            }))?,
+            encode_gfx(ServerPdu::CreateSurface(CreateSurfacePdu {
+                surface_id: SURFACE_ID,
+                width,
+                height,
+                pixel_format: SurfacePixelFormat::XRgb,
+            }))?,
+            encode_gfx(ServerPdu::MapSurfaceToOutput(MapSurfaceToOutputPdu {
+                surface_id: SURFACE_ID,
+                output_origin_x: 0,
+                output_origin_y: 0,
+            }))?,
+        ])
+    }
+
+    fn acknowledge(&mut self, frame_id: u32, queue_depth: QueueDepth) {
+        self.suspended = queue_depth == QueueDepth::Suspend;
+        if self.suspended {
+            self.frames.clear();
+        } else {
+            self.frames.acknowledge(frame_id);
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct GfxFrame {
+    pub channel_id: u32,
+    pub frame_id: u32,
+    pub bitmaps: Vec<BitmapUpdate>,
+}
+
+/// Graphics Pipeline Extension (RDPEGFX) dynamic channel
+#[doc(hidden)]
+pub struct GraphicsPipelineHandler {
+    pub state: Arc<Mutex<GfxState>>,
+}
+
+impl dvc::DvcProcessor for GraphicsPipelineHandler {
+    fn channel_name(&self) -> &str {
+        ironrdp_pdu::dvc::gfx::CHANNEL_NAME
+    }
+
+    fn start(&mut self, channel_id: u32) -> PduResult<dvc::DvcMessages> {
+        // The client starts by advertising its capabilities
+        self.state.lock().unwrap().channel_id = Some(channel_id);
+
+        Ok(vec![])
+    }
+
+    fn close(&mut self, _channel_id: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.channel_id = None;
+        state.capabilities = None;
+        let desktop_size = state.desktop_size;
+        state.damage.reset(desktop_size);
+    }
+
+    fn process(&mut self, _channel_id: u32, payload: &[u8]) -> PduResult<dvc::DvcMessages> {
+        let mut state = self.state.lock().unwrap();
+
+        let pdu = match ClientPdu::from_buffer(payload) {
+            Ok(pdu) => pdu,
+            Err(GraphicsPipelineError::UnexpectedClientPduType(pdu_type)) => {
+                debug!(?pdu_type, "Ignoring graphics pipeline PDU");
+                return Ok(vec![]);
+            }
+            Err(e) => return Err(custom_err!("RDPEGFX", e)),
+        };
+
+        match pdu {
+            ClientPdu::CapabilitiesAdvertise(advertise) => {
+                debug!(?advertise);
+
+                let Some(capabilities) = advertise
+                    .0
+                    .into_iter()
+                    .filter(|capabilities| capability_rank(capabilities) > 0)
+                    .max_by_key(capability_rank)
+                else {
+                    warn!("No graphics pipeline capability set advertised");
+                    return Ok(vec![]);
+                };
+
+                let mut messages = vec![encode_gfx(ServerPdu::CapabilitiesConfirm(CapabilitiesConfirmPdu(
+                    capabilities.clone(),
+                )))?];
+                messages.extend(state.surface_setup()?);
+
+                state.capabilities = Some(capabilities);
+                state.frames.clear();
+
+                Ok(messages)
+            }
+
+            ClientPdu::FrameAcknowledge(ack) => {
+                trace!(?ack);
+                state.acknowledge(ack.frame_id, ack.queue_depth);
+
+                Ok(vec![])
+            }
+        }
+    }
+}
+
+impl dvc::DvcServerProcessor for GraphicsPipelineHandler {}
+
+/// Encodes bitmaps on the graphics pipeline surface, using the planar codec or uncompressed data
+#[doc(hidden)]
+#[derive(Default)]
+pub struct GfxEncoder {
+    buffer: Vec<u8>,
+}
+
+impl GfxEncoder {
+    pub fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    pub fn frame(&mut self, frame: &GfxFrame) -> PduResult<dvc::DvcMessages> {
+        let GfxFrame { frame_id, bitmaps, .. } = frame;
+        let frame_id = *frame_id;
+        let mut messages = Vec::with_capacity(bitmaps.len() + 2);
+
+        messages.push(encode_gfx(ServerPdu::StartFrame(StartFramePdu {
+            timestamp: timestamp(),
+            frame_id,
+        }))?);
+
+        for bitmap in bitmaps {
+            messages.push(encode_gfx(ServerPdu::WireToSurface1(self.wire_to_surface(bitmap)))?);
+        }
+
+        messages.push(encode_gfx(ServerPdu::EndFrame(EndFramePdu { frame_id }))?);
+
+        Ok(messages)
+    }
+
+    fn wire_to_surface(&mut self, bitmap: &BitmapUpdate) -> WireToSurface1Pdu {
+        let width = usize::from(bitmap.width.get());
+        let height = usize::from(bitmap.height.get());
+        let row_len = width * usize::from(bitmap.format.bytes_per_pixel());
+        let raw_len = width * height * 4;
+
+        // RLE planes may be larger than the raw planes, with one control byte per run
+        self.buffer.resize(raw_len * 2 + 16, 0);
+
+        let encoder = BitmapStreamEncoder::new(width, height);
+        let len = match bitmap.order {
+            PixelOrder::TopToBottom => {
+                BitmapEncoder::encode_slice(encoder, bitmap.format, &bitmap.data, &mut self.buffer)
+            }
+            PixelOrder::BottomToTop => {
+                let bytes_per_pixel = usize::from(bitmap.format.bytes_per_pixel());
+                let pixels = bitmap
+                    .data
+                    .chunks(row_len)
+                    .rev()
+                    .flat_map(|row| row.chunks(bytes_per_pixel));
+
+                BitmapEncoder::encode_iter(encoder, bitmap.format, pixels, &mut self.buffer)
+            }
+        };
+
+        let (codec_id, bitmap_data) = if len < raw_len {
+            (Codec1Type::Planar, self.buffer[..len].to_vec())
+        } else {
+            (Codec1Type::Uncompressed, uncompressed(bitmap, row_len))
+        };
+
+        WireToSurface1Pdu {
+            surface_id: SURFACE_ID,
+            codec_id,
+            pixel_format: SurfacePixelFormat::XRgb,
+            destination_rectangle: InclusiveRectangle {
+                left: bitmap.left,
+                top: bitmap.top,
+                right: bitmap.left + bitmap.width.get() - 1,
+                bottom: bitmap.top + bitmap.height.get() - 1,
+            },
+            bitmap_data,
+        }
+    }
+}
+
+/// Converts the bitmap to top-down XRGB pixels, as expected by the surface
+fn uncompressed(bitmap: &BitmapUpdate, row_len: usize) -> Vec<u8> {
+    let rows = bitmap.data.chunks_exact(row_len);
+    let rows: Box<dyn Iterator<Item = &[u8]>> = match bitmap.order {
+        PixelOrder::TopToBottom => Box::new(rows),
+        PixelOrder::BottomToTop => Box::new(rows.rev()),
+    };
+
+    let mut data = Vec::with_capacity(bitmap.data.len());
+    for row in rows {
+        if bitmap.format == PixelFormat::BgrX32 || bitmap.format == PixelFormat::BgrA32 {
+            data.extend_from_slice(row);
+            continue;
+        }
+
+        for pixel in row.chunks_exact(usize::from(bitmap.format.bytes_per_pixel())) {
+            let color = bitmap.format.read_color(pixel).expect("pixel has the format size");
+            let mut converted = [0; 4];
+            PixelFormat::BgrX32
+                .write_color(color, &mut converted)
+                .expect("buffer has the format size");
+            data.extend_from_slice(&converted);
+        }
+    }
+
+    data
+}
+
+/// Wraps a graphics pipeline PDU into uncompressed ZGFX segments.
+fn encode_gfx(pdu: ServerPdu) -> PduResult<Box<dyn ironrdp_pdu::PduEncode + Send>> {
+    let mut buffer = Vec::with_capacity(pdu.buffer_length());
+    pdu.to_buffer(&mut buffer).map_err(|e| custom_err!("RDPEGFX", e))?;
+
+    let mut segments = Vec::with_capacity(buffer.len() + 8);
+    zgfx::wrap_uncompressed(&buffer, &mut segments).map_err(|e| custom_err!("ZGFX", e))?;
+
+    Ok(Box::new(segments))
+}
+
+/// Orders capability sets by preference, the most recent version being preferred
+fn capability_rank(capabilities: &CapabilitySet) -> u8 {
+    match capabilities {
+        CapabilitySet::V8 { .. } => 1,
+        CapabilitySet::V8_1 { .. } => 2,
+        CapabilitySet::V10 { .. } => 3,
+        CapabilitySet::V10_1 => 4,
+        CapabilitySet::V10_2 { .. } => 5,
+        CapabilitySet::V10_3 { .. } => 6,
+        CapabilitySet::V10_4 { .. } => 7,
+        CapabilitySet::V10_5 { .. } => 8,
+        CapabilitySet::V10_6 { .. } | CapabilitySet::V10_6Err { .. } => 9,
+        CapabilitySet::V10_7 { .. } => 10,
+        CapabilitySet::Unknown(_) => 0,
+    }
+}
+
+fn timestamp() -> Timestamp {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = elapsed.as_secs() % 86_400;
+
+    Timestamp {
+        milliseconds: u16::try_from(elapsed.subsec_millis()).unwrap(),
+        seconds: u8::try_from(seconds % 60).unwrap(),
+        minutes: u8::try_from(seconds / 60 % 60).unwrap(),
+        hours: u16::try_from(seconds / 3600).unwrap(),
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+pub use ironrdp_pdu::rdp::server_error_info::{ErrorInfo, ProtocolIndependentCode};
+use ironrdp_pdu::rdp::session_info::{ClientAutoReconnect, ServerAutoReconnect};
+use tokio::sync::{mpsc, watch};
+
+use crate::display::{RdpServerDisplay, RdpServerDisplayUpdates};
+use crate::frame::Damage;
+use crate::handler::RdpServerInputHandler;
+
+/// Identifier of a session, unique for the lifetime of a server
+pub type SessionId = u32;
+
+/// Active session, as listed by [`RdpServerHandle::sessions`]
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub id: SessionId,
+    /// Address of the client
+    pub peer: SocketAddr,
+    /// Name of the client computer
+    pub client_name: Option<String>,
+    /// Name of the authenticated user
+    pub username: Option<String>,
+    /// When the connection sequence completed
+    pub connected_at: SystemTime,
+    /// The connection dropped, and the session waits for the client to reconnect
+    pub awaiting_reconnect: bool,
+}
+
+/// Requests sent to a running session
+#[doc(hidden)]
+#[derive(Debug)]
+pub enum SessionControl {
+    Disconnect(ErrorInfo),
+}
+
+struct Session {
+    info: SessionInfo,
+    control: mpsc::UnboundedSender<SessionControl>,
+}
+
+/// Session kept after its connection dropped, until the client reconnects with its auto-reconnect cookie
+#[doc(hidden)]
+pub struct ParkedSession {
+    pub registration: SessionRegistration,
+    pub control: mpsc::UnboundedReceiver<SessionControl>,
+    /// Auto-reconnect random sent to the client
+    pub auto_reconnect: ServerAutoReconnect,
+    pub display: Box<dyn RdpServerDisplay>,
+    pub display_updates: Box<dyn RdpServerDisplayUpdates>,
+    pub handler: Arc<Mutex<Box<dyn RdpServerInputHandler>>>,
+    pub damage: Damage,
+}
+
+struct Shared {
+    stop: watch::Sender<bool>,
+    next_id: AtomicU32,
+    sessions: Mutex<HashMap<SessionId, Session>>,
+    parked: Mutex<HashMap<SessionId, ParkedSession>>,
+}
+
+/// Controls a running [`RdpServer`](crate::RdpServer)
+///
+/// The handle is cheap to clone, and may be used from any task or thread.
+#[derive(Clone)]
+pub struct RdpServerHandle {
+    shared: Arc<Shared>,
+}
+
+impl RdpServerHandle {
+    #[doc(hidden)]
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                stop: watch::channel(false).0,
+                next_id: AtomicU32::new(0),
+                sessions: Mutex::new(HashMap::new()),
+                parked: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Stops accepting connections, making [`RdpServer::run`](crate::RdpServer::run) return.
+    ///
+    /// Active sessions are not affected, see [`RdpServerHandle::disconnect_all`].
+    pub fn stop(&self) {
+        self.shared.stop.send_replace(true);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        *self.shared.stop.borrow()
+    }
+
+    /// Returns the sessions which completed the connection sequence.
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<_> = self
+            .shared
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|session| session.info.clone())
+            .collect();
+
+        sessions.sort_by_key(|info| info.id);
+        sessions
+    }
+
+    /// Disconnects a session, sending `reason` to the client.
+    ///
+    /// A session awaiting its client to reconnect is ended right away.
+    /// Returns `false` if there is no such session.
+    pub fn disconnect(&self, id: SessionId, reason: ErrorInfo) -> bool {
+        let parked = self.shared.parked.lock().unwrap().remove(&id);
+        if parked.is_some() {
+            return true;
+        }
+
+        self.shared
+            .sessions
+            .lock()
+            .unwrap()
+            .get(&id)
+            .is_some_and(|session| session.control.send(SessionControl::Disconnect(reason)).is_ok())
+    }
+
+    /// Disconnects all the active sessions, sending `reason` to the clients.
+    pub fn disconnect_all(&self, reason: ErrorInfo) {
+        let parked = mem::take(&mut *self.shared.parked.lock().unwrap());
+        drop(parked);
+
+        for session in self.shared.sessions.lock().unwrap().values() {
+            let _ = session.control.send(SessionControl::Disconnect(reason));
+        }
+    }
+
+    /// Resolves once [`RdpServerHandle::stop`] is called.
+    #[doc(hidden)]
+    pub async fn stopped(&self) {
+        let mut stop = self.shared.stop.subscribe();
+
+        // The sender is owned by `self`, so waiting can't fail
+        let _ = stop.wait_for(|stopped| *stopped).await;
+    }
+
+    /// Lists a new session until the returned registration is dropped.
+    #[doc(hidden)]
+    pub fn register(
+        &self,
+        info: impl FnOnce(SessionId) -> SessionInfo,
+    ) -> (SessionRegistration, mpsc::UnboundedReceiver<SessionControl>) {
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let (control, receiver) = mpsc::unbounded_channel();
+        let session = Session {
+            info: info(id),
+            control,
+        };
+        self.shared.sessions.lock().unwrap().insert(id, session);
+
+        let registration = SessionRegistration {
+            id,
+            handle: self.clone(),
+        };
+
+        (registration, receiver)
+    }
+
+    /// Keeps a session for `grace_period`, waiting for its client to reconnect.
+    #[doc(hidden)]
+    pub fn park(&self, session: ParkedSession, grace_period: Duration) {
+        let id = session.registration.id;
+        let random_bits = session.auto_reconnect.random_bits;
+        self.shared.parked.lock().unwrap().insert(id, session);
+
+        let handle = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(grace_period).await;
+
+            let mut parked = handle.shared.parked.lock().unwrap();
+
+            // The client may have reconnected and dropped again since
+            if parked
+                .get(&id)
+                .is_some_and(|session| session.auto_reconnect.random_bits == random_bits)
+            {
+                let expired = parked.remove(&id);
+                drop(parked);
+                drop(expired);
+
+                debug!(session_id = id, "Session expired while awaiting reconnection");
+            }
+        });
+    }
+
+    /// Takes the session a client reconnects to, if its auto-reconnect cookie is valid.
+    #[doc(hidden)]
+    pub fn reconnect(&self, cookie: &ClientAutoReconnect, client_random: &[u8]) -> Option<ParkedSession> {
+        let mut parked = self.shared.parked.lock().unwrap();
+
+        let session = parked.get(&cookie.logon_id)?;
+        if !cookie.verify(&session.auto_reconnect, client_random) {
+            return None;
+        }
+
+        parked.remove(&cookie.logon_id)
+    }
+}
+
+/// Removes a session from the list when dropped
+#[doc(hidden)]
+pub struct SessionRegistration {
+    id: SessionId,
+    handle: RdpServerHandle,
+}
+
+impl SessionRegistration {
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut SessionInfo)) {
+        if let Some(session) = self.handle.shared.sessions.lock().unwrap().get_mut(&self.id) {
+            f(&mut session.info);
+        }
+    }
+}
+
+impl Drop for SessionRegistration {
+    fn drop(&mut self) {
+        self.handle.shared.sessions.lock().unwrap().remove(&self.id);
+    }
+}
@@ -1,3 +1,4 @@
+use ironrdp_acceptor::{ClientConnectionData, UserIdentity};
 use ironrdp_ainput as ainput;
 use ironrdp_pdu::input::fast_path::{self, SynchronizeFlags};
 use ironrdp_pdu::input::mouse::PointerFlags;
@@ -73,6 +74,21 @@ pub enum MouseEvent {
 pub trait RdpServerInputHandler: Send {
     fn keyboard(&mut self, event: KeyboardEvent);
     fn mouse(&mut self, event: MouseEvent);
+
+    /// Called with the settings sent by the client, such as its keyboard layout, before any input event is handled.
+    fn connected(&mut self, _client: &ClientConnectionData) {}
+
+    /// Called with the identity of the authenticated user, before any input event is handled.
+    fn logged_on(&mut self, _identity: &UserIdentity) {}
+}
+
+/// Input Event Handler factory for an RDP server
+///
+/// The RDP server serves each client on its own task, and calls this factory once per accepted
+/// connection to build the [`RdpServerInputHandler`] dedicated to that connection.
+pub trait RdpServerInputHandlerFactory: Send {
+    /// Builds a new input handler for a freshly accepted connection.
+    fn build_input_handler(&self) -> Box<dyn RdpServerInputHandler>;
 }
 
 impl From<(u8, fast_path::KeyboardFlags)> for KeyboardEvent {
@@ -5,11 +5,26 @@ extern crate tracing;
 
 mod builder;
 mod capabilities;
+mod channel;
 mod display;
 mod encoder;
+mod frame;
+mod gfx;
+mod handle;
 mod handler;
 mod server;
+mod sound;
 
+pub use channel::*;
 pub use display::*;
+// Internals exercised by the test suite, not part of the public API
+#[doc(hidden)]
+pub use encoder::pointer::{pointer_masks, PointerCache, PointerMasks};
+#[doc(hidden)]
+pub use frame::{Damage, FrameTracker};
+#[doc(hidden)]
+pub use gfx::{GfxEncoder, GfxFrame, GfxState, GraphicsPipelineHandler};
+pub use handle::*;
 pub use handler::*;
 pub use server::*;
+pub use sound::*;
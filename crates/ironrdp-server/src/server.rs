@@ -1,37 +1,95 @@
+use std::any::TypeId;
 use std::io::Cursor;
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use std::{mem, slice};
 
 use anyhow::{bail, Result};
+pub use ironrdp_acceptor::credssp::KerberosConfig;
 use ironrdp_acceptor::{self, Acceptor, AcceptorResult, BeginResult};
-use ironrdp_cliprdr::backend::CliprdrBackendFactory;
+pub use ironrdp_acceptor::{
+    ClientConnectionData, ConnectionRedirector, CredentialStore, CredentialValidator, Credentials, UserIdentity,
+};
+use ironrdp_cliprdr::backend::{CliprdrBackend, CliprdrBackendFactory};
 use ironrdp_cliprdr::CliprdrServer;
 use ironrdp_dvc as dvc;
+use ironrdp_pdu::codecs::rfx::EntropyAlgorithm;
 use ironrdp_pdu::input::fast_path::{FastPathInput, FastPathInputEvent};
 use ironrdp_pdu::input::InputEventPdu;
-use ironrdp_pdu::mcs::SendDataRequest;
-use ironrdp_pdu::rdp::capability_sets::{CapabilitySet, CmdFlags, GeneralExtraFlags};
+use ironrdp_pdu::mcs::{SendDataIndication, SendDataRequest};
+use ironrdp_pdu::rdp::capability_sets::{
+    BitmapCodecs, CapabilitySet, CmdFlags, CodecProperty, EntropyBits, GeneralExtraFlags, LargePointerSupportFlags,
+    RemoteFxContainer, RfxCaps, RfxCapset,
+};
+pub use ironrdp_pdu::rdp::server_redirection::{ServerRedirectionFlags, ServerRedirectionPdu};
+use ironrdp_pdu::rdp::session_info::{
+    ClientAutoReconnect, InfoData, InfoType, LogonExFlags, LogonInfo, LogonInfoExtended, LogonInfoVersion2,
+    SaveSessionInfoPdu, ServerAutoReconnect, ENHANCED_SECURITY_CLIENT_RANDOM,
+};
+use ironrdp_pdu::surface_commands::FrameAction;
+use ironrdp_pdu::write_buf::WriteBuf;
 use ironrdp_pdu::{self, custom_err, decode, mcs, nego, rdp, Action, PduParsing, PduResult};
+use ironrdp_rdpsnd::pdu::ClientAudioFormatPdu;
+use ironrdp_rdpsnd::server::{RdpsndServer, RdpsndServerHandler};
 use ironrdp_svc::{server_encode_svc_messages, StaticChannelSet};
 use ironrdp_tokio::{Framed, FramedRead, FramedWrite, TokioFramed};
+use rand_core::{OsRng, RngCore as _};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::Sleep;
 use tokio_rustls::TlsAcceptor;
 
-use crate::display::{DisplayUpdate, RdpServerDisplay};
-use crate::encoder::UpdateEncoder;
-use crate::handler::RdpServerInputHandler;
+use crate::channel::{
+    ChannelEvent, DvcMessageSender, DvcServerProcessor, DynamicChannelFactory, StaticChannelFactory, SvcMessageSender,
+    SvcServerProcessor,
+};
+use crate::display::{
+    BitmapUpdate, DesktopSize, DisplayUpdate, InclusiveRectangle, MonitorLayoutPdu, RdpServerDisplay,
+    RdpServerDisplayFactory,
+};
+use crate::encoder::{PointerSettings, RfxCodec, UpdateEncoder, UpdateFragmenter};
+use crate::frame::{Damage, FrameTracker};
+use crate::gfx::{GfxEncoder, GfxState, GraphicsPipelineHandler};
+use crate::handle::{ErrorInfo, ParkedSession, ProtocolIndependentCode, RdpServerHandle, SessionControl, SessionInfo};
+use crate::handler::{RdpServerInputHandler, RdpServerInputHandlerFactory};
+use crate::sound::{AudioFormat, RdpServerSound, RdpServerSoundFactory, RdpServerSoundFrames};
 use crate::{builder, capabilities};
 
 #[derive(Clone)]
 pub struct RdpServerOptions {
     pub addr: SocketAddr,
     pub security: RdpServerSecurity,
+    /// Validates the credentials sent by clients in the Client Info PDU
+    pub credential_validator: Option<Arc<dyn CredentialValidator>>,
+    /// Redirects clients to other servers
+    pub redirector: Option<Arc<dyn ConnectionRedirector>>,
+    /// Sessions without user input for this long are disconnected
+    pub idle_timeout: Option<Duration>,
+    /// Sessions are disconnected once connected for this long
+    pub max_session_duration: Option<Duration>,
+    /// Sessions are kept for this long after their connection dropped, waiting for the client to auto-reconnect
+    pub auto_reconnect: Option<Duration>,
+    /// Maximum number of monitors advertised to clients on the display control channel
+    pub max_monitors: u32,
 }
 
 #[derive(Clone)]
 pub enum RdpServerSecurity {
     None,
     Tls(TlsAcceptor),
+    /// TLS + Network Level Authentication (NLA) using CredSSP
+    ///
+    /// Clients are authenticated before the connection sequence starts.
+    Hybrid {
+        tls: TlsAcceptor,
+        /// Public key of the TLS server certificate, used to bind CredSSP to the TLS channel
+        public_key: Vec<u8>,
+        credentials: Arc<dyn CredentialStore>,
+        /// Enables Kerberos authentication, NTLM being used otherwise
+        kerberos_config: Option<KerberosConfig>,
+    },
 }
 
 impl RdpServerSecurity {
@@ -39,11 +97,28 @@ impl RdpServerSecurity {
         match self {
             RdpServerSecurity::None => ironrdp_pdu::nego::SecurityProtocol::empty(),
             RdpServerSecurity::Tls(_) => ironrdp_pdu::nego::SecurityProtocol::SSL,
+            RdpServerSecurity::Hybrid { .. } => {
+                ironrdp_pdu::nego::SecurityProtocol::HYBRID | ironrdp_pdu::nego::SecurityProtocol::HYBRID_EX
+            }
         }
     }
 }
 
-struct DisplayControlHandler {}
+/// Display Control channel, forwarding the monitor layouts requested by the client
+#[doc(hidden)]
+pub struct DisplayControlHandler {
+    max_num_monitors: u32,
+    layouts: mpsc::UnboundedSender<MonitorLayoutPdu>,
+}
+
+impl DisplayControlHandler {
+    pub fn new(max_num_monitors: u32, layouts: mpsc::UnboundedSender<MonitorLayoutPdu>) -> Self {
+        Self {
+            max_num_monitors,
+            layouts,
+        }
+    }
+}
 
 impl dvc::DvcProcessor for DisplayControlHandler {
     fn channel_name(&self) -> &str {
@@ -54,7 +129,7 @@ impl dvc::DvcProcessor for DisplayControlHandler {
         use ironrdp_pdu::dvc::display::{DisplayControlCapsPdu, ServerPdu};
 
         let pdu = ServerPdu::DisplayControlCaps(DisplayControlCapsPdu {
-            max_num_monitors: 1,
+            max_num_monitors: self.max_num_monitors,
             max_monitor_area_factora: 3840,
             max_monitor_area_factorb: 2400,
         });
@@ -70,6 +145,10 @@ impl dvc::DvcProcessor for DisplayControlHandler {
         match ClientPdu::from_buffer(payload).map_err(|e| custom_err!(e))? {
             ClientPdu::DisplayControlMonitorLayout(layout) => {
                 debug!(?layout);
+
+                if self.layouts.send(layout).is_err() {
+                    warn!("Monitor layout request dropped, the connection is closing");
+                }
             }
         }
         Ok(vec![])
@@ -80,6 +159,7 @@ impl dvc::DvcServerProcessor for DisplayControlHandler {}
 
 struct AInputHandler {
     handler: Arc<Mutex<Box<dyn RdpServerInputHandler>>>,
+    last_input: Arc<Mutex<Instant>>,
 }
 
 impl dvc::DvcProcessor for AInputHandler {
@@ -102,6 +182,8 @@ impl dvc::DvcProcessor for AInputHandler {
 
         match decode(payload)? {
             ClientPdu::Mouse(pdu) => {
+                *self.last_input.lock().unwrap() = Instant::now();
+
                 let mut handler = self.handler.lock().unwrap();
                 handler.mouse(pdu.into());
             }
@@ -113,9 +195,47 @@ impl dvc::DvcProcessor for AInputHandler {
 
 impl dvc::DvcServerProcessor for AInputHandler {}
 
+#[derive(Debug)]
+enum SoundEvent {
+    Start(AudioFormat),
+    Stop,
+}
+
+/// Forwards the RDPSND channel state to the client loop, which owns the [`RdpServerSound`]
+#[derive(Debug)]
+struct SoundHandler {
+    formats: Vec<AudioFormat>,
+    events: mpsc::UnboundedSender<SoundEvent>,
+}
+
+impl RdpsndServerHandler for SoundHandler {
+    fn get_formats(&self) -> &[AudioFormat] {
+        &self.formats
+    }
+
+    fn start(&mut self, client_format: &ClientAudioFormatPdu) -> Option<u16> {
+        let (format_no, format) = self.formats.iter().find_map(|format| {
+            let format_no = client_format.formats.iter().position(|f| f == format)?;
+            Some((u16::try_from(format_no).ok()?, format.clone()))
+        })?;
+
+        if self.events.send(SoundEvent::Start(format)).is_err() {
+            warn!("Audio output start dropped, the connection is closing");
+        }
+
+        Some(format_no)
+    }
+
+    fn stop(&mut self) {
+        let _ = self.events.send(SoundEvent::Stop);
+    }
+}
+
 /// RDP Server
 ///
 /// A server is created to listen for connections.
+/// Each accepted client is served on its own task, with its own [`RdpServerDisplay`] and
+/// [`RdpServerInputHandler`] built by the configured factories.
 /// After the connection sequence is finalized using the provided security mechanism, the server can:
 ///  - receive display updates from a [`RdpServerDisplay`] and forward them to the client
 ///  - receive input events from a client and forward them to an [`RdpServerInputHandler`]
@@ -128,11 +248,13 @@ impl dvc::DvcServerProcessor for AInputHandler {}
 ///# use anyhow::Result;
 ///# use ironrdp_server::{DisplayUpdate, DesktopSize, KeyboardEvent, MouseEvent};
 ///# use tokio_rustls::TlsAcceptor;
+///# #[derive(Clone)]
 ///# struct NoopInputHandler;
 ///# impl RdpServerInputHandler for NoopInputHandler {
 ///#     fn keyboard(&mut self, _: KeyboardEvent) {}
 ///#     fn mouse(&mut self, _: MouseEvent) {}
 ///# }
+///# #[derive(Clone)]
 ///# struct NoopDisplay;
 ///# #[async_trait::async_trait]
 ///# impl RdpServerDisplay for NoopDisplay {
@@ -149,12 +271,12 @@ impl dvc::DvcServerProcessor for AInputHandler {}
 ///#    todo!()
 /// }
 ///
-/// fn make_input_handler() -> impl RdpServerInputHandler {
+/// fn make_input_handler() -> impl RdpServerInputHandler + Clone {
 ///    /* snip */
 ///#    NoopInputHandler
 /// }
 ///
-/// fn make_display_handler() -> impl RdpServerDisplay {
+/// fn make_display_handler() -> impl RdpServerDisplay + Clone {
 ///    /* snip */
 ///#    NoopDisplay
 /// }
@@ -175,26 +297,34 @@ impl dvc::DvcServerProcessor for AInputHandler {}
 /// ```
 pub struct RdpServer {
     opts: RdpServerOptions,
-    // FIXME: replace with a channel and poll/process the handler?
-    handler: Arc<Mutex<Box<dyn RdpServerInputHandler>>>,
-    display: Box<dyn RdpServerDisplay>,
-    static_channels: StaticChannelSet,
+    handler_factory: Box<dyn RdpServerInputHandlerFactory>,
+    display_factory: Box<dyn RdpServerDisplayFactory>,
     cliprdr_factory: Option<Box<dyn CliprdrBackendFactory + Send>>,
+    sound_factory: Option<Box<dyn RdpServerSoundFactory>>,
+    static_channel_factories: Vec<Box<dyn StaticChannelFactory>>,
+    dynamic_channel_factories: Vec<Box<dyn DynamicChannelFactory>>,
+    handle: RdpServerHandle,
 }
 
 impl RdpServer {
     pub fn new(
         opts: RdpServerOptions,
-        handler: Box<dyn RdpServerInputHandler>,
-        display: Box<dyn RdpServerDisplay>,
+        handler_factory: Box<dyn RdpServerInputHandlerFactory>,
+        display_factory: Box<dyn RdpServerDisplayFactory>,
         cliprdr_factory: Option<Box<dyn CliprdrBackendFactory + Send>>,
+        sound_factory: Option<Box<dyn RdpServerSoundFactory>>,
+        static_channel_factories: Vec<Box<dyn StaticChannelFactory>>,
+        dynamic_channel_factories: Vec<Box<dyn DynamicChannelFactory>>,
     ) -> Self {
         Self {
             opts,
-            handler: Arc::new(Mutex::new(handler)),
-            display,
-            static_channels: StaticChannelSet::new(),
+            handler_factory,
+            display_factory,
             cliprdr_factory,
+            sound_factory,
+            static_channel_factories,
+            dynamic_channel_factories,
+            handle: RdpServerHandle::new(),
         }
     }
 
@@ -202,44 +332,234 @@ impl RdpServer {
         builder::RdpServerBuilder::new()
     }
 
-    pub async fn run_connection(&mut self, stream: TcpStream) -> Result<()> {
+    /// Returns a handle to stop the server and manage its sessions, while it runs.
+    pub fn handle(&self) -> RdpServerHandle {
+        self.handle.clone()
+    }
+
+    /// Serves a single connection on the current task, until the client disconnects.
+    pub async fn run_connection(&self, stream: TcpStream) -> Result<()> {
+        let peer = stream.peer_addr()?;
+        self.new_connection(peer).run(stream).await
+    }
+
+    /// Accepts connections until the listener fails or the server is stopped, serving each client on its own task.
+    pub async fn run(&mut self) -> Result<()> {
+        let listener = TcpListener::bind(self.opts.addr).await?;
+
+        debug!("Listening for connections");
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(error) => {
+                        error!(?error, "Listener error");
+                        break;
+                    }
+                },
+
+                () = self.handle.stopped() => {
+                    debug!("Server stopped");
+                    break;
+                }
+            };
+
+            debug!(?peer, "Received connection");
+
+            let connection = self.new_connection(peer);
+
+            tokio::spawn(async move {
+                if let Err(error) = connection.run(stream).await {
+                    error!(?error, ?peer, "Connection error");
+                }
+
+                debug!(?peer, "Connection closed");
+            });
+        }
+
+        Ok(())
+    }
+
+    fn new_connection(&self, peer: SocketAddr) -> RdpServerConnection {
+        let (channel_sender, channel_events) = mpsc::unbounded_channel();
+
+        let svc_processors: Vec<_> = self
+            .static_channel_factories
+            .iter()
+            .enumerate()
+            .map(|(index, factory)| factory.build_static_channel(SvcMessageSender::new(index, channel_sender.clone())))
+            .collect();
+
+        let dvc_processors: Vec<_> = self
+            .dynamic_channel_factories
+            .iter()
+            .enumerate()
+            .map(|(index, factory)| factory.build_dynamic_channel(DvcMessageSender::new(index, channel_sender.clone())))
+            .collect();
+
+        RdpServerConnection {
+            opts: self.opts.clone(),
+            peer,
+            server: self.handle.clone(),
+            last_input: Arc::new(Mutex::new(Instant::now())),
+            handler: Arc::new(Mutex::new(self.handler_factory.build_input_handler())),
+            display: self.display_factory.build_display(),
+            static_channels: StaticChannelSet::new(),
+            cliprdr_backend: self
+                .cliprdr_factory
+                .as_deref()
+                .map(|factory| factory.build_cliprdr_backend()),
+            sound: self.sound_factory.as_deref().map(|factory| factory.build_sound()),
+            frames: None,
+            track_damage: false,
+            custom_svcs: svc_processors.iter().map(|svc| svc.as_any().type_id()).collect(),
+            custom_dvcs: dvc_processors.iter().map(|dvc| dvc.channel_name().to_owned()).collect(),
+            svc_processors,
+            dvc_processors,
+            channel_events,
+            output_suppressed: false,
+            refresh_areas: Vec::new(),
+        }
+    }
+}
+
+/// State owned by a single client connection
+struct RdpServerConnection {
+    opts: RdpServerOptions,
+    peer: SocketAddr,
+    /// Lists the session while it runs
+    server: RdpServerHandle,
+    /// Time of the last input event, for the idle timeout
+    last_input: Arc<Mutex<Instant>>,
+    // FIXME: replace with a channel and poll/process the handler?
+    handler: Arc<Mutex<Box<dyn RdpServerInputHandler>>>,
+    display: Box<dyn RdpServerDisplay>,
+    static_channels: StaticChannelSet,
+    cliprdr_backend: Option<Box<dyn CliprdrBackend>>,
+    sound: Option<Box<dyn RdpServerSound>>,
+    /// Fast-path frames waiting for an acknowledgement, when the client sends them
+    frames: Option<FrameTracker>,
+    /// The client acknowledges frames or may suppress its output, so the desktop content is kept
+    /// to send the updates held back meanwhile and to repaint areas on request
+    track_damage: bool,
+    /// Application channels, attached to the acceptor when the connection starts
+    svc_processors: Vec<Box<dyn SvcServerProcessor>>,
+    dvc_processors: Vec<Box<dyn DvcServerProcessor>>,
+    /// Type of the application static channels, by factory index
+    custom_svcs: Vec<TypeId>,
+    /// Name of the application dynamic channels, by factory index
+    custom_dvcs: Vec<String>,
+    channel_events: mpsc::UnboundedReceiver<ChannelEvent>,
+    /// The client doesn't display the desktop, bitmap updates are held back
+    output_suppressed: bool,
+    /// Areas the client asked to repaint
+    refresh_areas: Vec<InclusiveRectangle>,
+}
+
+impl RdpServerConnection {
+    async fn run(mut self, stream: TcpStream) -> Result<()> {
         let framed = TokioFramed::new(stream);
 
         let size = self.display.size().await;
         let capabilities = capabilities::capabilities(&self.opts, size);
         let mut acceptor = Acceptor::new(self.opts.security.flag(), size, capabilities);
 
-        if let Some(cliprdr_factory) = self.cliprdr_factory.as_deref() {
-            let backend = cliprdr_factory.build_cliprdr_backend();
+        if let Some(validator) = &self.opts.credential_validator {
+            acceptor.attach_credential_validator(Arc::clone(validator));
+        }
+
+        if let Some(redirector) = &self.opts.redirector {
+            acceptor.attach_redirector(Arc::clone(redirector));
+        }
 
+        if let Some(backend) = self.cliprdr_backend.take() {
             let cliprdr = CliprdrServer::new(backend);
 
             acceptor.attach_static_channel(cliprdr);
         }
 
-        let dvc = dvc::DrdynvcServer::new()
+        for svc in mem::take(&mut self.svc_processors) {
+            acceptor.attach_static_channel(svc);
+        }
+
+        let (sound_sender, sound_events) = mpsc::unbounded_channel();
+        if let Some(sound) = &self.sound {
+            let rdpsnd = RdpsndServer::new(Box::new(SoundHandler {
+                formats: sound.formats(),
+                events: sound_sender,
+            }));
+
+            acceptor.attach_static_channel(rdpsnd);
+        }
+
+        let (layout_sender, layouts) = mpsc::unbounded_channel();
+        let gfx = GfxState::new(size);
+
+        let mut dvc = dvc::DrdynvcServer::new()
             .with_dynamic_channel(AInputHandler {
                 handler: Arc::clone(&self.handler),
+                last_input: Arc::clone(&self.last_input),
             })
-            .with_dynamic_channel(DisplayControlHandler {});
+            .with_dynamic_channel(DisplayControlHandler::new(self.opts.max_monitors, layout_sender))
+            .with_dynamic_channel(GraphicsPipelineHandler {
+                state: Arc::clone(&gfx),
+            });
+        for channel in mem::take(&mut self.dvc_processors) {
+            dvc = dvc.with_dynamic_channel(channel);
+        }
         acceptor.attach_static_channel(dvc);
 
         match ironrdp_acceptor::accept_begin(framed, &mut acceptor).await {
             Ok(BeginResult::ShouldUpgrade(stream)) => {
-                let framed = TokioFramed::new(match &self.opts.security {
-                    RdpServerSecurity::Tls(acceptor) => acceptor.accept(stream).await?,
+                let mut framed = TokioFramed::new(match &self.opts.security {
+                    RdpServerSecurity::Tls(acceptor) | RdpServerSecurity::Hybrid { tls: acceptor, .. } => {
+                        acceptor.accept(stream).await?
+                    }
                     RdpServerSecurity::None => unreachable!(),
                 });
 
+                acceptor.mark_security_upgrade_as_done()?;
+
+                if let RdpServerSecurity::Hybrid {
+                    public_key,
+                    credentials,
+                    kerberos_config,
+                    ..
+                } = &self.opts.security
+                {
+                    if acceptor.should_perform_credssp().is_some() {
+                        if let Err(error) = ironrdp_acceptor::accept_credssp(
+                            &mut framed,
+                            &mut acceptor,
+                            Arc::clone(credentials),
+                            public_key.clone(),
+                            kerberos_config.clone(),
+                        )
+                        .await
+                        {
+                            error!(?error, "CredSSP error");
+                            return Ok(());
+                        }
+                    }
+                }
+
                 match ironrdp_acceptor::accept_finalize(framed, &mut acceptor).await {
-                    Ok((framed, result)) => self.client_loop(framed, result).await?,
+                    Ok((framed, result)) => {
+                        self.client_loop(framed, acceptor, result, layouts, gfx, sound_events)
+                            .await?
+                    }
+                    Err(_) if acceptor.redirection().is_some() => debug!("Client redirected"),
                     Err(error) => error!(?error, "Accept finalize error"),
                 };
             }
 
             Ok(BeginResult::Continue(framed)) => {
                 match ironrdp_acceptor::accept_finalize(framed, &mut acceptor).await {
-                    Ok((framed, result)) => self.client_loop(framed, result).await?,
+                    Ok((framed, result)) => {
+                        self.client_loop(framed, acceptor, result, layouts, gfx, sound_events)
+                            .await?
+                    }
+                    Err(_) if acceptor.redirection().is_some() => debug!("Client redirected"),
                     Err(error) => error!(?error, "Accept finalize error"),
                 };
             }
@@ -252,35 +572,78 @@ impl RdpServer {
         Ok(())
     }
 
-    pub async fn run(&mut self) -> Result<()> {
-        let listener = TcpListener::bind(self.opts.addr).await?;
-
-        debug!("Listening for connections");
-        while let Ok((stream, peer)) = listener.accept().await {
-            debug!(?peer, "Received connection");
-            if let Err(error) = self.run_connection(stream).await {
-                error!(?error, "Connection error");
-            }
-        }
-
-        Ok(())
-    }
-
-    async fn client_loop<S>(&mut self, mut framed: Framed<S>, result: AcceptorResult) -> Result<()>
+    async fn client_loop<S>(
+        mut self,
+        mut framed: Framed<S>,
+        mut acceptor: Acceptor,
+        result: AcceptorResult,
+        mut layouts: mpsc::UnboundedReceiver<MonitorLayoutPdu>,
+        gfx: Arc<Mutex<GfxState>>,
+        mut sound_events: mpsc::UnboundedReceiver<SoundEvent>,
+    ) -> Result<()>
     where
         S: FramedWrite + FramedRead,
     {
         debug!("Starting client loop");
 
+        let client_name = result
+            .client_data
+            .as_ref()
+            .map(|client| client.gcc_blocks.core.client_name.clone())
+            .filter(|name| !name.is_empty());
+
+        let (session, mut control, resumed) = match self.reconnecting_session(&result) {
+            Some(parked) => {
+                mem::swap(&mut *self.handler.lock().unwrap(), &mut *parked.handler.lock().unwrap());
+                self.display = parked.display;
+
+                parked.registration.update(|info| {
+                    info.peer = self.peer;
+                    info.client_name = client_name;
+                    info.awaiting_reconnect = false;
+                });
+                info!(session_id = parked.registration.id(), peer = %self.peer, "Session resumed");
+
+                let resumed = (parked.display_updates, parked.damage);
+                (parked.registration, parked.control, Some(resumed))
+            }
+
+            None => {
+                if let Some(client) = &result.client_data {
+                    self.handler.lock().unwrap().connected(client);
+                    self.display.connected(client);
+                }
+
+                if let Some(identity) = &result.identity {
+                    self.handler.lock().unwrap().logged_on(identity);
+                    self.display.logged_on(identity);
+                }
+
+                let (session, control) = self.server.register(|id| SessionInfo {
+                    id,
+                    peer: self.peer,
+                    client_name,
+                    username: result.identity.as_ref().map(|identity| identity.username.clone()),
+                    connected_at: SystemTime::now(),
+                    awaiting_reconnect: false,
+                });
+                info!(session_id = session.id(), peer = %self.peer, "Session started");
+
+                (session, control, None)
+            }
+        };
+
+        let started = Instant::now();
+        *self.last_input.lock().unwrap() = started;
+        let mut timeout = SessionTimeout::new(&self.opts, started);
+
+        let io_channel_id = result.io_channel_id;
+        let user_channel_id = result.user_channel_id;
+
         if !result.input_events.is_empty() {
             debug!("Handling input event backlog from acceptor sequence");
-            self.handle_input_backlog(
-                &mut framed,
-                result.io_channel_id,
-                result.user_channel_id,
-                result.input_events,
-            )
-            .await?;
+            self.handle_input_backlog(&mut framed, io_channel_id, user_channel_id, result.input_events)
+                .await?;
         }
 
         self.static_channels = result.static_channels;
@@ -289,35 +652,77 @@ impl RdpServer {
                 continue;
             };
             let svc_responses = channel.start()?;
-            let response = server_encode_svc_messages(svc_responses, channel_id, result.user_channel_id)?;
+            let response = server_encode_svc_messages(svc_responses, channel_id, user_channel_id)?;
             framed.write_all(&response).await?;
         }
 
-        let mut surface_flags = CmdFlags::empty();
-        for c in result.capabilities {
-            match c {
-                CapabilitySet::General(c) => {
-                    let fastpath = c.extra_flags.contains(GeneralExtraFlags::FASTPATH_OUTPUT_SUPPORTED);
-                    if !fastpath {
-                        bail!("Fastpath output not supported!");
-                    }
-                }
-                CapabilitySet::SurfaceCommands(c) => {
-                    surface_flags = c.flags;
-                }
-                _ => {}
+        let auto_reconnect = self.opts.auto_reconnect.map(|_| {
+            let mut random_bits = [0; 16];
+            OsRng.fill_bytes(&mut random_bits);
+
+            ServerAutoReconnect {
+                logon_id: session.id(),
+                random_bits,
             }
-        }
+        });
+        send_logon_info(
+            &mut framed,
+            session.id(),
+            result.identity.as_ref(),
+            result.client_data.as_ref(),
+            auto_reconnect.as_ref(),
+            io_channel_id,
+            user_channel_id,
+        )
+        .await?;
+
+        let size = self.display.size().await;
+        self.frames = frame_tracker(&result.capabilities);
+        self.track_damage = self.frames.is_some() || suppress_output_support(&result.capabilities);
+        let mut encoder = update_encoder(result.capabilities, size)?;
+        let mut gfx_encoder = GfxEncoder::new();
 
         let mut buffer = vec![0u8; 4096];
-        let mut encoder = UpdateEncoder::new(surface_flags);
 
-        let mut display_updates = self.display.updates().await?;
+        let (mut display_updates, mut damage, mut pending_update) = match resumed {
+            Some((display_updates, damage)) => {
+                // The client lost the desktop content
+                let desktop = InclusiveRectangle {
+                    left: 0,
+                    top: 0,
+                    right: size.width.saturating_sub(1),
+                    bottom: size.height.saturating_sub(1),
+                };
+                self.display.refresh(slice::from_ref(&desktop)).await;
+                self.refresh_areas.push(desktop);
+
+                // The size announced by the acceptor may differ from the size of the resumed display
+                let resize = (result.desktop_size != size).then_some(DisplayUpdate::Resize(size));
+                (display_updates, damage, resize)
+            }
+
+            None => (self.display.updates().await?, Damage::new(size), None),
+        };
+        let mut sound_frames = None;
+        let mut transport_dropped = false;
 
         'main: loop {
-            tokio::select! {
+            timeout.input(*self.last_input.lock().unwrap());
+
+            let ack_deadline = self
+                .frames
+                .as_ref()
+                .and_then(FrameTracker::deadline)
+                .into_iter()
+                .chain(gfx.lock().unwrap().deadline())
+                .min();
+
+            let update = tokio::select! {
+                Some(update) = async { pending_update.take() }, if pending_update.is_some() => update,
+
                 frame = framed.read_pdu() => {
                     let Ok((action, bytes)) = frame else {
+                        transport_dropped = true;
                         break;
                     };
 
@@ -328,7 +733,7 @@ impl RdpServer {
                         }
 
                         Action::X224 => {
-                            match self.handle_x224(&mut framed, result.io_channel_id, result.user_channel_id, &bytes).await {
+                            match self.handle_x224(&mut framed, io_channel_id, user_channel_id, &bytes).await {
                                 Ok(disconnect) => {
                                     if disconnect {
                                         break 'main;
@@ -339,34 +744,457 @@ impl RdpServer {
                                     error!(?error, "X224 input error");
                                 }
                             };
+
+                            // The client may have acknowledged frames, accepted the graphics pipeline,
+                            // resumed its output or asked for a repaint
+                            for area in self.refresh_areas.drain(..) {
+                                let mut gfx = gfx.lock().unwrap();
+                                if gfx.is_open() {
+                                    gfx.invalidate(&area);
+                                } else {
+                                    damage.invalidate(&area);
+                                }
+                            }
+
+                            self.send_frame(&mut framed, &mut encoder, &mut damage, &mut buffer).await?;
+                            self.send_gfx_frame(&mut framed, &gfx, &mut gfx_encoder, user_channel_id).await?;
                         }
                     }
+
+                    continue;
                 },
 
-                Some(update) = display_updates.next_update() => {
-                    let fragmenter = match update {
-                        DisplayUpdate::Bitmap(bitmap) => encoder.bitmap(bitmap)
-                    };
+                Some(layout) = layouts.recv() => {
+                    self.display.request_layout(layout).await;
 
-                    if let Some(mut fragmenter) = fragmenter {
-                        if fragmenter.size_hint() > buffer.len() {
-                            buffer.resize(fragmenter.size_hint(), 0);
-                        }
+                    continue;
+                },
 
-                        while let Some(len) = fragmenter.next(&mut buffer) {
-                            if let Err(error) = framed.write_all(&buffer[..len]).await {
-                                error!(?error, "Write display update error");
-                                break;
-                            };
-                        }
+                Some(event) = sound_events.recv() => {
+                    self.handle_sound_event(event, &mut sound_frames).await;
+
+                    continue;
+                },
+
+                Some(event) = self.channel_events.recv() => {
+                    self.handle_channel_event(&mut framed, event, user_channel_id).await?;
+
+                    continue;
+                },
+
+                Some(SessionControl::Disconnect(reason)) = control.recv() => {
+                    info!(session_id = session.id(), ?reason, "Disconnecting session");
+                    disconnect(&mut framed, reason, io_channel_id, user_channel_id).await?;
+
+                    break;
+                },
+
+                reason = timeout.elapsed() => {
+                    info!(session_id = session.id(), ?reason, "Session timed out");
+                    disconnect(&mut framed, reason, io_channel_id, user_channel_id).await?;
+
+                    break;
+                },
+
+                () = sleep_until(ack_deadline) => {
+                    // The client is too far behind, the held back updates are sent anyway
+                    let now = Instant::now();
+                    if let Some(frames) = &mut self.frames {
+                        frames.expire(now);
                     }
+                    gfx.lock().unwrap().expire(now);
+
+                    self.send_frame(&mut framed, &mut encoder, &mut damage, &mut buffer).await?;
+                    self.send_gfx_frame(&mut framed, &gfx, &mut gfx_encoder, user_channel_id).await?;
+
+                    continue;
+                },
+
+                Some((data, timestamp)) = next_sound_frames(&mut sound_frames) => {
+                    self.send_sound(&mut framed, &data, timestamp, user_channel_id).await?;
+
+                    continue;
+                },
+
+                Some(update) = display_updates.next_update() => update,
+            };
+
+            let fragmenter = match update {
+                DisplayUpdate::Bitmap(bitmap) if gfx.lock().unwrap().is_open() => {
+                    gfx.lock().unwrap().queue(bitmap);
+                    self.send_gfx_frame(&mut framed, &gfx, &mut gfx_encoder, user_channel_id)
+                        .await?;
+
+                    None
+                }
+                DisplayUpdate::Bitmap(bitmap) => {
+                    self.send_bitmap(&mut framed, &mut encoder, &mut damage, bitmap, &mut buffer)
+                        .await?;
+
+                    None
+                }
+                DisplayUpdate::PointerPosition(position) => encoder.pointer_position(position),
+                DisplayUpdate::PointerBitmap(pointer) => encoder.pointer_bitmap(pointer),
+                DisplayUpdate::PointerDefault => encoder.pointer_default(),
+                DisplayUpdate::PointerHidden => encoder.pointer_hidden(),
+                DisplayUpdate::PointerCached(id) => encoder.pointer_cached(id),
+
+                DisplayUpdate::Resize(desktop_size) => {
+                    debug!(?desktop_size, "Starting deactivation-reactivation sequence");
+
+                    let mut reactivation = Acceptor::new_deactivation_reactivation(
+                        acceptor,
+                        mem::take(&mut self.static_channels),
+                        desktop_size,
+                    )?;
+
+                    let (reactivated, result) = ironrdp_acceptor::accept_finalize(framed, &mut reactivation).await?;
+                    framed = reactivated;
+                    acceptor = reactivation;
+                    self.static_channels = result.static_channels;
+
+                    if !result.input_events.is_empty() {
+                        debug!("Handling input event backlog from deactivation-reactivation sequence");
+                        self.handle_input_backlog(&mut framed, io_channel_id, user_channel_id, result.input_events)
+                            .await?;
+                    }
+
+                    self.frames = frame_tracker(&result.capabilities);
+                    self.track_damage = self.frames.is_some() || suppress_output_support(&result.capabilities);
+                    encoder = update_encoder(result.capabilities, desktop_size)?;
+                    damage.reset(desktop_size);
+
+                    let surface = gfx.lock().unwrap().resize(desktop_size)?;
+                    if let Some((channel_id, messages)) = surface {
+                        self.send_dvc_messages(&mut framed, channel_id, messages, user_channel_id)
+                            .await?;
+                    }
+
+                    None
+                }
+            };
+
+            if let Some(fragmenter) = fragmenter {
+                if let Err(error) = write_update(&mut framed, fragmenter, &mut buffer).await {
+                    error!(?error, "Write display update error");
+                }
+            }
+        }
+
+        if sound_frames.is_some() {
+            if let Some(sound) = &mut self.sound {
+                sound.stop();
+            }
+        }
+
+        if let (true, Some(grace_period), Some(auto_reconnect)) =
+            (transport_dropped, self.opts.auto_reconnect, auto_reconnect)
+        {
+            info!(
+                session_id = session.id(),
+                ?grace_period,
+                "Connection dropped, awaiting reconnection"
+            );
+            session.update(|info| info.awaiting_reconnect = true);
+
+            self.server.park(
+                ParkedSession {
+                    registration: session,
+                    control,
+                    auto_reconnect,
+                    display: self.display,
+                    display_updates,
+                    handler: self.handler,
+                    damage,
+                },
+                grace_period,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Takes the parked session the client reconnects to, if it sent a valid auto-reconnect cookie
+    fn reconnecting_session(&self, result: &AcceptorResult) -> Option<ParkedSession> {
+        self.opts.auto_reconnect?;
+
+        let client_info = &result.client_data.as_ref()?.client_info;
+        let cookie = client_info.extra_info.optional_data.reconnect_cookie()?;
+
+        let cookie = match ClientAutoReconnect::from_cookie(cookie) {
+            Ok(cookie) => cookie,
+            Err(error) => {
+                warn!(?error, "Invalid auto-reconnect cookie");
+                return None;
+            }
+        };
+
+        let session = self.server.reconnect(&cookie, &ENHANCED_SECURITY_CLIENT_RANDOM);
+        if session.is_none() {
+            debug!(
+                logon_id = cookie.logon_id,
+                "No session to resume for the auto-reconnect cookie"
+            );
+        }
+
+        session
+    }
+
+    /// Sends the messages pushed by the application on its channels
+    async fn handle_channel_event<S>(
+        &mut self,
+        framed: &mut Framed<S>,
+        event: ChannelEvent,
+        user_channel_id: u16,
+    ) -> Result<()>
+    where
+        S: FramedWrite,
+    {
+        match event {
+            ChannelEvent::Static { index, messages } => {
+                let channel_id = self
+                    .custom_svcs
+                    .get(index)
+                    .and_then(|&type_id| self.static_channels.get_channel_id_by_type_id(type_id));
+
+                let Some(channel_id) = channel_id else {
+                    debug!(index, "Static channel is not joined, dropping messages");
+                    return Ok(());
+                };
+
+                let data = server_encode_svc_messages(messages, channel_id, user_channel_id)?;
+                framed.write_all(&data).await?;
+            }
+
+            ChannelEvent::Dynamic { index, messages } => {
+                let channel_id = self.custom_dvcs.get(index).and_then(|name| {
+                    self.static_channels
+                        .get_by_type::<dvc::DrdynvcServer>()
+                        .and_then(|svc| svc.channel_processor_downcast_ref::<dvc::DrdynvcServer>())
+                        .and_then(|drdynvc| drdynvc.get_channel_id_by_name(name))
+                });
+
+                let Some(channel_id) = channel_id else {
+                    debug!(index, "Dynamic channel is not opened, dropping messages");
+                    return Ok(());
+                };
+
+                self.send_dvc_messages(framed, channel_id, messages, user_channel_id)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_sound_event(
+        &mut self,
+        event: SoundEvent,
+        sound_frames: &mut Option<(Box<dyn RdpServerSoundFrames>, Instant)>,
+    ) {
+        let Some(sound) = &mut self.sound else {
+            return;
+        };
+
+        match event {
+            SoundEvent::Start(format) => {
+                debug!(?format, "Starting audio output");
+
+                match sound.start(format).await {
+                    Ok(frames) => *sound_frames = Some((frames, Instant::now())),
+                    Err(error) => error!(?error, "Audio output start error"),
+                }
+            }
+
+            SoundEvent::Stop => {
+                debug!("Stopping audio output");
+
+                if sound_frames.take().is_some() {
+                    sound.stop();
+                }
+            }
+        }
+    }
+
+    /// Sends audio data on the RDPSND channel
+    async fn send_sound<S>(
+        &mut self,
+        framed: &mut Framed<S>,
+        data: &[u8],
+        timestamp: u32,
+        user_channel_id: u16,
+    ) -> Result<()>
+    where
+        S: FramedWrite,
+    {
+        let Some(rdpsnd_channel_id) = self.static_channels.get_channel_id_by_type::<RdpsndServer>() else {
+            return Ok(());
+        };
+
+        let rdpsnd = self
+            .static_channels
+            .get_by_type_mut::<RdpsndServer>()
+            .and_then(|svc| svc.channel_processor_downcast_mut::<RdpsndServer>())
+            .expect("RDPSND channel is attached");
+
+        let messages = rdpsnd.wave(data, timestamp)?;
+        let data = server_encode_svc_messages(messages.into(), rdpsnd_channel_id, user_channel_id)?;
+        framed.write_all(&data).await?;
+
+        Ok(())
+    }
+
+    /// Sends a bitmap right away, unless the client is behind or suppressed its output
+    ///
+    /// Bitmaps held back are merged with later updates, and sent once the client catches up.
+    async fn send_bitmap<S>(
+        &mut self,
+        framed: &mut Framed<S>,
+        encoder: &mut UpdateEncoder,
+        damage: &mut Damage,
+        bitmap: BitmapUpdate,
+        buffer: &mut Vec<u8>,
+    ) -> Result<()>
+    where
+        S: FramedWrite,
+    {
+        let held_back =
+            self.output_suppressed || !damage.is_empty() || self.frames.as_mut().is_some_and(FrameTracker::is_full);
+
+        if held_back {
+            damage.push(&bitmap);
+            return self.send_frame(framed, encoder, damage, buffer).await;
+        }
+
+        if self.track_damage {
+            damage.record(&bitmap);
+        }
+
+        self.write_frame(framed, encoder, vec![bitmap], buffer).await
+    }
+
+    /// Sends the damaged regions, once the client caught up and resumed its output
+    async fn send_frame<S>(
+        &mut self,
+        framed: &mut Framed<S>,
+        encoder: &mut UpdateEncoder,
+        damage: &mut Damage,
+        buffer: &mut Vec<u8>,
+    ) -> Result<()>
+    where
+        S: FramedWrite,
+    {
+        if damage.is_empty() || self.output_suppressed || self.frames.as_mut().is_some_and(FrameTracker::is_full) {
+            return Ok(());
+        }
+
+        let bitmaps = damage.take();
+        if !self.track_damage {
+            // The desktop content isn't kept up to date by the bitmaps sent right away
+            damage.clear();
+        }
+
+        self.write_frame(framed, encoder, bitmaps, buffer).await
+    }
+
+    /// Sends bitmaps, as a fast-path frame when the client acknowledges frames
+    async fn write_frame<S>(
+        &mut self,
+        framed: &mut Framed<S>,
+        encoder: &mut UpdateEncoder,
+        bitmaps: Vec<BitmapUpdate>,
+        buffer: &mut Vec<u8>,
+    ) -> Result<()>
+    where
+        S: FramedWrite,
+    {
+        let Some(frames) = &mut self.frames else {
+            for bitmap in bitmaps {
+                if let Some(update) = encoder.bitmap(bitmap) {
+                    write_update(framed, update, buffer).await?;
                 }
             }
+
+            return Ok(());
+        };
+
+        let frame_id = frames.start_frame(true);
+        trace!(frame_id, bitmaps = bitmaps.len(), "Sending frame");
+
+        if let Some(marker) = encoder.frame_marker(FrameAction::Begin, frame_id) {
+            write_update(framed, marker, buffer).await?;
+        }
+
+        for bitmap in bitmaps {
+            if let Some(update) = encoder.bitmap(bitmap) {
+                write_update(framed, update, buffer).await?;
+            }
+        }
+
+        if let Some(marker) = encoder.frame_marker(FrameAction::End, frame_id) {
+            write_update(framed, marker, buffer).await?;
         }
 
         Ok(())
     }
 
+    async fn send_gfx_frame<S>(
+        &mut self,
+        framed: &mut Framed<S>,
+        gfx: &Mutex<GfxState>,
+        encoder: &mut GfxEncoder,
+        user_channel_id: u16,
+    ) -> Result<()>
+    where
+        S: FramedWrite,
+    {
+        if self.output_suppressed {
+            return Ok(());
+        }
+
+        let Some(frame) = gfx.lock().unwrap().next_frame() else {
+            return Ok(());
+        };
+
+        trace!(
+            frame_id = frame.frame_id,
+            bitmaps = frame.bitmaps.len(),
+            "Sending graphics pipeline frame"
+        );
+
+        let messages = encoder.frame(&frame)?;
+        self.send_dvc_messages(framed, frame.channel_id, messages, user_channel_id)
+            .await
+    }
+
+    /// Sends messages on a dynamic channel, outside of a response to the client
+    async fn send_dvc_messages<S>(
+        &mut self,
+        framed: &mut Framed<S>,
+        channel_id: u32,
+        messages: dvc::DvcMessages,
+        user_channel_id: u16,
+    ) -> Result<()>
+    where
+        S: FramedWrite,
+    {
+        let Some(drdynvc_channel_id) = self.static_channels.get_channel_id_by_type::<dvc::DrdynvcServer>() else {
+            bail!("DRDYNVC channel is not joined");
+        };
+
+        let drdynvc = self
+            .static_channels
+            .get_by_type_mut::<dvc::DrdynvcServer>()
+            .and_then(|svc| svc.channel_processor_downcast_mut::<dvc::DrdynvcServer>())
+            .expect("DRDYNVC channel is attached");
+
+        let svc_messages = drdynvc.encode_dvc_messages(channel_id, messages)?;
+        let data = server_encode_svc_messages(svc_messages, drdynvc_channel_id, user_channel_id)?;
+        framed.write_all(&data).await?;
+
+        Ok(())
+    }
+
     async fn handle_input_backlog<S>(
         &mut self,
         framed: &mut Framed<S>,
@@ -398,6 +1226,8 @@ impl RdpServer {
     }
 
     async fn handle_fastpath(&mut self, input: FastPathInput) {
+        *self.last_input.lock().unwrap() = Instant::now();
+
         for event in input.0 {
             let mut handler = self.handler.lock().unwrap();
             match event {
@@ -441,6 +1271,34 @@ impl RdpServer {
                     self.handle_input_event(pdu).await;
                 }
 
+                rdp::headers::ShareDataPdu::FrameAcknowledge(ack) => {
+                    trace!(?ack);
+
+                    if let Some(frames) = &mut self.frames {
+                        frames.acknowledge(ack.frame_id);
+                    }
+                }
+
+                rdp::headers::ShareDataPdu::SuppressOutput(pdu) => {
+                    let suppressed = pdu.desktop_rect.is_none();
+                    debug!(suppressed, "Client display output");
+
+                    // The whole desktop is repainted when the client resumes
+                    self.refresh_areas.extend(pdu.desktop_rect);
+
+                    if suppressed != self.output_suppressed {
+                        self.output_suppressed = suppressed;
+                        self.display.suppress_output(suppressed).await;
+                    }
+                }
+
+                rdp::headers::ShareDataPdu::RefreshRectangle(pdu) => {
+                    debug!(areas = ?pdu.areas_to_refresh, "Client refresh request");
+
+                    self.display.refresh(&pdu.areas_to_refresh).await;
+                    self.refresh_areas.extend(pdu.areas_to_refresh);
+                }
+
                 rdp::headers::ShareDataPdu::ShutdownRequest => {
                     return Ok(true);
                 }
@@ -498,6 +1356,8 @@ impl RdpServer {
     }
 
     async fn handle_input_event(&mut self, input: InputEventPdu) {
+        *self.last_input.lock().unwrap() = Instant::now();
+
         for event in input.0 {
             let mut handler = self.handler.lock().unwrap();
             match event {
@@ -530,3 +1390,315 @@ impl RdpServer {
         }
     }
 }
+
+/// Writes a fragmented fast-path update
+async fn write_update<S>(
+    framed: &mut Framed<S>,
+    mut fragmenter: UpdateFragmenter<'_>,
+    buffer: &mut Vec<u8>,
+) -> Result<()>
+where
+    S: FramedWrite,
+{
+    if fragmenter.size_hint() > buffer.len() {
+        buffer.resize(fragmenter.size_hint(), 0);
+    }
+
+    while let Some(len) = fragmenter.next(buffer) {
+        framed.write_all(&buffer[..len]).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends a share data PDU on the I/O channel
+async fn write_share_data<S>(
+    framed: &mut Framed<S>,
+    pdu: rdp::headers::ShareDataPdu,
+    io_channel_id: u16,
+    user_channel_id: u16,
+) -> Result<()>
+where
+    S: FramedWrite,
+{
+    let pdu = rdp::headers::ShareControlHeader {
+        share_id: 0,
+        pdu_source: io_channel_id,
+        share_control_pdu: rdp::headers::ShareControlPdu::Data(rdp::headers::ShareDataHeader {
+            share_data_pdu: pdu,
+            stream_priority: rdp::headers::StreamPriority::Undefined,
+            compression_flags: rdp::headers::CompressionFlags::empty(),
+            compression_type: rdp::client_info::CompressionType::K8,
+        }),
+    };
+
+    let mut user_data = Vec::with_capacity(pdu.buffer_length());
+    pdu.to_buffer(&mut user_data)?;
+
+    let indication = SendDataIndication {
+        initiator_id: user_channel_id,
+        channel_id: io_channel_id,
+        user_data: user_data.into(),
+    };
+    framed.write_all(&ironrdp_pdu::encode_vec(&indication)?).await?;
+
+    Ok(())
+}
+
+/// Sends the logon notification, followed by the auto-reconnect random if any
+async fn send_logon_info<S>(
+    framed: &mut Framed<S>,
+    session_id: u32,
+    identity: Option<&UserIdentity>,
+    client_data: Option<&ClientConnectionData>,
+    auto_reconnect: Option<&ServerAutoReconnect>,
+    io_channel_id: u16,
+    user_channel_id: u16,
+) -> Result<()>
+where
+    S: FramedWrite,
+{
+    let (user_name, domain_name) = match (identity, client_data) {
+        (Some(identity), _) => (identity.username.clone(), identity.domain.clone()),
+        (None, Some(client)) => (
+            client.client_info.credentials.username.clone(),
+            client.client_info.credentials.domain.clone(),
+        ),
+        (None, None) => (String::new(), None),
+    };
+
+    let logon = SaveSessionInfoPdu {
+        info_type: InfoType::LogonLong,
+        info_data: InfoData::LogonInfoV2(LogonInfoVersion2 {
+            logon_info: LogonInfo {
+                session_id,
+                user_name,
+                domain_name: domain_name.unwrap_or_default(),
+            },
+        }),
+    };
+    write_share_data(
+        framed,
+        rdp::headers::ShareDataPdu::SaveSessionInfo(logon),
+        io_channel_id,
+        user_channel_id,
+    )
+    .await?;
+
+    if let Some(auto_reconnect) = auto_reconnect {
+        let extended = SaveSessionInfoPdu {
+            info_type: InfoType::LogonExtended,
+            info_data: InfoData::LogonExtended(LogonInfoExtended {
+                present_fields_flags: LogonExFlags::AUTO_RECONNECT_COOKIE,
+                auto_reconnect: Some(auto_reconnect.clone()),
+                errors_info: None,
+            }),
+        };
+        write_share_data(
+            framed,
+            rdp::headers::ShareDataPdu::SaveSessionInfo(extended),
+            io_channel_id,
+            user_channel_id,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Sends a Set Error Info PDU with `reason`, followed by a Disconnect Provider Ultimatum
+async fn disconnect<S>(
+    framed: &mut Framed<S>,
+    reason: ErrorInfo,
+    io_channel_id: u16,
+    user_channel_id: u16,
+) -> Result<()>
+where
+    S: FramedWrite,
+{
+    let mut buf = WriteBuf::new();
+    ironrdp_acceptor::encode_disconnect(reason, user_channel_id, io_channel_id, &mut buf)?;
+    framed.write_all(buf.filled()).await?;
+
+    Ok(())
+}
+
+/// Idle and maximum duration timeouts of a session
+///
+/// The deadlines are computed once, and only the idle deadline is pushed back on input.
+#[doc(hidden)]
+pub struct SessionTimeout {
+    idle_timeout: Option<Duration>,
+    last_input: Instant,
+    idle: Option<Pin<Box<Sleep>>>,
+    max_duration: Option<Pin<Box<Sleep>>>,
+}
+
+impl SessionTimeout {
+    pub fn new(opts: &RdpServerOptions, started: Instant) -> Self {
+        let deadline = |timeout: Duration| Box::pin(tokio::time::sleep_until((started + timeout).into()));
+
+        Self {
+            idle_timeout: opts.idle_timeout,
+            last_input: started,
+            idle: opts.idle_timeout.map(deadline),
+            max_duration: opts.max_session_duration.map(deadline),
+        }
+    }
+
+    /// Pushes the idle deadline back, if the client sent input since the last call.
+    pub fn input(&mut self, last_input: Instant) {
+        if last_input == self.last_input {
+            return;
+        }
+        self.last_input = last_input;
+
+        if let (Some(timeout), Some(idle)) = (self.idle_timeout, &mut self.idle) {
+            idle.as_mut().reset((last_input + timeout).into());
+        }
+    }
+
+    /// Waits for the first timeout to elapse, returning the disconnection reason.
+    ///
+    /// This method is cancellation safe.
+    pub async fn elapsed(&mut self) -> ErrorInfo {
+        let Self { idle, max_duration, .. } = self;
+
+        let code = tokio::select! {
+            () = sleep(idle) => ProtocolIndependentCode::IdleTimeout,
+            () = sleep(max_duration) => ProtocolIndependentCode::LogonTimeout,
+        };
+
+        ErrorInfo::ProtocolIndependentCode(code)
+    }
+}
+
+/// Waits for the timer, or forever if there is none
+async fn sleep(timer: &mut Option<Pin<Box<Sleep>>>) {
+    match timer {
+        Some(timer) => timer.await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Whether the client supports the Suppress Output PDU
+fn suppress_output_support(capabilities: &[CapabilitySet]) -> bool {
+    capabilities
+        .iter()
+        .any(|c| matches!(c, CapabilitySet::General(c) if c.suppress_output_support))
+}
+
+/// Waits until `deadline`, or forever if there is none
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Fast-path frames are tracked when the client acknowledges frame markers
+fn frame_tracker(capabilities: &[CapabilitySet]) -> Option<FrameTracker> {
+    let frame_markers = capabilities
+        .iter()
+        .any(|c| matches!(c, CapabilitySet::SurfaceCommands(c) if c.flags.contains(CmdFlags::FRAME_MARKER)));
+
+    let max_in_flight = capabilities.iter().find_map(|c| match c {
+        CapabilitySet::FrameAcknowledge(c) => Some(c.max_unacknowledged_frame_count),
+        _ => None,
+    });
+
+    // A zero count means that the client doesn't acknowledge frames
+    let max_in_flight = max_in_flight.filter(|&count| frame_markers && count > 0)?;
+    debug!(max_in_flight, "Frame acknowledgement enabled");
+
+    Some(FrameTracker::new(usize::try_from(max_in_flight).unwrap_or(usize::MAX)))
+}
+
+/// Waits for audio data, with its timestamp in milliseconds, or forever if the audio output is stopped
+async fn next_sound_frames(
+    sound_frames: &mut Option<(Box<dyn RdpServerSoundFrames>, Instant)>,
+) -> Option<(Vec<u8>, u32)> {
+    let Some((frames, start)) = sound_frames else {
+        return std::future::pending().await;
+    };
+
+    let Some(data) = frames.next_frames().await else {
+        debug!("Audio output ended");
+        *sound_frames = None;
+        return None;
+    };
+
+    // Truncation is intended, the timestamps wrap around
+    #[allow(clippy::cast_possible_truncation)]
+    let timestamp = start.elapsed().as_millis() as u32;
+
+    Some((data, timestamp))
+}
+
+/// Builds the display update encoder from the capabilities confirmed by the client
+fn update_encoder(capabilities: Vec<CapabilitySet>, desktop_size: DesktopSize) -> Result<UpdateEncoder> {
+    let mut surface_flags = CmdFlags::empty();
+    let mut rfx_codec = None;
+    let mut pointer = PointerSettings {
+        cache_size: 0,
+        alpha: false,
+        large: false,
+    };
+    for c in capabilities {
+        match c {
+            CapabilitySet::General(c) => {
+                let fastpath = c.extra_flags.contains(GeneralExtraFlags::FASTPATH_OUTPUT_SUPPORTED);
+                if !fastpath {
+                    bail!("Fastpath output not supported!");
+                }
+            }
+            CapabilitySet::SurfaceCommands(c) => {
+                surface_flags = c.flags;
+            }
+            CapabilitySet::Pointer(c) => {
+                // A non-zero pointer cache size means that the client supports 32 bpp pointers
+                pointer.alpha = c.pointer_cache_size != 0;
+                pointer.cache_size = if pointer.alpha {
+                    c.pointer_cache_size
+                } else {
+                    c.color_pointer_cache_size
+                }
+                .min(capabilities::POINTER_CACHE_SIZE);
+            }
+            CapabilitySet::LargePointer(c) => {
+                pointer.large = c.flags.contains(LargePointerSupportFlags::UP_TO_384X384_PIXELS);
+            }
+            CapabilitySet::BitmapCodecs(BitmapCodecs(codecs)) => {
+                rfx_codec = codecs.into_iter().find_map(|codec| match codec.property {
+                    CodecProperty::RemoteFx(RemoteFxContainer::ClientContainer(container)) => {
+                        let RfxCaps(RfxCapset(icaps)) = container.caps_data;
+
+                        // RLGR3 compresses better, but RLGR1 is mandatory
+                        let entropy_algorithm = if icaps.iter().any(|icap| icap.entropy_bits == EntropyBits::Rlgr3) {
+                            EntropyAlgorithm::Rlgr3
+                        } else {
+                            EntropyAlgorithm::Rlgr1
+                        };
+
+                        Some(RfxCodec {
+                            id: codec.id,
+                            entropy_algorithm,
+                        })
+                    }
+                    _ => None,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    debug!(
+        ?surface_flags,
+        ?rfx_codec,
+        ?pointer,
+        ?desktop_size,
+        "Negotiated display encoding"
+    );
+
+    Ok(UpdateEncoder::new(surface_flags, rfx_codec, pointer, desktop_size))
+}
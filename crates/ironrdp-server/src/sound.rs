@@ -0,0 +1,68 @@
+use anyhow::Result;
+pub use ironrdp_rdpsnd::pdu::{AudioFormat, WaveFormat};
+
+/// Audio frames receiver for an RDP server
+///
+/// The RDP server will repeatedly call the `next_frames` method to receive
+/// audio data which will then be sent to the client on the RDPSND channel
+#[async_trait::async_trait]
+pub trait RdpServerSoundFrames: Send {
+    /// Returns audio data in the format given to [`RdpServerSound::start`].
+    ///
+    /// # Cancel safety
+    ///
+    /// This method MUST be cancellation safe because it is used in a
+    /// `tokio::select!` statement. If some other branch completes first, it
+    /// MUST be guaranteed that no data is lost.
+    async fn next_frames(&mut self) -> Option<Vec<u8>>;
+}
+
+/// Audio output for an RDP server
+///
+/// # Example
+///
+/// ```
+///# use anyhow::Result;
+/// use ironrdp_server::{AudioFormat, RdpServerSound, RdpServerSoundFrames};
+///
+/// pub struct SoundFrames {
+///     receiver: tokio::sync::mpsc::Receiver<Vec<u8>>,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl RdpServerSoundFrames for SoundFrames {
+///     async fn next_frames(&mut self) -> Option<Vec<u8>> {
+///         self.receiver.recv().await
+///     }
+/// }
+///
+/// pub struct SoundHandler;
+///
+/// #[async_trait::async_trait]
+/// impl RdpServerSound for SoundHandler {
+///     fn formats(&self) -> Vec<AudioFormat> {
+///         vec![AudioFormat::pcm(2, 44_100, 16)]
+///     }
+///
+///     async fn start(&mut self, _format: AudioFormat) -> Result<Box<dyn RdpServerSoundFrames>> {
+///         Ok(Box::new(SoundFrames { receiver: todo!() }))
+///     }
+/// }
+/// ```
+#[async_trait::async_trait]
+pub trait RdpServerSound: Send {
+    /// Returns the formats the application can produce, in order of preference.
+    fn formats(&self) -> Vec<AudioFormat>;
+
+    /// Called once the client is ready to play audio, with the first of [`RdpServerSound::formats`]
+    /// supported by the client.
+    async fn start(&mut self, format: AudioFormat) -> Result<Box<dyn RdpServerSoundFrames>>;
+
+    /// Called when the client stops playing audio.
+    fn stop(&mut self) {}
+}
+
+pub trait RdpServerSoundFactory: Send {
+    /// Builds a new audio output for a freshly accepted connection.
+    fn build_sound(&self) -> Box<dyn RdpServerSound>;
+}
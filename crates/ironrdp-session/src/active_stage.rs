@@ -1,10 +1,14 @@
+use std::borrow::Cow;
+use std::mem;
 use std::rc::Rc;
 
 use ironrdp_connector::ConnectionResult;
 use ironrdp_graphics::pointer::DecodedPointer;
+use ironrdp_pdu::crypto::standard_security::StandardSecurity;
 use ironrdp_pdu::geometry::InclusiveRectangle;
 use ironrdp_pdu::input::fast_path::{FastPathInput, FastPathInputEvent};
-use ironrdp_pdu::rdp::headers::ShareDataPdu;
+use ironrdp_pdu::rdp::headers::{BasicSecurityHeaderFlags, ShareDataPdu};
+use ironrdp_pdu::rdp::session_info::ServerAutoReconnect;
 use ironrdp_pdu::write_buf::WriteBuf;
 use ironrdp_pdu::{mcs, Action, PduParsing};
 use ironrdp_svc::{SvcProcessor, SvcProcessorMessages};
@@ -12,12 +16,14 @@ use ironrdp_svc::{SvcProcessor, SvcProcessorMessages};
 use crate::fast_path::UpdateKind;
 use crate::image::DecodedImage;
 use crate::x224::GfxHandler;
-use crate::{fast_path, x224, SessionError, SessionResult};
+use crate::{fast_path, x224, SessionError, SessionErrorExt as _, SessionResult};
 
 pub struct ActiveStage {
     x224_processor: x224::Processor,
     fast_path_processor: fast_path::Processor,
     no_server_pointer: bool,
+    /// Set when Standard RDP Security is used, received frames are then decrypted and sent frames encrypted
+    standard_security: Option<StandardSecurity>,
 }
 
 impl ActiveStage {
@@ -42,6 +48,7 @@ impl ActiveStage {
             x224_processor,
             fast_path_processor,
             no_server_pointer: connection_result.no_server_pointer,
+            standard_security: connection_result.standard_security,
         }
     }
 
@@ -71,7 +78,7 @@ impl ActiveStage {
         fastpath_input
             .to_buffer(&mut frame)
             .map_err(|e| custom_err!("FastPathInput encode", e))?;
-        output.push(ActiveStageOutput::ResponseFrame(frame));
+        output.push(ActiveStageOutput::ResponseFrame(self.encrypt_frames(frame)?));
 
         // If pointer rendering is disabled - we can skip the rest
         if self.no_server_pointer {
@@ -106,10 +113,15 @@ impl ActiveStage {
         action: Action,
         frame: &[u8],
     ) -> SessionResult<Vec<ActiveStageOutput>> {
+        let frame = match self.standard_security.as_mut() {
+            Some(standard_security) => Cow::Owned(decrypt_frame(standard_security, action, frame)?),
+            None => Cow::Borrowed(frame),
+        };
+
         let (mut stage_outputs, processor_updates) = match action {
             Action::FastPath => {
                 let mut output = WriteBuf::new();
-                let processor_updates = self.fast_path_processor.process(image, frame, &mut output)?;
+                let processor_updates = self.fast_path_processor.process(image, &frame, &mut output)?;
                 (
                     vec![ActiveStageOutput::ResponseFrame(output.into_inner())],
                     processor_updates,
@@ -118,7 +130,7 @@ impl ActiveStage {
             Action::X224 => {
                 let outputs = self
                     .x224_processor
-                    .process(frame)?
+                    .process(&frame)?
                     .into_iter()
                     .map(TryFrom::try_from)
                     .collect::<Result<Vec<_>, _>>()?;
@@ -126,6 +138,12 @@ impl ActiveStage {
             }
         };
 
+        for output in stage_outputs.iter_mut() {
+            if let ActiveStageOutput::ResponseFrame(frame) = output {
+                *frame = self.encrypt_frames(mem::take(frame))?;
+            }
+        }
+
         for update in processor_updates {
             match update {
                 UpdateKind::None => {}
@@ -156,22 +174,38 @@ impl ActiveStage {
     /// Client-side graceful shutdown is defined in [MS-RDPBCGR]
     ///
     /// [MS-RDPBCGR]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpbcgr/27915739-8f77-487e-9927-55008af7fd68
-    pub fn graceful_shutdown(&self) -> SessionResult<Vec<ActiveStageOutput>> {
+    pub fn graceful_shutdown(&mut self) -> SessionResult<Vec<ActiveStageOutput>> {
         let mut frame = WriteBuf::new();
-        self.x224_processor
-            .encode_static(&mut frame, ShareDataPdu::ShutdownRequest)?;
+        self.encode_static(&mut frame, ShareDataPdu::ShutdownRequest)?;
 
         Ok(vec![ActiveStageOutput::ResponseFrame(frame.into_inner())])
     }
 
     /// Sends a PDU on the dynamic channel.
-    pub fn encode_dynamic(&self, output: &mut WriteBuf, channel_name: &str, dvc_data: &[u8]) -> SessionResult<()> {
-        self.x224_processor.encode_dynamic(output, channel_name, dvc_data)
+    pub fn encode_dynamic(&mut self, output: &mut WriteBuf, channel_name: &str, dvc_data: &[u8]) -> SessionResult<()> {
+        let mut frame = WriteBuf::new();
+        self.x224_processor.encode_dynamic(&mut frame, channel_name, dvc_data)?;
+        output.write_slice(&self.encrypt_frames(frame.into_inner())?);
+
+        Ok(())
     }
 
     /// Send a pdu on the static global channel. Typically used to send input events
-    pub fn encode_static(&self, output: &mut WriteBuf, pdu: ShareDataPdu) -> SessionResult<usize> {
-        self.x224_processor.encode_static(output, pdu)
+    pub fn encode_static(&mut self, output: &mut WriteBuf, pdu: ShareDataPdu) -> SessionResult<usize> {
+        let mut frame = WriteBuf::new();
+        self.x224_processor.encode_static(&mut frame, pdu)?;
+        let frame = self.encrypt_frames(frame.into_inner())?;
+        output.write_slice(&frame);
+
+        Ok(frame.len())
+    }
+
+    /// Returns the auto-reconnect cookie received from the server, if any.
+    ///
+    /// It can be set in [`ironrdp_connector::Config::auto_reconnect_cookie`] to resume this session
+    /// when connecting again.
+    pub fn auto_reconnect_cookie(&self) -> Option<&ServerAutoReconnect> {
+        self.x224_processor.auto_reconnect_cookie()
     }
 
     pub fn get_svc_processor<T: SvcProcessor + 'static>(&mut self) -> Option<&T> {
@@ -185,11 +219,87 @@ impl ActiveStage {
     /// Completes user's SVC request with data, required to sent it over the network and returns
     /// a buffer with encoded data.
     pub fn process_svc_processor_messages<C: SvcProcessor + 'static>(
-        &self,
+        &mut self,
         messages: SvcProcessorMessages<C>,
     ) -> SessionResult<Vec<u8>> {
-        self.x224_processor.process_svc_processor_messages(messages)
+        let frames = self.x224_processor.process_svc_processor_messages(messages)?;
+        self.encrypt_frames(frames)
     }
+
+    /// Encrypts each PDU of `frames` when Standard RDP Security is used
+    fn encrypt_frames(&mut self, frames: Vec<u8>) -> SessionResult<Vec<u8>> {
+        let Some(standard_security) = self.standard_security.as_mut() else {
+            return Ok(frames);
+        };
+
+        let mut encrypted = Vec::with_capacity(frames.len());
+        let mut remaining = frames.as_slice();
+
+        while !remaining.is_empty() {
+            let pdu_info = ironrdp_pdu::find_size(remaining)
+                .map_err(SessionError::pdu)?
+                .ok_or_else(|| general_err!("truncated PDU"))?;
+            if remaining.len() < pdu_info.length {
+                return Err(general_err!("truncated PDU"));
+            }
+            let (pdu, rest) = remaining.split_at(pdu_info.length);
+
+            match pdu_info.action {
+                Action::FastPath => encrypted.extend_from_slice(
+                    &standard_security
+                        .encrypt_fast_path(pdu)
+                        .map_err(|e| custom_err!("encrypt fast-path PDU", e))?,
+                ),
+                Action::X224 => encrypted.extend_from_slice(&encrypt_send_data_request(standard_security, pdu)?),
+            }
+
+            remaining = rest;
+        }
+
+        Ok(encrypted)
+    }
+}
+
+/// Decrypts a frame received from the server, re-encoding it without encryption
+fn decrypt_frame(standard_security: &mut StandardSecurity, action: Action, frame: &[u8]) -> SessionResult<Vec<u8>> {
+    match action {
+        Action::FastPath => standard_security
+            .decrypt_fast_path(frame)
+            .map_err(|e| custom_err!("decrypt fast-path PDU", e)),
+        Action::X224 => {
+            let mcs::McsMessage::SendDataIndication(send_data_indication) =
+                ironrdp_pdu::decode::<mcs::McsMessage<'_>>(frame).map_err(SessionError::pdu)?
+            else {
+                return Ok(frame.to_vec());
+            };
+
+            // The basic security header is present even when the server does not encrypt its data.
+            let (_, user_data) = standard_security
+                .decrypt_user_data(&send_data_indication.user_data)
+                .map_err(|e| custom_err!("decrypt user data", e))?;
+
+            let send_data_indication = mcs::SendDataIndication {
+                user_data: Cow::Owned(user_data),
+                ..send_data_indication
+            };
+
+            ironrdp_pdu::encode_vec(&send_data_indication).map_err(SessionError::pdu)
+        }
+    }
+}
+
+/// Encrypts the user data of a Send Data Request
+fn encrypt_send_data_request(standard_security: &mut StandardSecurity, pdu: &[u8]) -> SessionResult<Vec<u8>> {
+    let send_data_request = ironrdp_pdu::decode::<mcs::SendDataRequest<'_>>(pdu).map_err(SessionError::pdu)?;
+
+    let send_data_request = mcs::SendDataRequest {
+        user_data: Cow::Owned(
+            standard_security.encrypt_user_data(BasicSecurityHeaderFlags::empty(), &send_data_request.user_data),
+        ),
+        ..send_data_request
+    };
+
+    ironrdp_pdu::encode_vec(&send_data_request).map_err(SessionError::pdu)
 }
 
 #[derive(Debug)]
@@ -4,6 +4,7 @@ use ironrdp_graphics::color_conversion::rdp_16bit_to_rgb;
 use ironrdp_graphics::image_processing::{ImageRegion, ImageRegionMut, PixelFormat};
 use ironrdp_graphics::pointer::DecodedPointer;
 use ironrdp_graphics::rectangle_processing::Region;
+use ironrdp_pdu::gcc;
 use ironrdp_pdu::geometry::{InclusiveRectangle, Rectangle as _};
 
 use crate::SessionResult;
@@ -158,6 +159,40 @@ impl DecodedImage {
         self.height
     }
 
+    /// Returns the area of each monitor of a layout in the image, which spans the whole virtual desktop
+    ///
+    /// Monitors left of or above the primary monitor have negative coordinates, whereas the top-left
+    /// corner of the image is the top-left corner of the bounding box of the monitors. Areas are clipped
+    /// to the image, and empty ones are `None`.
+    pub fn monitor_regions(&self, monitors: &[gcc::Monitor]) -> Vec<Option<InclusiveRectangle>> {
+        let origin_x = monitors.iter().map(|m| i64::from(m.left)).min().unwrap_or(0);
+        let origin_y = monitors.iter().map(|m| i64::from(m.top)).min().unwrap_or(0);
+
+        let max_x = i64::from(self.width) - 1;
+        let max_y = i64::from(self.height) - 1;
+
+        monitors
+            .iter()
+            .map(|m| {
+                let left = i64::from(m.left) - origin_x;
+                let top = i64::from(m.top) - origin_y;
+                let right = (i64::from(m.right) - origin_x).min(max_x);
+                let bottom = (i64::from(m.bottom) - origin_y).min(max_y);
+
+                if left > right || top > bottom {
+                    return None;
+                }
+
+                Some(InclusiveRectangle {
+                    left: u16::try_from(left).ok()?,
+                    top: u16::try_from(top).ok()?,
+                    right: u16::try_from(right).ok()?,
+                    bottom: u16::try_from(bottom).ok()?,
+                })
+            })
+            .collect()
+    }
+
     fn apply_pointer_layer(&mut self, layer: PointerLayer) -> SessionResult<Option<InclusiveRectangle>> {
         // Pointer is not hidden, but its texture is not visible on the screen, so we don't
         // need to render it
@@ -10,6 +10,7 @@ use ironrdp_pdu::dvc::FieldType;
 use ironrdp_pdu::mcs::{DisconnectProviderUltimatum, DisconnectReason, McsMessage};
 use ironrdp_pdu::rdp::headers::ShareDataPdu;
 use ironrdp_pdu::rdp::server_error_info::{ErrorInfo, ProtocolIndependentCode, ServerSetErrorInfoPdu};
+use ironrdp_pdu::rdp::session_info::{InfoData, LogonInfoExtended, ServerAutoReconnect};
 use ironrdp_pdu::rdp::vc::dvc;
 use ironrdp_pdu::write_buf::WriteBuf;
 use ironrdp_svc::{client_encode_svc_messages, StaticChannelSet, SvcMessage, SvcProcessor, SvcProcessorMessages};
@@ -42,6 +43,8 @@ pub struct Processor {
     drdynvc_initialized: bool,
     graphics_config: Option<GraphicsConfig>,
     graphics_handler: Option<Box<dyn GfxHandler + Send>>,
+    /// Latest auto-reconnect cookie sent by the server.
+    auto_reconnect_cookie: Option<ServerAutoReconnect>,
 }
 
 impl Processor {
@@ -70,9 +73,15 @@ impl Processor {
             drdynvc_initialized: false,
             graphics_config,
             graphics_handler,
+            auto_reconnect_cookie: None,
         }
     }
 
+    /// Returns the auto-reconnect cookie received from the server, if any.
+    pub fn auto_reconnect_cookie(&self) -> Option<&ServerAutoReconnect> {
+        self.auto_reconnect_cookie.as_ref()
+    }
+
     pub fn get_svc_processor<T: SvcProcessor + 'static>(&mut self) -> Option<&T> {
         self.static_channels
             .get_by_type::<T>()
@@ -120,7 +129,7 @@ impl Processor {
         }
     }
 
-    fn process_io_channel(&self, data_ctx: SendDataIndicationCtx<'_>) -> SessionResult<Vec<ProcessorOutput>> {
+    fn process_io_channel(&mut self, data_ctx: SendDataIndicationCtx<'_>) -> SessionResult<Vec<ProcessorOutput>> {
         debug_assert_eq!(data_ctx.channel_id, self.io_channel_id);
 
         let ctx = ironrdp_connector::legacy::decode_share_data(data_ctx).map_err(crate::legacy::map_error)?;
@@ -128,6 +137,16 @@ impl Processor {
         match ctx.pdu {
             ShareDataPdu::SaveSessionInfo(session_info) => {
                 debug!("Got Session Save Info PDU: {session_info:?}");
+
+                if let InfoData::LogonExtended(LogonInfoExtended {
+                    auto_reconnect: Some(auto_reconnect),
+                    ..
+                }) = session_info.info_data
+                {
+                    debug!(logon_id = auto_reconnect.logon_id, "Received auto-reconnect cookie");
+                    self.auto_reconnect_cookie = Some(auto_reconnect);
+                }
+
                 Ok(Vec::new())
             }
             ShareDataPdu::ServerSetErrorInfo(ServerSetErrorInfoPdu(ErrorInfo::ProtocolIndependentCode(
@@ -259,6 +259,44 @@ pub trait SvcServerProcessor: SvcProcessor {}
 
 assert_obj_safe!(SvcServerProcessor);
 
+/// Boxed processors keep the type information of the boxed value, so that they can be downcast
+/// and stored in a [`StaticChannelSet`] alongside other boxed processors.
+impl AsAny for Box<dyn SvcServerProcessor> {
+    #[inline]
+    fn as_any(&self) -> &dyn Any {
+        (**self).as_any()
+    }
+
+    #[inline]
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        (**self).as_any_mut()
+    }
+}
+
+impl SvcProcessor for Box<dyn SvcServerProcessor> {
+    fn channel_name(&self) -> ChannelName {
+        (**self).channel_name()
+    }
+
+    fn compression_condition(&self) -> CompressionCondition {
+        (**self).compression_condition()
+    }
+
+    fn start(&mut self) -> PduResult<Vec<SvcMessage>> {
+        (**self).start()
+    }
+
+    fn process(&mut self, payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
+        (**self).process(payload)
+    }
+
+    fn is_drdynvc(&self) -> bool {
+        (**self).is_drdynvc()
+    }
+}
+
+impl SvcServerProcessor for Box<dyn SvcServerProcessor> {}
+
 /// ChunkProcessor is used to chunkify/de-chunkify static virtual channel PDUs.
 #[derive(Debug)]
 struct ChunkProcessor {
@@ -459,8 +497,10 @@ impl StaticChannelSet {
     /// Inserts a [`StaticVirtualChannel`] into this [`StaticChannelSet`].
     ///
     /// If a static virtual channel of this type already exists, it is returned.
+    /// Boxed processors are inserted under the type of the boxed value.
     pub fn insert<T: SvcProcessor + 'static>(&mut self, val: T) -> Option<StaticVirtualChannel> {
-        self.channels.insert(TypeId::of::<T>(), StaticVirtualChannel::new(val))
+        let type_id = val.as_any().type_id();
+        self.channels.insert(type_id, StaticVirtualChannel::new(val))
     }
 
     /// Gets a reference to a [`StaticVirtualChannel`] by looking up its internal [`SvcProcessor`]'s [`TypeId`].
@@ -2,6 +2,7 @@ use ironrdp_pdu::gcc;
 use ironrdp_pdu::rdp::finalization_messages::*;
 use ironrdp_pdu::rdp::headers::*;
 use ironrdp_pdu::rdp::server_license::*;
+use ironrdp_pdu::rdp::server_redirection::*;
 use ironrdp_pdu::rdp::*;
 
 use crate::capsets::{
@@ -139,6 +140,56 @@ pub const SERVER_FONT_MAP_BUFFER: [u8; 26] = [
     0x04, 0x00, // entry size
 ];
 
+pub const SERVER_DEACTIVATE_ALL_BUFFER: [u8; 13] = [
+    0x0d, 0x00, // ShareControlHeader::totalLength
+    0x16, 0x00, // ShareControlHeader::pduType
+    0xea, 0x03, // ShareControlHeader::PduSource
+    0xea, 0x03, 0x01, 0x00, // share id
+    0x01, 0x00, // length source descriptor
+    0x00, // source descriptor
+];
+
+/// Redirection of a client to a session host by a connection broker, with the routing token
+/// encoding the IP address and port of the session host
+pub const SERVER_REDIRECTION_BUFFER: [u8; 220] = [
+    0xdc, 0x00, // ShareControlHeader::totalLength
+    0x1a, 0x00, // ShareControlHeader::pduType: PDUTYPE_SERVER_REDIR_PKT
+    0xea, 0x03, // ShareControlHeader::PduSource
+    0x00, 0x00, // pad2Octets
+    0x00, 0x04, // Flags: SEC_REDIRECTION_PKT
+    0xd4, 0x00, // Length
+    0x03, 0x00, 0x00, 0x00, // SessionID
+    0x0f, 0x2b, 0x00,
+    0x00, // RedirFlags: LB_TARGET_NET_ADDRESS | LB_LOAD_BALANCE_INFO | LB_USERNAME | LB_DOMAIN | LB_TARGET_FQDN
+    // | LB_TARGET_NETBIOS_NAME | LB_TARGET_NET_ADDRESSES | LB_SERVER_TSV_CAPABLE
+    0x12, 0x00, 0x00, 0x00, // TargetNetAddressLength
+    0x31, 0x00, 0x30, 0x00, 0x2e, 0x00, 0x30, 0x00, 0x2e, 0x00, 0x30, 0x00, 0x2e, 0x00, 0x32, 0x00, 0x00,
+    0x00, // TargetNetAddress: "10.0.0.2"
+    0x22, 0x00, 0x00, 0x00, // LoadBalanceInfoLength
+    0x43, 0x6f, 0x6f, 0x6b, 0x69, 0x65, 0x3a, 0x20, 0x6d, 0x73, 0x74, 0x73, 0x3d, 0x33, 0x33, 0x35, 0x35, 0x34, 0x34,
+    0x34, 0x32, 0x2e, 0x31, 0x35, 0x36, 0x32, 0x39, 0x2e, 0x30, 0x30, 0x30, 0x30, 0x0d,
+    0x0a, // LoadBalanceInfo: "Cookie: msts=33554442.15629.0000\r\n"
+    0x0a, 0x00, 0x00, 0x00, // UserNameLength
+    0x75, 0x00, 0x73, 0x00, 0x65, 0x00, 0x72, 0x00, 0x00, 0x00, // UserName: "user"
+    0x10, 0x00, 0x00, 0x00, // DomainLength
+    0x43, 0x00, 0x4f, 0x00, 0x4e, 0x00, 0x54, 0x00, 0x4f, 0x00, 0x53, 0x00, 0x4f, 0x00, 0x00,
+    0x00, // Domain: "CONTOSO"
+    0x24, 0x00, 0x00, 0x00, // TargetFQDNLength
+    0x72, 0x00, 0x64, 0x00, 0x73, 0x00, 0x68, 0x00, 0x32, 0x00, 0x2e, 0x00, 0x63, 0x00, 0x6f, 0x00, 0x6e, 0x00, 0x74,
+    0x00, 0x6f, 0x00, 0x73, 0x00, 0x6f, 0x00, 0x2e, 0x00, 0x63, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x00,
+    0x00, // TargetFQDN: "rdsh2.contoso.com"
+    0x0c, 0x00, 0x00, 0x00, // TargetNetBiosNameLength
+    0x52, 0x00, 0x44, 0x00, 0x53, 0x00, 0x48, 0x00, 0x32, 0x00, 0x00, 0x00, // TargetNetBiosName: "RDSH2"
+    0x2e, 0x00, 0x00, 0x00, // TargetNetAddressesLength
+    0x02, 0x00, 0x00, 0x00, // TargetNetAddresses::addressCount
+    0x12, 0x00, 0x00, 0x00, // TargetNetAddress::length
+    0x31, 0x00, 0x30, 0x00, 0x2e, 0x00, 0x30, 0x00, 0x2e, 0x00, 0x30, 0x00, 0x2e, 0x00, 0x32, 0x00, 0x00,
+    0x00, // TargetNetAddress::address: "10.0.0.2"
+    0x10, 0x00, 0x00, 0x00, // TargetNetAddress::length
+    0x66, 0x00, 0x64, 0x00, 0x30, 0x00, 0x30, 0x00, 0x3a, 0x00, 0x3a, 0x00, 0x32, 0x00, 0x00,
+    0x00, // TargetNetAddress::address: "fd00::2"
+];
+
 pub const SERVER_LICENSE_BUFFER: [u8; 20] = [
     0x80, 0x00, // flags
     0x00, 0x00, // flagsHi
@@ -260,6 +311,27 @@ lazy_static! {
         pdu_source: 1002,
         share_id: 66_538,
     };
+    pub static ref SERVER_DEACTIVATE_ALL: ShareControlHeader = ShareControlHeader {
+        share_control_pdu: ShareControlPdu::ServerDeactivateAll(ServerDeactivateAll),
+        pdu_source: 1002,
+        share_id: 66_538,
+    };
+    pub static ref SERVER_REDIRECTION: ShareControlHeader = ShareControlHeader {
+        share_control_pdu: ShareControlPdu::ServerRedirect(ServerRedirectionPdu {
+            session_id: 3,
+            flags: ServerRedirectionFlags::SERVER_TSV_CAPABLE,
+            target_net_address: Some("10.0.0.2".to_owned()),
+            load_balance_info: Some(b"Cookie: msts=33554442.15629.0000\r\n".to_vec()),
+            username: Some("user".to_owned()),
+            domain: Some("CONTOSO".to_owned()),
+            target_fqdn: Some("rdsh2.contoso.com".to_owned()),
+            target_netbios_name: Some("RDSH2".to_owned()),
+            target_net_addresses: Some(vec!["10.0.0.2".to_owned(), "fd00::2".to_owned()]),
+            ..Default::default()
+        }),
+        pdu_source: 1002,
+        share_id: 0,
+    };
     pub static ref MONITOR_LAYOUT_PDU: ShareControlHeader = ShareControlHeader {
         share_control_pdu: ShareControlPdu::Data(ShareDataHeader {
             share_data_pdu: ShareDataPdu::MonitorLayout(MonitorLayoutPdu {
@@ -0,0 +1,564 @@
+mod standard_security;
+
+use std::sync::Arc;
+
+use ironrdp_acceptor::{
+    Acceptor, AcceptorResult, AcceptorState, CredentialStore as _, CredentialValidator as _, Credentials, DesktopSize,
+    RdstlsAuthenticator, UserIdentity,
+};
+use ironrdp_connector::{ClientConnector, ClientConnectorState, ConnectorResult, Sequence, State as _};
+use ironrdp_pdu::gcc::KeyboardType;
+use ironrdp_pdu::input::fast_path::{FastPathInput, FastPathInputEvent, KeyboardFlags};
+use ironrdp_pdu::pcb::{PcbVersion, PreconnectionBlob};
+use ironrdp_pdu::rdp::capability_sets::{self, CapabilitySet, MajorPlatformType};
+use ironrdp_pdu::rdp::client_info::CompressionType;
+use ironrdp_pdu::rdp::headers::{
+    CompressionFlags, ShareControlHeader, ShareControlPdu, ShareDataHeader, ShareDataPdu, StreamPriority,
+};
+use ironrdp_pdu::rdp::refresh_rectangle::RefreshRectanglePdu;
+use ironrdp_pdu::rdp::server_error_info::{ErrorInfo, ProtocolIndependentCode};
+use ironrdp_pdu::rdstls::{
+    RdstlsAuthenticationRequest, RdstlsAuthenticationResponse, RdstlsCapabilities, RdstlsResultCode,
+};
+use ironrdp_pdu::write_buf::WriteBuf;
+use ironrdp_pdu::{mcs, nego, PduParsing as _};
+use ironrdp_testsuite_core::rdp::CLIENT_INFO_PDU;
+use rstest::rstest;
+
+const DESKTOP_SIZE: DesktopSize = DesktopSize {
+    width: 1024,
+    height: 768,
+};
+
+fn negotiate(server: nego::SecurityProtocol, client: nego::SecurityProtocol) -> (Acceptor, nego::ConnectionConfirm) {
+    let mut acceptor = Acceptor::new(server, DESKTOP_SIZE, Vec::new());
+    let mut buf = WriteBuf::new();
+
+    let request = nego::ConnectionRequest {
+        nego_data: None,
+        flags: nego::RequestFlags::empty(),
+        protocol: client,
+    };
+    let request = ironrdp_pdu::encode_vec(&request).unwrap();
+
+    acceptor.step(&request, &mut buf).unwrap();
+    acceptor.step_no_input(&mut buf).unwrap();
+
+    let confirm = ironrdp_pdu::decode::<nego::ConnectionConfirm>(buf.filled()).unwrap();
+
+    (acceptor, confirm)
+}
+
+fn client_config(enable_credssp: bool) -> ironrdp_connector::Config {
+    ironrdp_connector::Config {
+        desktop_size: DESKTOP_SIZE,
+        monitors: Vec::new(),
+        enable_tls: true,
+        enable_credssp,
+        enable_standard_security: false,
+        credential_delegation: ironrdp_connector::CredentialDelegation::Full,
+        credentials: ironrdp_connector::Credentials::UsernamePassword {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        },
+        domain: None,
+        client_build: 0,
+        client_name: "client".to_owned(),
+        keyboard_type: KeyboardType::IbmEnhanced,
+        keyboard_subtype: 0,
+        keyboard_functional_keys_count: 12,
+        ime_file_name: String::new(),
+        graphics: None,
+        bitmap: None,
+        dig_product_id: String::new(),
+        client_dir: String::new(),
+        platform: MajorPlatformType::UNSPECIFIED,
+        autologon: false,
+        auto_reconnect_cookie: None,
+        pcb: None,
+        no_server_pointer: true,
+        pointer_software_rendering: false,
+    }
+}
+
+fn client(enable_credssp: bool) -> ClientConnector {
+    ClientConnector::new(client_config(enable_credssp)).with_server_addr(([127, 0, 0, 1], 3389).into())
+}
+
+/// User authenticated by the CredSSP exchange, which is skipped by [`Wire::exchange_until`]
+fn credssp_identity() -> UserIdentity {
+    UserIdentity {
+        username: "user".to_owned(),
+        domain: Some("CONTOSO".to_owned()),
+    }
+}
+
+/// Steps the sequence once if it has something to send, or if `input` holds the PDU it waits for
+fn step(sequence: &mut dyn Sequence, input: &mut Vec<u8>, output: &mut Vec<u8>) -> ConnectorResult<bool> {
+    if sequence.state().is_terminal() {
+        return Ok(false);
+    }
+
+    let mut buf = WriteBuf::new();
+
+    match sequence.next_pdu_hint().map(|hint| hint.find_size(input).unwrap()) {
+        Some(Some(size)) => {
+            sequence.step(&input[..size], &mut buf)?;
+            input.drain(..size);
+        }
+        Some(None) => return Ok(false),
+        None => {
+            sequence.step_no_input(&mut buf)?;
+        }
+    }
+
+    output.extend_from_slice(buf.filled());
+
+    Ok(true)
+}
+
+/// PDUs sent by each side and not yet processed by the other one
+#[derive(Default)]
+struct Wire {
+    to_server: Vec<u8>,
+    to_client: Vec<u8>,
+}
+
+impl Wire {
+    /// Exchanges PDUs between the client and the server until `done` returns `true`
+    ///
+    /// The security upgrade and the CredSSP exchange are skipped on both sides.
+    fn exchange_until(
+        &mut self,
+        connector: &mut ClientConnector,
+        acceptor: &mut Acceptor,
+        done: impl Fn(&ClientConnector, &Acceptor) -> bool,
+    ) -> ConnectorResult<()> {
+        while !done(connector, acceptor) {
+            if connector.should_perform_security_upgrade() {
+                connector.mark_security_upgrade_as_done();
+            }
+            if connector.should_perform_credssp() {
+                connector.mark_credssp_as_done();
+            }
+            if acceptor.reached_security_upgrade().is_some() {
+                acceptor.mark_security_upgrade_as_done()?;
+            }
+            if acceptor.should_perform_credssp().is_some() {
+                acceptor.mark_credssp_as_done_for(credssp_identity())?;
+            }
+
+            let client_progress = step(connector, &mut self.to_client, &mut self.to_server)?;
+            let server_progress = step(acceptor, &mut self.to_server, &mut self.to_client)?;
+
+            assert!(
+                client_progress || server_progress,
+                "stalled with client in {} and server in {}",
+                connector.state.name(),
+                acceptor.state().name()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Runs the connection sequence on both sides, returning the server result
+    fn connect(&mut self, connector: &mut ClientConnector, acceptor: &mut Acceptor) -> ConnectorResult<AcceptorResult> {
+        self.exchange_until(connector, acceptor, |connector, acceptor| {
+            connector.state.is_terminal() && acceptor.state().is_terminal()
+        })?;
+
+        Ok(acceptor.get_result().unwrap())
+    }
+}
+
+#[rstest]
+#[case(nego::SecurityProtocol::SSL, nego::SecurityProtocol::SSL | nego::SecurityProtocol::HYBRID, nego::SecurityProtocol::SSL)]
+#[case(
+    nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX,
+    nego::SecurityProtocol::SSL | nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX,
+    nego::SecurityProtocol::HYBRID_EX
+)]
+#[case(
+    nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX,
+    nego::SecurityProtocol::SSL | nego::SecurityProtocol::HYBRID,
+    nego::SecurityProtocol::HYBRID
+)]
+#[case(
+    nego::SecurityProtocol::RDSTLS | nego::SecurityProtocol::HYBRID_EX,
+    nego::SecurityProtocol::RDSTLS,
+    nego::SecurityProtocol::RDSTLS
+)]
+fn selects_strongest_common_protocol(
+    #[case] server: nego::SecurityProtocol,
+    #[case] client: nego::SecurityProtocol,
+    #[case] expected: nego::SecurityProtocol,
+) {
+    let (mut acceptor, confirm) = negotiate(server, client);
+
+    let nego::ConnectionConfirm::Response { protocol, .. } = confirm else {
+        panic!("unexpected failure: {confirm:?}");
+    };
+    assert_eq!(protocol, expected);
+    assert_eq!(acceptor.reached_security_upgrade(), Some(expected));
+
+    acceptor.mark_security_upgrade_as_done().unwrap();
+
+    let nla = expected.intersects(nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX);
+    assert_eq!(acceptor.should_perform_credssp().is_some(), nla);
+}
+
+#[test]
+fn marking_steps_out_of_order_fails() {
+    let mut acceptor = Acceptor::new(nego::SecurityProtocol::HYBRID_EX, DESKTOP_SIZE, Vec::new());
+    acceptor.mark_security_upgrade_as_done().unwrap_err();
+    acceptor.mark_credssp_as_done().unwrap_err();
+
+    let (mut acceptor, _) = negotiate(nego::SecurityProtocol::HYBRID_EX, nego::SecurityProtocol::HYBRID_EX);
+    acceptor.mark_credssp_as_done_for(credssp_identity()).unwrap_err();
+    acceptor.mark_security_upgrade_as_done().unwrap();
+    acceptor.mark_security_upgrade_as_done().unwrap_err();
+    acceptor.mark_credssp_as_done_for(credssp_identity()).unwrap();
+    acceptor.mark_credssp_as_done().unwrap_err();
+}
+
+#[test]
+fn rejects_client_without_nla_when_required() {
+    let (mut acceptor, confirm) = negotiate(
+        nego::SecurityProtocol::HYBRID | nego::SecurityProtocol::HYBRID_EX,
+        nego::SecurityProtocol::SSL,
+    );
+
+    let nego::ConnectionConfirm::Failure { code } = confirm else {
+        panic!("unexpected response: {confirm:?}");
+    };
+    assert_eq!(code, nego::FailureCode::HYBRID_REQUIRED_BY_SERVER);
+
+    acceptor.step_no_input(&mut WriteBuf::new()).unwrap_err();
+}
+
+#[test]
+fn single_user_credential_store() {
+    let credentials = Credentials {
+        username: "user".to_owned(),
+        password: "pass".to_owned(),
+        domain: Some("CONTOSO".to_owned()),
+    };
+
+    assert!(credentials.credentials("User", Some("contoso")).is_some());
+    assert!(credentials.credentials("user", None).is_none());
+    assert!(credentials.credentials("other", Some("CONTOSO")).is_none());
+}
+
+#[test]
+fn single_user_credential_validator() {
+    let expected = Credentials {
+        username: "user".to_owned(),
+        password: "pass".to_owned(),
+        domain: None,
+    };
+    let client_info = &CLIENT_INFO_PDU.client_info;
+
+    let mut credentials = expected.clone();
+    credentials.username = "USER".to_owned();
+    assert_eq!(expected.validate(&credentials, client_info), Ok(()));
+
+    credentials.password = "wrong".to_owned();
+    assert_eq!(
+        expected.validate(&credentials, client_info),
+        Err(ErrorInfo::ProtocolIndependentCode(
+            ProtocolIndependentCode::ServerDeniedConnection
+        ))
+    );
+}
+
+fn single_user_validator(password: &str) -> Arc<Credentials> {
+    Arc::new(Credentials {
+        username: "user".to_owned(),
+        password: password.to_owned(),
+        domain: None,
+    })
+}
+
+#[test]
+fn logon_is_validated_without_nla() {
+    let mut connector = client(false);
+    let mut acceptor = Acceptor::new(nego::SecurityProtocol::SSL, DESKTOP_SIZE, Vec::new());
+    acceptor.attach_credential_validator(single_user_validator("pass"));
+
+    let result = Wire::default().connect(&mut connector, &mut acceptor).unwrap();
+
+    assert_eq!(
+        result.identity,
+        Some(UserIdentity {
+            username: "user".to_owned(),
+            domain: None,
+        })
+    );
+    assert!(result.client_data.unwrap().client_info.credentials.password.is_empty());
+}
+
+#[test]
+fn logon_with_wrong_password_is_rejected() {
+    let mut connector = client(false);
+    let mut acceptor = Acceptor::new(nego::SecurityProtocol::SSL, DESKTOP_SIZE, Vec::new());
+    acceptor.attach_credential_validator(single_user_validator("other"));
+
+    Wire::default()
+        .exchange_until(&mut connector, &mut acceptor, |_, acceptor| {
+            acceptor.state().name() == "LogonRejected"
+        })
+        .unwrap();
+
+    let Some(AcceptorState::LogonRejected { reason }) = acceptor.state().as_any().downcast_ref() else {
+        unreachable!()
+    };
+    assert_eq!(
+        *reason,
+        ErrorInfo::ProtocolIndependentCode(ProtocolIndependentCode::ServerDeniedConnection)
+    );
+    acceptor.step_no_input(&mut WriteBuf::new()).unwrap_err();
+}
+
+#[test]
+fn credssp_authenticated_logon_is_not_validated_again() {
+    let mut connector = client(true);
+    let mut acceptor = Acceptor::new(nego::SecurityProtocol::HYBRID_EX, DESKTOP_SIZE, Vec::new());
+    // The Client Info PDU holds a different password, such as when the client delegates a smart card logon
+    acceptor.attach_credential_validator(single_user_validator("other"));
+
+    let result = Wire::default().connect(&mut connector, &mut acceptor).unwrap();
+
+    assert_eq!(result.identity, Some(credssp_identity()));
+}
+
+#[test]
+fn deactivation_reactivation_requires_capabilities_exchange() {
+    let (acceptor, _) = negotiate(nego::SecurityProtocol::SSL, nego::SecurityProtocol::SSL);
+
+    let reactivation = Acceptor::new_deactivation_reactivation(acceptor, Default::default(), DESKTOP_SIZE);
+    assert!(reactivation.is_err());
+}
+
+#[test]
+fn deactivation_reactivation() {
+    const NEW_SIZE: DesktopSize = DesktopSize {
+        width: 1280,
+        height: 1024,
+    };
+
+    let bitmap = capability_sets::Bitmap {
+        pref_bits_per_pix: 32,
+        desktop_width: DESKTOP_SIZE.width,
+        desktop_height: DESKTOP_SIZE.height,
+        desktop_resize_flag: true,
+        drawing_flags: capability_sets::BitmapDrawingFlags::empty(),
+    };
+
+    let mut connector = client(false);
+    let mut acceptor = Acceptor::new(
+        nego::SecurityProtocol::SSL,
+        DESKTOP_SIZE,
+        vec![CapabilitySet::Bitmap(bitmap)],
+    );
+    let mut wire = Wire::default();
+
+    let result = wire.connect(&mut connector, &mut acceptor).unwrap();
+
+    let ClientConnectorState::Connected { result: connection } = &connector.state else {
+        unreachable!()
+    };
+    let (io_channel_id, user_channel_id) = (connection.io_channel_id, connection.user_channel_id);
+
+    let mut acceptor = Acceptor::new_deactivation_reactivation(acceptor, result.static_channels, NEW_SIZE).unwrap();
+
+    let mut buf = WriteBuf::new();
+    acceptor.step_no_input(&mut buf).unwrap();
+    let deactivate_all = ironrdp_pdu::decode::<mcs::SendDataIndication<'_>>(buf.filled()).unwrap();
+    let deactivate_all = ShareControlHeader::from_buffer(deactivate_all.user_data.as_ref()).unwrap();
+    assert!(matches!(
+        deactivate_all.share_control_pdu,
+        ShareControlPdu::ServerDeactivateAll(_)
+    ));
+
+    // The client runs the capabilities exchange again after the Deactivate All PDU
+    connector.state = ClientConnectorState::CapabilitiesExchange {
+        io_channel_id,
+        user_channel_id,
+    };
+
+    wire.exchange_until(&mut connector, &mut acceptor, |_, acceptor| {
+        acceptor.state().name() == "CapabilitiesWaitConfirm"
+    })
+    .unwrap();
+
+    // Input and virtual channel data sent by the client before processing the Deactivate All PDU
+    let fast_path_input = FastPathInput(vec![FastPathInputEvent::KeyboardEvent(KeyboardFlags::empty(), 0x1E)]);
+    let mut fast_path_input_frame = Vec::new();
+    fast_path_input.to_buffer(&mut fast_path_input_frame).unwrap();
+
+    let slow_path_input = ShareControlHeader {
+        share_id: 0,
+        pdu_source: user_channel_id,
+        share_control_pdu: ShareControlPdu::Data(ShareDataHeader {
+            share_data_pdu: ShareDataPdu::RefreshRectangle(RefreshRectanglePdu {
+                areas_to_refresh: Vec::new(),
+            }),
+            stream_priority: StreamPriority::Undefined,
+            compression_flags: CompressionFlags::empty(),
+            compression_type: CompressionType::K8,
+        }),
+    };
+    let mut user_data = Vec::new();
+    slow_path_input.to_buffer(&mut user_data).unwrap();
+    let slow_path_input_frame = ironrdp_pdu::encode_vec(&mcs::SendDataRequest {
+        initiator_id: user_channel_id,
+        channel_id: io_channel_id,
+        user_data: user_data.into(),
+    })
+    .unwrap();
+
+    let channel_data_frame = ironrdp_pdu::encode_vec(&mcs::SendDataRequest {
+        initiator_id: user_channel_id,
+        channel_id: io_channel_id + 1,
+        user_data: vec![0xAB; 8].into(),
+    })
+    .unwrap();
+
+    for frame in [&fast_path_input_frame, &slow_path_input_frame, &channel_data_frame] {
+        wire.to_server.extend_from_slice(frame);
+    }
+
+    let result = wire.connect(&mut connector, &mut acceptor).unwrap();
+
+    assert_eq!(result.desktop_size, NEW_SIZE);
+    assert_eq!(
+        result.input_events,
+        [fast_path_input_frame, slow_path_input_frame, channel_data_frame]
+    );
+
+    let ClientConnectorState::Connected { result: connection } = &connector.state else {
+        unreachable!()
+    };
+    assert_eq!(connection.desktop_size, NEW_SIZE);
+}
+
+struct PasswordCookieAuthenticator;
+
+impl RdstlsAuthenticator for PasswordCookieAuthenticator {
+    fn authenticate(&self, request: &RdstlsAuthenticationRequest) -> Result<UserIdentity, RdstlsResultCode> {
+        match request {
+            RdstlsAuthenticationRequest::Password { username, password, .. } if password == b"cookie" => {
+                Ok(UserIdentity {
+                    username: username.clone(),
+                    domain: None,
+                })
+            }
+            _ => Err(RdstlsResultCode::LOGON_FAILURE),
+        }
+    }
+}
+
+/// Runs the RDSTLS authentication, returning the result code sent to the client
+fn rdstls_authenticate(acceptor: &mut Acceptor, password: &[u8]) -> RdstlsResultCode {
+    acceptor.mark_security_upgrade_as_done().unwrap();
+
+    let mut buf = WriteBuf::new();
+    acceptor.step_no_input(&mut buf).unwrap();
+    let capabilities = ironrdp_pdu::decode::<RdstlsCapabilities>(buf.filled()).unwrap();
+    assert_eq!(capabilities, RdstlsCapabilities::default());
+
+    let request = ironrdp_pdu::encode_vec(&RdstlsAuthenticationRequest::Password {
+        redirection_guid: vec![0xAA, 0xBB],
+        username: "user".to_owned(),
+        domain: String::new(),
+        password: password.to_vec(),
+    })
+    .unwrap();
+    acceptor.step(&request, &mut WriteBuf::new()).unwrap();
+
+    let mut buf = WriteBuf::new();
+    acceptor.step_no_input(&mut buf).unwrap();
+
+    ironrdp_pdu::decode::<RdstlsAuthenticationResponse>(buf.filled())
+        .unwrap()
+        .result_code
+}
+
+#[test]
+fn rdstls_authentication() {
+    let (mut acceptor, _) = negotiate(nego::SecurityProtocol::RDSTLS, nego::SecurityProtocol::RDSTLS);
+    acceptor.attach_rdstls_authenticator(Arc::new(PasswordCookieAuthenticator));
+
+    assert_eq!(rdstls_authenticate(&mut acceptor, b"cookie"), RdstlsResultCode::SUCCESS);
+    assert_eq!(acceptor.state().name(), "BasicSettingsWaitInitial");
+}
+
+#[test]
+fn rdstls_authentication_failure() {
+    let (mut acceptor, _) = negotiate(nego::SecurityProtocol::RDSTLS, nego::SecurityProtocol::RDSTLS);
+    acceptor.attach_rdstls_authenticator(Arc::new(PasswordCookieAuthenticator));
+
+    assert_eq!(
+        rdstls_authenticate(&mut acceptor, b"wrong"),
+        RdstlsResultCode::LOGON_FAILURE
+    );
+    acceptor.step_no_input(&mut WriteBuf::new()).unwrap_err();
+}
+
+#[test]
+fn rdstls_without_authenticator_denies_access() {
+    let (mut acceptor, _) = negotiate(nego::SecurityProtocol::RDSTLS, nego::SecurityProtocol::RDSTLS);
+
+    assert_eq!(
+        rdstls_authenticate(&mut acceptor, b"cookie"),
+        RdstlsResultCode::ACCESS_DENIED
+    );
+}
+
+#[test]
+fn preconnection_blob_precedes_connection_request() {
+    let mut acceptor = Acceptor::new(nego::SecurityProtocol::HYBRID_EX, DESKTOP_SIZE, Vec::new());
+    let mut buf = WriteBuf::new();
+
+    let pcb = PreconnectionBlob {
+        version: PcbVersion::V2,
+        id: 0,
+        v2_payload: Some("A8A3B2C6-1E2D-4F5A-9B8C-7D6E5F4A3B2C".to_owned()),
+    };
+    let pcb_frame = ironrdp_pdu::encode_vec(&pcb).unwrap();
+
+    let request = ironrdp_pdu::encode_vec(&nego::ConnectionRequest {
+        nego_data: None,
+        flags: nego::RequestFlags::empty(),
+        protocol: nego::SecurityProtocol::HYBRID_EX,
+    })
+    .unwrap();
+
+    let hint = acceptor.next_pdu_hint().unwrap();
+    assert_eq!(hint.find_size(&pcb_frame).unwrap(), Some(pcb_frame.len()));
+    assert_eq!(hint.find_size(&request).unwrap(), Some(request.len()));
+
+    acceptor.step(&pcb_frame, &mut buf).unwrap();
+    assert_eq!(acceptor.preconnection_blob(), Some(&pcb));
+
+    assert!(acceptor.step(&pcb_frame, &mut buf).is_err());
+}
+
+#[test]
+fn preconnection_blob_smaller_than_fixed_part_is_rejected() {
+    let acceptor = Acceptor::new(nego::SecurityProtocol::HYBRID_EX, DESKTOP_SIZE, Vec::new());
+    let hint = acceptor.next_pdu_hint().unwrap();
+
+    assert!(hint.find_size(&[0x00, 0x00, 0x00, 0x00]).is_err());
+    assert!(hint.find_size(&[0x0F, 0x00, 0x00, 0x00]).is_err());
+    assert_eq!(hint.find_size(&[0x10, 0x00, 0x00, 0x00]).unwrap(), Some(16));
+}
+
+#[test]
+fn preconnection_blob_is_optional() {
+    let (acceptor, _) = negotiate(nego::SecurityProtocol::HYBRID_EX, nego::SecurityProtocol::HYBRID_EX);
+
+    assert!(acceptor.reached_security_upgrade().is_some());
+    assert!(acceptor.preconnection_blob().is_none());
+}
@@ -0,0 +1,405 @@
+use std::borrow::Cow;
+use std::mem;
+
+use ironrdp_acceptor::Acceptor;
+use ironrdp_connector::{ClientConnector, ClientConnectorState, ConnectionResult, Sequence as _, State as _};
+use ironrdp_graphics::image_processing::PixelFormat;
+use ironrdp_pdu::crypto::standard_security::{SessionKeys, StandardSecurity, KEY_UPDATE_INTERVAL};
+use ironrdp_pdu::cursor::WriteCursor;
+use ironrdp_pdu::fast_path::{EncryptionFlags, FastPathHeader, FastPathUpdatePdu, Fragmentation, UpdateCode};
+use ironrdp_pdu::gcc::{EncryptionLevel, EncryptionMethod};
+use ironrdp_pdu::input::fast_path::{FastPathInput, FastPathInputEvent, KeyboardFlags};
+use ironrdp_pdu::rdp::client_info::CompressionType;
+use ironrdp_pdu::rdp::headers::{
+    BasicSecurityHeader, BasicSecurityHeaderFlags, CompressionFlags, ShareControlHeader, ShareControlPdu,
+    ShareDataHeader, ShareDataPdu, StreamPriority,
+};
+use ironrdp_pdu::rdp::server_license::cert::{CertificateType, ProprietaryCertificate, RsaPublicKey};
+use ironrdp_pdu::rdp::server_license::ServerCertificate;
+use ironrdp_pdu::rdp::session_info::{
+    ClientAutoReconnect, InfoData, InfoType, LogonExFlags, LogonInfoExtended, SaveSessionInfoPdu, ServerAutoReconnect,
+    ENHANCED_SECURITY_CLIENT_RANDOM,
+};
+use ironrdp_pdu::rdp::SecurityExchangePdu;
+use ironrdp_pdu::{mcs, nego, Action, PduEncode as _, PduParsing as _};
+use ironrdp_session::image::DecodedImage;
+use ironrdp_session::{ActiveStage, ActiveStageOutput};
+use num_bigint::BigUint;
+
+use super::{client_config, step, DESKTOP_SIZE};
+
+/// Modulus of the 512-bit RSA key of the server, little-endian
+const MODULUS: [u8; 64] = [
+    0xa7, 0xf4, 0xb1, 0x0d, 0x43, 0xec, 0x53, 0xf3, 0x4e, 0x9d, 0x96, 0xb9, 0x98, 0x5a, 0x72, 0x66, 0x10, 0x74, 0x1a,
+    0x80, 0x1b, 0x4b, 0x75, 0x1c, 0x83, 0x40, 0x43, 0x1b, 0xea, 0x9c, 0x11, 0x5b, 0xf3, 0xda, 0x2b, 0xae, 0x0a, 0x02,
+    0x83, 0x8d, 0xc8, 0xd2, 0x50, 0x28, 0x9d, 0xd8, 0xe3, 0x7c, 0xd5, 0x8d, 0xb0, 0xa7, 0xf0, 0x47, 0xaa, 0x0e, 0x3e,
+    0xc8, 0x97, 0x44, 0xc9, 0xdd, 0x70, 0xb9,
+];
+
+/// Private exponent of the RSA key of the server, little-endian
+const PRIVATE_EXPONENT: [u8; 64] = [
+    0xa1, 0x9e, 0x2f, 0xf9, 0x54, 0x0c, 0xfc, 0x89, 0x60, 0x83, 0x21, 0xe4, 0xe8, 0x3b, 0xfe, 0xeb, 0x99, 0x3c, 0xbf,
+    0x4a, 0x83, 0x47, 0x03, 0xd9, 0xbd, 0xb2, 0x04, 0x50, 0x34, 0x93, 0xcd, 0x8f, 0xdc, 0x50, 0x9b, 0x6c, 0xea, 0x43,
+    0x11, 0x6b, 0xb5, 0x45, 0x10, 0xcc, 0x63, 0xd5, 0xb9, 0xde, 0x1e, 0xc5, 0xef, 0x6e, 0xef, 0xc8, 0x5b, 0xa3, 0x45,
+    0x1d, 0x35, 0x72, 0x0c, 0xc9, 0xc3, 0xb3,
+];
+
+const PUBLIC_EXPONENT: u32 = 65537;
+
+const SERVER_RANDOM: [u8; 32] = [0x5A; 32];
+
+/// Server side of Standard RDP Security, which the acceptor does not implement
+///
+/// Placed between the acceptor and the client, it announces the server random and certificate in the
+/// MCS Connect Response, then encrypts and decrypts the traffic once the client random is received.
+#[derive(Default)]
+struct ServerSecurity {
+    security: Option<StandardSecurity>,
+    client_random: Option<[u8; 32]>,
+    license_sent: bool,
+    client_info_received: bool,
+}
+
+impl ServerSecurity {
+    /// Processes the PDUs sent by the client, returning them as expected by the acceptor
+    fn receive(&mut self, frames: &[u8]) -> Vec<u8> {
+        pdus(frames)
+            .flat_map(|(action, pdu)| self.receive_pdu(action, pdu))
+            .collect()
+    }
+
+    /// Processes the PDUs sent by the acceptor, returning them as expected by the client
+    fn send(&mut self, frames: &[u8]) -> Vec<u8> {
+        pdus(frames)
+            .flat_map(|(action, pdu)| self.send_pdu(action, pdu))
+            .collect()
+    }
+
+    fn receive_pdu(&mut self, action: Action, pdu: &[u8]) -> Vec<u8> {
+        let Some(security) = self.security.as_mut() else {
+            // The Security Exchange PDU is the first data sent by the client
+            if let Ok(mcs::McsMessage::SendDataRequest(request)) = ironrdp_pdu::decode::<mcs::McsMessage<'_>>(pdu) {
+                let security_exchange = SecurityExchangePdu::from_buffer(request.user_data.as_ref()).unwrap();
+                let (security, client_random) = server_keys(&security_exchange.encrypted_client_random);
+                self.security = Some(security);
+                self.client_random = Some(client_random);
+
+                return Vec::new();
+            }
+
+            return pdu.to_vec();
+        };
+
+        if action == Action::FastPath {
+            return security.decrypt_fast_path(pdu).unwrap();
+        }
+
+        let request = ironrdp_pdu::decode::<mcs::SendDataRequest<'_>>(pdu).unwrap();
+        let (flags, data) = security.decrypt_user_data(&request.user_data).unwrap();
+
+        // The Client Info PDU keeps its security header, which is only found in the following PDUs when encrypted
+        let user_data = if self.client_info_received {
+            data
+        } else {
+            self.client_info_received = true;
+
+            let header = BasicSecurityHeader {
+                flags: flags - BasicSecurityHeaderFlags::ENCRYPT - BasicSecurityHeaderFlags::SECURE_CHECKSUM,
+            };
+            let mut user_data = Vec::new();
+            header.to_buffer(&mut user_data).unwrap();
+            user_data.extend_from_slice(&data);
+            user_data
+        };
+
+        ironrdp_pdu::encode_vec(&mcs::SendDataRequest {
+            user_data: Cow::Owned(user_data),
+            ..request
+        })
+        .unwrap()
+    }
+
+    fn send_pdu(&mut self, action: Action, pdu: &[u8]) -> Vec<u8> {
+        let Some(security) = self.security.as_mut() else {
+            return match ironrdp_connector::legacy::decode_x224_packet::<mcs::ConnectResponse>(pdu) {
+                Ok(connect_response) => announce_server_security(connect_response),
+                Err(_) => pdu.to_vec(),
+            };
+        };
+
+        if action == Action::FastPath {
+            return security.encrypt_fast_path(pdu).unwrap();
+        }
+
+        let mcs::McsMessage::SendDataIndication(indication) = ironrdp_pdu::decode::<mcs::McsMessage<'_>>(pdu).unwrap()
+        else {
+            return pdu.to_vec();
+        };
+
+        // The licensing PDU sent right after the Client Info PDU is not encrypted
+        if !self.license_sent {
+            self.license_sent = true;
+            return pdu.to_vec();
+        }
+
+        ironrdp_pdu::encode_vec(&mcs::SendDataIndication {
+            user_data: Cow::Owned(security.encrypt_user_data(BasicSecurityHeaderFlags::empty(), &indication.user_data)),
+            ..indication
+        })
+        .unwrap()
+    }
+}
+
+fn pdus(mut frames: &[u8]) -> impl Iterator<Item = (Action, &[u8])> {
+    std::iter::from_fn(move || {
+        let info = ironrdp_pdu::find_size(frames).unwrap()?;
+        let (pdu, rest) = frames.split_at(info.length);
+        frames = rest;
+
+        Some((info.action, pdu))
+    })
+}
+
+fn announce_server_security(mut connect_response: mcs::ConnectResponse) -> Vec<u8> {
+    let certificate = ServerCertificate {
+        issued_permanently: false,
+        certificate: CertificateType::Proprietary(ProprietaryCertificate {
+            public_key: RsaPublicKey {
+                public_exponent: PUBLIC_EXPONENT,
+                modulus: [MODULUS.as_slice(), &[0; 8]].concat(),
+            },
+            signature: vec![0; 72],
+        }),
+    };
+
+    let security = &mut connect_response.conference_create_response.gcc_blocks.security;
+    security.encryption_method = EncryptionMethod::BIT_128;
+    security.encryption_level = EncryptionLevel::ClientCompatible;
+    security.server_random = Some(SERVER_RANDOM);
+    security.server_cert = Vec::new();
+    certificate.to_buffer(&mut security.server_cert).unwrap();
+
+    let mut buf = ironrdp_pdu::write_buf::WriteBuf::new();
+    ironrdp_connector::legacy::encode_x224_packet(&connect_response, &mut buf).unwrap();
+    buf.into_inner()
+}
+
+/// Decrypts the client random, and derives the keys of the server which decrypts with the client encryption key
+fn server_keys(encrypted_client_random: &[u8]) -> (StandardSecurity, [u8; 32]) {
+    let client_random = BigUint::from_bytes_le(encrypted_client_random).modpow(
+        &BigUint::from_bytes_le(&PRIVATE_EXPONENT),
+        &BigUint::from_bytes_le(&MODULUS),
+    );
+    let mut client_random = client_random.to_bytes_le();
+    client_random.resize(32, 0);
+    let client_random = client_random.try_into().unwrap();
+
+    let keys = SessionKeys::derive(&client_random, &SERVER_RANDOM, EncryptionMethod::BIT_128).unwrap();
+
+    let security = StandardSecurity::new(SessionKeys {
+        method: keys.method,
+        mac_key: keys.mac_key,
+        encrypt_key: keys.decrypt_key,
+        decrypt_key: keys.encrypt_key,
+    });
+
+    (security, client_random)
+}
+
+fn connect(connector: &mut ClientConnector, acceptor: &mut Acceptor, server: &mut ServerSecurity) -> ConnectionResult {
+    let (mut to_server, mut to_client) = (Vec::new(), Vec::new());
+
+    while !(connector.state.is_terminal() && acceptor.state().is_terminal()) {
+        if connector.should_perform_security_upgrade() {
+            assert!(connector.is_standard_security_selected());
+            connector.mark_security_upgrade_as_done();
+        }
+        if acceptor.reached_security_upgrade().is_some() {
+            acceptor.mark_security_upgrade_as_done().unwrap();
+        }
+
+        let mut output = Vec::new();
+        let client_progress = step(connector, &mut to_client, &mut output).unwrap();
+        to_server.extend_from_slice(&server.receive(&output));
+
+        let mut output = Vec::new();
+        let server_progress = step(acceptor, &mut to_server, &mut output).unwrap();
+        to_client.extend_from_slice(&server.send(&output));
+
+        assert!(
+            client_progress || server_progress,
+            "stalled with client in {} and server in {}",
+            connector.state.name(),
+            acceptor.state().name()
+        );
+    }
+
+    let ClientConnectorState::Connected { result } = mem::take(&mut connector.state) else {
+        unreachable!()
+    };
+
+    result
+}
+
+fn share_data_frame(result: &ConnectionResult, share_data_pdu: ShareDataPdu) -> Vec<u8> {
+    let pdu = ShareControlHeader {
+        share_id: 0,
+        pdu_source: result.io_channel_id,
+        share_control_pdu: ShareControlPdu::Data(ShareDataHeader {
+            share_data_pdu,
+            stream_priority: StreamPriority::Undefined,
+            compression_flags: CompressionFlags::empty(),
+            compression_type: CompressionType::K8,
+        }),
+    };
+
+    let mut user_data = Vec::new();
+    pdu.to_buffer(&mut user_data).unwrap();
+
+    ironrdp_pdu::encode_vec(&mcs::SendDataIndication {
+        initiator_id: result.user_channel_id,
+        channel_id: result.io_channel_id,
+        user_data: user_data.into(),
+    })
+    .unwrap()
+}
+
+fn hidden_pointer_frame() -> Vec<u8> {
+    let update = FastPathUpdatePdu {
+        fragmentation: Fragmentation::Single,
+        update_code: UpdateCode::HiddenPointer,
+        compression_flags: None,
+        compression_type: None,
+        data: &[],
+    };
+    let header = FastPathHeader::new(EncryptionFlags::empty(), update.size());
+
+    let mut frame = vec![0; header.size() + update.size()];
+    let mut cursor = WriteCursor::new(&mut frame);
+    header.encode(&mut cursor).unwrap();
+    update.encode(&mut cursor).unwrap();
+
+    frame
+}
+
+#[test]
+fn session_is_encrypted_after_finalization() {
+    let mut connector = ClientConnector::new(ironrdp_connector::Config {
+        enable_tls: false,
+        enable_credssp: false,
+        enable_standard_security: true,
+        no_server_pointer: false,
+        ..client_config(false)
+    })
+    .with_server_addr(([127, 0, 0, 1], 3389).into());
+    let mut acceptor = Acceptor::new(nego::SecurityProtocol::empty(), DESKTOP_SIZE, Vec::new());
+    let mut server = ServerSecurity::default();
+
+    let result = connect(&mut connector, &mut acceptor, &mut server);
+    assert!(result.standard_security.is_some());
+    assert!(acceptor.get_result().is_some());
+
+    let server_frame = |server: &mut ServerSecurity, frame: &[u8]| {
+        let encrypted = server.send(frame);
+        assert_ne!(encrypted, frame);
+        encrypted
+    };
+
+    let cookie = ServerAutoReconnect {
+        logon_id: 7,
+        random_bits: [0x3C; 16],
+    };
+    let save_session_info = share_data_frame(
+        &result,
+        ShareDataPdu::SaveSessionInfo(SaveSessionInfoPdu {
+            info_type: InfoType::LogonExtended,
+            info_data: InfoData::LogonExtended(LogonInfoExtended {
+                present_fields_flags: LogonExFlags::AUTO_RECONNECT_COOKIE,
+                auto_reconnect: Some(cookie.clone()),
+                errors_info: None,
+            }),
+        }),
+    );
+
+    let mut stage = ActiveStage::new(result, None);
+    let mut image = DecodedImage::new(PixelFormat::RgbA32, DESKTOP_SIZE.width, DESKTOP_SIZE.height);
+
+    // Slow-path PDUs sent by the server
+    let frame = server_frame(&mut server, &save_session_info);
+    stage.process(&mut image, Action::X224, &frame).unwrap();
+    assert_eq!(stage.auto_reconnect_cookie(), Some(&cookie));
+
+    // Fast-path PDUs sent by the server, past the key update
+    let hidden_pointer = hidden_pointer_frame();
+    for _ in 0..=KEY_UPDATE_INTERVAL {
+        let frame = server_frame(&mut server, &hidden_pointer);
+        let outputs = stage.process(&mut image, Action::FastPath, &frame).unwrap();
+        assert!(matches!(outputs.as_slice(), [.., ActiveStageOutput::PointerHidden]));
+    }
+
+    // Fast-path input sent by the client, past the key update
+    let events = [FastPathInputEvent::KeyboardEvent(KeyboardFlags::empty(), 0x1E)];
+    for _ in 0..=KEY_UPDATE_INTERVAL {
+        let outputs = stage.process_fastpath_input(&mut image, &events).unwrap();
+        let [ActiveStageOutput::ResponseFrame(frame)] = outputs.as_slice() else {
+            panic!("unexpected outputs: {outputs:?}");
+        };
+
+        let input = FastPathInput::from_buffer(server.receive(frame).as_slice()).unwrap();
+        assert_eq!(input.0, events);
+    }
+
+    // Slow-path PDUs sent by the client
+    let outputs = stage.graceful_shutdown().unwrap();
+    let [ActiveStageOutput::ResponseFrame(frame)] = outputs.as_slice() else {
+        panic!("unexpected outputs: {outputs:?}");
+    };
+
+    let request = server.receive(frame);
+    let request = ironrdp_pdu::decode::<mcs::SendDataRequest<'_>>(&request).unwrap();
+    let pdu = ShareControlHeader::from_buffer(request.user_data.as_ref()).unwrap();
+    assert!(matches!(
+        pdu.share_control_pdu,
+        ShareControlPdu::Data(ShareDataHeader {
+            share_data_pdu: ShareDataPdu::ShutdownRequest,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn auto_reconnect_verifier_uses_client_random() {
+    let cookie = ServerAutoReconnect {
+        logon_id: 7,
+        random_bits: [0x3C; 16],
+    };
+
+    let mut connector = ClientConnector::new(ironrdp_connector::Config {
+        enable_tls: false,
+        enable_credssp: false,
+        enable_standard_security: true,
+        auto_reconnect_cookie: Some(cookie.clone()),
+        ..client_config(false)
+    })
+    .with_server_addr(([127, 0, 0, 1], 3389).into());
+    let mut acceptor = Acceptor::new(nego::SecurityProtocol::empty(), DESKTOP_SIZE, Vec::new());
+    let mut server = ServerSecurity::default();
+
+    connect(&mut connector, &mut acceptor, &mut server);
+
+    let client_random = server.client_random.unwrap();
+    assert_eq!(connector.client_random, Some(client_random));
+
+    let client_data = acceptor.get_result().unwrap().client_data.unwrap();
+    let reconnect_cookie = client_data
+        .client_info
+        .extra_info
+        .optional_data
+        .reconnect_cookie()
+        .expect("reconnect cookie");
+    let client_cookie = ClientAutoReconnect::from_cookie(reconnect_cookie).unwrap();
+
+    assert_eq!(client_cookie, ClientAutoReconnect::new(&cookie, &client_random));
+    assert!(client_cookie.verify(&cookie, &client_random));
+    assert!(!client_cookie.verify(&cookie, &ENHANCED_SECURITY_CLIENT_RANDOM));
+}
@@ -0,0 +1,621 @@
+use ironrdp_connector::credssp::CredsspSequence;
+use ironrdp_connector::{
+    ClientConnector, ClientConnectorState, Config, ConnectionFinalizationSequence, ConnectionFinalizationState,
+    CredentialDelegation, Credentials, DesktopSize, MonitorConfig, Redirection, Sequence as _, ServerName,
+};
+use ironrdp_pdu::gcc::{self, KeyboardType};
+use ironrdp_pdu::pcb::{PcbVersion, PreconnectionBlob};
+use ironrdp_pdu::rdp::capability_sets::MajorPlatformType;
+use ironrdp_pdu::rdp::client_info::CompressionType;
+use ironrdp_pdu::rdp::finalization_messages::MonitorLayoutPdu;
+use ironrdp_pdu::rdp::headers::{
+    CompressionFlags, ShareControlHeader, ShareControlPdu, ShareDataHeader, ShareDataPdu, StreamPriority,
+};
+use ironrdp_pdu::rdp::server_redirection::{ServerRedirectionFlags, ServerRedirectionPdu};
+use ironrdp_pdu::rdstls::{
+    RdstlsAuthenticationRequest, RdstlsAuthenticationResponse, RdstlsCapabilities, RdstlsResultCode,
+};
+use ironrdp_pdu::write_buf::WriteBuf;
+use ironrdp_pdu::{mcs, nego, PduParsing as _};
+use ironrdp_testsuite_core::rdp::SERVER_REDIRECTION_BUFFER;
+
+fn config() -> Config {
+    Config {
+        desktop_size: DesktopSize {
+            width: 1024,
+            height: 768,
+        },
+        monitors: Vec::new(),
+        enable_tls: true,
+        enable_credssp: true,
+        enable_standard_security: false,
+        credential_delegation: CredentialDelegation::Full,
+        credentials: Credentials::UsernamePassword {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        },
+        domain: None,
+        client_build: 0,
+        client_name: "client".to_owned(),
+        keyboard_type: KeyboardType::IbmEnhanced,
+        keyboard_subtype: 0,
+        keyboard_functional_keys_count: 12,
+        ime_file_name: String::new(),
+        graphics: None,
+        bitmap: None,
+        dig_product_id: String::new(),
+        client_dir: String::new(),
+        platform: MajorPlatformType::UNSPECIFIED,
+        autologon: false,
+        auto_reconnect_cookie: None,
+        pcb: None,
+        no_server_pointer: true,
+        pointer_software_rendering: false,
+    }
+}
+
+fn encoded_connection_request(mut connector: ClientConnector) -> Vec<u8> {
+    let mut buf = WriteBuf::new();
+    connector.step_no_input(&mut buf).unwrap();
+    buf.filled().to_vec()
+}
+
+fn connection_request(connector: ClientConnector) -> nego::ConnectionRequest {
+    ironrdp_pdu::decode::<nego::ConnectionRequest>(&encoded_connection_request(connector)).unwrap()
+}
+
+#[test]
+fn redirection_target_addresses_are_ordered_and_deduplicated() {
+    let redirection = Redirection::from(ServerRedirectionPdu {
+        session_id: 3,
+        target_net_address: Some("10.0.0.2".to_owned()),
+        target_net_addresses: Some(vec!["10.0.0.2".to_owned(), "10.0.0.3".to_owned()]),
+        target_fqdn: Some("host.contoso.com".to_owned()),
+        ..Default::default()
+    });
+
+    assert_eq!(redirection.session_id, 3);
+    assert_eq!(
+        redirection.target_addresses,
+        ["10.0.0.2", "10.0.0.3", "host.contoso.com"]
+    );
+}
+
+#[test]
+fn no_redirect_keeps_the_current_server() {
+    let redirection = Redirection::from(ServerRedirectionPdu {
+        flags: ServerRedirectionFlags::NO_REDIRECT,
+        target_net_address: Some("10.0.0.2".to_owned()),
+        load_balance_info: Some(b"Cookie: msts=3640205228.15629.0000\r\n".to_vec()),
+        ..Default::default()
+    });
+
+    assert!(redirection.target_addresses.is_empty());
+    assert_eq!(
+        redirection.routing_token(),
+        Some(nego::NegoRequestData::load_balance_info(
+            b"Cookie: msts=3640205228.15629.0000\r\n".to_vec()
+        ))
+    );
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+#[test]
+fn broker_load_balance_info_is_sent_verbatim() {
+    let load_balance_info = b"tsv://MS Terminal Services Plugin.1.Sessions\r\n";
+
+    let redirection = Redirection {
+        load_balance_info: Some(load_balance_info.to_vec()),
+        ..Default::default()
+    };
+
+    let connector = ClientConnector::new(config()).with_redirection(redirection);
+    let request = encoded_connection_request(connector);
+    assert!(contains(&request, load_balance_info));
+
+    let request = ironrdp_pdu::decode::<nego::ConnectionRequest>(&request).unwrap();
+    assert_eq!(
+        request.nego_data,
+        Some(nego::NegoRequestData::load_balance_info(load_balance_info.to_vec()))
+    );
+}
+
+#[test]
+fn load_balance_info_is_terminated() {
+    let redirection = Redirection {
+        load_balance_info: Some(b"tsv://MS Terminal Services Plugin.1.Sessions".to_vec()),
+        ..Default::default()
+    };
+
+    let connector = ClientConnector::new(config()).with_redirection(redirection);
+    let request = encoded_connection_request(connector);
+
+    assert!(contains(&request, b"tsv://MS Terminal Services Plugin.1.Sessions\r\n"));
+}
+
+#[test]
+fn broker_redirection_routing_token() {
+    let ShareControlPdu::ServerRedirect(pdu) = ShareControlHeader::from_buffer(SERVER_REDIRECTION_BUFFER.as_slice())
+        .unwrap()
+        .share_control_pdu
+    else {
+        panic!("not a server redirection");
+    };
+    let load_balance_info = pdu.load_balance_info.clone().unwrap();
+
+    let connector = ClientConnector::new(config()).with_redirection(Redirection::from(pdu));
+    let request = encoded_connection_request(connector);
+    assert!(contains(&request, &load_balance_info));
+
+    let request = ironrdp_pdu::decode::<nego::ConnectionRequest>(&request).unwrap();
+    assert_eq!(
+        request.nego_data,
+        Some(nego::NegoRequestData::routing_token("33554442.15629.0000".to_owned()))
+    );
+}
+
+#[test]
+fn connection_request_sends_username_cookie() {
+    let request = connection_request(ClientConnector::new(config()));
+
+    assert_eq!(
+        request.nego_data,
+        Some(nego::NegoRequestData::cookie("user".to_owned()))
+    );
+}
+
+#[test]
+fn redirected_connection_request_sends_routing_token() {
+    let redirection = Redirection {
+        load_balance_info: Some(b"Cookie: msts=3640205228.15629.0000\r\n".to_vec()),
+        username: Some("redirected".to_owned()),
+        domain: Some("CONTOSO".to_owned()),
+        ..Default::default()
+    };
+
+    let connector = ClientConnector::new(config()).with_redirection(redirection);
+
+    let Credentials::UsernamePassword { username, password } = &connector.config.credentials else {
+        panic!("unexpected credentials");
+    };
+    assert_eq!(username, "redirected");
+    assert_eq!(password, "pass");
+    assert_eq!(connector.config.domain.as_deref(), Some("CONTOSO"));
+
+    let request = connection_request(connector);
+
+    assert_eq!(
+        request.nego_data,
+        Some(nego::NegoRequestData::routing_token("3640205228.15629.0000".to_owned()))
+    );
+}
+
+#[test]
+fn standard_security_is_rejected_unless_enabled() {
+    let mut connector = ClientConnector::new(Config {
+        enable_tls: false,
+        enable_credssp: false,
+        ..config()
+    });
+
+    assert!(connector.step_no_input(&mut WriteBuf::new()).is_err());
+}
+
+#[test]
+fn standard_security_selected_by_server_skips_upgrade() {
+    let mut connector = ClientConnector::new(Config {
+        enable_tls: false,
+        enable_credssp: false,
+        enable_standard_security: true,
+        ..config()
+    });
+
+    let mut buf = WriteBuf::new();
+    connector.step_no_input(&mut buf).unwrap();
+    let request = ironrdp_pdu::decode::<nego::ConnectionRequest>(buf.filled()).unwrap();
+    assert!(request.protocol.is_standard_rdp_security());
+
+    let confirm = ironrdp_pdu::encode_vec(&nego::ConnectionConfirm::Response {
+        flags: nego::ResponseFlags::empty(),
+        protocol: nego::SecurityProtocol::empty(),
+    })
+    .unwrap();
+    connector.step(&confirm, &mut WriteBuf::new()).unwrap();
+
+    assert!(connector.should_perform_security_upgrade());
+    assert!(connector.is_standard_security_selected());
+}
+
+fn rdstls_redirection() -> Redirection {
+    Redirection {
+        flags: ServerRedirectionFlags::PASSWORD_IS_PK_ENCRYPTED,
+        username: Some("redirected".to_owned()),
+        domain: Some("CONTOSO".to_owned()),
+        password_cookie: Some(vec![0x01, 0x02, 0x03]),
+        redirection_guid: Some(vec![0xAA, 0xBB]),
+        ..Default::default()
+    }
+}
+
+/// Negotiates RDSTLS and performs the TLS upgrade
+fn rdstls_connector() -> ClientConnector {
+    let mut connector = ClientConnector::new(config()).with_redirection(rdstls_redirection());
+
+    let mut buf = WriteBuf::new();
+    connector.step_no_input(&mut buf).unwrap();
+
+    let confirm = ironrdp_pdu::encode_vec(&nego::ConnectionConfirm::Response {
+        flags: nego::ResponseFlags::empty(),
+        protocol: nego::SecurityProtocol::RDSTLS,
+    })
+    .unwrap();
+    connector.step(&confirm, &mut WriteBuf::new()).unwrap();
+    connector.mark_security_upgrade_as_done();
+
+    connector
+}
+
+#[test]
+fn redirection_password_cookie_requests_rdstls_only() {
+    let connector = ClientConnector::new(config()).with_redirection(rdstls_redirection());
+
+    let Credentials::RedirectionCookie {
+        username,
+        redirection_guid,
+        password_cookie,
+    } = &connector.config.credentials
+    else {
+        panic!("unexpected credentials");
+    };
+    assert_eq!(username, "redirected");
+    assert_eq!(redirection_guid, &[0xAA, 0xBB]);
+    assert_eq!(password_cookie, &[0x01, 0x02, 0x03]);
+
+    let request = connection_request(connector);
+
+    assert_eq!(request.protocol, nego::SecurityProtocol::RDSTLS);
+}
+
+#[test]
+fn rdstls_authentication() {
+    let mut connector = rdstls_connector();
+
+    let capabilities = ironrdp_pdu::encode_vec(&RdstlsCapabilities::default()).unwrap();
+    let mut buf = WriteBuf::new();
+    connector.step(&capabilities, &mut buf).unwrap();
+
+    let request = ironrdp_pdu::decode::<RdstlsAuthenticationRequest>(buf.filled()).unwrap();
+    assert_eq!(
+        request,
+        RdstlsAuthenticationRequest::Password {
+            redirection_guid: vec![0xAA, 0xBB],
+            username: "redirected".to_owned(),
+            domain: "CONTOSO".to_owned(),
+            password: vec![0x01, 0x02, 0x03],
+        }
+    );
+
+    let response = ironrdp_pdu::encode_vec(&RdstlsAuthenticationResponse {
+        result_code: RdstlsResultCode::SUCCESS,
+    })
+    .unwrap();
+    connector.step(&response, &mut WriteBuf::new()).unwrap();
+
+    assert!(matches!(
+        connector.state,
+        ClientConnectorState::BasicSettingsExchangeSendInitial { .. }
+    ));
+}
+
+#[test]
+fn rdstls_authentication_failure() {
+    let mut connector = rdstls_connector();
+
+    let capabilities = ironrdp_pdu::encode_vec(&RdstlsCapabilities::default()).unwrap();
+    connector.step(&capabilities, &mut WriteBuf::new()).unwrap();
+
+    let response = ironrdp_pdu::encode_vec(&RdstlsAuthenticationResponse {
+        result_code: RdstlsResultCode::ACCESS_DENIED,
+    })
+    .unwrap();
+
+    assert!(connector.step(&response, &mut WriteBuf::new()).is_err());
+}
+
+/// Sends the Connection Request, and feeds the given Connection Confirm to the connector
+fn negotiate(
+    connector: &mut ClientConnector,
+    protocol: nego::SecurityProtocol,
+    flags: nego::ResponseFlags,
+) -> (nego::ConnectionRequest, ironrdp_connector::ConnectorResult<()>) {
+    let mut buf = WriteBuf::new();
+    connector.step_no_input(&mut buf).unwrap();
+    let request = ironrdp_pdu::decode::<nego::ConnectionRequest>(buf.filled()).unwrap();
+
+    let confirm = ironrdp_pdu::encode_vec(&nego::ConnectionConfirm::Response { flags, protocol }).unwrap();
+    let result = connector.step(&confirm, &mut WriteBuf::new()).map(|_| ());
+
+    (request, result)
+}
+
+#[test]
+fn restricted_admin_mode_is_requested() {
+    let mut connector = ClientConnector::new(Config {
+        credential_delegation: CredentialDelegation::RestrictedAdmin,
+        ..config()
+    });
+
+    let (request, result) = negotiate(
+        &mut connector,
+        nego::SecurityProtocol::HYBRID_EX,
+        nego::ResponseFlags::RESTRICTED_ADMIN_MODE_SUPPORTED,
+    );
+
+    assert_eq!(request.flags, nego::RequestFlags::RESTRICTED_ADMIN_MODE_REQUIRED);
+    result.unwrap();
+    assert!(connector.should_perform_security_upgrade());
+}
+
+#[test]
+fn restricted_admin_mode_unsupported_by_server() {
+    let mut connector = ClientConnector::new(Config {
+        credential_delegation: CredentialDelegation::RestrictedAdmin,
+        ..config()
+    });
+
+    let (_, result) = negotiate(
+        &mut connector,
+        nego::SecurityProtocol::HYBRID_EX,
+        nego::ResponseFlags::EXTENDED_CLIENT_DATA_SUPPORTED,
+    );
+
+    let error = result.unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("server does not support restricted admin mode"));
+}
+
+#[test]
+fn restricted_admin_mode_requires_credssp() {
+    let mut connector = ClientConnector::new(Config {
+        credential_delegation: CredentialDelegation::RestrictedAdmin,
+        ..config()
+    });
+
+    let (_, result) = negotiate(
+        &mut connector,
+        nego::SecurityProtocol::SSL,
+        nego::ResponseFlags::RESTRICTED_ADMIN_MODE_SUPPORTED,
+    );
+
+    assert!(result.is_err());
+
+    let mut connector = ClientConnector::new(Config {
+        enable_credssp: false,
+        credential_delegation: CredentialDelegation::RestrictedAdmin,
+        ..config()
+    });
+
+    assert!(connector.step_no_input(&mut WriteBuf::new()).is_err());
+}
+
+#[test]
+fn remote_credential_guard_is_requested() {
+    let mut connector = ClientConnector::new(Config {
+        credential_delegation: CredentialDelegation::RemoteCredentialGuard,
+        ..config()
+    });
+
+    let (request, result) = negotiate(
+        &mut connector,
+        nego::SecurityProtocol::HYBRID_EX,
+        nego::ResponseFlags::REDIRECTED_AUTHENTICATION_MODE_SUPPORTED,
+    );
+
+    assert_eq!(
+        request.flags,
+        nego::RequestFlags::REDIRECTED_AUTHENTICATION_MODE_REQUIRED
+    );
+    result.unwrap();
+    connector.mark_security_upgrade_as_done();
+
+    // TSRemoteGuardCreds can't be sent yet, the connection fails before any credentials are exchanged
+    let error = CredsspSequence::init(&connector, ServerName::new("server"), Vec::new(), None).unwrap_err();
+    assert!(error.to_string().contains("TSRemoteGuardCreds"));
+}
+
+#[test]
+fn remote_credential_guard_unsupported_by_server() {
+    let mut connector = ClientConnector::new(Config {
+        credential_delegation: CredentialDelegation::RemoteCredentialGuard,
+        ..config()
+    });
+
+    let (_, result) = negotiate(
+        &mut connector,
+        nego::SecurityProtocol::HYBRID_EX,
+        nego::ResponseFlags::RESTRICTED_ADMIN_MODE_SUPPORTED,
+    );
+
+    let error = result.unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("server does not support Remote Credential Guard"));
+}
+
+fn dual_monitor_config() -> Config {
+    Config {
+        monitors: vec![
+            MonitorConfig::new(0, 0, 1920, 1080, true),
+            MonitorConfig {
+                orientation: gcc::MonitorOrientation::Portrait,
+                desktop_scale_factor: 150,
+                ..MonitorConfig::new(-1080, -200, 1080, 1920, false)
+            },
+        ],
+        ..config()
+    }
+}
+
+/// Negotiates TLS and returns the GCC blocks sent in the MCS Connect Initial
+fn client_gcc_blocks(
+    config: Config,
+    flags: nego::ResponseFlags,
+) -> ironrdp_connector::ConnectorResult<gcc::ClientGccBlocks> {
+    let mut connector = ClientConnector::new(config);
+
+    let (_, result) = negotiate(&mut connector, nego::SecurityProtocol::SSL, flags);
+    result.unwrap();
+    connector.mark_security_upgrade_as_done();
+
+    let mut buf = WriteBuf::new();
+    connector.step_no_input(&mut buf)?;
+
+    let connect_initial = ironrdp_connector::legacy::decode_x224_packet::<mcs::ConnectInitial>(buf.filled()).unwrap();
+
+    Ok(connect_initial.conference_create_request.gcc_blocks)
+}
+
+#[test]
+fn monitor_layout_is_sent() {
+    let gcc_blocks = client_gcc_blocks(
+        dual_monitor_config(),
+        nego::ResponseFlags::EXTENDED_CLIENT_DATA_SUPPORTED,
+    )
+    .unwrap();
+
+    assert_eq!(gcc_blocks.core.desktop_width, 3000);
+    assert_eq!(gcc_blocks.core.desktop_height, 1920);
+    assert!(gcc_blocks
+        .core
+        .optional_data
+        .early_capability_flags
+        .unwrap()
+        .contains(gcc::ClientEarlyCapabilityFlags::SUPPORT_MONITOR_LAYOUT_PDU));
+
+    assert_eq!(
+        gcc_blocks.monitor.unwrap().monitors,
+        [
+            gcc::Monitor {
+                left: 0,
+                top: 0,
+                right: 1919,
+                bottom: 1079,
+                flags: gcc::MonitorFlags::PRIMARY,
+            },
+            gcc::Monitor {
+                left: -1080,
+                top: -200,
+                right: -1,
+                bottom: 1719,
+                flags: gcc::MonitorFlags::empty(),
+            },
+        ]
+    );
+
+    let extended_monitors_info = gcc_blocks.monitor_extended.unwrap().extended_monitors_info;
+    assert_eq!(extended_monitors_info.len(), 2);
+    assert_eq!(extended_monitors_info[1].orientation, gcc::MonitorOrientation::Portrait);
+    assert_eq!(extended_monitors_info[1].desktop_scale_factor, 150);
+}
+
+#[test]
+fn monitor_extended_data_requires_server_support() {
+    let gcc_blocks = client_gcc_blocks(dual_monitor_config(), nego::ResponseFlags::empty()).unwrap();
+
+    assert!(gcc_blocks.monitor.is_some());
+    assert!(gcc_blocks.monitor_extended.is_none());
+}
+
+#[test]
+fn invalid_monitor_layout_is_rejected() {
+    let no_primary = Config {
+        monitors: vec![MonitorConfig::new(0, 0, 1920, 1080, false)],
+        ..config()
+    };
+    assert!(client_gcc_blocks(no_primary, nego::ResponseFlags::empty()).is_err());
+
+    let primary_not_at_origin = Config {
+        monitors: vec![
+            MonitorConfig::new(0, 0, 1920, 1080, false),
+            MonitorConfig::new(1920, 0, 1920, 1080, true),
+        ],
+        ..config()
+    };
+    assert!(client_gcc_blocks(primary_not_at_origin, nego::ResponseFlags::empty()).is_err());
+}
+
+#[test]
+fn server_monitor_layout_is_recorded() {
+    const IO_CHANNEL_ID: u16 = 1003;
+    const USER_CHANNEL_ID: u16 = 1007;
+
+    let monitors = vec![gcc::Monitor {
+        left: 0,
+        top: 0,
+        right: 1919,
+        bottom: 1079,
+        flags: gcc::MonitorFlags::PRIMARY,
+    }];
+
+    let pdu = ShareControlHeader {
+        share_id: 0,
+        pdu_source: IO_CHANNEL_ID,
+        share_control_pdu: ShareControlPdu::Data(ShareDataHeader {
+            share_data_pdu: ShareDataPdu::MonitorLayout(MonitorLayoutPdu {
+                monitors: monitors.clone(),
+            }),
+            stream_priority: StreamPriority::Undefined,
+            compression_flags: CompressionFlags::empty(),
+            compression_type: CompressionType::K8,
+        }),
+    };
+
+    let mut user_data = Vec::new();
+    pdu.to_buffer(&mut user_data).unwrap();
+
+    let frame = ironrdp_pdu::encode_vec(&mcs::SendDataIndication {
+        initiator_id: USER_CHANNEL_ID,
+        channel_id: IO_CHANNEL_ID,
+        user_data: user_data.into(),
+    })
+    .unwrap();
+
+    let mut finalization = ConnectionFinalizationSequence::new(IO_CHANNEL_ID, USER_CHANNEL_ID);
+    finalization.state = ConnectionFinalizationState::WaitForResponse;
+
+    finalization.step(&frame, &mut WriteBuf::new()).unwrap();
+
+    assert!(matches!(
+        finalization.state,
+        ConnectionFinalizationState::WaitForResponse
+    ));
+    assert_eq!(finalization.monitors, monitors);
+}
+
+#[test]
+fn preconnection_blob_is_sent_first() {
+    let pcb = PreconnectionBlob {
+        version: PcbVersion::V2,
+        id: 0,
+        v2_payload: Some("A8A3B2C6-1E2D-4F5A-9B8C-7D6E5F4A3B2C".to_owned()),
+    };
+
+    let mut connector = ClientConnector::new(Config {
+        pcb: Some(pcb.clone()),
+        ..config()
+    });
+
+    let mut buf = WriteBuf::new();
+    connector.step_no_input(&mut buf).unwrap();
+    assert_eq!(ironrdp_pdu::decode::<PreconnectionBlob>(buf.filled()).unwrap(), pcb);
+
+    let request = connection_request(connector);
+    assert_eq!(
+        request.nego_data,
+        Some(nego::NegoRequestData::cookie("user".to_owned()))
+    );
+}
@@ -1608,3 +1608,19 @@ const RGB_BUFFER: [u8; 4 * 64 * 64] = [
     0xFF, 0xF6, 0x9D, 0x13, 0xFF, 0xF6, 0x9C, 0x12, 0xFF, 0xF5, 0x9A, 0x11, 0xFF, 0xF5, 0x9A, 0x11, 0xFF, 0xF5, 0x9A,
     0x11, 0xFF, 0xF5, 0x9A, 0x11, 0xFF,
 ];
+
+#[test]
+fn ycbcr_from_rgb_round_trips() {
+    for rgb in [
+        Rgb { r: 0, g: 0, b: 0 },
+        Rgb { r: 255, g: 255, b: 255 },
+        Rgb { r: 36, g: 159, b: 224 },
+        Rgb { r: 200, g: 10, b: 90 },
+    ] {
+        let actual = Rgb::from(YCbCr::from(rgb));
+
+        assert!(actual.r.abs_diff(rgb.r) <= 1, "{actual:?} != {rgb:?}");
+        assert!(actual.g.abs_diff(rgb.g) <= 1, "{actual:?} != {rgb:?}");
+        assert!(actual.b.abs_diff(rgb.b) <= 1, "{actual:?} != {rgb:?}");
+    }
+}
@@ -30,6 +30,24 @@ fn decode_works_for_regular_values() {
     assert_eq!(expected.as_ref(), buffer.as_ref());
 }
 
+#[test]
+fn encode_round_trips() {
+    let expected: Vec<i16> = (0..4096)
+        .map(|i| ((i % 64) * 40 + (i / 64) * 25 - 2000) as i16)
+        .collect();
+    let mut buffer = expected.clone();
+
+    let mut temp = vec![0; 4096];
+    encode(&mut buffer, temp.as_mut_slice());
+    decode(&mut buffer, temp.as_mut_slice());
+
+    // The transform is lossy because of rounding, but the error is far below a color level
+    // once the 11.5 fixed-point coefficients are converted back to pixels.
+    for (actual, expected) in buffer.iter().zip(expected.iter()) {
+        assert!(actual.abs_diff(*expected) <= 8, "{actual} != {expected}");
+    }
+}
+
 const DECODED_DWT_FOR_MAX_VALUES: [i16; 4096] = [
     2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
     2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 4092, 8191, -4100, -16383, -4100,
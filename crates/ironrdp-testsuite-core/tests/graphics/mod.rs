@@ -1,5 +1,6 @@
 mod color_conversion;
 mod dwt;
 mod image_processing;
+mod quantization;
 mod rle;
 mod rlgr;
@@ -0,0 +1,84 @@
+use ironrdp_graphics::quantization::{decode, encode};
+use ironrdp_pdu::codecs::rfx::Quant;
+
+fn round_trip(quant: &Quant) -> (Vec<i16>, Vec<i16>) {
+    let original: Vec<i16> = (0..4096).map(|i| (i * 37 % 8192 - 4096) as i16).collect();
+
+    let mut buffer = original.clone();
+    encode(&mut buffer, quant);
+    decode(&mut buffer, quant);
+
+    (original, buffer)
+}
+
+/// Checks that values are within half a quantization step of the original ones
+fn assert_within_step(original: &[i16], decoded: &[i16], quant: u8) {
+    let half_step = (1 << (quant - 1)) / 2;
+
+    for (original, decoded) in original.iter().zip(decoded) {
+        assert!(
+            (original - decoded).abs() <= half_step,
+            "quant {quant}: {original} decoded as {decoded}"
+        );
+    }
+}
+
+#[test]
+fn encode_then_decode_with_low_quant_values() {
+    let quant = Quant {
+        ll3: 1,
+        lh3: 2,
+        hl3: 2,
+        hh3: 3,
+        lh2: 3,
+        hl2: 3,
+        hh2: 4,
+        lh1: 4,
+        hl1: 5,
+        hh1: 5,
+    };
+
+    let (original, decoded) = round_trip(&quant);
+
+    // HL1, LH1, HH1, HL2, LH2, HH2, HL3, LH3, HH3, LL3
+    let subbands = [
+        (0..1024, quant.hl1),
+        (1024..2048, quant.lh1),
+        (2048..3072, quant.hh1),
+        (3072..3328, quant.hl2),
+        (3328..3584, quant.lh2),
+        (3584..3840, quant.hh2),
+        (3840..3904, quant.hl3),
+        (3904..3968, quant.lh3),
+        (3968..4032, quant.hh3),
+        (4032..4096, quant.ll3),
+    ];
+
+    for (range, quant) in subbands {
+        assert_within_step(&original[range.clone()], &decoded[range], quant);
+    }
+
+    // A factor of 1 is lossless
+    assert_eq!(original[4032..], decoded[4032..]);
+}
+
+#[test]
+fn encode_then_decode_with_high_quant_values() {
+    let quant = Quant {
+        ll3: 6,
+        lh3: 6,
+        hl3: 6,
+        hh3: 6,
+        lh2: 7,
+        hl2: 7,
+        hh2: 8,
+        lh1: 8,
+        hl1: 8,
+        hh1: 9,
+    };
+
+    let (original, decoded) = round_trip(&quant);
+
+    assert_within_step(&original[..1024], &decoded[..1024], quant.hl1);
+    assert_within_step(&original[4032..], &decoded[4032..], quant.ll3);
+}
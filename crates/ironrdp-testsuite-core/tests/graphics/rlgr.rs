@@ -1,5 +1,6 @@
 use ironrdp_graphics::rlgr::*;
 use ironrdp_pdu::codecs::rfx::EntropyAlgorithm;
+use rstest::rstest;
 
 #[test]
 fn decode_works_with_rlgr3() {
@@ -95,6 +96,43 @@ fn decode_correctly_decodes_rlgr1() {
     assert_eq!(expected.as_ref(), output.as_slice());
 }
 
+#[rstest]
+#[case(EntropyAlgorithm::Rlgr1)]
+#[case(EntropyAlgorithm::Rlgr3)]
+fn encode_round_trips(#[case] mode: EntropyAlgorithm) {
+    for expected in [Y_DATA_DECODED, CB_DATA_DECODED, CR_DATA_DECODED] {
+        let mut encoded = vec![0u8; 16384];
+        let len = encode(mode, &expected, &mut encoded).unwrap();
+
+        let mut output = vec![0i16; expected.len()];
+        decode(mode, &encoded[..len], &mut output).unwrap();
+        assert_eq!(expected.as_ref(), output.as_slice());
+    }
+}
+
+#[rstest]
+#[case(EntropyAlgorithm::Rlgr1)]
+#[case(EntropyAlgorithm::Rlgr3)]
+fn encode_round_trips_trailing_zeros(#[case] mode: EntropyAlgorithm) {
+    let expected = [[3, -2, 0, 5].as_ref(), [0; 60].as_ref()].concat();
+
+    let mut encoded = [0u8; 64];
+    let len = encode(mode, &expected, &mut encoded).unwrap();
+
+    let mut output = vec![0i16; expected.len()];
+    decode(mode, &encoded[..len], &mut output).unwrap();
+    assert_eq!(expected, output);
+}
+
+#[test]
+fn encode_fails_with_small_buffer() {
+    let mut encoded = [0u8; 16];
+    assert!(matches!(
+        encode(EntropyAlgorithm::Rlgr3, &Y_DATA_DECODED, &mut encoded),
+        Err(RlgrError::BufferTooSmall)
+    ));
+}
+
 const Y_DATA_ENCODED: [u8; 942] = [
     0xc0, 0x01, 0x01, 0x15, 0x48, 0x99, 0xc7, 0x41, 0xa1, 0x12, 0x68, 0x11, 0xdc, 0x22, 0x29, 0x74, 0xef, 0xfd, 0x20,
     0x92, 0xe0, 0x4e, 0xa8, 0x69, 0x3b, 0xfd, 0x41, 0x83, 0xbf, 0x28, 0x53, 0x0c, 0x1f, 0xe2, 0x54, 0x0c, 0x77, 0x7c,
@@ -9,7 +9,9 @@
 //! Cargo will run all tests from a single binary in parallel, but
 //! binaries themselves are run sequentally.
 
+mod acceptor;
 mod clipboard;
+mod connector;
 mod displaycontrol;
 mod fuzz_regression;
 mod graphics;
@@ -17,5 +19,9 @@ mod input;
 mod pcb;
 mod pdu;
 mod rdcleanpath;
+mod rdpsnd;
+mod server;
 mod server_name;
 mod session;
+mod svc;
+mod tls;
@@ -4,5 +4,7 @@ mod input;
 mod mcs;
 mod pointer;
 mod rdp;
+mod rdstls;
 mod rfx;
+mod standard_security;
 mod x224;
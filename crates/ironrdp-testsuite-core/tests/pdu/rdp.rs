@@ -2,6 +2,7 @@ use ironrdp_pdu::rdp::capability_sets::*;
 use ironrdp_pdu::rdp::client_info::*;
 use ironrdp_pdu::rdp::headers::*;
 use ironrdp_pdu::rdp::server_license::*;
+use ironrdp_pdu::rdp::server_redirection::*;
 use ironrdp_pdu::rdp::*;
 use ironrdp_pdu::PduParsing as _;
 use ironrdp_testsuite_core::capsets::*;
@@ -97,6 +98,16 @@ fn from_buffer_correctly_parses_rdp_pdu_server_font_map() {
     assert_eq!(SERVER_FONT_MAP.clone(), ShareControlHeader::from_buffer(buf).unwrap());
 }
 
+#[test]
+fn from_buffer_correctly_parses_rdp_pdu_server_deactivate_all() {
+    let buf = SERVER_DEACTIVATE_ALL_BUFFER.as_ref();
+
+    assert_eq!(
+        SERVER_DEACTIVATE_ALL.clone(),
+        ShareControlHeader::from_buffer(buf).unwrap()
+    );
+}
+
 #[test]
 fn from_buffer_correctly_parses_rdp_pdu_server_monitor_layout() {
     let buf = MONITOR_LAYOUT_PDU_BUFFER.clone();
@@ -202,6 +213,17 @@ fn to_buffer_correctly_serializes_rdp_pdu_server_font_map() {
     assert_eq!(expected_buf, buf);
 }
 
+#[test]
+fn to_buffer_correctly_serializes_rdp_pdu_server_deactivate_all() {
+    let pdu = SERVER_DEACTIVATE_ALL.clone();
+    let expected_buf = SERVER_DEACTIVATE_ALL_BUFFER.to_vec();
+
+    let mut buf = Vec::new();
+    pdu.to_buffer(&mut buf).unwrap();
+
+    assert_eq!(expected_buf, buf);
+}
+
 #[test]
 fn to_buffer_correctly_serializes_rdp_pdu_server_monitor_layout() {
     let pdu = MONITOR_LAYOUT_PDU.clone();
@@ -493,3 +515,59 @@ fn buffer_length_is_correct_for_client_demand_active() {
 
     assert_eq!(expected_buffer_len, len);
 }
+
+#[test]
+fn from_buffer_correctly_parses_server_redirection() {
+    let buf = SERVER_REDIRECTION_BUFFER.as_ref();
+
+    assert_eq!(
+        SERVER_REDIRECTION.clone(),
+        ShareControlHeader::from_buffer(buf).unwrap()
+    );
+}
+
+#[test]
+fn to_buffer_correctly_serializes_server_redirection() {
+    let mut buf = Vec::new();
+    SERVER_REDIRECTION.to_buffer(&mut buf).unwrap();
+
+    assert_eq!(SERVER_REDIRECTION_BUFFER.as_ref(), buf.as_slice());
+    assert_eq!(SERVER_REDIRECTION_BUFFER.len(), SERVER_REDIRECTION.buffer_length());
+}
+
+#[test]
+fn from_buffer_skips_server_redirection_padding() {
+    const PADDING: usize = 8;
+
+    let mut buf = SERVER_REDIRECTION_BUFFER.to_vec();
+    buf[0] += PADDING as u8; // ShareControlHeader::totalLength
+    buf[10] += PADDING as u8; // Length
+    buf.extend_from_slice(&[0; PADDING]);
+    buf.push(0xff); // Following data
+
+    let mut stream = buf.as_slice();
+    assert_eq!(
+        SERVER_REDIRECTION.clone(),
+        ShareControlHeader::from_buffer(&mut stream).unwrap()
+    );
+    assert_eq!(stream, [0xff]);
+}
+
+#[test]
+fn server_redirection_flags_follow_the_fields() {
+    let redirection = ServerRedirectionPdu {
+        flags: ServerRedirectionFlags::NO_REDIRECT | ServerRedirectionFlags::PASSWORD,
+        target_net_addresses: Some(vec!["10.0.0.2".to_owned(), "fd00::2".to_owned()]),
+        ..Default::default()
+    };
+
+    let mut buf = Vec::new();
+    redirection.to_buffer(&mut buf).unwrap();
+
+    // RedirFlags: LB_NOREDIRECT | LB_TARGET_NET_ADDRESSES, the password being absent
+    assert_eq!(buf[8..12], 0x0880u32.to_le_bytes());
+
+    let decoded = ServerRedirectionPdu::from_buffer(buf.as_slice()).unwrap();
+    assert_eq!(decoded.flags, ServerRedirectionFlags::NO_REDIRECT);
+    assert_eq!(decoded.target_net_addresses, redirection.target_net_addresses);
+}
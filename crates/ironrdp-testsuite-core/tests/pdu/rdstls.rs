@@ -0,0 +1,96 @@
+use ironrdp_pdu::rdstls::{
+    RdstlsAuthenticationRequest, RdstlsAuthenticationResponse, RdstlsCapabilities, RdstlsResultCode, RDSTLS_HINT,
+};
+use ironrdp_pdu::PduHint as _;
+use ironrdp_testsuite_core::encode_decode_test;
+
+const PASSWORD_AUTHENTICATION_REQUEST: [u8; 34] = [
+    0x01, 0x00, // version
+    0x02, 0x00, // PDU type
+    0x01, 0x00, // data type
+    0x02, 0x00, 0xAA, 0xBB, // redirection GUID
+    0x06, 0x00, b'u', 0x00, b'1', 0x00, 0x00, 0x00, // username
+    0x06, 0x00, b'd', 0x00, b'1', 0x00, 0x00, 0x00, // domain
+    0x04, 0x00, 0x01, 0x02, 0x03, 0x04, // password
+    0xFF, 0xFF, // following data
+];
+
+encode_decode_test! {
+    capabilities:
+        RdstlsCapabilities::default(),
+        [
+            0x01, 0x00, // version
+            0x01, 0x00, // PDU type
+            0x01, 0x00, // data type
+            0x01, 0x00, // supported versions
+        ];
+    password_authentication_request:
+        RdstlsAuthenticationRequest::Password {
+            redirection_guid: vec![0xAA, 0xBB],
+            username: "u1".to_owned(),
+            domain: "d1".to_owned(),
+            password: vec![0x01, 0x02, 0x03, 0x04],
+        },
+        PASSWORD_AUTHENTICATION_REQUEST[..32].to_vec();
+    auto_reconnect_cookie_authentication_request:
+        RdstlsAuthenticationRequest::AutoReconnectCookie {
+            session_id: 3,
+            auto_reconnect_cookie: vec![0x01, 0x02, 0x03],
+        },
+        [
+            0x01, 0x00, // version
+            0x02, 0x00, // PDU type
+            0x02, 0x00, // data type
+            0x03, 0x00, 0x00, 0x00, // session ID
+            0x03, 0x00, 0x01, 0x02, 0x03, // auto-reconnect cookie
+        ];
+    authentication_response:
+        RdstlsAuthenticationResponse {
+            result_code: RdstlsResultCode::LOGON_FAILURE,
+        },
+        [
+            0x01, 0x00, // version
+            0x04, 0x00, // PDU type
+            0x01, 0x00, // data type
+            0x2E, 0x05, 0x00, 0x00, // result code
+        ];
+}
+
+#[test]
+fn hint_finds_size_of_variable_length_request() {
+    // The size is known once the length of the password is received
+    for len in 0..28 {
+        assert_eq!(
+            RDSTLS_HINT.find_size(&PASSWORD_AUTHENTICATION_REQUEST[..len]).unwrap(),
+            None
+        );
+    }
+
+    assert_eq!(
+        RDSTLS_HINT.find_size(&PASSWORD_AUTHENTICATION_REQUEST[..28]).unwrap(),
+        Some(32)
+    );
+
+    assert_eq!(
+        RDSTLS_HINT.find_size(&PASSWORD_AUTHENTICATION_REQUEST).unwrap(),
+        Some(32)
+    );
+}
+
+#[test]
+fn hint_rejects_unknown_pdu_type() {
+    assert!(RDSTLS_HINT.find_size(&[0x01, 0x00, 0x03, 0x00, 0x01, 0x00]).is_err());
+}
+
+#[test]
+fn unsupported_version_is_rejected() {
+    let encoded = [0x02, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00];
+
+    assert!(ironrdp_pdu::decode::<RdstlsCapabilities>(&encoded).is_err());
+}
+
+#[test]
+fn result_code_description() {
+    assert_eq!(RdstlsResultCode::ACCOUNT_LOCKED_OUT.to_string(), "account locked out");
+    assert_eq!(RdstlsResultCode(0x1234).to_string(), "unknown result code 0x00001234");
+}
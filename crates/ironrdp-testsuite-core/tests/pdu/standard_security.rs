@@ -0,0 +1,279 @@
+//! Reference values were computed independently from the algorithms described in [MS-RDPBCGR] 5.3,
+//! using the server random and proprietary certificate of the Server Security Data test vectors.
+
+use ironrdp_pdu::crypto::standard_security::{
+    encrypt_client_random, mac_signature, update_key, SessionKeys, StandardSecurity, StandardSecurityError,
+    KEY_UPDATE_INTERVAL,
+};
+use ironrdp_pdu::gcc::EncryptionMethod;
+use ironrdp_pdu::rdp::headers::BasicSecurityHeaderFlags;
+use ironrdp_testsuite_core::security_data::{SERVER_CERT_BUFFER, SERVER_RANDOM_BUFFER};
+
+const CLIENT_RANDOM: [u8; 32] = [
+    0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e, 0x2f, 0x30, 0x31, 0x32,
+    0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f,
+];
+
+const ENCRYPTED_CLIENT_RANDOM: [u8; 72] = [
+    0xe4, 0x80, 0xac, 0x55, 0xe1, 0x14, 0x98, 0x06, 0xce, 0xf7, 0x65, 0x41, 0x2e, 0x4d, 0x75, 0x62, 0x17, 0x3f, 0x9f,
+    0x13, 0x32, 0xb8, 0xbe, 0x54, 0x09, 0xbf, 0x05, 0x19, 0x6f, 0xf8, 0x86, 0x3e, 0x9d, 0x7e, 0x50, 0x91, 0x0e, 0x64,
+    0x5d, 0xea, 0xc4, 0x42, 0xd4, 0x0b, 0xf5, 0xfd, 0x09, 0xe4, 0x8b, 0xb1, 0x5f, 0x34, 0x97, 0x58, 0x4d, 0x76, 0xed,
+    0x54, 0x26, 0x56, 0x94, 0xcb, 0x34, 0x82, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+const MESSAGE: &[u8] = b"hello world";
+
+fn session_keys(method: EncryptionMethod) -> SessionKeys {
+    SessionKeys::derive(&CLIENT_RANDOM, &SERVER_RANDOM_BUFFER, method).unwrap()
+}
+
+/// Encryption state of the server, which decrypts with the client encryption key and conversely
+fn server_side(keys: &SessionKeys) -> StandardSecurity {
+    StandardSecurity::new(SessionKeys {
+        method: keys.method,
+        mac_key: keys.mac_key.clone(),
+        encrypt_key: keys.decrypt_key.clone(),
+        decrypt_key: keys.encrypt_key.clone(),
+    })
+}
+
+#[test]
+fn client_random_is_encrypted_with_proprietary_certificate_public_key() {
+    let encrypted = encrypt_client_random(&CLIENT_RANDOM, &SERVER_CERT_BUFFER).unwrap();
+
+    assert_eq!(ENCRYPTED_CLIENT_RANDOM.as_slice(), encrypted.as_slice());
+}
+
+#[test]
+fn session_keys_128_bit() {
+    let keys = session_keys(EncryptionMethod::BIT_128);
+
+    assert_eq!(
+        [0xe5, 0x08, 0x47, 0xa3, 0x90, 0xb2, 0x9d, 0x74, 0x6d, 0x6e, 0x56, 0x34, 0x29, 0x65, 0x60, 0xda].as_slice(),
+        keys.mac_key.as_slice()
+    );
+    assert_eq!(
+        [0x48, 0xe5, 0x92, 0x7c, 0xd2, 0xf2, 0x06, 0xdf, 0x08, 0x17, 0x63, 0x5c, 0x8a, 0x7d, 0xa6, 0xb4].as_slice(),
+        keys.encrypt_key.as_slice()
+    );
+    assert_eq!(
+        [0x40, 0x5e, 0x03, 0xd7, 0x97, 0x23, 0x39, 0xf1, 0xcd, 0xf2, 0x10, 0x00, 0xc0, 0xb0, 0x5c, 0x26].as_slice(),
+        keys.decrypt_key.as_slice()
+    );
+}
+
+#[test]
+fn session_keys_56_bit() {
+    let keys = session_keys(EncryptionMethod::BIT_56);
+
+    assert_eq!(
+        [0xd1, 0x08, 0x47, 0xa3, 0x90, 0xb2, 0x9d, 0x74].as_slice(),
+        keys.mac_key.as_slice()
+    );
+    assert_eq!(
+        [0xd1, 0xe5, 0x92, 0x7c, 0xd2, 0xf2, 0x06, 0xdf].as_slice(),
+        keys.encrypt_key.as_slice()
+    );
+    assert_eq!(
+        [0xd1, 0x5e, 0x03, 0xd7, 0x97, 0x23, 0x39, 0xf1].as_slice(),
+        keys.decrypt_key.as_slice()
+    );
+}
+
+#[test]
+fn session_keys_40_bit() {
+    let keys = session_keys(EncryptionMethod::BIT_40);
+
+    assert_eq!(
+        [0xd1, 0x26, 0x9e, 0xa3, 0x90, 0xb2, 0x9d, 0x74].as_slice(),
+        keys.mac_key.as_slice()
+    );
+    assert_eq!(
+        [0xd1, 0x26, 0x9e, 0x7c, 0xd2, 0xf2, 0x06, 0xdf].as_slice(),
+        keys.encrypt_key.as_slice()
+    );
+    assert_eq!(
+        [0xd1, 0x26, 0x9e, 0xd7, 0x97, 0x23, 0x39, 0xf1].as_slice(),
+        keys.decrypt_key.as_slice()
+    );
+}
+
+#[test]
+fn fips_encryption_is_not_supported() {
+    let result = SessionKeys::derive(&CLIENT_RANDOM, &SERVER_RANDOM_BUFFER, EncryptionMethod::FIPS);
+
+    assert!(matches!(
+        result,
+        Err(StandardSecurityError::UnsupportedEncryptionMethod(
+            EncryptionMethod::FIPS
+        ))
+    ));
+}
+
+#[test]
+fn salted_mac_signature() {
+    let keys = session_keys(EncryptionMethod::BIT_128);
+
+    assert_eq!(
+        [0x8c, 0x86, 0x5b, 0xdf, 0x5d, 0x75, 0x32, 0xa8],
+        mac_signature(&keys.mac_key, MESSAGE, Some(5))
+    );
+}
+
+#[test]
+fn key_update() {
+    let keys = session_keys(EncryptionMethod::BIT_128);
+    assert_eq!(
+        vec![0x73, 0x5e, 0xa9, 0xc6, 0xe4, 0x86, 0xd4, 0x38, 0x43, 0x6c, 0xae, 0x45, 0xa0, 0x0b, 0xaf, 0xd6],
+        update_key(&keys.encrypt_key, &keys.encrypt_key, EncryptionMethod::BIT_128)
+    );
+
+    let keys = session_keys(EncryptionMethod::BIT_40);
+    assert_eq!(
+        vec![0xd1, 0x26, 0x9e, 0x6c, 0x03, 0x50, 0xa0, 0x0e],
+        update_key(&keys.encrypt_key, &keys.encrypt_key, EncryptionMethod::BIT_40)
+    );
+}
+
+fn check_encryption_across_key_update(method: EncryptionMethod, expected: [[u8; 19]; 4]) {
+    let keys = session_keys(method);
+    let mut client = StandardSecurity::new(keys.clone());
+    let mut server = server_side(&keys);
+
+    let encrypted = (0..=KEY_UPDATE_INTERVAL)
+        .map(|_| client.encrypt(MESSAGE))
+        .collect::<Vec<_>>();
+
+    assert_eq!(expected[0].as_slice(), encrypted[0].as_slice());
+    assert_eq!(expected[1].as_slice(), encrypted[1].as_slice());
+    // The first packet encrypted with the updated key
+    assert_eq!(expected[2].as_slice(), encrypted[4096].as_slice());
+
+    for encrypted in encrypted {
+        assert_eq!(MESSAGE, server.decrypt(&encrypted, false).unwrap());
+    }
+
+    assert_eq!(expected[3].as_slice(), client.encrypt(MESSAGE).as_slice());
+}
+
+#[test]
+fn encryption_128_bit_across_key_update() {
+    check_encryption_across_key_update(
+        EncryptionMethod::BIT_128,
+        [
+            [
+                0xc0, 0xab, 0x22, 0xb9, 0x5b, 0x6b, 0xe4, 0x32, 0x1c, 0x27, 0x24, 0x53, 0xdc, 0xa7, 0x77, 0x29, 0xc7,
+                0xfc, 0xb3,
+            ],
+            [
+                0xc0, 0xab, 0x22, 0xb9, 0x5b, 0x6b, 0xe4, 0x32, 0xab, 0x1d, 0x8d, 0x36, 0x82, 0xae, 0xb1, 0x25, 0x57,
+                0x19, 0xc8,
+            ],
+            [
+                0xc0, 0xab, 0x22, 0xb9, 0x5b, 0x6b, 0xe4, 0x32, 0x59, 0x47, 0x16, 0x18, 0x38, 0x84, 0x95, 0x65, 0xfc,
+                0xed, 0x94,
+            ],
+            [
+                0xc0, 0xab, 0x22, 0xb9, 0x5b, 0x6b, 0xe4, 0x32, 0xf8, 0x0d, 0x9d, 0xa1, 0xb5, 0x1f, 0xd8, 0xb3, 0xb4,
+                0xd7, 0x4f,
+            ],
+        ],
+    );
+}
+
+#[test]
+fn encryption_40_bit_across_key_update() {
+    check_encryption_across_key_update(
+        EncryptionMethod::BIT_40,
+        [
+            [
+                0xbe, 0x0b, 0x0e, 0xae, 0x4c, 0xd0, 0x95, 0xe0, 0x0d, 0xd9, 0xf3, 0x90, 0xaf, 0x0d, 0xb4, 0xe4, 0x13,
+                0x06, 0xa5,
+            ],
+            [
+                0xbe, 0x0b, 0x0e, 0xae, 0x4c, 0xd0, 0x95, 0xe0, 0x36, 0xcf, 0x91, 0xdf, 0x74, 0x3f, 0x13, 0x95, 0x43,
+                0xcf, 0xe5,
+            ],
+            [
+                0xbe, 0x0b, 0x0e, 0xae, 0x4c, 0xd0, 0x95, 0xe0, 0xd0, 0x38, 0x2d, 0x96, 0xec, 0xa3, 0xd1, 0x7f, 0xed,
+                0x68, 0xa1,
+            ],
+            [
+                0xbe, 0x0b, 0x0e, 0xae, 0x4c, 0xd0, 0x95, 0xe0, 0x00, 0x12, 0x03, 0x7a, 0xe5, 0xd3, 0xb3, 0xa4, 0x56,
+                0x52, 0xbb,
+            ],
+        ],
+    );
+}
+
+#[test]
+fn tampered_data_is_rejected() {
+    let keys = session_keys(EncryptionMethod::BIT_128);
+    let mut client = StandardSecurity::new(keys.clone());
+    let mut server = server_side(&keys);
+
+    let mut encrypted = client.encrypt(MESSAGE);
+    *encrypted.last_mut().unwrap() ^= 0x01;
+
+    assert!(matches!(
+        server.decrypt(&encrypted, false),
+        Err(StandardSecurityError::InvalidMacSignature)
+    ));
+}
+
+#[test]
+fn user_data_round_trip() {
+    let keys = session_keys(EncryptionMethod::BIT_128);
+    let mut client = StandardSecurity::new(keys.clone());
+    let mut server = server_side(&keys);
+
+    let user_data = client.encrypt_user_data(BasicSecurityHeaderFlags::INFO_PKT, MESSAGE);
+    let (flags, data) = server.decrypt_user_data(&user_data).unwrap();
+
+    assert_eq!(
+        BasicSecurityHeaderFlags::INFO_PKT | BasicSecurityHeaderFlags::ENCRYPT,
+        flags
+    );
+    assert_eq!(MESSAGE, data);
+
+    let (flags, data) = server.decrypt_user_data(&[0x80, 0x00, 0x00, 0x00, 0x01, 0x02]).unwrap();
+
+    assert_eq!(BasicSecurityHeaderFlags::LICENSE_PKT, flags);
+    assert_eq!([0x01, 0x02].as_slice(), data);
+}
+
+#[test]
+fn fast_path_round_trip() {
+    let keys = session_keys(EncryptionMethod::BIT_128);
+    let mut client = StandardSecurity::new(keys.clone());
+    let mut server = server_side(&keys);
+
+    // The length of the second PDU no longer fits in one byte once encrypted
+    let short_pdu = [0x04, 0x05, 0x00, 0x00, 0x1E];
+    let long_pdu = [[0x00, 0x7D, 0x0A].as_slice(), &[0xAB; 0x7A]].concat();
+
+    for pdu in [short_pdu.as_slice(), &long_pdu] {
+        let encrypted = client.encrypt_fast_path(pdu).unwrap();
+
+        assert_eq!(pdu[0] | 0x80, encrypted[0]);
+        assert_eq!(pdu.len() + 8 + usize::from(pdu.len() == 0x7D), encrypted.len());
+        assert_eq!(pdu, server.decrypt_fast_path(&encrypted).unwrap());
+    }
+
+    // Unencrypted PDUs are left as is
+    assert_eq!(short_pdu.as_slice(), server.decrypt_fast_path(&short_pdu).unwrap());
+}
+
+#[test]
+fn keys_are_not_printed() {
+    let keys = session_keys(EncryptionMethod::BIT_128);
+    let security = StandardSecurity::new(keys.clone());
+
+    let printed = format!("{security:?}");
+
+    for key in [&keys.mac_key, &keys.encrypt_key, &keys.decrypt_key] {
+        assert!(!printed.contains(&format!("{key:?}")), "{printed}");
+        assert!(!printed.contains(&format!("{:?}", &key[..4])), "{printed}");
+    }
+    assert!(printed.contains("BIT_128"), "{printed}");
+}
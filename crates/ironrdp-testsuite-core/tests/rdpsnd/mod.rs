@@ -0,0 +1,197 @@
+use ironrdp_rdpsnd::pdu::{
+    AudioFormat, AudioFormatFlags, ClientAudioFormatPdu, ClientAudioOutputPdu, ServerAudioOutputPdu,
+    TrainingConfirmPdu, Version, Wave2Pdu, WaveConfirmPdu, WavePdu,
+};
+use ironrdp_rdpsnd::server::{RdpsndServer, RdpsndServerHandler};
+use ironrdp_svc::{StaticVirtualChannel, SvcMessage, SvcProcessor as _};
+use ironrdp_testsuite_core::encode_decode_test;
+
+encode_decode_test! {
+    client_formats: ClientAudioOutputPdu::AudioFormat(ClientAudioFormatPdu {
+        version: Version::V8,
+        flags: AudioFormatFlags::ALIVE,
+        formats: vec![AudioFormat::pcm(2, 22_050, 16)],
+        volume: 0xFFFF_FFFF,
+        pitch: 0x0001_0000,
+        dgram_port: 0,
+    }),
+    [
+        // Header
+        0x07, 0x00, 0x26, 0x00,
+        // dwFlags, dwVolume, dwPitch
+        0x01, 0x00, 0x00, 0x00,
+        0xFF, 0xFF, 0xFF, 0xFF,
+        0x00, 0x00, 0x01, 0x00,
+        // wDGramPort, wNumberOfFormats, cLastBlockConfirmed, wVersion, bPad
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x08, 0x00, 0x00,
+        // PCM, 2 channels, 22050 Hz, 16 bits
+        0x01, 0x00, 0x02, 0x00,
+        0x22, 0x56, 0x00, 0x00,
+        0x88, 0x58, 0x01, 0x00,
+        0x04, 0x00, 0x10, 0x00,
+        0x00, 0x00,
+    ];
+
+    wave_confirm: ClientAudioOutputPdu::WaveConfirm(WaveConfirmPdu {
+        timestamp: 0x1234,
+        block_no: 7,
+    }),
+    [
+        0x05, 0x00, 0x04, 0x00,
+        0x34, 0x12, 0x07, 0x00,
+    ];
+
+    wave: ServerAudioOutputPdu::Wave(WavePdu {
+        timestamp: 0x10,
+        format_no: 0,
+        block_no: 1,
+        data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+    }),
+    [
+        // Wave Info PDU
+        0x02, 0x00, 0x10, 0x00,
+        0x10, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+        0x01, 0x02, 0x03, 0x04,
+        // Wave PDU
+        0x00, 0x00, 0x00, 0x00,
+        0x05, 0x06, 0x07, 0x08,
+    ];
+
+    wave2: ServerAudioOutputPdu::Wave2(Wave2Pdu {
+        timestamp: 0x10,
+        format_no: 1,
+        block_no: 2,
+        audio_timestamp: 0x100,
+        data: vec![1, 2, 3, 4],
+    }),
+    [
+        0x0D, 0x00, 0x10, 0x00,
+        0x10, 0x00, 0x01, 0x00,
+        0x02, 0x00, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x00,
+        0x01, 0x02, 0x03, 0x04,
+    ];
+}
+
+#[derive(Debug)]
+struct TestHandler {
+    formats: Vec<AudioFormat>,
+}
+
+impl RdpsndServerHandler for TestHandler {
+    fn get_formats(&self) -> &[AudioFormat] {
+        &self.formats
+    }
+
+    fn start(&mut self, client_format: &ClientAudioFormatPdu) -> Option<u16> {
+        let format_no = client_format.formats.iter().position(|f| self.formats.contains(f))?;
+        u16::try_from(format_no).ok()
+    }
+
+    fn stop(&mut self) {}
+}
+
+/// Returns the audio output PDU carried by the messages, without the channel PDU headers
+fn decode_server_pdu(messages: Vec<SvcMessage>) -> ServerAudioOutputPdu {
+    let data: Vec<u8> = StaticVirtualChannel::chunkify(messages)
+        .unwrap()
+        .iter()
+        .flat_map(|chunk| chunk.filled()[8..].to_vec())
+        .collect();
+
+    ironrdp_pdu::decode(&data).unwrap()
+}
+
+fn process(server: &mut RdpsndServer, pdu: ClientAudioOutputPdu) -> Vec<SvcMessage> {
+    server.process(&ironrdp_pdu::encode_vec(&pdu).unwrap()).unwrap()
+}
+
+fn ready_server(version: Version) -> RdpsndServer {
+    let format = AudioFormat::pcm(2, 44_100, 16);
+    let mut server = RdpsndServer::new(Box::new(TestHandler {
+        formats: vec![format.clone()],
+    }));
+
+    let ServerAudioOutputPdu::AudioFormat(server_format) = decode_server_pdu(server.start().unwrap()) else {
+        panic!("expected server audio formats");
+    };
+    assert_eq!(server_format.formats, [format.clone()]);
+
+    let client_format = ClientAudioFormatPdu {
+        version,
+        flags: AudioFormatFlags::ALIVE,
+        formats: vec![AudioFormat::pcm(1, 22_050, 8), format.clone()],
+        volume: 0,
+        pitch: 0,
+        dgram_port: 0,
+    };
+    let training = process(&mut server, ClientAudioOutputPdu::AudioFormat(client_format));
+    assert!(matches!(decode_server_pdu(training), ServerAudioOutputPdu::Training(_)));
+
+    let confirm = TrainingConfirmPdu {
+        timestamp: 0,
+        pack_size: 0,
+    };
+    assert!(process(&mut server, ClientAudioOutputPdu::TrainingConfirm(confirm)).is_empty());
+    assert_eq!(server.format(), Some(&format));
+
+    server
+}
+
+#[test]
+fn server_sends_wave2() {
+    let mut server = ready_server(Version::V8);
+
+    let messages = server.wave(&[1, 2, 3, 4, 5, 6], 1000).unwrap();
+    let ServerAudioOutputPdu::Wave2(wave) = decode_server_pdu(messages.into()) else {
+        panic!("expected wave2 PDU");
+    };
+    assert_eq!(wave.format_no, 1);
+    assert_eq!(wave.audio_timestamp, 1000);
+    assert_eq!(wave.data, [1, 2, 3, 4, 5, 6]);
+    assert_eq!(server.blocks_in_flight(), 1);
+
+    let confirm = WaveConfirmPdu {
+        timestamp: wave.timestamp,
+        block_no: wave.block_no,
+    };
+    process(&mut server, ClientAudioOutputPdu::WaveConfirm(confirm));
+    assert_eq!(server.blocks_in_flight(), 0);
+}
+
+#[test]
+fn server_sends_wave_to_older_clients() {
+    let mut server = ready_server(Version::V6);
+
+    let messages: Vec<SvcMessage> = server.wave(&[1, 2, 3, 4, 5, 6], 1000).unwrap().into();
+    assert_eq!(messages.len(), 2);
+
+    let ServerAudioOutputPdu::Wave(wave) = decode_server_pdu(messages) else {
+        panic!("expected wave PDU");
+    };
+    assert_eq!(wave.format_no, 1);
+    assert_eq!(wave.data, [1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn server_drops_wave_before_training() {
+    let mut server = RdpsndServer::new(Box::new(TestHandler {
+        formats: vec![AudioFormat::pcm(2, 44_100, 16)],
+    }));
+    server.start().unwrap();
+
+    let messages: Vec<SvcMessage> = server.wave(&[1, 2, 3, 4], 0).unwrap().into();
+    assert!(messages.is_empty());
+}
+
+#[test]
+fn server_closes() {
+    let mut server = ready_server(Version::V8);
+
+    assert_eq!(
+        decode_server_pdu(server.close().unwrap().into()),
+        ServerAudioOutputPdu::Close
+    );
+    assert!(server.format().is_none());
+}
@@ -0,0 +1,61 @@
+use ironrdp_dvc::DvcProcessor as _;
+use ironrdp_pdu::dvc::display::{
+    ClientPdu, DisplayControlCapsPdu, Monitor, MonitorFlags, MonitorLayoutPdu, Orientation, ServerPdu,
+};
+use ironrdp_pdu::PduParsing as _;
+use ironrdp_server::DisplayControlHandler;
+use tokio::sync::mpsc;
+
+fn monitor(left: u32, flags: MonitorFlags) -> Monitor {
+    Monitor {
+        flags,
+        left,
+        top: 0,
+        width: 1920,
+        height: 1080,
+        physical_width: 0,
+        physical_height: 0,
+        orientation: Orientation::Landscape,
+        desktop_scale_factor: 100,
+        device_scale_factor: 100,
+    }
+}
+
+#[test]
+fn display_control_advertises_max_monitors() {
+    let (layouts, _) = mpsc::unbounded_channel();
+    let mut handler = DisplayControlHandler::new(16, layouts);
+
+    let messages = handler.start(1).unwrap();
+    assert_eq!(messages.len(), 1);
+
+    let caps = ironrdp_pdu::encode_vec(messages[0].as_ref()).unwrap();
+    assert_eq!(
+        ServerPdu::from_buffer(caps.as_slice()).unwrap(),
+        ServerPdu::DisplayControlCaps(DisplayControlCapsPdu {
+            max_num_monitors: 16,
+            max_monitor_area_factora: 3840,
+            max_monitor_area_factorb: 2400,
+        })
+    );
+}
+
+#[test]
+fn display_control_forwards_layout() {
+    let (layouts, mut received) = mpsc::unbounded_channel();
+    let mut handler = DisplayControlHandler::new(16, layouts);
+
+    let layout = MonitorLayoutPdu {
+        monitors: vec![monitor(0, MonitorFlags::PRIMARY), monitor(1920, MonitorFlags::empty())],
+    };
+    let mut payload = Vec::new();
+    ClientPdu::DisplayControlMonitorLayout(layout.clone())
+        .to_buffer(&mut payload)
+        .unwrap();
+
+    let responses = handler.process(1, &payload).unwrap();
+    assert!(responses.is_empty());
+
+    assert_eq!(received.try_recv().unwrap(), layout);
+    assert!(received.try_recv().is_err());
+}
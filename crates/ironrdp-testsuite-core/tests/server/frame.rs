@@ -0,0 +1,125 @@
+use std::num::NonZeroU16;
+use std::time::Duration;
+
+use ironrdp_server::{BitmapUpdate, Damage, DesktopSize, FrameTracker, InclusiveRectangle, PixelFormat, PixelOrder};
+
+fn bitmap(left: u16, top: u16, color: u8) -> BitmapUpdate {
+    BitmapUpdate {
+        top,
+        left,
+        width: NonZeroU16::new(2).unwrap(),
+        height: NonZeroU16::new(2).unwrap(),
+        format: PixelFormat::BgrA32,
+        order: PixelOrder::TopToBottom,
+        data: vec![color; 2 * 2 * 4],
+    }
+}
+
+fn area(left: u16, top: u16, right: u16, bottom: u16) -> InclusiveRectangle {
+    InclusiveRectangle {
+        left,
+        top,
+        right,
+        bottom,
+    }
+}
+
+#[test]
+fn recorded_bitmaps_are_repainted_on_request() {
+    let mut damage = Damage::new(DesktopSize { width: 4, height: 4 });
+
+    damage.record(&bitmap(0, 0, 0x11));
+    damage.record(&bitmap(2, 2, 0x22));
+    assert!(damage.is_empty());
+
+    damage.invalidate(&area(2, 2, 3, 3));
+    let bitmaps = damage.take();
+    assert_eq!(bitmaps.len(), 1);
+    assert_eq!((bitmaps[0].left, bitmaps[0].top), (2, 2));
+    assert_eq!(bitmaps[0].data, [0x22; 2 * 2 * 4]);
+}
+
+#[test]
+fn cleared_damage_ignores_repaint_requests() {
+    let mut damage = Damage::new(DesktopSize { width: 4, height: 4 });
+
+    damage.push(&bitmap(0, 0, 0x11));
+    assert!(!damage.is_empty());
+
+    damage.clear();
+    assert!(damage.is_empty());
+
+    // The desktop content is unknown
+    damage.invalidate(&area(0, 0, 3, 3));
+    assert!(damage.is_empty());
+}
+
+#[test]
+fn frames_are_held_back_until_acknowledged() {
+    let mut frames = FrameTracker::new(2);
+
+    assert_eq!(frames.start_frame(true), 0);
+    assert!(!frames.is_full());
+    assert_eq!(frames.deadline(), None);
+
+    assert_eq!(frames.start_frame(true), 1);
+    assert!(frames.is_full());
+    assert!(frames.deadline().is_some());
+
+    frames.acknowledge(0);
+    assert!(!frames.is_full());
+    assert_eq!(frames.deadline(), None);
+
+    // Acknowledging a frame acknowledges the previous ones
+    frames.start_frame(true);
+    frames.acknowledge(2);
+    assert!(!frames.is_full());
+    frames.start_frame(true);
+    assert!(!frames.is_full());
+}
+
+#[test]
+fn untracked_frames_are_not_waited_for() {
+    let mut frames = FrameTracker::new(1);
+
+    assert_eq!(frames.start_frame(false), 0);
+    assert_eq!(frames.start_frame(false), 1);
+    assert!(!frames.is_full());
+}
+
+#[test]
+fn stalled_client_recovers_after_timeout() {
+    let mut frames = FrameTracker::new(2);
+    frames.start_frame(true);
+    frames.start_frame(true);
+
+    let deadline = frames.deadline().unwrap();
+
+    frames.expire(deadline - Duration::from_millis(1));
+    assert!(frames.is_full());
+    assert_eq!(frames.deadline(), Some(deadline));
+
+    frames.expire(deadline);
+    assert!(!frames.is_full());
+    assert_eq!(frames.deadline(), None);
+
+    // New frames are tracked again, and late acknowledgements are ignored
+    assert_eq!(frames.start_frame(true), 2);
+    frames.acknowledge(1);
+    assert_eq!(frames.start_frame(true), 3);
+    assert!(frames.is_full());
+
+    frames.acknowledge(3);
+    assert!(!frames.is_full());
+}
+
+#[test]
+fn reset_stops_waiting_for_frames() {
+    let mut frames = FrameTracker::new(1);
+    frames.start_frame(true);
+    assert!(frames.is_full());
+
+    frames.clear();
+    assert!(!frames.is_full());
+    assert_eq!(frames.deadline(), None);
+}
@@ -0,0 +1,257 @@
+use std::num::NonZeroU16;
+use std::sync::{Arc, Mutex};
+
+use ironrdp_dvc::DvcProcessor as _;
+use ironrdp_graphics::zgfx;
+use ironrdp_pdu::dvc::gfx::{
+    CapabilitiesAdvertisePdu, CapabilitiesConfirmPdu, CapabilitiesV104Flags, CapabilitiesV8Flags, CapabilitySet,
+    ClientPdu, CreateSurfacePdu, DeleteSurfacePdu, EndFramePdu, FrameAcknowledgePdu, MapSurfaceToOutputPdu,
+    PixelFormat as SurfacePixelFormat, QueueDepth, ResetGraphicsPdu, ServerPdu,
+};
+use ironrdp_pdu::gcc::{Monitor, MonitorFlags};
+use ironrdp_pdu::PduParsing as _;
+use ironrdp_server::{
+    BitmapUpdate, DesktopSize, GfxEncoder, GfxState, GraphicsPipelineHandler, InclusiveRectangle, PixelFormat,
+    PixelOrder,
+};
+
+const CHANNEL_ID: u32 = 7;
+
+/// Surface on which the server draws the desktop
+const SURFACE_ID: u16 = 0;
+
+/// Frames sent by the server without being acknowledged, after which frames are held back
+const MAX_FRAMES_IN_FLIGHT: u32 = 3;
+
+const DESKTOP_SIZE: DesktopSize = DesktopSize { width: 64, height: 32 };
+
+fn bitmap() -> BitmapUpdate {
+    BitmapUpdate {
+        top: 8,
+        left: 16,
+        width: NonZeroU16::new(4).unwrap(),
+        height: NonZeroU16::new(2).unwrap(),
+        format: PixelFormat::BgrA32,
+        order: PixelOrder::TopToBottom,
+        data: vec![0xff; 4 * 2 * 4],
+    }
+}
+
+fn decode(messages: ironrdp_dvc::DvcMessages) -> Vec<ServerPdu> {
+    let mut decompressor = zgfx::Decompressor::new();
+
+    messages
+        .iter()
+        .map(|message| {
+            let segments = ironrdp_pdu::encode_vec(message.as_ref()).unwrap();
+            let mut pdu = Vec::new();
+            decompressor.decompress(&segments, &mut pdu).unwrap();
+            ServerPdu::from_buffer(pdu.as_slice()).unwrap()
+        })
+        .collect()
+}
+
+fn client_pdu(pdu: ClientPdu) -> Vec<u8> {
+    let mut payload = Vec::new();
+    pdu.to_buffer(&mut payload).unwrap();
+    payload
+}
+
+fn acknowledge(handler: &mut GraphicsPipelineHandler, frame_id: u32, queue_depth: QueueDepth) {
+    let ack = client_pdu(ClientPdu::FrameAcknowledge(FrameAcknowledgePdu {
+        queue_depth,
+        frame_id,
+        total_frames_decoded: frame_id + 1,
+    }));
+
+    assert!(handler.process(CHANNEL_ID, &ack).unwrap().is_empty());
+}
+
+/// Opens the channel, and negotiates the capabilities
+fn open() -> (Arc<Mutex<GfxState>>, GraphicsPipelineHandler) {
+    let state = GfxState::new(DESKTOP_SIZE);
+    let mut handler = GraphicsPipelineHandler {
+        state: Arc::clone(&state),
+    };
+
+    assert!(handler.start(CHANNEL_ID).unwrap().is_empty());
+
+    let advertise = client_pdu(ClientPdu::CapabilitiesAdvertise(CapabilitiesAdvertisePdu(vec![
+        CapabilitySet::V10_4 {
+            flags: CapabilitiesV104Flags::SMALL_CACHE,
+        },
+    ])));
+    handler.process(CHANNEL_ID, &advertise).unwrap();
+
+    (state, handler)
+}
+
+#[test]
+fn capabilities_advertise_to_first_frame() {
+    let state = GfxState::new(DESKTOP_SIZE);
+    let mut handler = GraphicsPipelineHandler {
+        state: Arc::clone(&state),
+    };
+
+    assert!(!state.lock().unwrap().is_open());
+    assert!(handler.start(CHANNEL_ID).unwrap().is_empty());
+    assert!(state.lock().unwrap().is_open());
+
+    // Frames wait for the surface, created once the capabilities are negotiated
+    state.lock().unwrap().queue(bitmap());
+    assert!(state.lock().unwrap().next_frame().is_none());
+
+    let advertise = client_pdu(ClientPdu::CapabilitiesAdvertise(CapabilitiesAdvertisePdu(vec![
+        CapabilitySet::V8 {
+            flags: CapabilitiesV8Flags::THIN_CLIENT,
+        },
+        CapabilitySet::V10_4 {
+            flags: CapabilitiesV104Flags::SMALL_CACHE,
+        },
+    ])));
+    let setup = decode(handler.process(CHANNEL_ID, &advertise).unwrap());
+
+    assert_eq!(
+        setup,
+        [
+            ServerPdu::CapabilitiesConfirm(CapabilitiesConfirmPdu(CapabilitySet::V10_4 {
+                flags: CapabilitiesV104Flags::SMALL_CACHE,
+            })),
+            ServerPdu::ResetGraphics(ResetGraphicsPdu {
+                width: 64,
+                height: 32,
+                monitors: vec![Monitor {
+                    left: 0,
+                    top: 0,
+                    right: 63,
+                    bottom: 31,
+                    flags: MonitorFlags::PRIMARY,
+                }],
+            }),
+            ServerPdu::CreateSurface(CreateSurfacePdu {
+                surface_id: SURFACE_ID,
+                width: 64,
+                height: 32,
+                pixel_format: SurfacePixelFormat::XRgb,
+            }),
+            ServerPdu::MapSurfaceToOutput(MapSurfaceToOutputPdu {
+                surface_id: SURFACE_ID,
+                output_origin_x: 0,
+                output_origin_y: 0,
+            }),
+        ]
+    );
+
+    let frame = state.lock().unwrap().next_frame().unwrap();
+    assert_eq!(frame.channel_id, CHANNEL_ID);
+    assert_eq!(frame.frame_id, 0);
+    assert_eq!(frame.bitmaps.len(), 1);
+
+    let pdus = decode(GfxEncoder::new().frame(&frame).unwrap());
+    assert_eq!(pdus.len(), 3);
+    assert!(matches!(&pdus[0], ServerPdu::StartFrame(start) if start.frame_id == 0));
+    match &pdus[1] {
+        ServerPdu::WireToSurface1(pdu) => {
+            assert_eq!(pdu.surface_id, SURFACE_ID);
+            assert_eq!(
+                pdu.destination_rectangle,
+                InclusiveRectangle {
+                    left: 16,
+                    top: 8,
+                    right: 19,
+                    bottom: 9,
+                }
+            );
+        }
+        unexpected => panic!("unexpected PDU: {unexpected:?}"),
+    }
+    assert_eq!(pdus[2], ServerPdu::EndFrame(EndFramePdu { frame_id: 0 }));
+
+    // Nothing left to send
+    assert!(state.lock().unwrap().next_frame().is_none());
+}
+
+#[test]
+fn unsupported_capabilities_are_ignored() {
+    let state = GfxState::new(DESKTOP_SIZE);
+    let mut handler = GraphicsPipelineHandler {
+        state: Arc::clone(&state),
+    };
+    handler.start(CHANNEL_ID).unwrap();
+
+    let advertise = client_pdu(ClientPdu::CapabilitiesAdvertise(CapabilitiesAdvertisePdu(vec![
+        CapabilitySet::Unknown(vec![0; 4]),
+    ])));
+    assert!(handler.process(CHANNEL_ID, &advertise).unwrap().is_empty());
+
+    state.lock().unwrap().queue(bitmap());
+    assert!(state.lock().unwrap().next_frame().is_none());
+}
+
+#[test]
+fn frames_are_held_back_until_acknowledged() {
+    let (state, mut handler) = open();
+
+    for frame_id in 0..MAX_FRAMES_IN_FLIGHT {
+        state.lock().unwrap().queue(bitmap());
+        assert_eq!(state.lock().unwrap().next_frame().unwrap().frame_id, frame_id);
+    }
+
+    state.lock().unwrap().queue(bitmap());
+    assert!(state.lock().unwrap().next_frame().is_none());
+
+    // Acknowledging a frame acknowledges the previous ones
+    acknowledge(&mut handler, 1, QueueDepth::AvailableBytes(0));
+
+    let frame = state.lock().unwrap().next_frame().unwrap();
+    assert_eq!(frame.frame_id, 3);
+    assert_eq!(frame.bitmaps.len(), 1);
+}
+
+#[test]
+fn suspended_client_frames_are_not_held_back() {
+    let (state, mut handler) = open();
+
+    state.lock().unwrap().queue(bitmap());
+    state.lock().unwrap().next_frame().unwrap();
+    acknowledge(&mut handler, 0, QueueDepth::Suspend);
+
+    for _ in 0..MAX_FRAMES_IN_FLIGHT * 2 {
+        state.lock().unwrap().queue(bitmap());
+        assert!(state.lock().unwrap().next_frame().is_some());
+    }
+}
+
+#[test]
+fn closing_the_channel_resets_the_pipeline() {
+    let (state, mut handler) = open();
+
+    state.lock().unwrap().queue(bitmap());
+    handler.close(CHANNEL_ID);
+
+    assert!(!state.lock().unwrap().is_open());
+    assert!(state.lock().unwrap().next_frame().is_none());
+
+    // The surface is created again once the channel is reopened
+    handler.start(CHANNEL_ID).unwrap();
+    assert!(state.lock().unwrap().next_frame().is_none());
+    assert!(state.lock().unwrap().resize(DESKTOP_SIZE).unwrap().is_none());
+}
+
+#[test]
+fn resize_recreates_the_surface() {
+    let (state, _handler) = open();
+
+    let size = DesktopSize { width: 32, height: 16 };
+    let (channel_id, messages) = state.lock().unwrap().resize(size).unwrap().unwrap();
+    assert_eq!(channel_id, CHANNEL_ID);
+
+    let pdus = decode(messages);
+    assert_eq!(
+        pdus[0],
+        ServerPdu::DeleteSurface(DeleteSurfacePdu { surface_id: SURFACE_ID })
+    );
+    assert!(matches!(&pdus[1], ServerPdu::ResetGraphics(reset) if reset.width == 32 && reset.height == 16));
+    assert!(matches!(&pdus[2], ServerPdu::CreateSurface(create) if create.width == 32 && create.height == 16));
+    assert!(matches!(&pdus[3], ServerPdu::MapSurfaceToOutput(_)));
+}
@@ -0,0 +1,243 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use ironrdp_pdu::rdp::session_info::{ClientAutoReconnect, ServerAutoReconnect, ENHANCED_SECURITY_CLIENT_RANDOM};
+use ironrdp_server::{
+    Damage, DesktopSize, DisplayUpdate, ErrorInfo, KeyboardEvent, MouseEvent, ParkedSession, ProtocolIndependentCode,
+    RdpServerDisplay, RdpServerDisplayUpdates, RdpServerHandle, RdpServerInputHandler, SessionControl, SessionId,
+    SessionInfo,
+};
+
+const REASON: ErrorInfo = ErrorInfo::ProtocolIndependentCode(ProtocolIndependentCode::RpcInitiatedDisconnect);
+
+fn info(id: SessionId) -> SessionInfo {
+    SessionInfo {
+        id,
+        peer: ([192, 168, 1, 10], 50000).into(),
+        client_name: Some("WORKSTATION".to_owned()),
+        username: Some("user".to_owned()),
+        connected_at: SystemTime::now(),
+        awaiting_reconnect: false,
+    }
+}
+
+fn ids(handle: &RdpServerHandle) -> Vec<SessionId> {
+    handle.sessions().iter().map(|info| info.id).collect()
+}
+
+#[test]
+fn sessions_are_listed_while_registered() {
+    let handle = RdpServerHandle::new();
+    assert!(handle.sessions().is_empty());
+
+    let (first, _first_control) = handle.register(info);
+    let (second, _second_control) = handle.register(info);
+    assert_ne!(first.id(), second.id());
+    assert_eq!(ids(&handle), [first.id(), second.id()]);
+
+    second.update(|info| info.client_name = None);
+    let sessions = handle.sessions();
+    assert_eq!(sessions[0].client_name.as_deref(), Some("WORKSTATION"));
+    assert_eq!(sessions[1].client_name, None);
+
+    let second_id = second.id();
+    drop(first);
+    assert_eq!(ids(&handle), [second_id]);
+
+    drop(second);
+    assert!(handle.sessions().is_empty());
+}
+
+#[test]
+fn disconnect_is_sent_to_the_session() {
+    let handle = RdpServerHandle::new();
+    let (session, mut control) = handle.register(info);
+
+    assert!(handle.disconnect(session.id(), REASON));
+    assert!(matches!(control.try_recv(), Ok(SessionControl::Disconnect(reason)) if reason == REASON));
+
+    assert!(!handle.disconnect(session.id() + 1, REASON));
+    assert!(control.try_recv().is_err());
+
+    // The session is ending
+    drop(control);
+    assert!(!handle.disconnect(session.id(), REASON));
+}
+
+#[test]
+fn disconnect_all_is_sent_to_every_session() {
+    let handle = RdpServerHandle::new();
+    let (_first, mut first_control) = handle.register(info);
+    let (_second, mut second_control) = handle.register(info);
+
+    handle.disconnect_all(REASON);
+
+    assert!(matches!(first_control.try_recv(), Ok(SessionControl::Disconnect(reason)) if reason == REASON));
+    assert!(matches!(second_control.try_recv(), Ok(SessionControl::Disconnect(reason)) if reason == REASON));
+}
+
+#[tokio::test]
+async fn stop_resolves_stopped() {
+    let handle = RdpServerHandle::new();
+    assert!(!handle.is_stopped());
+
+    let stopped = tokio::spawn({
+        let handle = handle.clone();
+        async move { handle.stopped().await }
+    });
+
+    handle.stop();
+    stopped.await.unwrap();
+    assert!(handle.is_stopped());
+
+    // Once stopped, waiting resolves right away
+    handle.stopped().await;
+}
+
+struct TestDisplay;
+
+#[async_trait::async_trait]
+impl RdpServerDisplay for TestDisplay {
+    async fn size(&mut self) -> DesktopSize {
+        DesktopSize {
+            width: 1024,
+            height: 768,
+        }
+    }
+
+    async fn updates(&mut self) -> anyhow::Result<Box<dyn RdpServerDisplayUpdates>> {
+        Ok(Box::new(TestDisplay))
+    }
+}
+
+#[async_trait::async_trait]
+impl RdpServerDisplayUpdates for TestDisplay {
+    async fn next_update(&mut self) -> Option<DisplayUpdate> {
+        std::future::pending().await
+    }
+}
+
+struct TestInputHandler;
+
+impl RdpServerInputHandler for TestInputHandler {
+    fn keyboard(&mut self, _: KeyboardEvent) {}
+    fn mouse(&mut self, _: MouseEvent) {}
+}
+
+/// Registers a session, and parks it as its connection dropped
+fn park(handle: &RdpServerHandle, grace_period: Duration) -> ServerAutoReconnect {
+    let (registration, control) = handle.register(info);
+    let auto_reconnect = ServerAutoReconnect {
+        logon_id: registration.id(),
+        random_bits: [0x5a; 16],
+    };
+
+    registration.update(|info| info.awaiting_reconnect = true);
+    handle.park(
+        ParkedSession {
+            registration,
+            control,
+            auto_reconnect: auto_reconnect.clone(),
+            display: Box::new(TestDisplay),
+            display_updates: Box::new(TestDisplay),
+            handler: Arc::new(Mutex::new(Box::new(TestInputHandler))),
+            damage: Damage::new(DesktopSize {
+                width: 1024,
+                height: 768,
+            }),
+        },
+        grace_period,
+    );
+
+    auto_reconnect
+}
+
+/// Cookie sent by a client in its Client Info PDU
+fn cookie(logon_id: u32, security_verifier: [u8; 16]) -> ClientAutoReconnect {
+    let cookie = ClientAutoReconnect {
+        logon_id,
+        security_verifier,
+    };
+
+    ClientAutoReconnect::from_cookie(&cookie.to_cookie()).unwrap()
+}
+
+#[tokio::test]
+async fn parked_session_resumes_with_valid_cookie() {
+    let handle = RdpServerHandle::new();
+    let auto_reconnect = park(&handle, Duration::from_secs(60));
+    let id = auto_reconnect.logon_id;
+
+    let sessions = handle.sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, id);
+    assert!(sessions[0].awaiting_reconnect);
+
+    let valid = cookie(id, auto_reconnect.security_verifier(&ENHANCED_SECURITY_CLIENT_RANDOM));
+    let session = handle.reconnect(&valid, &ENHANCED_SECURITY_CLIENT_RANDOM).unwrap();
+    assert_eq!(session.registration.id(), id);
+
+    // The cookie can't be used twice
+    assert!(handle.reconnect(&valid, &ENHANCED_SECURITY_CLIENT_RANDOM).is_none());
+
+    session.registration.update(|info| info.awaiting_reconnect = false);
+    let sessions = handle.sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, id);
+    assert!(!sessions[0].awaiting_reconnect);
+
+    drop(session);
+    assert!(handle.sessions().is_empty());
+}
+
+#[tokio::test]
+async fn parked_session_is_not_resumed_with_forged_cookie() {
+    let handle = RdpServerHandle::new();
+    let auto_reconnect = park(&handle, Duration::from_secs(60));
+    let id = auto_reconnect.logon_id;
+
+    let other_random = ServerAutoReconnect {
+        logon_id: id,
+        random_bits: [0xa5; 16],
+    };
+    let forged = [
+        cookie(id, [0; 16]),
+        cookie(id, other_random.security_verifier(&ENHANCED_SECURITY_CLIENT_RANDOM)),
+        cookie(
+            id + 1,
+            auto_reconnect.security_verifier(&ENHANCED_SECURITY_CLIENT_RANDOM),
+        ),
+        cookie(id, auto_reconnect.security_verifier(&[0xff; 32])),
+    ];
+
+    for cookie in &forged {
+        assert!(handle.reconnect(cookie, &ENHANCED_SECURITY_CLIENT_RANDOM).is_none());
+    }
+
+    // The session is still waiting for its client
+    let sessions = handle.sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, id);
+    assert!(sessions[0].awaiting_reconnect);
+
+    assert!(handle.disconnect(id, REASON));
+    assert!(handle.sessions().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn parked_session_expires_after_grace_period() {
+    let handle = RdpServerHandle::new();
+    let auto_reconnect = park(&handle, Duration::from_secs(60));
+
+    tokio::time::sleep(Duration::from_secs(59)).await;
+    assert_eq!(handle.sessions().len(), 1);
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(handle.sessions().is_empty());
+
+    let valid = cookie(
+        auto_reconnect.logon_id,
+        auto_reconnect.security_verifier(&ENHANCED_SECURITY_CLIENT_RANDOM),
+    );
+    assert!(handle.reconnect(&valid, &ENHANCED_SECURITY_CLIENT_RANDOM).is_none());
+}
@@ -0,0 +1,6 @@
+mod display_control;
+mod frame;
+mod gfx;
+mod handle;
+mod pointer;
+mod timeout;
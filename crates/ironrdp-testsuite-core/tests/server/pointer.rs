@@ -0,0 +1,98 @@
+use ironrdp_server::{pointer_masks, PointerBitmap, PointerCache};
+
+#[test]
+fn cache_evicts_least_recently_used_pointer() {
+    let mut cache = PointerCache::new(2);
+
+    assert_eq!(cache.insert(10), 0);
+    assert_eq!(cache.insert(20), 1);
+
+    // 20 becomes the least recently used pointer
+    assert_eq!(cache.get(10), Some(0));
+
+    assert_eq!(cache.insert(30), 1);
+    assert_eq!(cache.get(20), None);
+    assert_eq!(cache.get(10), Some(0));
+    assert_eq!(cache.get(30), Some(1));
+
+    // 10 is now the least recently used pointer
+    assert_eq!(cache.insert(40), 0);
+    assert_eq!(cache.get(10), None);
+    assert_eq!(cache.get(40), Some(0));
+}
+
+#[test]
+fn cache_reuses_index_of_updated_pointer() {
+    let mut cache = PointerCache::new(2);
+
+    assert_eq!(cache.insert(10), 0);
+    assert_eq!(cache.insert(20), 1);
+    assert_eq!(cache.insert(10), 0);
+
+    // Updating 10 made 20 the least recently used pointer
+    assert_eq!(cache.insert(30), 1);
+    assert_eq!(cache.get(10), Some(0));
+}
+
+#[test]
+fn empty_cache_holds_one_pointer() {
+    let mut cache = PointerCache::new(0);
+
+    assert_eq!(cache.insert(10), 0);
+    assert_eq!(cache.insert(20), 0);
+    assert_eq!(cache.get(10), None);
+    assert_eq!(cache.get(20), Some(0));
+}
+
+fn pointer(width: u16, height: u16, data: Vec<u8>) -> PointerBitmap {
+    PointerBitmap {
+        id: 0,
+        width,
+        height,
+        hot_x: 0,
+        hot_y: 0,
+        data,
+    }
+}
+
+#[test]
+fn masks_with_alpha_channel() {
+    #[rustfmt::skip]
+    let pointer = pointer(2, 2, vec![
+        0x10, 0x20, 0x30, 0xff, 0x40, 0x50, 0x60, 0x00,
+        0x70, 0x80, 0x90, 0x7f, 0xa0, 0xb0, 0xc0, 0x80,
+    ]);
+
+    let masks = pointer_masks(&pointer, 32);
+
+    // BGRA pixels, from the bottom row to the top row
+    #[rustfmt::skip]
+    assert_eq!(masks.xor_mask, [
+        0x90, 0x80, 0x70, 0x7f, 0xc0, 0xb0, 0xa0, 0x80,
+        0x30, 0x20, 0x10, 0xff, 0x60, 0x50, 0x40, 0x00,
+    ]);
+    assert_eq!(masks.and_mask, [0; 4]);
+}
+
+#[test]
+fn masks_without_alpha_channel() {
+    #[rustfmt::skip]
+    let pointer = pointer(3, 2, vec![
+        0x10, 0x20, 0x30, 0xff, 0x40, 0x50, 0x60, 0x00, 0x70, 0x80, 0x90, 0x80,
+        0xa0, 0xb0, 0xc0, 0x7f, 0xd0, 0xe0, 0xf0, 0xff, 0x01, 0x02, 0x03, 0x00,
+    ]);
+
+    let masks = pointer_masks(&pointer, 24);
+
+    // BGR pixels padded to 16 bits, transparent pixels are black, from the bottom row to the top row
+    #[rustfmt::skip]
+    assert_eq!(masks.xor_mask, [
+        0x00, 0x00, 0x00, 0xf0, 0xe0, 0xd0, 0x00, 0x00, 0x00, 0x00,
+        0x30, 0x20, 0x10, 0x00, 0x00, 0x00, 0x90, 0x80, 0x70, 0x00,
+    ]);
+    #[rustfmt::skip]
+    assert_eq!(masks.and_mask, [
+        0b1010_0000, 0x00,
+        0b0100_0000, 0x00,
+    ]);
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use ironrdp_server::{ErrorInfo, ProtocolIndependentCode, RdpServerOptions, RdpServerSecurity, SessionTimeout};
+
+const SECOND: Duration = Duration::from_secs(1);
+
+fn options(idle_timeout: Option<Duration>, max_session_duration: Option<Duration>) -> RdpServerOptions {
+    RdpServerOptions {
+        addr: ([127, 0, 0, 1], 3389).into(),
+        security: RdpServerSecurity::None,
+        credential_validator: None,
+        redirector: None,
+        idle_timeout,
+        max_session_duration,
+        auto_reconnect: None,
+        max_monitors: 16,
+    }
+}
+
+/// Waits for a session timeout for up to `delay`
+async fn elapsed_within(timeout: &mut SessionTimeout, delay: Duration) -> Option<ErrorInfo> {
+    tokio::select! {
+        reason = timeout.elapsed() => Some(reason),
+        () = tokio::time::sleep(delay) => None,
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn idle_timeout_is_pushed_back_by_input() {
+    let started = Instant::now();
+    let mut timeout = SessionTimeout::new(&options(Some(60 * SECOND), None), started);
+
+    assert_eq!(elapsed_within(&mut timeout, 59 * SECOND).await, None);
+
+    timeout.input(started + 30 * SECOND);
+    assert_eq!(elapsed_within(&mut timeout, 20 * SECOND).await, None);
+
+    // The same input doesn't push the deadline back again
+    timeout.input(started + 30 * SECOND);
+    assert_eq!(
+        elapsed_within(&mut timeout, 20 * SECOND).await,
+        Some(ErrorInfo::ProtocolIndependentCode(ProtocolIndependentCode::IdleTimeout))
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn max_session_duration_ignores_input() {
+    let started = Instant::now();
+    let mut timeout = SessionTimeout::new(&options(Some(60 * SECOND), Some(100 * SECOND)), started);
+
+    assert_eq!(elapsed_within(&mut timeout, 50 * SECOND).await, None);
+
+    timeout.input(started + 50 * SECOND);
+    assert_eq!(
+        elapsed_within(&mut timeout, 60 * SECOND).await,
+        Some(ErrorInfo::ProtocolIndependentCode(
+            ProtocolIndependentCode::LogonTimeout
+        ))
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn sessions_without_timeouts_are_kept() {
+    let mut timeout = SessionTimeout::new(&options(None, None), Instant::now());
+
+    assert_eq!(elapsed_within(&mut timeout, 365 * 24 * 3600 * SECOND).await, None);
+}
@@ -0,0 +1,111 @@
+use ironrdp_connector::{ConnectionResult, DesktopSize};
+use ironrdp_graphics::image_processing::PixelFormat;
+use ironrdp_pdu::mcs::SendDataIndication;
+use ironrdp_pdu::rdp::client_info::CompressionType;
+use ironrdp_pdu::rdp::headers::{
+    CompressionFlags, ShareControlHeader, ShareControlPdu, ShareDataHeader, ShareDataPdu, StreamPriority,
+};
+use ironrdp_pdu::rdp::session_info::{
+    InfoData, InfoType, LogonExFlags, LogonInfoExtended, SaveSessionInfoPdu, ServerAutoReconnect,
+};
+use ironrdp_pdu::{Action, PduParsing as _};
+use ironrdp_session::image::DecodedImage;
+use ironrdp_session::ActiveStage;
+use ironrdp_svc::StaticChannelSet;
+
+const IO_CHANNEL_ID: u16 = 1003;
+const USER_CHANNEL_ID: u16 = 1007;
+
+fn active_stage() -> ActiveStage {
+    let connection_result = ConnectionResult {
+        io_channel_id: IO_CHANNEL_ID,
+        user_channel_id: USER_CHANNEL_ID,
+        static_channels: StaticChannelSet::new(),
+        desktop_size: DesktopSize { width: 64, height: 64 },
+        graphics_config: None,
+        no_server_pointer: true,
+        pointer_software_rendering: false,
+        standard_security: None,
+        monitors: Vec::new(),
+    };
+
+    ActiveStage::new(connection_result, None)
+}
+
+fn save_session_info_frame(info: SaveSessionInfoPdu) -> Vec<u8> {
+    let pdu = ShareControlHeader {
+        share_id: 0,
+        pdu_source: IO_CHANNEL_ID,
+        share_control_pdu: ShareControlPdu::Data(ShareDataHeader {
+            share_data_pdu: ShareDataPdu::SaveSessionInfo(info),
+            stream_priority: StreamPriority::Undefined,
+            compression_flags: CompressionFlags::empty(),
+            compression_type: CompressionType::K8,
+        }),
+    };
+
+    let mut user_data = Vec::new();
+    pdu.to_buffer(&mut user_data).unwrap();
+
+    let indication = SendDataIndication {
+        initiator_id: USER_CHANNEL_ID,
+        channel_id: IO_CHANNEL_ID,
+        user_data: user_data.into(),
+    };
+
+    ironrdp_pdu::encode_vec(&indication).unwrap()
+}
+
+#[test]
+fn stores_auto_reconnect_cookie_from_logon_extended() {
+    let mut stage = active_stage();
+    let mut image = DecodedImage::new(PixelFormat::RgbA32, 64, 64);
+
+    assert!(stage.auto_reconnect_cookie().is_none());
+
+    let cookie = ServerAutoReconnect {
+        logon_id: 42,
+        random_bits: [0xA5; 16],
+    };
+    let frame = save_session_info_frame(SaveSessionInfoPdu {
+        info_type: InfoType::LogonExtended,
+        info_data: InfoData::LogonExtended(LogonInfoExtended {
+            present_fields_flags: LogonExFlags::AUTO_RECONNECT_COOKIE,
+            auto_reconnect: Some(cookie.clone()),
+            errors_info: None,
+        }),
+    });
+
+    let outputs = stage.process(&mut image, Action::X224, &frame).unwrap();
+
+    assert!(outputs.is_empty());
+    assert_eq!(stage.auto_reconnect_cookie(), Some(&cookie));
+}
+
+#[test]
+fn keeps_auto_reconnect_cookie_on_plain_notify() {
+    let mut stage = active_stage();
+    let mut image = DecodedImage::new(PixelFormat::RgbA32, 64, 64);
+
+    let cookie = ServerAutoReconnect {
+        logon_id: 7,
+        random_bits: [0x11; 16],
+    };
+    let extended = save_session_info_frame(SaveSessionInfoPdu {
+        info_type: InfoType::LogonExtended,
+        info_data: InfoData::LogonExtended(LogonInfoExtended {
+            present_fields_flags: LogonExFlags::AUTO_RECONNECT_COOKIE,
+            auto_reconnect: Some(cookie.clone()),
+            errors_info: None,
+        }),
+    });
+    let plain = save_session_info_frame(SaveSessionInfoPdu {
+        info_type: InfoType::PlainNotify,
+        info_data: InfoData::PlainNotify,
+    });
+
+    stage.process(&mut image, Action::X224, &extended).unwrap();
+    stage.process(&mut image, Action::X224, &plain).unwrap();
+
+    assert_eq!(stage.auto_reconnect_cookie(), Some(&cookie));
+}
@@ -0,0 +1,79 @@
+use ironrdp_graphics::image_processing::PixelFormat;
+use ironrdp_pdu::gcc::{Monitor, MonitorFlags};
+use ironrdp_pdu::geometry::InclusiveRectangle;
+use ironrdp_session::image::DecodedImage;
+
+#[test]
+fn monitor_regions_are_relative_to_the_virtual_desktop() {
+    let image = DecodedImage::new(PixelFormat::RgbA32, 3000, 1920);
+
+    let monitors = [
+        Monitor {
+            left: 0,
+            top: 0,
+            right: 1919,
+            bottom: 1079,
+            flags: MonitorFlags::PRIMARY,
+        },
+        Monitor {
+            left: -1080,
+            top: -200,
+            right: -1,
+            bottom: 1719,
+            flags: MonitorFlags::empty(),
+        },
+    ];
+
+    assert_eq!(
+        image.monitor_regions(&monitors),
+        [
+            Some(InclusiveRectangle {
+                left: 1080,
+                top: 200,
+                right: 2999,
+                bottom: 1279,
+            }),
+            Some(InclusiveRectangle {
+                left: 0,
+                top: 0,
+                right: 1079,
+                bottom: 1919,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn monitor_regions_are_clipped_to_the_image() {
+    let image = DecodedImage::new(PixelFormat::RgbA32, 1024, 768);
+
+    let monitors = [
+        Monitor {
+            left: 0,
+            top: 0,
+            right: 1919,
+            bottom: 1079,
+            flags: MonitorFlags::PRIMARY,
+        },
+        Monitor {
+            left: 1920,
+            top: 0,
+            right: 3839,
+            bottom: 1079,
+            flags: MonitorFlags::empty(),
+        },
+    ];
+
+    assert_eq!(
+        image.monitor_regions(&monitors),
+        [
+            Some(InclusiveRectangle {
+                left: 0,
+                top: 0,
+                right: 1023,
+                bottom: 767,
+            }),
+            None,
+        ]
+    );
+}
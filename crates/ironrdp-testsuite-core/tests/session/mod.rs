@@ -1 +1,3 @@
+mod auto_reconnect;
+mod image;
 mod rfx;
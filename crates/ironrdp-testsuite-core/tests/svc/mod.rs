@@ -0,0 +1,54 @@
+use ironrdp_pdu::gcc::ChannelName;
+use ironrdp_pdu::PduResult;
+use ironrdp_svc::{impl_as_any, StaticChannelSet, SvcMessage, SvcProcessor, SvcServerProcessor};
+
+#[derive(Debug)]
+struct Telemetry;
+
+impl_as_any!(Telemetry);
+
+impl SvcProcessor for Telemetry {
+    fn channel_name(&self) -> ChannelName {
+        ChannelName::from_static(b"telemtr\0")
+    }
+
+    fn process(&mut self, _payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
+        Ok(Vec::new())
+    }
+}
+
+impl SvcServerProcessor for Telemetry {}
+
+#[derive(Debug)]
+struct Control;
+
+impl_as_any!(Control);
+
+impl SvcProcessor for Control {
+    fn channel_name(&self) -> ChannelName {
+        ChannelName::from_static(b"control\0")
+    }
+
+    fn process(&mut self, _payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
+        Ok(Vec::new())
+    }
+}
+
+impl SvcServerProcessor for Control {}
+
+#[test]
+fn boxed_processors_are_inserted_under_their_boxed_type() {
+    let telemetry: Box<dyn SvcServerProcessor> = Box::new(Telemetry);
+    let control: Box<dyn SvcServerProcessor> = Box::new(Control);
+
+    let mut channels = StaticChannelSet::new();
+    assert!(channels.insert(telemetry).is_none());
+    assert!(channels.insert(control).is_none());
+
+    let telemetry = channels.get_by_type::<Telemetry>().unwrap();
+    assert_eq!(telemetry.channel_name(), ChannelName::from_static(b"telemtr\0"));
+    assert!(telemetry.channel_processor_downcast_ref::<Telemetry>().is_some());
+
+    let control = channels.get_by_type_mut::<Control>().unwrap();
+    assert!(control.channel_processor_downcast_mut::<Control>().is_some());
+}
@@ -0,0 +1,225 @@
+use std::io;
+use std::sync::Arc;
+
+use ironrdp_tls::{fingerprint, KnownHosts, VerificationError, VerificationPolicy};
+use tokio_rustls::rustls;
+
+/// Self-signed certificate for `localhost`
+const CERTIFICATE: &[u8] = include_bytes!("../../test_data/tls/localhost.der");
+const PRIVATE_KEY: &[u8] = include_bytes!("../../test_data/tls/localhost.key.der");
+
+const OTHER_CERTIFICATE: &[u8] = b"not the certificate of the host";
+
+fn known_hosts_path() -> (tempfile::TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("known_hosts");
+    (dir, path)
+}
+
+#[test]
+fn known_hosts_round_trip() {
+    let (_dir, path) = known_hosts_path();
+
+    let known_hosts = KnownHosts::load(&path, false).unwrap();
+    assert_eq!(known_hosts.fingerprint("server.contoso.com"), None);
+
+    known_hosts.insert("server.contoso.com", fingerprint(CERTIFICATE));
+    known_hosts.insert("other.contoso.com", fingerprint(OTHER_CERTIFICATE));
+    known_hosts.save().unwrap();
+
+    let known_hosts = KnownHosts::load(&path, false).unwrap();
+    assert_eq!(
+        known_hosts.fingerprint("server.contoso.com"),
+        Some(fingerprint(CERTIFICATE))
+    );
+    assert_eq!(
+        known_hosts.fingerprint("other.contoso.com"),
+        Some(fingerprint(OTHER_CERTIFICATE))
+    );
+    known_hosts.verify("server.contoso.com", CERTIFICATE).unwrap();
+}
+
+#[test]
+fn known_hosts_fingerprint_mismatch() {
+    let known_hosts = KnownHosts::new(true);
+    known_hosts.insert("server.contoso.com", fingerprint(CERTIFICATE));
+
+    assert_eq!(
+        known_hosts.verify("server.contoso.com", OTHER_CERTIFICATE),
+        Err(VerificationError::FingerprintMismatch {
+            server_name: "server.contoso.com".to_owned(),
+            expected: fingerprint(CERTIFICATE),
+            actual: fingerprint(OTHER_CERTIFICATE),
+        })
+    );
+
+    // The recorded fingerprint is kept, even with trust on first use
+    assert_eq!(
+        known_hosts.fingerprint("server.contoso.com"),
+        Some(fingerprint(CERTIFICATE))
+    );
+}
+
+#[test]
+fn unknown_host_without_trust_on_first_use() {
+    let (_dir, path) = known_hosts_path();
+
+    let known_hosts = KnownHosts::load(&path, false).unwrap();
+
+    assert_eq!(
+        known_hosts.verify("server.contoso.com", CERTIFICATE),
+        Err(VerificationError::UnknownHost {
+            server_name: "server.contoso.com".to_owned(),
+            fingerprint: fingerprint(CERTIFICATE),
+        })
+    );
+    assert_eq!(known_hosts.fingerprint("server.contoso.com"), None);
+    assert!(!path.exists());
+}
+
+#[test]
+fn unknown_host_with_trust_on_first_use() {
+    let (_dir, path) = known_hosts_path();
+
+    let known_hosts = KnownHosts::load(&path, true).unwrap();
+    known_hosts.verify("server.contoso.com", CERTIFICATE).unwrap();
+    known_hosts.verify("server.contoso.com", CERTIFICATE).unwrap();
+
+    // The new host is appended to the store file right away
+    let known_hosts = KnownHosts::load(&path, false).unwrap();
+    assert_eq!(
+        known_hosts.fingerprint("server.contoso.com"),
+        Some(fingerprint(CERTIFICATE))
+    );
+    assert!(known_hosts.verify("server.contoso.com", OTHER_CERTIFICATE).is_err());
+}
+
+#[test]
+fn known_hosts_comments_and_blank_lines_are_ignored() {
+    let (_dir, path) = known_hosts_path();
+
+    let known_hosts = KnownHosts::load(&path, false).unwrap();
+    known_hosts.insert("server.contoso.com", fingerprint(CERTIFICATE));
+    known_hosts.save().unwrap();
+
+    let entry = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, format!("# recorded hosts\n\n  {}  \n", entry.trim())).unwrap();
+
+    let known_hosts = KnownHosts::load(&path, false).unwrap();
+    assert_eq!(
+        known_hosts.fingerprint("server.contoso.com"),
+        Some(fingerprint(CERTIFICATE))
+    );
+}
+
+#[test]
+fn malformed_known_hosts_lines_are_rejected() {
+    let (_dir, path) = known_hosts_path();
+
+    let valid_hex = "00".repeat(32);
+
+    for content in [
+        "server.contoso.com".to_owned(),
+        "server.contoso.com 0011".to_owned(),
+        format!("server.contoso.com {}", "zz".repeat(32)),
+        format!("server.contoso.com {valid_hex}00"),
+        format!("server.contoso.com {}", "é".repeat(32)),
+    ] {
+        std::fs::write(&path, format!("other.contoso.com {valid_hex}\n{content}\n")).unwrap();
+
+        let error = KnownHosts::load(&path, false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{content}");
+        assert!(error.to_string().contains("line 2"), "{content}: {error}");
+    }
+}
+
+/// Performs a TLS handshake with a server presenting the test certificate
+async fn handshake(policy: VerificationPolicy) -> io::Result<Vec<u8>> {
+    let (client, server) = tokio::io::duplex(16 * 1024);
+
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(CERTIFICATE.to_vec())],
+            rustls::PrivateKey(PRIVATE_KEY.to_vec()),
+        )
+        .unwrap();
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+    let server = tokio::spawn(async move {
+        // The client aborts the handshake when rejecting the certificate
+        let _ = acceptor.accept(server).await;
+    });
+
+    let result = ironrdp_tls::upgrade_with_policy(client, "localhost", &policy)
+        .await
+        .map(|(_, server_public_key)| server_public_key);
+
+    server.await.unwrap();
+
+    result
+}
+
+fn verification_error(error: &io::Error) -> Option<&VerificationError> {
+    error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<VerificationError>())
+}
+
+#[tokio::test]
+async fn pinned_certificate_is_accepted() {
+    let known_hosts = KnownHosts::new(false);
+    known_hosts.insert("localhost", fingerprint(CERTIFICATE));
+
+    let server_public_key = handshake(VerificationPolicy::Pinned(Arc::new(known_hosts)))
+        .await
+        .unwrap();
+    assert!(!server_public_key.is_empty());
+}
+
+#[tokio::test]
+async fn verification_error_is_surfaced() {
+    let known_hosts = Arc::new(KnownHosts::new(false));
+
+    let error = handshake(VerificationPolicy::Pinned(known_hosts)).await.unwrap_err();
+
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        verification_error(&error),
+        Some(&VerificationError::UnknownHost {
+            server_name: "localhost".to_owned(),
+            fingerprint: fingerprint(CERTIFICATE),
+        })
+    );
+}
+
+#[tokio::test]
+async fn custom_verifier_rejection_is_surfaced() {
+    let verifier = |chain: &[&[u8]], server_name: &str| {
+        assert_eq!(chain, [CERTIFICATE]);
+        assert_eq!(server_name, "localhost");
+        Err("not trusted by policy".to_owned())
+    };
+
+    let error = handshake(VerificationPolicy::Custom(Arc::new(verifier)))
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        verification_error(&error),
+        Some(&VerificationError::Rejected("not trusted by policy".to_owned()))
+    );
+}
+
+#[tokio::test]
+async fn self_signed_certificate_is_untrusted_without_roots() {
+    let error = handshake(VerificationPolicy::CustomRoots(Vec::new()))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        verification_error(&error),
+        Some(VerificationError::Untrusted(_))
+    ));
+}
@@ -12,6 +12,8 @@ mod impl_;
 #[path = "stub.rs"]
 mod impl_;
 
+mod verification;
+
 #[cfg(any(
     not(any(feature = "stub", feature = "native-tls", feature = "rustls")),
     all(feature = "stub", feature = "native-tls"),
@@ -22,7 +24,10 @@ compile_error!("a TLS backend must be selected by enabling a single feature out
 
 // The whole public API of this crate.
 #[cfg(any(feature = "stub", feature = "native-tls", feature = "rustls"))]
-pub use impl_::{upgrade, TlsStream};
+pub use impl_::{upgrade, upgrade_with_policy, TlsStream};
+pub use verification::{
+    fingerprint, load_ca_bundle, CertificateVerifier, Fingerprint, KnownHosts, VerificationError, VerificationPolicy,
+};
 
 #[cfg(any(feature = "native-tls", feature = "rustls"))]
 pub(crate) fn extract_tls_server_public_key(cert: &[u8]) -> std::io::Result<Vec<u8>> {
@@ -1,21 +1,55 @@
 use std::io;
 
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt as _};
+use tokio_native_tls::native_tls;
+
+use crate::VerificationPolicy;
 
 pub type TlsStream<S> = tokio_native_tls::TlsStream<S>;
 
 pub async fn upgrade<S>(stream: S, server_name: &str) -> io::Result<(TlsStream<S>, Vec<u8>)>
+where
+    S: Unpin + AsyncRead + AsyncWrite,
+{
+    upgrade_with_policy(stream, server_name, &VerificationPolicy::Disabled).await
+}
+
+pub async fn upgrade_with_policy<S>(
+    stream: S,
+    server_name: &str,
+    policy: &VerificationPolicy,
+) -> io::Result<(TlsStream<S>, Vec<u8>)>
 where
     S: Unpin + AsyncRead + AsyncWrite,
 {
     let mut tls_stream = {
-        let connector = tokio_native_tls::native_tls::TlsConnector::builder()
-            .danger_accept_invalid_certs(true)
-            .use_sni(false)
+        let mut builder = native_tls::TlsConnector::builder();
+        builder.use_sni(false);
+
+        match policy {
+            // The system store is used by default
+            VerificationPolicy::SystemRoots => {}
+            VerificationPolicy::CustomRoots(certificates) => {
+                builder.disable_built_in_roots(true);
+
+                for certificate in certificates {
+                    let certificate = native_tls::Certificate::from_der(certificate)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                    builder.add_root_certificate(certificate);
+                }
+            }
+            // The certificate is checked once the handshake is done
+            VerificationPolicy::Disabled | VerificationPolicy::Pinned(_) | VerificationPolicy::Custom(_) => {
+                builder.danger_accept_invalid_certs(true);
+            }
+        }
+
+        let connector = builder
             .build()
             .map(tokio_native_tls::TlsConnector::from)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
+        // Chain verification failures are reported by native-tls along with the other handshake errors
         connector
             .connect(server_name, stream)
             .await
@@ -24,15 +58,23 @@ where
 
     tls_stream.flush().await?;
 
-    let server_public_key = {
-        let cert = tls_stream
-            .get_ref()
-            .peer_certificate()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "peer certificate is missing"))?;
-        let cert = cert.to_der().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        crate::extract_tls_server_public_key(&cert)?
-    };
+    let cert = tls_stream
+        .get_ref()
+        .peer_certificate()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+        .ok_or(crate::VerificationError::MissingCertificate)?;
+    let cert = cert.to_der().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    // Only the end-entity certificate is exposed by native-tls
+    match policy {
+        VerificationPolicy::Pinned(known_hosts) => known_hosts.verify(server_name, &cert)?,
+        VerificationPolicy::Custom(verifier) => verifier
+            .verify(&[&cert], server_name)
+            .map_err(crate::VerificationError::Rejected)?,
+        _ => {}
+    }
+
+    let server_public_key = crate::extract_tls_server_public_key(&cert)?;
 
     Ok((tls_stream, server_public_key))
 }
@@ -2,16 +2,31 @@ use std::io;
 
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt as _};
 
+use crate::VerificationPolicy;
+
 pub type TlsStream<S> = tokio_rustls::client::TlsStream<S>;
 
 pub async fn upgrade<S>(stream: S, server_name: &str) -> io::Result<(TlsStream<S>, Vec<u8>)>
+where
+    S: Unpin + AsyncRead + AsyncWrite,
+{
+    upgrade_with_policy(stream, server_name, &VerificationPolicy::Disabled).await
+}
+
+pub async fn upgrade_with_policy<S>(
+    stream: S,
+    server_name: &str,
+    policy: &VerificationPolicy,
+) -> io::Result<(TlsStream<S>, Vec<u8>)>
 where
     S: Unpin + AsyncRead + AsyncWrite,
 {
     let mut tls_stream = {
+        let verifier = verifier::PolicyVerifier::new(policy.clone(), server_name)?;
+
         let mut config = tokio_rustls::rustls::client::ClientConfig::builder()
             .with_safe_defaults()
-            .with_custom_certificate_verifier(std::sync::Arc::new(danger::NoCertificateVerification))
+            .with_custom_certificate_verifier(std::sync::Arc::new(verifier))
             .with_no_client_auth();
 
         // This adds support for the SSLKEYLOGFILE env variable (https://wiki.wireshark.org/TLS#using-the-pre-master-secret)
@@ -26,11 +41,14 @@ where
 
         let config = std::sync::Arc::new(config);
 
-        let server_name = server_name.try_into().unwrap();
+        let server_name = server_name
+            .try_into()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
         tokio_rustls::TlsConnector::from(config)
             .connect(server_name, stream)
-            .await?
+            .await
+            .map_err(verifier::unwrap_verification_error)?
     };
 
     tls_stream.flush().await?;
@@ -48,25 +66,112 @@ where
     Ok((tls_stream, server_public_key))
 }
 
-mod danger {
+mod verifier {
+    use std::io;
+    use std::sync::Arc;
     use std::time::SystemTime;
 
-    use tokio_rustls::rustls::client::ServerCertVerified;
-    use tokio_rustls::rustls::{Certificate, Error, ServerName};
+    use tokio_rustls::rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+    use tokio_rustls::rustls::{Certificate, CertificateError, Error, RootCertStore, ServerName};
+
+    use crate::{VerificationError, VerificationPolicy};
+
+    pub(super) struct PolicyVerifier {
+        policy: VerificationPolicy,
+        server_name: String,
+        /// Set when the chain is verified against root certificates
+        webpki: Option<WebPkiVerifier>,
+    }
+
+    impl PolicyVerifier {
+        pub(super) fn new(policy: VerificationPolicy, server_name: &str) -> io::Result<Self> {
+            let roots = match &policy {
+                VerificationPolicy::SystemRoots => {
+                    let mut roots = RootCertStore::empty();
+
+                    let certificates = rustls_native_certs::load_native_certs()?;
+                    roots.add_parsable_certificates(&certificates.into_iter().map(|c| c.0).collect::<Vec<_>>());
+
+                    if roots.is_empty() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            "no root certificate found in the system store",
+                        ));
+                    }
 
-    pub(super) struct NoCertificateVerification;
+                    Some(roots)
+                }
+                VerificationPolicy::CustomRoots(certificates) => {
+                    let mut roots = RootCertStore::empty();
 
-    impl tokio_rustls::rustls::client::ServerCertVerifier for NoCertificateVerification {
+                    for certificate in certificates {
+                        roots
+                            .add(&Certificate(certificate.clone()))
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                    }
+
+                    Some(roots)
+                }
+                _ => None,
+            };
+
+            Ok(Self {
+                policy,
+                server_name: server_name.to_owned(),
+                webpki: roots.map(|roots| WebPkiVerifier::new(roots, None)),
+            })
+        }
+    }
+
+    impl ServerCertVerifier for PolicyVerifier {
         fn verify_server_cert(
             &self,
-            _end_entity: &Certificate,
-            _intermediates: &[Certificate],
-            _server_name: &ServerName,
-            _scts: &mut dyn Iterator<Item = &[u8]>,
-            _ocsp_response: &[u8],
-            _now: SystemTime,
+            end_entity: &Certificate,
+            intermediates: &[Certificate],
+            server_name: &ServerName,
+            scts: &mut dyn Iterator<Item = &[u8]>,
+            ocsp_response: &[u8],
+            now: SystemTime,
         ) -> Result<ServerCertVerified, Error> {
-            Ok(tokio_rustls::rustls::client::ServerCertVerified::assertion())
+            let result = match (&self.policy, &self.webpki) {
+                (_, Some(webpki)) => webpki
+                    .verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)
+                    .map(|_| ())
+                    .map_err(|e| VerificationError::Untrusted(e.to_string())),
+                (VerificationPolicy::Pinned(known_hosts), None) => known_hosts.verify(&self.server_name, &end_entity.0),
+                (VerificationPolicy::Custom(verifier), None) => {
+                    let chain: Vec<&[u8]> = core::iter::once(end_entity)
+                        .chain(intermediates)
+                        .map(|certificate| certificate.0.as_slice())
+                        .collect();
+
+                    verifier
+                        .verify(&chain, &self.server_name)
+                        .map_err(VerificationError::Rejected)
+                }
+                (_, None) => Ok(()),
+            };
+
+            result
+                .map(|()| ServerCertVerified::assertion())
+                .map_err(|e| Error::InvalidCertificate(CertificateError::Other(Arc::new(e))))
+        }
+    }
+
+    /// Reports the [`VerificationError`] itself instead of the wrapping rustls error
+    pub(super) fn unwrap_verification_error(error: io::Error) -> io::Error {
+        let verification_error = error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+            .and_then(|inner| match inner {
+                Error::InvalidCertificate(CertificateError::Other(other)) => other.downcast_ref::<VerificationError>(),
+                _ => None,
+            })
+            .cloned();
+
+        match verification_error {
+            Some(verification_error) => verification_error.into(),
+            None => error,
         }
     }
 }
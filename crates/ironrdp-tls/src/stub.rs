@@ -37,3 +37,15 @@ where
     let _ = (stream, server_name);
     Err(io::Error::other("no TLS backend enabled for this build"))
 }
+
+pub async fn upgrade_with_policy<S>(
+    stream: S,
+    server_name: &str,
+    policy: &crate::VerificationPolicy,
+) -> io::Result<(TlsStream<S>, Vec<u8>)>
+where
+    S: Unpin + AsyncRead + AsyncWrite,
+{
+    let _ = policy;
+    upgrade(stream, server_name).await
+}
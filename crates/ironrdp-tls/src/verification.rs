@@ -0,0 +1,308 @@
+use core::fmt;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use sha2::Digest as _;
+
+/// SHA-256 digest of a DER certificate
+pub type Fingerprint = [u8; 32];
+
+/// Policy used to verify the certificate presented by the server during the TLS upgrade
+#[derive(Clone)]
+pub enum VerificationPolicy {
+    /// Any certificate is accepted, leaving the connection open to man-in-the-middle attacks
+    Disabled,
+    /// The chain must be issued by a root certificate of the operating system store
+    SystemRoots,
+    /// The chain must be issued by one of these DER certificates, see [`load_ca_bundle`]
+    CustomRoots(Vec<Vec<u8>>),
+    /// The server certificate must match the fingerprint recorded for the host
+    Pinned(Arc<KnownHosts>),
+    /// The chain is checked by user code
+    Custom(Arc<dyn CertificateVerifier>),
+}
+
+impl fmt::Debug for VerificationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "Disabled"),
+            Self::SystemRoots => write!(f, "SystemRoots"),
+            Self::CustomRoots(roots) => write!(f, "CustomRoots({} certificates)", roots.len()),
+            Self::Pinned(known_hosts) => f.debug_tuple("Pinned").field(known_hosts).finish(),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// Custom verification of the server certificate chain
+pub trait CertificateVerifier: Send + Sync {
+    /// `chain` holds the DER certificates sent by the server, starting with the end-entity certificate.
+    ///
+    /// With the native-tls backend, only the end-entity certificate is available.
+    fn verify(&self, chain: &[&[u8]], server_name: &str) -> Result<(), String>;
+}
+
+impl<F> CertificateVerifier for F
+where
+    F: Fn(&[&[u8]], &str) -> Result<(), String> + Send + Sync,
+{
+    fn verify(&self, chain: &[&[u8]], server_name: &str) -> Result<(), String> {
+        self(chain, server_name)
+    }
+}
+
+/// Reason why the server certificate was rejected
+///
+/// Returned as the inner error of the [`io::Error`] reported by the TLS upgrade.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    /// The chain could not be verified against the trusted root certificates
+    Untrusted(String),
+    /// The certificate differs from the one recorded for this host, which may indicate an attack
+    FingerprintMismatch {
+        server_name: String,
+        expected: Fingerprint,
+        actual: Fingerprint,
+    },
+    /// No certificate is recorded for this host, and trust on first use is disabled
+    UnknownHost {
+        server_name: String,
+        fingerprint: Fingerprint,
+    },
+    /// The certificate was rejected by the custom verifier
+    Rejected(String),
+    /// The fingerprint of a new host could not be recorded
+    KnownHostsFile(String),
+    /// The server did not send any certificate
+    MissingCertificate,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Untrusted(reason) => write!(f, "untrusted server certificate: {reason}"),
+            Self::FingerprintMismatch {
+                server_name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "certificate of {server_name} changed (expected SHA-256 {}, got {}): the connection may be intercepted",
+                to_hex(expected),
+                to_hex(actual)
+            ),
+            Self::UnknownHost {
+                server_name,
+                fingerprint,
+            } => write!(
+                f,
+                "unknown host {server_name} (certificate SHA-256 {})",
+                to_hex(fingerprint)
+            ),
+            Self::Rejected(reason) => write!(f, "server certificate rejected: {reason}"),
+            Self::KnownHostsFile(reason) => write!(f, "failed to update known hosts: {reason}"),
+            Self::MissingCertificate => write!(f, "peer certificate is missing"),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+impl From<VerificationError> for io::Error {
+    fn from(error: VerificationError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Fingerprints of the certificates of known hosts, for SHA-256 pinning
+///
+/// The store file holds one `<server name> <hex SHA-256>` entry per line. Lines starting with `#` are ignored.
+#[derive(Debug)]
+pub struct KnownHosts {
+    path: Option<PathBuf>,
+    trust_on_first_use: bool,
+    hosts: Mutex<HashMap<String, Fingerprint>>,
+}
+
+impl KnownHosts {
+    /// Creates an empty store which is not persisted
+    pub fn new(trust_on_first_use: bool) -> Self {
+        Self {
+            path: None,
+            trust_on_first_use,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Loads the store file, which is created when the first host is recorded
+    ///
+    /// When `trust_on_first_use` is set, the certificate of an unknown host is accepted and recorded.
+    pub fn load(path: impl Into<PathBuf>, trust_on_first_use: bool) -> io::Result<Self> {
+        let path = path.into();
+
+        let hosts = match fs::read_to_string(&path) {
+            Ok(content) => parse_known_hosts(&content)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            trust_on_first_use,
+            hosts: Mutex::new(hosts),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn fingerprint(&self, server_name: &str) -> Option<Fingerprint> {
+        self.hosts().get(server_name).copied()
+    }
+
+    /// Records the fingerprint of a host, replacing the previous one
+    ///
+    /// The store file, if any, is not updated: [`KnownHosts::save`] must be called.
+    pub fn insert(&self, server_name: impl Into<String>, fingerprint: Fingerprint) {
+        self.hosts().insert(server_name.into(), fingerprint);
+    }
+
+    /// Writes all the recorded fingerprints to the store file
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut hosts: Vec<_> = self
+            .hosts()
+            .iter()
+            .map(|(server_name, fingerprint)| format!("{server_name} {}\n", to_hex(fingerprint)))
+            .collect();
+        hosts.sort();
+
+        fs::write(path, hosts.concat())
+    }
+
+    /// Checks the end-entity certificate of a host
+    pub fn verify(&self, server_name: &str, certificate: &[u8]) -> Result<(), VerificationError> {
+        let actual = fingerprint(certificate);
+
+        let mut hosts = self.hosts();
+
+        match hosts.get(server_name) {
+            Some(&expected) if expected == actual => Ok(()),
+            Some(&expected) => Err(VerificationError::FingerprintMismatch {
+                server_name: server_name.to_owned(),
+                expected,
+                actual,
+            }),
+            None if self.trust_on_first_use => {
+                if let Some(path) = &self.path {
+                    append_known_host(path, server_name, &actual)
+                        .map_err(|e| VerificationError::KnownHostsFile(e.to_string()))?;
+                }
+
+                hosts.insert(server_name.to_owned(), actual);
+
+                Ok(())
+            }
+            None => Err(VerificationError::UnknownHost {
+                server_name: server_name.to_owned(),
+                fingerprint: actual,
+            }),
+        }
+    }
+
+    fn hosts(&self) -> std::sync::MutexGuard<'_, HashMap<String, Fingerprint>> {
+        self.hosts.lock().expect("poisoned known hosts lock")
+    }
+}
+
+/// Computes the SHA-256 fingerprint of a DER certificate
+pub fn fingerprint(certificate: &[u8]) -> Fingerprint {
+    sha2::Sha256::digest(certificate).into()
+}
+
+/// Reads the DER certificates of a PEM CA bundle
+pub fn load_ca_bundle(path: impl AsRef<Path>) -> io::Result<Vec<Vec<u8>>> {
+    use x509_cert::der::Encode as _;
+
+    let pem = fs::read(path)?;
+
+    let certificates = x509_cert::Certificate::load_pem_chain(&pem)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        .iter()
+        .map(|certificate| certificate.to_der())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    if certificates.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no certificate in CA bundle",
+        ));
+    }
+
+    Ok(certificates)
+}
+
+fn parse_known_hosts(content: &str) -> io::Result<HashMap<String, Fingerprint>> {
+    let invalid = |line: usize| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid known hosts entry at line {line}"),
+        )
+    };
+
+    let mut hosts = HashMap::new();
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (server_name, fingerprint) = line.split_once(' ').ok_or_else(|| invalid(idx + 1))?;
+        let fingerprint = from_hex(fingerprint.trim()).ok_or_else(|| invalid(idx + 1))?;
+
+        hosts.insert(server_name.to_owned(), fingerprint);
+    }
+
+    Ok(hosts)
+}
+
+fn append_known_host(path: &Path, server_name: &str, fingerprint: &Fingerprint) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{server_name} {}", to_hex(fingerprint))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    use core::fmt::Write as _;
+
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+fn from_hex(hex: &str) -> Option<Fingerprint> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut fingerprint = [0; 32];
+
+    for (byte, digits) in fingerprint.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let digits = core::str::from_utf8(digits).ok()?;
+        *byte = u8::from_str_radix(digits, 16).ok()?;
+    }
+
+    Some(fingerprint)
+}
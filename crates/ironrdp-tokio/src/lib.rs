@@ -2,10 +2,13 @@
 pub use ironrdp_async::*;
 
 use std::io;
+use std::net::SocketAddr;
 use std::pin::Pin;
 
 use bytes::BytesMut;
+use ironrdp_connector::{custom_err, ConnectorResult, Redirection};
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 
 pub type TokioFramed<S> = Framed<TokioStream<S>>;
 
@@ -37,7 +40,8 @@ impl<S> FramedRead for TokioStream<S>
 where
     S: Send + Sync + Unpin + AsyncRead,
 {
-    type ReadFut<'read> = Pin<Box<dyn std::future::Future<Output = io::Result<usize>> + Send + Sync + 'read>>
+    type ReadFut<'read>
+        = Pin<Box<dyn std::future::Future<Output = io::Result<usize>> + Send + Sync + 'read>>
     where
         Self: 'read;
 
@@ -52,7 +56,8 @@ impl<S> FramedWrite for TokioStream<S>
 where
     S: Send + Sync + Unpin + AsyncWrite,
 {
-    type WriteAllFut<'write> = Pin<Box<dyn std::future::Future<Output = io::Result<()>> + Send + Sync + 'write>>
+    type WriteAllFut<'write>
+        = Pin<Box<dyn std::future::Future<Output = io::Result<()>> + Send + Sync + 'write>>
     where
         Self: 'write;
 
@@ -98,7 +103,8 @@ impl<S> FramedRead for LocalTokioStream<S>
 where
     S: Unpin + AsyncRead,
 {
-    type ReadFut<'read> = Pin<Box<dyn std::future::Future<Output = io::Result<usize>> + 'read>>
+    type ReadFut<'read>
+        = Pin<Box<dyn std::future::Future<Output = io::Result<usize>> + 'read>>
     where
         Self: 'read;
 
@@ -113,7 +119,8 @@ impl<S> FramedWrite for LocalTokioStream<S>
 where
     S: Unpin + AsyncWrite,
 {
-    type WriteAllFut<'write> = Pin<Box<dyn std::future::Future<Output = io::Result<()>> + 'write>>
+    type WriteAllFut<'write>
+        = Pin<Box<dyn std::future::Future<Output = io::Result<()>> + 'write>>
     where
         Self: 'write;
 
@@ -128,3 +135,44 @@ where
         })
     }
 }
+
+/// Opens a TCP connection to the target of a server redirection
+///
+/// The target addresses are tried in order, using the port of `server_addr`. When the redirection carries
+/// no address, `server_addr` is connected to again. Returns the stream along with the address connected to.
+///
+/// The connection sequence is then performed with a connector set up using
+/// [`ClientConnector::with_redirection`](ironrdp_connector::ClientConnector::with_redirection).
+pub async fn connect_redirected(
+    redirection: &Redirection,
+    server_addr: SocketAddr,
+) -> ConnectorResult<(TcpStream, SocketAddr)> {
+    if redirection.target_addresses.is_empty() {
+        let stream = TcpStream::connect(server_addr)
+            .await
+            .map_err(|e| custom_err!("TCP connect", e))?;
+
+        return Ok((stream, server_addr));
+    }
+
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no target address could be resolved");
+
+    for address in &redirection.target_addresses {
+        let resolved = match tokio::net::lookup_host((address.as_str(), server_addr.port())).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                last_error = e;
+                continue;
+            }
+        };
+
+        for addr in resolved {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => return Ok((stream, addr)),
+                Err(e) => last_error = e,
+            }
+        }
+    }
+
+    Err(custom_err!("connect to redirection target", last_error))
+}
@@ -732,6 +732,8 @@ fn build_config(
         // TODO(#327): expose these options from the WASM module.
         enable_tls: true,
         enable_credssp: true,
+        enable_standard_security: false,
+        credential_delegation: connector::CredentialDelegation::Full,
         keyboard_type: ironrdp::pdu::gcc::KeyboardType::IbmEnhanced,
         keyboard_subtype: 0,
         keyboard_functional_keys_count: 12,
@@ -741,6 +743,7 @@ fn build_config(
             width: desktop_size.width,
             height: desktop_size.height,
         },
+        monitors: Vec::new(),
         graphics: None,
         bitmap: Some(connector::BitmapConfig {
             color_depth: 16,
@@ -759,6 +762,8 @@ fn build_config(
         platform: ironrdp::pdu::rdp::capability_sets::MajorPlatformType::UNSPECIFIED,
         no_server_pointer: false,
         autologon: false,
+        auto_reconnect_cookie: None,
+        pcb: None, // sent to the proxy in the RDCleanPath request instead
         pointer_software_rendering: false,
     }
 }
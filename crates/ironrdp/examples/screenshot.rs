@@ -178,6 +178,8 @@ fn build_config(username: String, password: String, domain: Option<String>) -> c
         domain,
         enable_tls: false, // This example does not expose any frontend.
         enable_credssp: true,
+        enable_standard_security: false,
+        credential_delegation: connector::CredentialDelegation::Full,
         keyboard_type: KeyboardType::IbmEnhanced,
         keyboard_subtype: 0,
         keyboard_functional_keys_count: 12,
@@ -187,6 +189,7 @@ fn build_config(username: String, password: String, domain: Option<String>) -> c
             width: 1280,
             height: 1024,
         },
+        monitors: Vec::new(),
         graphics: None,
         bitmap: None,
         client_build: 0,
@@ -215,6 +218,8 @@ fn build_config(username: String, password: String, domain: Option<String>) -> c
         // Disable custom pointers (there is no user interaction anyway)
         no_server_pointer: true,
         autologon: false,
+        auto_reconnect_cookie: None,
+        pcb: None,
         pointer_software_rendering: true,
     }
 }
@@ -0,0 +1,312 @@
+//! Example of accepting RDP connections in a blocking, synchronous fashion.
+//!
+//! Each client is served by its own thread: the connection sequence is driven by
+//! `ironrdp_blocking::accept_begin` and `ironrdp_blocking::accept_finalize`, then
+//! the input events sent by the client are logged until it disconnects.
+//!
+//! # Usage example
+//!
+//! ```shell
+//! cargo run --example=server_blocking --features=acceptor -- --cert cert.pem --key key.pem -u <USERNAME> -p <PASSWORD>
+//! ```
+
+#[macro_use]
+extern crate tracing;
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::Context as _;
+use ironrdp::acceptor::{Acceptor, AcceptorResult, Credentials, DesktopSize};
+use ironrdp::pdu::input::fast_path::FastPathInput;
+use ironrdp::pdu::rdp::capability_sets::{self, CapabilitySet};
+use ironrdp::pdu::{mcs, nego, PduParsing as _};
+use ironrdp_blocking::BeginResult;
+use rustls_pemfile::{certs, pkcs8_private_keys};
+
+const HELP: &str = "\
+USAGE:
+  cargo run --example=server_blocking --features=acceptor -- --host <HOSTNAME> --port <PORT>
+                                                             [--cert <CERT_FILE> --key <KEY_FILE>]
+                                                             [-u/--username <USERNAME> -p/--password <PASSWORD>]
+";
+
+const WIDTH: u16 = 1920;
+const HEIGHT: u16 = 1080;
+
+fn main() -> anyhow::Result<()> {
+    let action = match parse_args() {
+        Ok(action) => action,
+        Err(e) => {
+            println!("{HELP}");
+            return Err(e.context("invalid argument(s)"));
+        }
+    };
+
+    setup_logging()?;
+
+    match action {
+        Action::ShowHelp => {
+            println!("{HELP}");
+            Ok(())
+        }
+        Action::Run {
+            host,
+            port,
+            tls,
+            credentials,
+        } => run(host, port, tls, credentials),
+    }
+}
+
+#[derive(Debug)]
+enum Action {
+    ShowHelp,
+    Run {
+        host: String,
+        port: u16,
+        tls: Option<(String, String)>,
+        credentials: Option<Credentials>,
+    },
+}
+
+fn parse_args() -> anyhow::Result<Action> {
+    let mut args = pico_args::Arguments::from_env();
+
+    let action = if args.contains(["-h", "--help"]) {
+        Action::ShowHelp
+    } else {
+        let host = args.opt_value_from_str("--host")?.unwrap_or(String::from("localhost"));
+        let port = args.opt_value_from_str("--port")?.unwrap_or(3389);
+        let cert: Option<String> = args.opt_value_from_str("--cert")?;
+        let key: Option<String> = args.opt_value_from_str("--key")?;
+        let username: Option<String> = args.opt_value_from_str(["-u", "--username"])?;
+        let password: Option<String> = args.opt_value_from_str(["-p", "--password"])?;
+
+        Action::Run {
+            host,
+            port,
+            tls: cert.zip(key),
+            credentials: username.zip(password).map(|(username, password)| Credentials {
+                username,
+                password,
+                domain: None,
+            }),
+        }
+    };
+
+    Ok(action)
+}
+
+fn setup_logging() -> anyhow::Result<()> {
+    use tracing::metadata::LevelFilter;
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::EnvFilter;
+
+    let fmt_layer = tracing_subscriber::fmt::layer().compact();
+
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::WARN.into())
+        .with_env_var("IRONRDP_LOG")
+        .from_env_lossy();
+
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(env_filter)
+        .try_init()
+        .context("failed to set tracing global subscriber")?;
+
+    Ok(())
+}
+
+fn tls_config(cert_path: &str, key_path: &str) -> anyhow::Result<Arc<rustls::ServerConfig>> {
+    let cert = certs(&mut BufReader::new(File::open(cert_path)?))
+        .next()
+        .context("no certificate")??;
+    let key = pkcs8_private_keys(&mut BufReader::new(File::open(key_path)?))
+        .next()
+        .context("no private key")??;
+
+    let mut server_config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(cert.as_ref().to_vec())],
+            rustls::PrivateKey(key.secret_pkcs8_der().to_vec()),
+        )
+        .context("bad certificate/key")?;
+
+    // This adds support for the SSLKEYLOGFILE env variable (https://wiki.wireshark.org/TLS#using-the-pre-master-secret)
+    server_config.key_log = Arc::new(rustls::KeyLogFile::new());
+
+    Ok(Arc::new(server_config))
+}
+
+fn run(host: String, port: u16, tls: Option<(String, String)>, credentials: Option<Credentials>) -> anyhow::Result<()> {
+    let tls = tls
+        .map(|(cert, key)| tls_config(&cert, &key))
+        .transpose()
+        .context("TLS configuration")?;
+    let credentials = credentials.map(Arc::new);
+
+    let addr = SocketAddr::new(host.parse::<IpAddr>()?, port);
+    let listener = TcpListener::bind(addr).context("bind")?;
+
+    info!(%addr, "Listening for connections");
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                error!(?error, "Failed to accept connection");
+                continue;
+            }
+        };
+
+        let tls = tls.clone();
+        let credentials = credentials.clone();
+
+        thread::spawn(move || {
+            let peer = stream.peer_addr().ok();
+
+            if let Err(error) = serve(stream, tls, credentials) {
+                error!(?peer, ?error, "Connection error");
+            }
+
+            info!(?peer, "Connection closed");
+        });
+    }
+
+    Ok(())
+}
+
+type TlsStream = rustls::StreamOwned<rustls::ServerConnection, TcpStream>;
+
+fn serve(
+    stream: TcpStream,
+    tls: Option<Arc<rustls::ServerConfig>>,
+    credentials: Option<Arc<Credentials>>,
+) -> anyhow::Result<()> {
+    let security = if tls.is_some() {
+        nego::SecurityProtocol::SSL
+    } else {
+        nego::SecurityProtocol::empty()
+    };
+    let size = DesktopSize {
+        width: WIDTH,
+        height: HEIGHT,
+    };
+
+    let mut acceptor = Acceptor::new(security, size, capabilities(size));
+
+    if let Some(credentials) = credentials {
+        acceptor.attach_credential_validator(credentials);
+    }
+
+    let framed = ironrdp_blocking::Framed::new(stream);
+
+    match ironrdp_blocking::accept_begin(framed, &mut acceptor).context("begin connection")? {
+        BeginResult::ShouldUpgrade(stream) => {
+            let tls = tls.expect("TLS is the only security protocol offered");
+
+            let connection = rustls::ServerConnection::new(tls)?;
+            let upgraded: TlsStream = rustls::StreamOwned::new(connection, stream);
+
+            acceptor.mark_security_upgrade_as_done().context("security upgrade")?;
+
+            let framed = ironrdp_blocking::Framed::new(upgraded);
+            let (framed, result) =
+                ironrdp_blocking::accept_finalize(framed, &mut acceptor).context("finalize connection")?;
+
+            active_session(framed, result)
+        }
+
+        BeginResult::Continue(framed) => {
+            let (framed, result) =
+                ironrdp_blocking::accept_finalize(framed, &mut acceptor).context("finalize connection")?;
+
+            active_session(framed, result)
+        }
+    }
+}
+
+fn active_session<S>(mut framed: ironrdp_blocking::Framed<S>, result: AcceptorResult) -> anyhow::Result<()>
+where
+    S: std::io::Read + std::io::Write,
+{
+    info!(identity = ?result.identity, "Client connected");
+
+    loop {
+        let (action, payload) = framed.read_pdu().context("read frame")?;
+
+        match action {
+            ironrdp::pdu::Action::FastPath => {
+                let input = FastPathInput::from_buffer(Cursor::new(&payload))?;
+
+                for event in input.0 {
+                    info!(?event, "Input event");
+                }
+            }
+
+            ironrdp::pdu::Action::X224 => match ironrdp::pdu::decode::<mcs::McsMessage<'_>>(&payload)? {
+                mcs::McsMessage::DisconnectProviderUltimatum(ultimatum) => {
+                    info!(reason = ?ultimatum.reason, "Client disconnected");
+                    return Ok(());
+                }
+
+                mcs::McsMessage::SendDataRequest(data) => {
+                    debug!(
+                        channel_id = data.channel_id,
+                        length = data.user_data.len(),
+                        "Channel data"
+                    );
+                }
+
+                unexpected => {
+                    warn!(name = ironrdp::pdu::name(&unexpected), "Unexpected MCS message");
+                }
+            },
+        }
+    }
+}
+
+fn capabilities(size: DesktopSize) -> Vec<CapabilitySet> {
+    vec![
+        CapabilitySet::General(capability_sets::General {
+            extra_flags: capability_sets::GeneralExtraFlags::FASTPATH_OUTPUT_SUPPORTED,
+            ..Default::default()
+        }),
+        CapabilitySet::Bitmap(capability_sets::Bitmap {
+            pref_bits_per_pix: 32,
+            desktop_width: size.width,
+            desktop_height: size.height,
+            desktop_resize_flag: false,
+            drawing_flags: capability_sets::BitmapDrawingFlags::empty(),
+        }),
+        CapabilitySet::Order(capability_sets::Order::new(
+            capability_sets::OrderFlags::empty(),
+            capability_sets::OrderSupportExFlags::empty(),
+            2048,
+            224,
+        )),
+        CapabilitySet::Input(capability_sets::Input {
+            input_flags: capability_sets::InputFlags::SCANCODES
+                | capability_sets::InputFlags::MOUSEX
+                | capability_sets::InputFlags::FASTPATH_INPUT
+                | capability_sets::InputFlags::UNICODE
+                | capability_sets::InputFlags::FASTPATH_INPUT_2,
+            keyboard_layout: 0,
+            keyboard_type: None,
+            keyboard_subtype: 0,
+            keyboard_function_key: 128,
+            keyboard_ime_filename: "".into(),
+        }),
+        CapabilitySet::VirtualChannel(capability_sets::VirtualChannel {
+            flags: capability_sets::VirtualChannelFlags::NO_COMPRESSION,
+            chunk_size: None,
+        }),
+    ]
+}